@@ -82,6 +82,7 @@ pub async fn run(
         send_cwd,
         last_command,
         history_db: std::sync::Arc::new(history_db),
+        settings: std::sync::Arc::new(settings.clone()),
         git_root,
         capabilities: settings.ai.capabilities.clone(),
         daemon_enabled: settings.daemon.enabled,
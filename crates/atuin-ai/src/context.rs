@@ -25,6 +25,8 @@ pub struct AppContext {
     pub send_cwd: bool,
     pub last_command: Option<History>,
     pub history_db: Arc<atuin_client::database::Sqlite>,
+    /// The settings the history and output tools search with, loaded once per session.
+    pub settings: Arc<atuin_client::settings::Settings>,
     /// Git root of the current working directory, if inside a git repo.
     /// Resolves through worktrees to the main repo root.
     pub git_root: Option<PathBuf>,
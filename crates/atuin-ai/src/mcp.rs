@@ -10,6 +10,7 @@
 
 use atuin_client::database::Sqlite;
 use atuin_client::history::{AUTHOR_FILTER_ALL_AGENT, AUTHOR_FILTER_ALL_USER, KNOWN_AGENTS};
+use atuin_client::settings::Settings;
 use eyre::Result;
 use rmcp::model::{
     CallToolRequestParams, CallToolResult, ContentBlock, ErrorData, Implementation,
@@ -23,6 +24,7 @@ use crate::tools::{AtuinHistoryToolCall, AtuinOutputToolCall, ToolOutcome};
 
 struct AtuinMcp {
     db: Sqlite,
+    settings: Settings,
 }
 
 impl ServerHandler for AtuinMcp {
@@ -49,13 +51,13 @@ impl ServerHandler for AtuinMcp {
             "atuin_history" => {
                 AtuinHistoryToolCall::try_from(&arguments)
                     .map_err(|e| ErrorData::invalid_params(e.to_string(), None))?
-                    .execute(&self.db)
+                    .execute(&self.db, &self.settings)
                     .await
             }
             "atuin_output" => {
                 AtuinOutputToolCall::try_from(&arguments)
                     .map_err(|e| ErrorData::invalid_params(e.to_string(), None))?
-                    .execute()
+                    .execute(&self.settings)
                     .await
             }
             name => {
@@ -79,11 +81,12 @@ impl ServerHandler for AtuinMcp {
 ///
 /// stdout carries only JSON-RPC messages; anything else (logs, errors) must
 /// go to stderr or it will corrupt the protocol stream.
-pub async fn run(db: &Sqlite) -> Result<()> {
+pub async fn run(db: &Sqlite, settings: &Settings) -> Result<()> {
     let server = AtuinMcp {
         db: Sqlite {
             pool: db.pool.clone(),
         },
+        settings: settings.clone(),
     }
     .serve(rmcp::transport::stdio())
     .await?;
@@ -130,6 +133,13 @@ fn tool_definitions() -> Vec<Tool> {
                 "description": "Only return commands that recorded a non-zero exit \
                     code. Commands still running (no exit recorded yet) are excluded.",
             },
+            "output": {
+                "type": "string",
+                "description": "Only return commands whose captured terminal output \
+                    contains this phrase, ignoring case and punctuation, e.g. \
+                    'connection refused'. Only output captured by Atuin's pty-proxy is \
+                    searched; read it with atuin_output.",
+            },
             "authors": {
                 "type": "array",
                 "items": { "type": "string" },
@@ -180,8 +190,9 @@ fn tool_definitions() -> Vec<Tool> {
             "Search the user's shell command history, recorded by Atuin. Fuzzy-matches the query \
              against past commands and returns the most relevant entries, each with a history ID, \
              timestamp, working directory, exit code, and duration. Commands run by AI agents are \
-             annotated with the agent's name and stated intent. Pass a history ID to atuin_output \
-             to see what a command printed.",
+             annotated with the agent's name and stated intent. Can also find the commands whose \
+             captured output contains a phrase. Pass a history ID to atuin_output to see what a \
+             command printed.",
             history_schema,
         )
         .annotate(ToolAnnotations::new().read_only(true)),
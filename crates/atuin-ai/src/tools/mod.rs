@@ -3,7 +3,7 @@ use std::num::NonZeroU16;
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
-use atuin_client::history::AuthorPattern;
+use atuin_client::history::{AuthorPattern, History, HistoryId};
 use atuin_common::ansi::{self, Vt100ParserExt as _};
 use atuin_common::filter::OrFilter;
 use atuin_common::time::UtcOffsetExt;
@@ -961,6 +961,8 @@ pub struct AtuinHistoryToolCall {
     pub limit: i64,
     pub only_failed: bool,
     pub authors: OrFilter<Vec<AuthorPattern>>,
+    /// Only commands whose captured output contains this phrase.
+    pub output: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -1031,12 +1033,19 @@ impl TryFrom<&serde_json::Value> for AtuinHistoryToolCall {
         // An omitted or empty `authors` array means no author filtering.
         let authors = OrFilter::from_list(authors).unwrap_or_default();
 
+        let output = value
+            .get("output")
+            .and_then(|v| v.as_str())
+            .filter(|v| !v.trim().is_empty())
+            .map(str::to_string);
+
         Ok(Self {
             filter_modes,
             query: query.to_string(),
             limit,
             only_failed,
             authors,
+            output,
         })
     }
 }
@@ -1052,8 +1061,80 @@ impl PermissibleToolCall for AtuinHistoryToolCall {
 }
 
 impl AtuinHistoryToolCall {
-    pub(crate) async fn execute(&self, db: &atuin_client::database::Sqlite) -> ToolOutcome {
-        use atuin_client::database::{self, DbSearchMode, OptFilters};
+    /// The entries among `ids`, or among all of them, matching the query and filters.
+    async fn search(
+        &self,
+        db: &atuin_client::database::Sqlite,
+        settings: &atuin_client::settings::Settings,
+        filter_mode: atuin_client::settings::FilterMode,
+        context: &atuin_client::database::Context,
+        ids: Option<&[HistoryId]>,
+    ) -> Result<Vec<History>> {
+        use atuin_client::database::{DbSearchMode, OptFilters};
+
+        let filter_options = OptFilters {
+            limit: Some(self.limit),
+            only_failed: self.only_failed,
+            authors: self.authors.as_slice_filter(),
+            ids,
+            // Each run of a command printed something different.
+            include_duplicates: ids.is_some(),
+            ..Default::default()
+        };
+
+        atuin_daemon::search_history(
+            settings,
+            db,
+            DbSearchMode::Fuzzy,
+            filter_mode,
+            context,
+            &self.query,
+            filter_options,
+        )
+        .await
+    }
+
+    /// The entries matching the query and filters whose output, kept on disk, contains `phrase`,
+    /// newest first. Output is searched a page at a time, until enough of it is from entries that
+    /// match.
+    async fn search_output(
+        &self,
+        db: &atuin_client::database::Sqlite,
+        settings: &atuin_client::settings::Settings,
+        phrase: &str,
+        filter_mode: atuin_client::settings::FilterMode,
+        context: &atuin_client::database::Context,
+    ) -> Result<Vec<History>> {
+        // Opening it would create it, empty.
+        if !Path::new(&settings.output.db_path).exists() {
+            return Ok(Vec::new());
+        }
+
+        let output_db = atuin_client::output::database::Database::new(
+            &settings.output.db_path,
+            settings.local_timeout,
+        )
+        .await?;
+
+        let limit = usize::try_from(self.limit).unwrap_or(0);
+        let mut pages = output_db.search_pages(phrase);
+        let mut results = Vec::new();
+        while results.len() < limit
+            && let Some(ids) = pages.next().await?
+        {
+            results.extend(self.search(db, settings, filter_mode, context, Some(&ids)).await?);
+        }
+        results.truncate(limit);
+
+        Ok(results)
+    }
+
+    pub(crate) async fn execute(
+        &self,
+        db: &atuin_client::database::Sqlite,
+        settings: &atuin_client::settings::Settings,
+    ) -> ToolOutcome {
+        use atuin_client::database;
 
         // query_context rather than current_context: when running outside an
         // atuin-hooked shell (e.g. as an MCP server) there is no ATUIN_SESSION.
@@ -1081,17 +1162,18 @@ impl AtuinHistoryToolCall {
             );
         }
 
-        let filter_options = OptFilters {
-            limit: Some(self.limit),
-            only_failed: self.only_failed,
-            authors: self.authors.as_slice_filter(),
-            ..Default::default()
+        // Only the entries are returned, not what they printed: reading that is up to
+        // atuin_output, and its own permission.
+        let results = match &self.output {
+            Some(phrase) => {
+                match self.search_output(db, settings, phrase, filter_mode, &context).await {
+                    Ok(results) => Ok(results),
+                    Err(e) => return ToolOutcome::Error(format!("Output search failed: {e}")),
+                }
+            }
+            None => self.search(db, settings, filter_mode, &context, None).await,
         };
-
-        let results = match db
-            .search(DbSearchMode::Fuzzy, filter_mode, &context, &self.query, filter_options)
-            .await
-        {
+        let results = match results {
             Ok(results) => results,
             Err(e) => return ToolOutcome::Error(format!("History search failed: {e}")),
         };
@@ -1195,13 +1277,8 @@ fn format_output_lines_for_llm(lines: &[atuin_daemon::semantic::OutputLine]) ->
 }
 
 impl AtuinOutputToolCall {
-    pub(crate) async fn execute(&self) -> ToolOutcome {
-        let settings = match atuin_client::settings::Settings::new() {
-            Ok(settings) => settings,
-            Err(e) => return ToolOutcome::Error(format!("Failed to load Atuin settings: {e}")),
-        };
-
-        let mut client = match atuin_daemon::SemanticClient::from_settings(&settings).await {
+    pub(crate) async fn execute(&self, settings: &atuin_client::settings::Settings) -> ToolOutcome {
+        let mut client = match atuin_daemon::SemanticClient::from_settings(settings).await {
             Ok(client) => client,
             Err(e) => return ToolOutcome::Error(format!("Failed to connect to Atuin daemon: {e}")),
         };
@@ -1334,6 +1411,16 @@ mod tests {
         let call = AtuinHistoryToolCall::try_from(&input).unwrap();
         assert!(call.only_failed);
         assert_eq!(call.authors.items(), filter::Items::Some([AuthorPattern::AllAgent].as_slice()));
+        assert_eq!(call.output, None);
+
+        let input = serde_json::json!({
+            "query": "",
+            "filter_modes": ["global"],
+            "output": "connection refused",
+        });
+
+        let call = AtuinHistoryToolCall::try_from(&input).unwrap();
+        assert_eq!(call.output.as_deref(), Some("connection refused"));
     }
 
     #[rstest]
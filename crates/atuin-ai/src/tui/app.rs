@@ -711,8 +711,9 @@ impl AiApp {
                     return;
                 };
                 let db = io.app_ctx.history_db.clone();
+                let settings = io.app_ctx.settings.clone();
                 ctx.perform(async move {
-                    let outcome = history_call.execute(&db).await;
+                    let outcome = history_call.execute(&db, &settings).await;
                     Msg::Fsm(Event::ToolExecutionDone {
                         tool_id,
                         outcome,
@@ -722,8 +723,12 @@ impl AiApp {
                 .detach();
             }
             ClientToolCall::AtuinOutput(output_call) => {
+                let Some(io) = &self.io else {
+                    return;
+                };
+                let settings = io.app_ctx.settings.clone();
                 ctx.perform(async move {
-                    let outcome = output_call.execute().await;
+                    let outcome = output_call.execute(&settings).await;
                     Msg::Fsm(Event::ToolExecutionDone {
                         tool_id,
                         outcome,
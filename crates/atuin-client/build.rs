@@ -5,5 +5,6 @@ fn main() {
     // missing the migration.
     println!("cargo:rerun-if-changed=migrations");
     println!("cargo:rerun-if-changed=meta-migrations");
+    println!("cargo:rerun-if-changed=output-migrations");
     println!("cargo:rerun-if-changed=record-migrations");
 }
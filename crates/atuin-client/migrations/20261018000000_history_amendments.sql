@@ -0,0 +1,18 @@
+-- Edits and redactions of history entries. They're kept apart from the entries, so they can be
+-- applied in whatever order they sync in. Only what's left of an entry's text after its
+-- redactions is kept, never the text it was recorded with.
+create table if not exists history_amendments (
+	id text primary key,
+
+	-- The entry as recorded, redacted. Null until the entry itself arrives.
+	recorded_command text,
+	recorded_intent text,
+
+	-- The latest edit, if any, redacted.
+	edited_at integer,
+	edited_command text,
+	edited_intent text,
+
+	-- A JSON array of the redactions: their patterns, or hashes of what they blank.
+	redactions text not null default '[]'
+);
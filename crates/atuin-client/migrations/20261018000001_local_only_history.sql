@@ -0,0 +1,5 @@
+-- History entries kept on this machine only, because they hold a secret and `secrets_action` is
+-- "local". They never go into the record store, so they're never synced.
+create table if not exists local_only_history (
+	id text primary key
+);
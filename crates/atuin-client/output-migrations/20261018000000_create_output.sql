@@ -0,0 +1,17 @@
+-- The output of commands captured by `atuin pty-proxy`, zstd-compressed, by the history entry
+-- that printed it.
+create table if not exists output (
+	history_id text primary key,
+	timestamp integer not null,
+	truncated integer not null,
+	observed_bytes integer not null,
+	size integer not null,
+	data blob not null
+);
+
+create index if not exists idx_output_timestamp on output(timestamp);
+
+-- Output records already built into this database, so a build only decrypts the new ones.
+create table if not exists built_records (
+	id text primary key
+);
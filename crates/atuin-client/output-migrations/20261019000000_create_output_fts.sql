@@ -0,0 +1,14 @@
+-- Full-text search over output. The output itself is compressed, so the index keeps none of it,
+-- and is filled in as output is saved. Rows are output's rowids.
+create virtual table if not exists output_fts using fts5(
+	output,
+	content = '',
+	contentless_delete = 1
+);
+
+-- Output saved before there was an index is indexed when the database is next opened.
+alter table output add column indexed integer not null default 0;
+
+create trigger if not exists output_fts_delete after delete on output begin
+	delete from output_fts where rowid = old.rowid;
+end;
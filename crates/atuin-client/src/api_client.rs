@@ -8,23 +8,27 @@ use async_stream::try_stream;
 use atuin_common::range::{Chunks, RangeExt};
 use atuin_common::url::UrlAppendExt;
 use atuin_domain::api::{
-    ATUIN_CARGO_VERSION, ATUIN_HEADER_VERSION, ATUIN_VERSION, ChangePasswordRequest, ErrorResponse,
-    LoginRequest, LoginResponse, MeResponse, PackfileDownloadResponse, PackfileResponse,
-    RegisterResponse,
+    ATUIN_CARGO_VERSION, ATUIN_HEADER_VERSION, ATUIN_VERSION, AddTeamMemberRequest,
+    ChangePasswordRequest, CreateTeamRequest, ErrorResponse, LoginRequest, LoginResponse,
+    MeResponse, PackfileDownloadResponse, PackfileRequest, PackfileResponse, PublicKeyRequest,
+    RegisterResponse, SessionsResponse, TeamMembersResponse,
 };
-use atuin_domain::caps::{CapClient, CapMismatch, CapabilitiesExt};
+use atuin_domain::caps::{CapClient, CapMiddleware, CapMismatch, CapabilitiesExt};
 use atuin_domain::record::{
     EncryptedData, Record, RecordId, RecordIdx, RecordSeriesKey, RecordStatus,
 };
+use base64::prelude::{BASE64_STANDARD, Engine};
 use eyre::{Result, bail};
 use futures::{Stream, StreamExt, TryStreamExt, stream};
 use reqwest::header::{AUTHORIZATION, HeaderMap, HeaderName, HeaderValue, USER_AGENT};
 use reqwest::{Response, StatusCode, Url};
 use reqwest_middleware::ClientWithMiddleware;
 use semver::Version;
+use sha2::{Digest, Sha256};
 use tracing::{Instrument, instrument};
 
 use crate::packfile::PackedPackfile;
+use crate::record::sync::peer::PeerAuth;
 
 static APP_USER_AGENT: &str = concat!("atuin/", env!("CARGO_PKG_VERSION"),);
 
@@ -34,6 +38,17 @@ const MAX_RECORDS_CONCURRENT_DOWNLOAD: usize = 8;
 /// How many packfile blobs [`Client::upload_packfiles`] transfers concurrently.
 const MAX_CONCURRENT_PACKFILE_UPLOADS: usize = 16;
 
+/// The header S3 checks an upload's SHA-256 against, when the server signs it into the URL.
+const PACKFILE_CHECKSUM_HEADER: &str = "x-amz-checksum-sha256";
+
+/// The server refused an upload because it would take the account over its storage quota.
+#[derive(Debug, thiserror::Error)]
+#[error("{reason}")]
+pub struct QuotaExceeded {
+    /// The reason the server gave.
+    pub reason: String,
+}
+
 /// Authentication token for sync API requests.
 ///
 /// The sync API supports two authentication methods:
@@ -124,12 +139,16 @@ pub async fn register(
     username: &str,
     email: &str,
     password: &str,
+    invite: Option<&str>,
     extra_headers: &HashMap<String, String>,
 ) -> Result<RegisterResponse> {
     let mut map = HashMap::new();
     map.insert("username", username);
     map.insert("email", email);
     map.insert("password", password);
+    if let Some(invite) = invite {
+        map.insert("invite", invite);
+    }
 
     let mut headers = extra_headers_map(extra_headers)?;
     headers.insert(USER_AGENT, APP_USER_AGENT.parse()?);
@@ -436,12 +455,56 @@ impl Client {
         })
     }
 
+    /// A client for another of this account's machines, whose daemon listens at `address`. Each
+    /// request answers a fresh challenge from the peer, so `secret` is never sent.
+    #[instrument(level = "trace", skip_all, fields(connect_timeout, timeout), err)]
+    pub fn peer(
+        address: Url,
+        secret: &str,
+        connect_timeout: u64,
+        timeout: u64,
+        caps: Arc<CapClient>,
+    ) -> Result<Self> {
+        let mut headers = HeaderMap::new();
+        headers.insert(USER_AGENT, APP_USER_AGENT.parse()?);
+        headers.insert(ATUIN_HEADER_VERSION, ATUIN_CARGO_VERSION.parse()?);
+
+        let http = reqwest::Client::builder()
+            .default_headers(headers)
+            .connect_timeout(Duration::from_secs(connect_timeout))
+            .timeout(Duration::from_secs(timeout))
+            .build()?;
+
+        let client = reqwest_middleware::ClientBuilder::new(http.clone())
+            .with(CapMiddleware::builder().caps(caps.clone()).build())
+            .with(PeerAuth::new(&address, secret, http)?)
+            .build();
+
+        Ok(Self {
+            sync_addr: Arc::new(address),
+            client,
+            lfs_client: reqwest::Client::builder()
+                .connect_timeout(Duration::from_secs(connect_timeout))
+                .timeout(Duration::from_secs(timeout))
+                .build()?,
+            caps,
+        })
+    }
+
     /// The capability reader this client negotiates against, for capability-gated features to
     /// consult (e.g. `client.caps().get_server::<SomeCap>()`).
     pub fn caps(&self) -> &Arc<CapClient> {
         &self.caps
     }
 
+    /// A client for the team's store, which is otherwise the same as this one.
+    pub fn for_team(&self, team: &str) -> Result<Self> {
+        Ok(Self {
+            sync_addr: Arc::new(self.sync_addr.append(["teams", team])?),
+            ..self.clone()
+        })
+    }
+
     #[instrument(level = "trace", skip_all, err)]
     pub async fn me(&self) -> Result<MeResponse> {
         let url = self.sync_addr.append_path("api/v0/me")?;
@@ -454,6 +517,167 @@ impl Client {
         Ok(status)
     }
 
+    #[instrument(level = "trace", skip_all, err)]
+    pub async fn sessions(&self) -> Result<SessionsResponse> {
+        let url = self.sync_addr.append_path("api/v0/sessions")?;
+
+        let resp = self.client.get(url).send().await?;
+        let resp = handle_resp_error(resp).await?;
+
+        let sessions = resp.json::<SessionsResponse>().await?;
+
+        Ok(sessions)
+    }
+
+    #[instrument(level = "trace", skip_all, err)]
+    pub async fn revoke_session(&self, id: i64) -> Result<()> {
+        let url = self.sync_addr.append(["api", "v0", "sessions", &id.to_string()])?;
+
+        let resp = self.client.delete(url).send().await?;
+
+        if resp.status() == StatusCode::NOT_FOUND {
+            bail!("no session with id {id}");
+        }
+
+        handle_resp_error(resp).await?;
+
+        Ok(())
+    }
+
+    /// Revoke every session except the one this client is using.
+    #[instrument(level = "trace", skip_all, err)]
+    pub async fn revoke_other_sessions(&self) -> Result<()> {
+        let url = self.sync_addr.append_path("api/v0/sessions")?;
+
+        let resp = self.client.delete(url).send().await?;
+
+        handle_resp_error(resp).await?;
+
+        Ok(())
+    }
+
+    /// Publish the public key team keys are sealed to.
+    #[instrument(level = "trace", skip_all, err)]
+    pub async fn put_public_key(&self, public_key: &str) -> Result<()> {
+        let url = self.sync_addr.append_path("api/v0/me/key")?;
+
+        let resp = self
+            .client
+            .put(url)
+            .json(&PublicKeyRequest {
+                public_key: public_key.to_owned(),
+            })
+            .send()
+            .await?;
+
+        handle_resp_error(resp).await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all, err)]
+    pub async fn public_key(&self, username: &str) -> Result<String> {
+        let url = self.sync_addr.append(["api", "v0", "users", username, "key"])?;
+
+        let resp = self.client.get(url).send().await?;
+
+        if resp.status() == StatusCode::NOT_FOUND {
+            bail!("{username} isn't a user, or hasn't synced since this server enabled teams");
+        }
+
+        let resp = handle_resp_error(resp).await?;
+
+        Ok(resp.json::<PublicKeyRequest>().await?.public_key)
+    }
+
+    /// The teams the user is in, with their team keys.
+    #[instrument(level = "trace", skip_all, err)]
+    pub async fn teams(&self) -> Result<TeamMembersResponse> {
+        let url = self.sync_addr.append_path("api/v0/teams")?;
+
+        let resp = self.client.get(url).send().await?;
+        let resp = handle_resp_error(resp).await?;
+
+        Ok(resp.json().await?)
+    }
+
+    #[instrument(level = "trace", skip_all, err)]
+    pub async fn create_team(&self, name: &str, sealed_key: &str) -> Result<()> {
+        let url = self.sync_addr.append_path("api/v0/teams")?;
+
+        let resp = self
+            .client
+            .post(url)
+            .json(&CreateTeamRequest {
+                name: name.to_owned(),
+                sealed_key: sealed_key.to_owned(),
+            })
+            .send()
+            .await?;
+
+        handle_resp_error(resp).await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all, err)]
+    pub async fn delete_team(&self, name: &str) -> Result<()> {
+        let url = self.sync_addr.append(["api", "v0", "teams", name])?;
+
+        let resp = self.client.delete(url).send().await?;
+
+        handle_resp_error(resp).await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all, err)]
+    pub async fn team_members(&self, team: &str) -> Result<TeamMembersResponse> {
+        let url = self.sync_addr.append(["api", "v0", "teams", team, "members"])?;
+
+        let resp = self.client.get(url).send().await?;
+        let resp = handle_resp_error(resp).await?;
+
+        Ok(resp.json().await?)
+    }
+
+    #[instrument(level = "trace", skip_all, err)]
+    pub async fn add_team_member(
+        &self,
+        team: &str,
+        username: &str,
+        sealed_key: &str,
+        admin: bool,
+    ) -> Result<()> {
+        let url = self.sync_addr.append(["api", "v0", "teams", team, "members"])?;
+
+        let resp = self
+            .client
+            .post(url)
+            .json(&AddTeamMemberRequest {
+                username: username.to_owned(),
+                sealed_key: sealed_key.to_owned(),
+                admin,
+            })
+            .send()
+            .await?;
+
+        handle_resp_error(resp).await?;
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all, err)]
+    pub async fn remove_team_member(&self, team: &str, username: &str) -> Result<()> {
+        let url = self.sync_addr.append(["api", "v0", "teams", team, "members", username])?;
+
+        let resp = self.client.delete(url).send().await?;
+
+        handle_resp_error(resp).await?;
+
+        Ok(())
+    }
+
     #[instrument(level = "trace", skip_all, err)]
     pub async fn delete_store(&self) -> Result<()> {
         let url = self.sync_addr.append_path("api/v0/store")?;
@@ -472,6 +696,15 @@ impl Client {
         debug!("uploading {} records to {url}", records.len());
 
         let resp = self.client.post(url).json(records).send().await?;
+
+        if resp.status() == StatusCode::INSUFFICIENT_STORAGE {
+            let reason = match resp.json::<ErrorResponse>().await {
+                Ok(error) => error.reason.into_owned(),
+                Err(_) => "storage quota exceeded".to_owned(),
+            };
+            return Err(QuotaExceeded { reason }.into());
+        }
+
         handle_resp_error(resp).await?;
 
         Ok(())
@@ -510,18 +743,20 @@ impl Client {
         packfile: impl AsRef<[u8]> + Into<reqwest::Body>,
     ) -> Result<()> {
         let url = self.sync_addr.append_path("api/v0/packfiles")?;
-        let body = serde_json::json!({
-            "manifest_id": manifest_id,
-            "records": record_ids,
-            "packfile_size_bytes": packfile.as_ref().len(),
-        });
+        let sha256 = BASE64_STANDARD.encode(Sha256::digest(packfile.as_ref()));
+        let body = PackfileRequest {
+            manifest_id,
+            records: record_ids.to_vec(),
+            packfile_size_bytes: packfile.as_ref().len() as u64,
+            packfile_sha256: Some(sha256.clone()),
+        };
         let resp = self.client.post(url).json(&body).send().await?;
         let resp = handle_resp_error(resp).await?;
 
         let parsed: PackfileResponse = resp.json().await?;
 
         // Awesome, we got the packfile response, let's proceed uploading it up now.
-        self.put_packfile(parsed.upload_url, packfile).await?;
+        self.put_packfile(parsed.upload_url, &sha256, packfile).await?;
 
         self.confirm_packfile(manifest_id).await?;
 
@@ -543,10 +778,22 @@ impl Client {
     async fn put_packfile(
         &self,
         upload_url: Url,
+        sha256: &str,
         packfile: impl Into<reqwest::Body>,
     ) -> Result<()> {
         // Not self.client: S3 rejects presigned requests that also carry an Authorization header.
-        let resp = self.lfs_client.put(upload_url.clone()).body(packfile).send().await?;
+        let mut req = self.lfs_client.put(upload_url.clone()).body(packfile);
+
+        // S3 also rejects unsigned `x-amz-` headers, so only send the checksum if it was signed.
+        let signed = upload_url
+            .query_pairs()
+            .find(|(k, _)| k == "X-Amz-SignedHeaders")
+            .is_some_and(|(_, v)| v.split(';').any(|h| h == PACKFILE_CHECKSUM_HEADER));
+        if signed {
+            req = req.header(PACKFILE_CHECKSUM_HEADER, sha256);
+        }
+
+        let resp = req.send().await?;
         handle_resp_error(resp).await?;
         Ok(())
     }
@@ -171,6 +171,7 @@ impl AuthClient for LegacyAuthClient {
             username,
             email,
             password,
+            None,
             &self.extra_headers,
         )
         .await?;
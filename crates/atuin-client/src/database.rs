@@ -1,3 +1,4 @@
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
@@ -11,6 +12,7 @@ use fs_err as fs;
 use itertools::Itertools;
 use sql_builder::bind::Bind;
 use sql_builder::{SqlBuilder, SqlName, esc, quote};
+use sqlx::migrate::{MigrateError, Migrator};
 use sqlx::sqlite::{
     SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteRow,
     SqliteSynchronous,
@@ -21,10 +23,50 @@ use tracing::instrument;
 use uuid::Uuid;
 
 use super::history::History;
+use super::history::amend::{Amendment, Amendments, HistoryEdit};
 use super::ordering;
+use super::ranking::{FrecencyData, RankContext, Ranker};
+use super::settings::retention::Rule;
 use super::settings::{FilterMode, SearchMode, Settings};
 use crate::history::{AuthorKind, AuthorPattern, HistoryId, HistoryStats, KNOWN_AGENTS};
 
+pub mod predicate;
+pub mod search;
+
+use predicate::QueryFilter;
+
+/// Checks `pool` has had exactly `migrator`'s migrations applied, for databases we only read
+/// from and so can't migrate: an older or newer schema wouldn't match our queries.
+pub(crate) async fn check_schema(pool: &SqlitePool, migrator: &Migrator) -> Result<()> {
+    let applied: Vec<(i64, bool, Vec<u8>)> =
+        sqlx::query_as("select version, success, checksum from _sqlx_migrations order by version")
+            .fetch_all(pool)
+            .await?;
+
+    for (version, success, checksum) in &applied {
+        if !success {
+            return Err(MigrateError::Dirty(*version).into());
+        }
+        match migrator.iter().find(|m| m.version == *version) {
+            Some(migration) if *migration.checksum == **checksum => {}
+            Some(_) => return Err(MigrateError::VersionMismatch(*version).into()),
+            None => return Err(MigrateError::VersionMissing(*version).into()),
+        }
+    }
+
+    let latest = applied.last().map_or(0, |(version, ..)| *version);
+    if let Some(pending) =
+        migrator.iter().find(|m| !applied.iter().any(|(version, ..)| *version == m.version))
+    {
+        return Err(MigrateError::VersionTooNew(pending.version, latest).into());
+    }
+
+    Ok(())
+}
+
+/// How many previous runs of a command [`Sqlite::followers`] looks at.
+const AFFINITY_SAMPLE: i64 = 500;
+
 #[derive(Clone)]
 pub struct Context {
     pub session: String,
@@ -54,6 +96,11 @@ pub struct OptFilters<'a> {
     pub authors: OrFilter<&'a [AuthorPattern]>,
     /// Shell filter. The empty string matches commands that have no recorded shell.
     pub shells: OrFilter<&'a [String]>,
+    /// Reorders the results after the query runs. Left as `None`, results come back in timestamp
+    /// order (or by match span, for fuzzy searches).
+    pub ranker: Option<&'a dyn Ranker>,
+    /// Only these history entries, such as those whose output matched a search.
+    pub ids: Option<&'a [HistoryId]>,
 }
 
 /// Build a query [`Context`] without requiring a live shell session.
@@ -105,11 +152,16 @@ impl Context {
 /// Each entry is OR'd: [`AuthorPattern::AllUser`] → not an agent, [`AuthorPattern::AllAgent`] → an
 /// agent, [`AuthorPattern::Name`] → exact match.
 fn apply_author_filter(sql: &mut SqlBuilder, authors: OrFilter<&[AuthorPattern]>) {
-    let authors = match authors.items() {
-        filter::Items::All => return,
-        filter::Items::Some(a) => a,
-    };
+    match authors.items() {
+        filter::Items::All => {}
+        filter::Items::Some(authors) => {
+            sql.and_where(author_sql_filter(authors));
+        }
+    }
+}
 
+/// The SQL condition behind [`apply_author_filter`]. `authors` must not be empty.
+fn author_sql_filter(authors: &[AuthorPattern]) -> String {
     // The username half of `hostname`, which is what `author` falls back to when nothing set it.
     let user_expr = "CASE WHEN instr(hostname, ':') > 0 THEN substr(hostname, instr(hostname, \
                      ':') + 1) ELSE hostname END";
@@ -148,8 +200,7 @@ fn apply_author_filter(sql: &mut SqlBuilder, authors: OrFilter<&[AuthorPattern]>
         }
     });
 
-    // Note: `conditions` cannot be empty; `OrFilter::items` is always non-empty.
-    sql.and_where(format!("({})", conditions.join(" OR ")));
+    format!("({})", conditions.join(" OR "))
 }
 
 fn apply_shell_filter(sql: &mut SqlBuilder, shells: OrFilter<&[String]>) {
@@ -213,7 +264,12 @@ fn origin_sql_filter(origin: &CmdOrigin) -> String {
     // the cloud. Recipe for disaster.
     //
     // So this function exists.
-    let host = origin.host().into_inner().to_lowercase();
+    host_sql_filter(origin.host().into_inner())
+}
+
+/// SQL predicate matching entries from `host`, whichever user ran them.
+fn host_sql_filter(host: &str) -> String {
+    let host = host.to_lowercase();
     format!(
         "(lower(hostname) = {eq} OR (lower(hostname) >= {lo} AND lower(hostname) < {hi}))",
         eq = quote(&host),
@@ -301,6 +357,32 @@ impl<'r> ::sqlx::FromRow<'r, SqliteRow> for History {
     }
 }
 
+fn amendments_from_row(row: &SqliteRow) -> Result<Amendments> {
+    let recorded = match row.try_get::<Option<String>, _>("recorded_command")? {
+        Some(command) => Some((command, row.try_get("recorded_intent")?)),
+        None => None,
+    };
+
+    let edit = match row.try_get::<Option<i64>, _>("edited_at")? {
+        Some(edited_at) => Some(HistoryEdit {
+            id: row.try_get::<String, _>("id")?.into(),
+            timestamp: OffsetDateTime::from_unix_nanos_i64(edited_at),
+            command: row.try_get("edited_command")?,
+            intent: row.try_get("edited_intent")?,
+        }),
+        None => None,
+    };
+
+    let redactions = serde_json::from_str(row.try_get("redactions")?)
+        .map_err(|e| sqlx::Error::Decode(e.into()))?;
+
+    Ok(Amendments {
+        recorded,
+        edit,
+        redactions,
+    })
+}
+
 /// A grouped history row plus its aggregate `count(*)`, used by the deduplicated
 /// list/search query. `#[sqlx(flatten)]` reuses `History`'s `FromRow` impl.
 #[derive(sqlx::FromRow)]
@@ -348,6 +430,29 @@ impl Sqlite {
         Ok(Self { pool })
     }
 
+    /// Opens someone else's database to read from, without creating, migrating or otherwise
+    /// writing to it.
+    ///
+    /// Refuses a database whose schema isn't exactly the one this version would migrate it to.
+    pub async fn open_read_only(path: impl AsRef<Path>, timeout: f64) -> Result<Self> {
+        let path = path.as_ref();
+        debug!("opening sqlite database at {path:?} read-only");
+
+        let opts = SqliteConnectOptions::from_str(path.as_os_str().to_str().unwrap())?
+            .read_only(true)
+            .with_regexp();
+
+        let pool = SqlitePoolOptions::new()
+            .acquire_timeout(Duration::try_from_secs_f64(timeout).map_err(|e| {
+                sqlx::Error::Decode(format!("invalid db timeout {timeout}: {e}").into())
+            })?)
+            .connect_with(opts)
+            .await?;
+
+        check_schema(&pool, &sqlx::migrate!("./migrations")).await?;
+        Ok(Self { pool })
+    }
+
     #[instrument(level = "trace", skip_all, err)]
     pub async fn sqlite_version(&self) -> Result<String> {
         sqlx::query_scalar("SELECT sqlite_version()").fetch_one(&self.pool).await
@@ -399,6 +504,86 @@ impl Sqlite {
             .execute(&mut **tx)
             .await?;
 
+        sqlx::query("delete from history_amendments where id = ?1")
+            .bind(id.0.as_str())
+            .execute(&mut **tx)
+            .await?;
+
+        sqlx::query("delete from local_only_history where id = ?1")
+            .bind(id.0.as_str())
+            .execute(&mut **tx)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn load_amendments_raw(
+        tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        id: &HistoryId,
+    ) -> Result<Option<Amendments>> {
+        let row = sqlx::query("select * from history_amendments where id = ?1")
+            .bind(id.0.as_str())
+            .fetch_optional(&mut **tx)
+            .await?;
+
+        row.as_ref().map(amendments_from_row).transpose()
+    }
+
+    /// Another entry, run in the same directory at the same time as `id`, with `command`. No two
+    /// entries can have all three the same.
+    async fn clashing_raw(
+        tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        id: &HistoryId,
+        command: &str,
+    ) -> Result<Option<HistoryId>> {
+        let other: Option<String> = sqlx::query_scalar(
+            "select other.id from history other, history this
+            where this.id = ?1 and other.id != this.id and other.command = ?2
+                and other.timestamp = this.timestamp and other.cwd = this.cwd",
+        )
+        .bind(id.0.as_str())
+        .bind(command)
+        .fetch_optional(&mut **tx)
+        .await?;
+
+        Ok(other.map(HistoryId))
+    }
+
+    /// Another entry, run in the same directory at the same time as `id`, with `command`, which
+    /// editing `id` to `command` would clash with.
+    pub async fn clashing(&self, id: &HistoryId, command: &str) -> Result<Option<HistoryId>> {
+        let mut tx = self.pool.begin().await?;
+        let other = Self::clashing_raw(&mut tx, id, command).await?;
+        tx.commit().await?;
+
+        Ok(other)
+    }
+
+    async fn save_amendments_raw(
+        tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        id: &HistoryId,
+        amendments: &Amendments,
+    ) -> Result<()> {
+        let redactions = serde_json::to_string(&amendments.redactions)
+            .map_err(|e| sqlx::Error::Encode(e.into()))?;
+        let (recorded_command, recorded_intent) = amendments.recorded.clone().unzip();
+
+        sqlx::query(
+            "insert or replace into history_amendments(
+                id, recorded_command, recorded_intent, edited_at, edited_command, edited_intent,
+                redactions
+            ) values(?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )
+        .bind(id.0.as_str())
+        .bind(recorded_command)
+        .bind(recorded_intent.flatten())
+        .bind(amendments.edit.as_ref().map(|e| e.timestamp.unix_timestamp_nanos() as i64))
+        .bind(amendments.edit.as_ref().map(|e| e.command.as_str()))
+        .bind(amendments.edit.as_ref().and_then(|e| e.intent.as_deref()))
+        .bind(redactions)
+        .execute(&mut **tx)
+        .await?;
+
         Ok(())
     }
 
@@ -628,6 +813,60 @@ impl Sqlite {
         Ok(res)
     }
 
+    /// Up to `limit` entries `rule` matches that were recorded before `timestamp` and aren't
+    /// deleted, oldest first. Pass the last entry of a page as `after` for the next.
+    #[instrument(level = "trace", skip_all, fields(limit), err)]
+    pub async fn expiring(
+        &self,
+        timestamp: OffsetDateTime,
+        rule: &Rule,
+        after: Option<&History>,
+        limit: usize,
+    ) -> Result<Vec<History>> {
+        let mut sql = SqlBuilder::select_from("history");
+        sql.and_where_lt("timestamp", timestamp.unix_timestamp_nanos() as i64)
+            .and_where_is_null("deleted_at")
+            .order_asc("timestamp")
+            .order_asc("id")
+            .limit(limit);
+
+        if rule.failed {
+            sql.and_where("exit != 0 and duration != -1");
+        }
+        if let Some(command) = &rule.command {
+            sql.and_where("command regexp ?".bind(&command.as_str()));
+        }
+        if let Some(cwd) = &rule.cwd {
+            sql.and_where("cwd regexp ?".bind(&cwd.as_str()));
+        }
+        if let Some(host) = &rule.host {
+            sql.and_where(host_sql_filter(host));
+        }
+        if let Some(after) = after {
+            let timestamp = after.timestamp.unix_timestamp_nanos() as i64;
+            sql.and_where("(timestamp, id) > (?, ?)".binds(&[&timestamp, &after.id.0.as_str()]));
+        }
+
+        let query = sql.sql().expect("bug in retention query. please report");
+        let res =
+            sqlx::query_as::<_, History>(sqlx::AssertSqlSafe(query)).fetch_all(&self.pool).await?;
+
+        Ok(res)
+    }
+
+    /// The `count` oldest entries that aren't deleted, oldest first.
+    #[instrument(level = "trace", skip_all, fields(count), err)]
+    pub async fn oldest(&self, count: i64) -> Result<Vec<History>> {
+        let res = sqlx::query_as::<_, History>(
+            "select * from history where deleted_at is null order by timestamp asc limit ?1",
+        )
+        .bind(count)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(res)
+    }
+
     #[instrument(level = "trace", skip_all, fields(include_deleted), err)]
     pub async fn history_count(&self, include_deleted: bool) -> Result<i64> {
         let query = if include_deleted {
@@ -640,6 +879,38 @@ impl Sqlite {
         Ok(res.0)
     }
 
+    /// Keep the entry `id` on this machine only: it's never added to the record store.
+    #[instrument(level = "trace", skip_all, fields(id = ?id), err)]
+    pub async fn mark_local_only(&self, id: &HistoryId) -> Result<()> {
+        sqlx::query("insert or ignore into local_only_history(id) values(?1)")
+            .bind(id.0.as_str())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Whether the entry `id` is kept on this machine only.
+    #[instrument(level = "trace", skip_all, fields(id = ?id), err)]
+    pub async fn is_local_only(&self, id: &HistoryId) -> Result<bool> {
+        let res: Option<(String,)> =
+            sqlx::query_as("select id from local_only_history where id = ?1")
+                .bind(id.0.as_str())
+                .fetch_optional(&self.pool)
+                .await?;
+
+        Ok(res.is_some())
+    }
+
+    /// Every entry kept on this machine only.
+    #[instrument(level = "trace", skip_all, err)]
+    pub async fn local_only_ids(&self) -> Result<HashSet<HistoryId>> {
+        let ids: Vec<(String,)> =
+            sqlx::query_as("select id from local_only_history").fetch_all(&self.pool).await?;
+
+        Ok(ids.into_iter().map(|(id,)| HistoryId(id)).collect())
+    }
+
     #[instrument(level = "trace", skip_all, err)]
     pub async fn search(
         &self,
@@ -663,6 +934,10 @@ impl Sqlite {
 
         let session_start = get_session_start_time(&context.session);
 
+        // Inline filters like `exit:!0` narrow the search, but aren't part of the command match.
+        let (query, query_filters) = predicate::split_filters(query);
+        let query = query.as_str();
+
         match filter {
             FilterMode::Global => &mut sql,
             FilterMode::Host => sql.and_where(origin_sql_filter(&context.cmd_origin)),
@@ -680,61 +955,29 @@ impl Sqlite {
 
         let orig_query = query;
 
-        let mut regexes = Vec::new();
-        match search_mode {
-            DbSearchMode::Prefix => sql.and_where_like_left("command", query.replace('*', "%")),
-            DbSearchMode::FullText | DbSearchMode::Fuzzy => {
-                let mut is_or = false;
-                for token in QueryTokenizer::new(query) {
-                    // TODO smart case mode could be made configurable like in fzf
-                    let (is_glob, glob) = if token.has_uppercase() {
-                        (true, "*")
-                    } else {
-                        (false, "%")
-                    };
-                    let param = match token {
-                        QueryToken::Regex(r) => {
-                            regexes.push(String::from(r));
-                            continue;
-                        }
-                        QueryToken::Or => {
-                            if !is_or {
-                                is_or = true;
-                                continue;
-                            } else {
-                                format!("{glob}|{glob}")
-                            }
-                        }
-                        QueryToken::MatchStart(term, _) => {
-                            format!("{term}{glob}")
-                        }
-                        QueryToken::MatchEnd(term, _) => {
-                            format!("{glob}{term}")
-                        }
-                        QueryToken::MatchFull(term, _) => {
-                            format!("{glob}{term}{glob}")
-                        }
-                        QueryToken::Match(term, _) => {
-                            if search_mode == DbSearchMode::FullText {
-                                format!("{glob}{term}{glob}")
-                            } else {
-                                term.split("").join(glob)
-                            }
-                        }
-                    };
-
-                    sql.fuzzy_condition("command", param, token.is_inverse(), is_glob, is_or);
-                    is_or = false;
-                }
-
-                &mut sql
-            }
-        };
+        let (patterns, regexes) = search::command_patterns(search_mode, query);
+        for pattern in patterns {
+            sql.fuzzy_condition(
+                "command",
+                pattern.pattern,
+                pattern.inverse,
+                pattern.glob,
+                pattern.or,
+            );
+        }
 
         for regex in regexes {
             sql.and_where("command regexp ?".bind(&regex));
         }
 
+        if let Some(ids) = filter_options.ids {
+            if ids.is_empty() {
+                return Ok(Vec::new());
+            }
+            let ids: Vec<String> = ids.iter().map(|id| quote(&id.0)).collect();
+            sql.and_where_in("id", &ids);
+        }
+
         filter_options.exit.map(|exit| sql.and_where_eq("exit", exit));
 
         filter_options.exclude_exit.map(|exclude_exit| sql.and_where_ne("exit", exclude_exit));
@@ -747,29 +990,30 @@ impl Sqlite {
 
         filter_options.exclude_cwd.map(|exclude_cwd| sql.and_where_ne("cwd", quote(exclude_cwd)));
 
+        let invalid_date = |e: search::SearchQueryError| sqlx::Error::Decode(e.to_string().into());
         if let Some(before) = filter_options.before {
-            let parsed =
-                interim::parse_date_string(before, OffsetDateTime::now_utc(), interim::Dialect::Uk)
-                    .map_err(|e| {
-                        sqlx::Error::Decode(
-                            format!("invalid `before` filter {before:?}: {e}").into(),
-                        )
-                    })?;
-            sql.and_where_lt("timestamp", quote(parsed.unix_timestamp_nanos() as i64));
+            let parsed = search::parse_date("before", before).map_err(invalid_date)?;
+            sql.and_where_lt("timestamp", quote(parsed));
         }
 
         if let Some(after) = filter_options.after {
-            let parsed =
-                interim::parse_date_string(after, OffsetDateTime::now_utc(), interim::Dialect::Uk)
-                    .map_err(|e| {
-                        sqlx::Error::Decode(format!("invalid `after` filter {after:?}: {e}").into())
-                    })?;
-            sql.and_where_gt("timestamp", quote(parsed.unix_timestamp_nanos() as i64));
+            let parsed = search::parse_date("after", after).map_err(invalid_date)?;
+            sql.and_where_gt("timestamp", quote(parsed));
         }
 
-        apply_author_filter(&mut sql, filter_options.authors);
+        // An inline author filter replaces the caller's, which in interactive search hides agents.
+        let has_author_filter = query_filters
+            .iter()
+            .any(|f| !f.negated && matches!(f.predicate, predicate::Predicate::Author(_)));
+        if !has_author_filter {
+            apply_author_filter(&mut sql, filter_options.authors);
+        }
         apply_shell_filter(&mut sql, filter_options.shells);
 
+        for filter in &query_filters {
+            sql.and_where(filter.to_sql());
+        }
+
         sql.and_where_is_null("deleted_at");
 
         // sql_builder inlines every bound value, so the inner query carries no
@@ -811,18 +1055,171 @@ impl Sqlite {
         let res =
             sqlx::query_as::<_, History>(sqlx::AssertSqlSafe(query)).fetch_all(&self.pool).await?;
 
+        self.arrange(search_mode, orig_query, context, filter_options.ranker, res).await
+    }
+
+    /// Put the results of a search in their final order: fuzzy searches by match span, then by
+    /// `ranker`, if there is one. `results` must come in the order [`Self::search`] fetches them.
+    ///
+    /// This is the part of a search that runs after the database query, and so also after the
+    /// daemon answers a search in its place.
+    #[instrument(level = "trace", skip_all, fields(results = results.len()), err)]
+    pub async fn arrange(
+        &self,
+        search_mode: DbSearchMode,
+        query: &str,
+        context: &Context,
+        ranker: Option<&dyn Ranker>,
+        results: Vec<History>,
+    ) -> Result<Vec<History>> {
+        let (query, _) = predicate::split_filters(query);
+
         // Rank against the same characters SQL matched: drop spaces, operators and negated terms.
-        let reorder_query: String = QueryTokenizer::new(orig_query)
+        let reorder_query: String = QueryTokenizer::new(&query)
             .filter(|token| !token.is_inverse())
             .filter_map(|token| match token {
                 QueryToken::Match(term, _)
                 | QueryToken::MatchStart(term, _)
                 | QueryToken::MatchEnd(term, _)
                 | QueryToken::MatchFull(term, _) => Some(term),
-                QueryToken::Or | QueryToken::Regex(_) => None,
+                QueryToken::Or | QueryToken::Regex(_) | QueryToken::Filter(_) => None,
             })
             .collect();
-        Ok(ordering::reorder_fuzzy(search_mode, &reorder_query, res))
+        let results = ordering::reorder_fuzzy(search_mode, &reorder_query, results);
+
+        match ranker {
+            Some(ranker) => self.rank(ranker, &query, context, results).await,
+            None => Ok(results),
+        }
+    }
+
+    /// Reorder `results` with `ranker`, first loading whatever extra data it asks for.
+    #[instrument(level = "trace", skip_all, fields(results = results.len()), err)]
+    pub async fn rank(
+        &self,
+        ranker: &dyn Ranker,
+        query: &str,
+        context: &Context,
+        results: Vec<History>,
+    ) -> Result<Vec<History>> {
+        let mut ctx = RankContext::new(query, context);
+
+        if ranker.uses_frecency() {
+            ctx.frecency = self.frecency(results.iter().map(|h| h.command.as_str())).await?;
+        }
+
+        if ranker.uses_affinity() {
+            ctx.followers = self.followers(&context.session).await?;
+        }
+
+        Ok(ranker.rank(&ctx, results))
+    }
+
+    /// Usage count and last use of each of `commands`, across all of history.
+    #[instrument(level = "trace", skip_all, err)]
+    pub async fn frecency<'a>(
+        &self,
+        commands: impl IntoIterator<Item = &'a str>,
+    ) -> Result<HashMap<String, FrecencyData>> {
+        let commands: Vec<String> = commands.into_iter().unique().map(quote).collect();
+        if commands.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let query = SqlBuilder::select_from("history")
+            .fields(&["command", "count(1)", "max(timestamp)"])
+            .and_where_in("command", &commands)
+            .and_where_is_null("deleted_at")
+            .group_by("command")
+            .sql()
+            .expect("bug in frecency query. please report");
+
+        let rows: Vec<(String, i64, i64)> =
+            sqlx::query_as(sqlx::AssertSqlSafe(query)).fetch_all(&self.pool).await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(command, count, last_used)| {
+                let data = FrecencyData {
+                    count: u32::try_from(count).unwrap_or(u32::MAX),
+                    last_used: OffsetDateTime::from_unix_nanos_i64(last_used).unix_timestamp(),
+                };
+                (command, data)
+            })
+            .collect())
+    }
+
+    /// How often each command has directly followed the last command run in `session`.
+    ///
+    /// Only the most recent [`AFFINITY_SAMPLE`] runs of that command are looked at, which keeps
+    /// this cheap for very common commands like `ls`.
+    #[instrument(level = "trace", skip_all, err)]
+    pub async fn followers(&self, session: &str) -> Result<HashMap<String, u32>> {
+        if session.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let rows: Vec<(String, i64)> = sqlx::query_as(
+            "select n.command, count(1) from (
+                select p.session, p.timestamp from history p
+                where p.command = (
+                    select command from history
+                    where session = ?1 and deleted_at is null
+                    order by timestamp desc limit 1
+                )
+                and p.deleted_at is null
+                order by p.timestamp desc limit ?2
+            ) p
+            join history n on n.session = p.session and n.timestamp = (
+                select min(timestamp) from history
+                where session = p.session and timestamp > p.timestamp
+            )
+            where n.deleted_at is null
+            group by n.command",
+        )
+        .bind(session)
+        .bind(AFFINITY_SAMPLE)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|(command, count)| (command, u32::try_from(count).unwrap_or(u32::MAX)))
+            .collect())
+    }
+
+    /// The most recent run of each of `commands`, in the order given. Commands that aren't in
+    /// history are skipped.
+    #[instrument(level = "trace", skip_all, err)]
+    pub async fn latest_by_command(&self, commands: &[String]) -> Result<Vec<History>> {
+        if commands.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let placeholders = ["?"].repeat(commands.len()).join(",");
+        let sql = format!(
+            "select * from history h
+            where command in ({placeholders}) and deleted_at is null
+            and id = (
+                select id from history
+                where command = h.command and deleted_at is null
+                order by timestamp desc limit 1
+            )"
+        );
+
+        let mut query = sqlx::query_as::<_, History>(sqlx::AssertSqlSafe(sql));
+        for command in commands {
+            query = query.bind(command.as_str());
+        }
+
+        let mut rows: HashMap<String, History> = query
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|h| (h.command.clone(), h))
+            .collect();
+
+        Ok(commands.iter().filter_map(|c| rows.remove(c)).collect())
     }
 
     #[instrument(level = "trace", skip_all, err)]
@@ -901,6 +1298,121 @@ impl Sqlite {
         Ok(())
     }
 
+    /// Apply edits and redactions to the entries they amend, whatever order they come in. An
+    /// entry that hasn't arrived yet is amended when it does, by [`Self::apply_amendments`].
+    ///
+    /// Returns the amended entries that aren't deleted, as they now are.
+    #[instrument(level = "trace", skip_all, err)]
+    pub async fn amend(
+        &self,
+        amendments: impl IntoIterator<Item = Amendment>,
+    ) -> Result<Vec<History>> {
+        let mut amendments = amendments.into_iter().peekable();
+        if amendments.peek().is_none() {
+            return Ok(Vec::new());
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let mut ids = Vec::new();
+
+        for amendment in amendments {
+            let id = amendment.id().clone();
+
+            let mut current = match Self::load_amendments_raw(&mut tx, &id).await? {
+                Some(current) => current,
+                // The entry's first amendment, so it's still as recorded, if it's here yet.
+                None => {
+                    let mut current = Amendments::default();
+                    let recorded: Option<(String, Option<String>)> =
+                        sqlx::query_as("select command, intent from history where id = ?1")
+                            .bind(id.0.as_str())
+                            .fetch_optional(&mut *tx)
+                            .await?;
+                    if let Some((command, intent)) = recorded {
+                        current.record(&command, intent.as_deref());
+                    }
+                    current
+                }
+            };
+
+            current.apply(amendment);
+            Self::save_amendments_raw(&mut tx, &id, &current).await?;
+
+            if let Some((command, intent)) = current.current() {
+                if let Some(other) = Self::clashing_raw(&mut tx, &id, &command).await? {
+                    // The amendment is kept, but the entry can't show it while the other's there.
+                    warn!(
+                        "can't amend history entry {id}: entry {other}, run in the same directory \
+                         at the same time, already has that command. Delete one of them to keep \
+                         the other"
+                    );
+                    continue;
+                }
+
+                sqlx::query("update history set command = ?2, intent = ?3 where id = ?1")
+                    .bind(id.0.as_str())
+                    .bind(command)
+                    .bind(intent)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+
+            ids.push(id);
+        }
+
+        tx.commit().await?;
+
+        self.load_active(ids.into_iter().unique()).await
+    }
+
+    /// Apply the amendments that arrived before `entries` did, ahead of saving them.
+    #[instrument(level = "trace", skip_all, err)]
+    pub async fn apply_amendments(&self, entries: &mut [History]) -> Result<()> {
+        // Kept under sqlite's cap on bound parameters, as in `load_active`.
+        const CHUNK: usize = 500;
+
+        let mut tx = self.pool.begin().await?;
+
+        for chunk in entries.chunks_mut(CHUNK) {
+            let placeholders = ["?"].repeat(chunk.len()).join(",");
+            let sql = format!("select * from history_amendments where id in ({placeholders})");
+
+            let mut query = sqlx::query(sqlx::AssertSqlSafe(sql));
+            for h in chunk.iter() {
+                query = query.bind(h.id.0.as_str());
+            }
+
+            let mut found = HashMap::new();
+            for row in query.fetch_all(&mut *tx).await? {
+                found.insert(row.try_get::<String, _>("id")?, amendments_from_row(&row)?);
+            }
+
+            if found.is_empty() {
+                continue;
+            }
+
+            for h in chunk.iter_mut() {
+                let Some(mut amendments) = found.remove(h.id.0.as_str()) else {
+                    continue;
+                };
+
+                if amendments.recorded.is_none() {
+                    amendments.record(&h.command, h.intent.as_deref());
+                    Self::save_amendments_raw(&mut tx, &h.id, &amendments).await?;
+                }
+
+                if let Some((command, intent)) = amendments.current() {
+                    h.command = command;
+                    h.intent = intent;
+                }
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
     #[instrument(level = "trace", skip_all, fields(id = ?h.id), err)]
     pub async fn stats(&self, h: &History) -> Result<HistoryStats> {
         // We select the previous in the session by time. Excluding deleted
@@ -1003,6 +1515,7 @@ impl Sqlite {
             exits,
             day_of_week,
             duration_over_time,
+            output: None,
         })
     }
 
@@ -1133,6 +1646,8 @@ pub enum QueryToken<'a> {
     MatchFull(&'a str, bool),
     Or,
     Regex(&'a str),
+    /// An inline filter, like `exit:!0`. See [`predicate`].
+    Filter(QueryFilter),
 }
 
 impl QueryToken<'_> {
@@ -1193,6 +1708,10 @@ impl<'a> Iterator for QueryTokenizer<'a> {
                 return Some(QueryToken::Or);
             }
 
+            if let Some(filter) = QueryFilter::parse(part) {
+                return Some(QueryToken::Filter(filter));
+            }
+
             let is_inverse = if let Some(s) = part.strip_prefix('!') {
                 part = s;
                 true
@@ -1221,6 +1740,8 @@ mod test {
     use time::format_description::well_known::Rfc3339;
 
     use super::*;
+    use crate::history::amend::{HistoryRedaction, Redacts};
+    use crate::ranking::{Affinity, Frecency, Pipeline};
     use crate::settings::test_local_timeout;
 
     /// `ATUIN_SESSION` comes from the environment: a stray value whose version nibble reads as a
@@ -1433,6 +1954,71 @@ mod test {
         assert_eq!(loaded[0].command, "echo bravo");
     }
 
+    #[rstest]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_amendments_keep_no_redacted_text(
+        #[future(awt)]
+        #[from(empty_db)]
+        db: Sqlite,
+    ) {
+        let entry = save_history_item(&db, "mysql -p hunter2").await;
+        let redacts = Redacts::Pattern("hunter2".to_string());
+        let redaction = HistoryRedaction::new(entry.id.clone(), redacts).unwrap();
+        let edit = HistoryEdit {
+            id: entry.id.clone(),
+            timestamp: OffsetDateTime::now_utc(),
+            command: "mysql -u root -p hunter2".to_string(),
+            intent: None,
+        };
+
+        db.amend([Amendment::Redact(redaction), Amendment::Edit(edit)]).await.unwrap();
+
+        let kept: (Option<String>, Option<String>) = sqlx::query_as(
+            "select recorded_command, edited_command from history_amendments where id = ?1",
+        )
+        .bind(entry.id.0.as_str())
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+        assert_eq!(
+            kept,
+            (
+                Some("mysql -p [REDACTED]".to_string()),
+                Some("mysql -u root -p [REDACTED]".to_string())
+            )
+        );
+    }
+
+    #[rstest]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_amendments_that_clash_leave_the_entry(
+        #[future(awt)]
+        #[from(empty_db)]
+        db: Sqlite,
+    ) {
+        let alpha = save_history_item(&db, "echo alpha").await;
+        let bravo = History {
+            id: HistoryId(utils::uuid_v7().as_simple().to_string()),
+            command: "echo bravo".to_string(),
+            ..alpha.clone()
+        };
+        db.save(&bravo).await.unwrap();
+
+        assert_eq!(db.clashing(&bravo.id, "echo alpha").await.unwrap(), Some(alpha.id.clone()));
+        assert_eq!(db.clashing(&bravo.id, "echo charlie").await.unwrap(), None);
+
+        let edit = HistoryEdit {
+            id: bravo.id.clone(),
+            timestamp: OffsetDateTime::now_utc(),
+            command: "echo alpha".to_string(),
+            intent: None,
+        };
+        let amended = db.amend([Amendment::Edit(edit)]).await.unwrap();
+
+        assert!(amended.is_empty());
+        assert_eq!(db.load(&bravo.id.0).await.unwrap().unwrap().command, "echo bravo");
+    }
+
     #[rstest]
     #[tokio::test(flavor = "multi_thread")]
     async fn test_load_active_missing_ids_are_omitted(
@@ -1546,6 +2132,38 @@ mod test {
         assert_eq!(hits.len(), expected);
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_search_ids() {
+        let db = db_with(&["ls", "cargo build", "ls"]).await;
+        let context = new_context();
+        let all = db
+            .search(DbSearchMode::FullText, FilterMode::Global, &context, "", OptFilters {
+                include_duplicates: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        let search = |ids: Vec<HistoryId>| {
+            let db = &db;
+            let context = &context;
+            async move {
+                db.search(DbSearchMode::FullText, FilterMode::Global, context, "ls", OptFilters {
+                    ids: Some(&ids),
+                    include_duplicates: true,
+                    ..Default::default()
+                })
+                .await
+                .unwrap()
+                .len()
+            }
+        };
+
+        assert_eq!(search(all.iter().map(|h| h.id.clone()).collect()).await, 2);
+        assert_eq!(search(vec![all[0].id.clone()]).await, 1);
+        assert_eq!(search(Vec::new()).await, 0);
+    }
+
     #[rstest]
     #[case::before("before")]
     #[case::after("after")]
@@ -1712,6 +2330,130 @@ mod test {
         .await;
     }
 
+    async fn save_in_session(db: &Sqlite, cmd: &str, session: &str, seconds_ago: i64) {
+        let history: History = History::import()
+            .timestamp(OffsetDateTime::now_utc() - time::Duration::seconds(seconds_ago))
+            .command(cmd)
+            .cwd("/home/ellie")
+            .session(session)
+            .exit(0)
+            .build()
+            .into();
+        db.save(&history).await.unwrap();
+    }
+
+    #[rstest]
+    #[case::exit(DbSearchMode::Fuzzy, "cargo exit:!0", &["cargo test"])]
+    #[case::duration(DbSearchMode::FullText, "dur:>10s cargo", &["cargo test"])]
+    #[case::cwd(DbSearchMode::Prefix, "cargo cwd:/tmp", &["cargo build"])]
+    #[case::only_filters(DbSearchMode::Fuzzy, "exit:0 dur:<10s", &["cargo build"])]
+    #[case::negated_author(DbSearchMode::FullText, "cargo !author:$all-user", &[])]
+    #[case::author_replaces_default(DbSearchMode::FullText, "author:$all-agent", &["cargo fmt"])]
+    #[case::half_typed_filter(DbSearchMode::FullText, "cargo dur:>3", &[])]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_search_inline_filters(
+        #[case] mode: DbSearchMode,
+        #[case] query: &str,
+        #[case] expected: &[&str],
+    ) {
+        let db = Sqlite::new("sqlite::memory:", test_local_timeout()).await.unwrap();
+        let now = OffsetDateTime::now_utc();
+
+        for (command, cwd, exit, seconds, author) in [
+            ("cargo build", "/tmp", 0, 1, None),
+            ("cargo test", "/home/ellie", 101, 40, None),
+            ("cargo fmt", "/home/ellie", 0, 1, Some("claude-code")),
+        ] {
+            let mut history: History = History::import()
+                .timestamp(now - time::Duration::seconds(seconds))
+                .command(command)
+                .cwd(cwd)
+                .exit(exit)
+                .duration(seconds * 1_000_000_000)
+                .build()
+                .into();
+            if let Some(author) = author {
+                history.author = author.to_string();
+                history.author_kind = Some(AuthorKind::Agent);
+            }
+            db.save(&history).await.unwrap();
+        }
+
+        let results = db
+            .search(mode, FilterMode::Global, &new_context(), query, OptFilters {
+                authors: crate::history::all_user_author_filter(),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        let mut commands: Vec<_> = results.iter().map(|h| h.command.as_str()).collect();
+        commands.sort_unstable();
+
+        assert_eq!(commands, expected);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_search_ranks_with_frecency_and_affinity() {
+        let db = Sqlite::new("sqlite::memory:", test_local_timeout()).await.unwrap();
+        let context = new_context();
+
+        for (i, next) in ["cargo test", "cargo test", "cargo clippy"].into_iter().enumerate() {
+            let session = format!("old-session-{i}");
+            save_in_session(&db, "cargo build", &session, 1000).await;
+            save_in_session(&db, next, &session, 990).await;
+        }
+        save_in_session(&db, "cargo fmt", "old-session-3", 500).await;
+        save_in_session(&db, "cargo build", &context.session, 10).await;
+
+        let frecency = db.frecency(["cargo build", "cargo test", "nope"]).await.unwrap();
+        assert_eq!(frecency["cargo build"].count, 4);
+        assert_eq!(frecency["cargo test"].count, 2);
+        assert!(!frecency.contains_key("nope"));
+
+        let followers = db.followers(&context.session).await.unwrap();
+        assert_eq!(
+            followers,
+            HashMap::from([("cargo test".to_string(), 2), ("cargo clippy".to_string(), 1)])
+        );
+
+        let search = async |ranker: Pipeline| {
+            let results = db
+                .search(DbSearchMode::FullText, FilterMode::Global, &context, "cargo", OptFilters {
+                    ranker: Some(&ranker),
+                    ..Default::default()
+                })
+                .await
+                .unwrap();
+            results.into_iter().map(|h| h.command).collect::<Vec<_>>()
+        };
+
+        let affinity = search(Pipeline::new().with(1.0, Affinity)).await;
+        assert_eq!(affinity[..2], ["cargo test", "cargo clippy"]);
+
+        let frecency = search(Pipeline::new().with(1.0, Frecency {
+            recency_mul: 0.0,
+            frequency_mul: 1.0,
+        }))
+        .await;
+        assert_eq!(frecency[..2], ["cargo build", "cargo test"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_latest_by_command() {
+        let db = Sqlite::new("sqlite::memory:", test_local_timeout()).await.unwrap();
+
+        save_in_session(&db, "cargo build", "old", 100).await;
+        save_in_session(&db, "cargo test", "old", 90).await;
+        save_in_session(&db, "cargo build", "new", 10).await;
+
+        let commands = ["cargo test", "nope", "cargo build"].map(String::from);
+        let latest = db.latest_by_command(&commands).await.unwrap();
+
+        let found: Vec<_> =
+            latest.iter().map(|h| (h.command.as_str(), h.session.as_str())).collect();
+        assert_eq!(found, [("cargo test", "old"), ("cargo build", "new")]);
+    }
+
     // Reproduces the trailing-space ranking bug (atuinsh/atuin#3603): "screen" ranked the results
     // containing `screen` first, but "screen " prioritized an unrelated `ls` command.
     #[rstest]
@@ -0,0 +1,504 @@
+//! Inline search filters, like `cwd:~/src`, `exit:!0` or `dur:>30s`.
+//!
+//! [`QueryTokenizer`] turns any word of the form `field:value` with a known field and a valid value
+//! into a [`QueryToken::Filter`]. Words that don't parse, such as a half-typed `dur:>3`, are left
+//! as ordinary search terms, so typing a filter in the interactive search never errors.
+//!
+//! `Sqlite::search` compiles filters to SQL with [`QueryFilter::to_sql`], and the daemon's search
+//! index checks them against each invocation of a command with [`QueryFilter::matches`]. The two
+//! must agree; the tests at the bottom of this file pin them together.
+//!
+//! | Field             | Value                                  | Example              |
+//! |-------------------|----------------------------------------|----------------------|
+//! | `cwd`, `dir`      | a directory; `*` and `?` are wildcards | `cwd:~/src/*`        |
+//! | `host`            | a hostname; `*` and `?` are wildcards  | `host:build-*`       |
+//! | `exit`            | an exit code, optionally compared      | `exit:0`, `exit:>1`  |
+//! | `dur`, `duration` | a duration, optionally compared        | `dur:>30s`           |
+//! | `after`, `before` | a date, or a duration ago              | `after:yesterday`    |
+//! | `author`          | an author, `$all-user` or `$all-agent` | `author:$all-agent`  |
+//!
+//! A `!` before the field or the value negates the filter: `exit:!0` and `!exit:0` are the same.
+
+use std::fmt;
+
+use atuin_common::utils;
+use sql_builder::quote;
+use time::OffsetDateTime;
+
+use super::{QueryToken, QueryTokenizer, author_sql_filter};
+use crate::history::{AuthorPattern, History};
+
+/// The host half of `hostname`, lowercased. See `origin_sql_filter` for why this can't just
+/// compare against the whole column.
+const HOST_SQL: &str = "lower(CASE WHEN instr(hostname, ':') > 0 THEN substr(hostname, 1, \
+                        instr(hostname, ':') - 1) ELSE hostname END)";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn eval(self, lhs: i64, rhs: i64) -> bool {
+        match self {
+            Self::Eq => lhs == rhs,
+            Self::Lt => lhs < rhs,
+            Self::Le => lhs <= rhs,
+            Self::Gt => lhs > rhs,
+            Self::Ge => lhs >= rhs,
+        }
+    }
+
+    /// Split a leading comparison operator off `value`, defaulting to [`Op::Eq`].
+    fn strip(value: &str) -> (Self, &str) {
+        [(">=", Self::Ge), ("<=", Self::Le), (">", Self::Gt), ("<", Self::Lt), ("=", Self::Eq)]
+            .into_iter()
+            .find_map(|(prefix, op)| value.strip_prefix(prefix).map(|rest| (op, rest)))
+            .unwrap_or((Self::Eq, value))
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Eq => "=",
+            Self::Lt => "<",
+            Self::Le => "<=",
+            Self::Gt => ">",
+            Self::Ge => ">=",
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Comparison {
+    pub op: Op,
+    pub value: i64,
+}
+
+impl Comparison {
+    fn matches(self, value: i64) -> bool {
+        self.op.eval(value, self.value)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Predicate {
+    /// The working directory matches a glob, with `~` expanded.
+    Cwd(String),
+    /// The host matches a lowercased glob.
+    Host(String),
+    /// The exit code. Commands that haven't finished never match.
+    Exit(Comparison),
+    /// The duration in nanoseconds. Commands that haven't finished never match.
+    Duration(Comparison),
+    /// Run strictly after this time, in unix nanoseconds.
+    After(i64),
+    /// Run strictly before this time, in unix nanoseconds.
+    Before(i64),
+    Author(AuthorPattern),
+}
+
+/// A single inline filter, parsed from a word of the search query.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueryFilter {
+    pub predicate: Predicate,
+    pub negated: bool,
+}
+
+/// The parts of a history entry that filters look at.
+///
+/// This lets the daemon check filters without keeping whole [`History`] entries around.
+#[derive(Clone, Copy, Debug)]
+pub struct Invocation<'a> {
+    pub cwd: &'a str,
+    /// Only the host half of the origin.
+    pub host: &'a str,
+    /// The whole origin, as stored in the `hostname` column.
+    pub origin: &'a str,
+    pub session: &'a str,
+    pub shell: Option<&'a str>,
+    /// The author, falling back to the origin's user like the SQL author filter does.
+    pub author: &'a str,
+    pub is_agent: bool,
+    pub exit: i64,
+    pub duration: i64,
+    /// In unix nanoseconds.
+    pub timestamp: i64,
+}
+
+impl<'a> From<&'a History> for Invocation<'a> {
+    fn from(history: &'a History) -> Self {
+        let author = if history.author.trim().is_empty() {
+            history.cmd_origin.user().into_inner()
+        } else {
+            history.author.as_str()
+        };
+
+        Self {
+            cwd: &history.cwd,
+            host: history.cmd_origin.host().into_inner(),
+            origin: history.cmd_origin.as_str(),
+            session: &history.session,
+            shell: history.shell.as_deref(),
+            author,
+            is_agent: history.is_agent(),
+            exit: history.exit,
+            duration: history.duration,
+            timestamp: history.timestamp.unix_timestamp_nanos() as i64,
+        }
+    }
+}
+
+impl QueryFilter {
+    /// Parse a single word of a query, returning `None` if it isn't a filter.
+    pub fn parse(word: &str) -> Option<Self> {
+        let (mut negated, word) = match word.strip_prefix('!') {
+            Some(word) => (true, word),
+            None => (false, word),
+        };
+
+        let (field, value) = word.split_once(':')?;
+        let value = match value.strip_prefix('!') {
+            Some(value) => {
+                negated = !negated;
+                value
+            }
+            None => value,
+        };
+        if value.is_empty() {
+            return None;
+        }
+
+        let predicate = match field.to_lowercase().as_str() {
+            "cwd" | "dir" => Predicate::Cwd(expand_dir(value)),
+            "host" => Predicate::Host(value.to_lowercase()),
+            "exit" => {
+                let (op, value) = Op::strip(value);
+                Predicate::Exit(Comparison {
+                    op,
+                    value: value.parse().ok()?,
+                })
+            }
+            "dur" | "duration" => {
+                let (op, value) = Op::strip(value);
+                let value = humantime::parse_duration(value).ok()?;
+                Predicate::Duration(Comparison {
+                    op,
+                    value: i64::try_from(value.as_nanos()).ok()?,
+                })
+            }
+            "after" => Predicate::After(parse_time(value)?),
+            "before" => Predicate::Before(parse_time(value)?),
+            "author" => Predicate::Author(AuthorPattern::from(value)),
+            _ => return None,
+        };
+
+        Some(Self { predicate, negated })
+    }
+
+    /// A SQL condition over the `history` table, with every value inlined.
+    pub fn to_sql(&self) -> String {
+        let (guard, condition) = match &self.predicate {
+            Predicate::Cwd(pattern) => (None, glob_sql("cwd", pattern)),
+            Predicate::Host(pattern) => (None, glob_sql(HOST_SQL, pattern)),
+            Predicate::Exit(cmp) => (Some("exit != -1"), format!("exit {} {}", cmp.op, cmp.value)),
+            Predicate::Duration(cmp) => {
+                (Some("duration != -1"), format!("duration {} {}", cmp.op, cmp.value))
+            }
+            Predicate::After(time) => (None, format!("timestamp > {time}")),
+            Predicate::Before(time) => (None, format!("timestamp < {time}")),
+            Predicate::Author(author) => (None, author_sql_filter(std::slice::from_ref(author))),
+        };
+
+        let condition = if self.negated {
+            format!("NOT ({condition})")
+        } else {
+            condition
+        };
+
+        match guard {
+            Some(guard) => format!("{guard} AND {condition}"),
+            None => condition,
+        }
+    }
+
+    /// Whether a single run of a command passes this filter.
+    pub fn matches(&self, invocation: &Invocation<'_>) -> bool {
+        let matched = match &self.predicate {
+            Predicate::Cwd(pattern) => glob_matches(pattern, invocation.cwd),
+            Predicate::Host(pattern) => glob_matches(pattern, &invocation.host.to_lowercase()),
+            Predicate::Exit(cmp) => {
+                if invocation.exit == -1 {
+                    return false;
+                }
+                cmp.matches(invocation.exit)
+            }
+            Predicate::Duration(cmp) => {
+                if invocation.duration == -1 {
+                    return false;
+                }
+                cmp.matches(invocation.duration)
+            }
+            Predicate::After(time) => invocation.timestamp > *time,
+            Predicate::Before(time) => invocation.timestamp < *time,
+            Predicate::Author(author) => author_matches(author, invocation),
+        };
+
+        matched != self.negated
+    }
+}
+
+/// Whether `invocation` passes a single element of an author filter, like `author_sql_filter`.
+pub(super) fn author_matches(author: &AuthorPattern, invocation: &Invocation<'_>) -> bool {
+    match author {
+        AuthorPattern::AllUser => !invocation.is_agent,
+        AuthorPattern::AllAgent => invocation.is_agent,
+        AuthorPattern::Name(name) => invocation.author == name,
+    }
+}
+
+/// Split the filters out of `query`, returning the rest of the query and the filters.
+pub fn split_filters(query: &str) -> (String, Vec<QueryFilter>) {
+    let mut rest = String::with_capacity(query.len());
+    let mut filters = Vec::new();
+
+    let mut tokens = QueryTokenizer::new(query);
+    loop {
+        let start = tokens.last_pos;
+        let Some(token) = tokens.next() else {
+            break;
+        };
+
+        match token {
+            QueryToken::Filter(filter) => filters.push(filter),
+            _ => rest.push_str(&query[start..tokens.last_pos]),
+        }
+    }
+
+    if filters.is_empty() {
+        // whitespace is significant in prefix and full-text searches
+        return (query.to_string(), filters);
+    }
+
+    (rest.trim_end().to_string(), filters)
+}
+
+fn expand_dir(value: &str) -> String {
+    let value = if value == "~" || value.starts_with("~/") {
+        format!("{}{}", utils::home_dir().display(), &value[1..])
+    } else {
+        value.to_string()
+    };
+
+    match value.trim_end_matches('/') {
+        "" => "/".to_string(),
+        trimmed => trimmed.to_string(),
+    }
+}
+
+/// A date understood by `interim` (`yesterday`, `2024-01-31`), or a duration ago (`2h`, `3days`).
+fn parse_time(value: &str) -> Option<i64> {
+    let now = OffsetDateTime::now_utc();
+    let time = match humantime::parse_duration(value) {
+        Ok(ago) => now - ago,
+        Err(_) => interim::parse_date_string(value, now, interim::Dialect::Uk).ok()?,
+    };
+    i64::try_from(time.unix_timestamp_nanos()).ok()
+}
+
+/// Compare `expr` against `pattern`, where only `*` and `?` are wildcards.
+fn glob_sql(expr: &str, pattern: &str) -> String {
+    if pattern.contains(['*', '?']) {
+        // `[` starts a character class in GLOB; match it literally.
+        format!("{expr} GLOB {}", quote(pattern.replace('[', "[[]")))
+    } else {
+        format!("{expr} = {}", quote(pattern))
+    }
+}
+
+/// Match `text` against a glob where `*` matches any run of characters and `?` matches one, the
+/// same as [`glob_sql`].
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    // Where to resume after the last `*`: its position in the pattern, and the text it has eaten.
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((star_p, star_t)) => {
+                    p = star_p + 1;
+                    t = star_t + 1;
+                    star = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use atuin_domain::record::CmdOrigin;
+    use rstest::rstest;
+    use time::Duration;
+
+    use super::*;
+    use crate::database::Sqlite;
+    use crate::history::AuthorKind;
+    use crate::settings::test_local_timeout;
+
+    fn filter(word: &str) -> QueryFilter {
+        QueryFilter::parse(word).unwrap_or_else(|| panic!("{word:?} should be a filter"))
+    }
+
+    #[rstest]
+    #[case("exit:0", Predicate::Exit(Comparison { op: Op::Eq, value: 0 }), false)]
+    #[case("exit:!0", Predicate::Exit(Comparison { op: Op::Eq, value: 0 }), true)]
+    #[case("!exit:0", Predicate::Exit(Comparison { op: Op::Eq, value: 0 }), true)]
+    #[case("!exit:!0", Predicate::Exit(Comparison { op: Op::Eq, value: 0 }), false)]
+    #[case("exit:>=2", Predicate::Exit(Comparison { op: Op::Ge, value: 2 }), false)]
+    #[case("dur:>30s", Predicate::Duration(Comparison { op: Op::Gt, value: 30_000_000_000 }), false)]
+    #[case("duration:<1m", Predicate::Duration(Comparison { op: Op::Lt, value: 60_000_000_000 }), false)]
+    #[case("host:Build-*", Predicate::Host("build-*".to_string()), false)]
+    #[case("dir:/tmp/", Predicate::Cwd("/tmp".to_string()), false)]
+    #[case("cwd:/", Predicate::Cwd("/".to_string()), false)]
+    #[case("author:$all-agent", Predicate::Author(AuthorPattern::AllAgent), false)]
+    #[case("author:ellie", Predicate::Author(AuthorPattern::Name("ellie".to_string())), false)]
+    fn parses_filters(#[case] word: &str, #[case] predicate: Predicate, #[case] negated: bool) {
+        assert_eq!(filter(word), QueryFilter { predicate, negated });
+    }
+
+    #[rstest]
+    #[case::unknown_field("http://example.com")]
+    #[case::empty_value("cwd:")]
+    #[case::only_negation("exit:!")]
+    #[case::not_a_number("exit:zero")]
+    #[case::no_unit("dur:>3")]
+    #[case::not_a_date("after:whenever")]
+    #[case::no_field(":foo")]
+    fn leaves_other_words_alone(#[case] word: &str) {
+        assert_eq!(QueryFilter::parse(word), None);
+    }
+
+    #[test]
+    fn expands_home() {
+        let home = utils::home_dir();
+        assert_eq!(
+            filter("cwd:~/src").predicate,
+            Predicate::Cwd(format!("{}/src", home.display()))
+        );
+    }
+
+    #[test]
+    fn relative_times_are_in_the_past() {
+        let Predicate::After(after) = filter("after:2h").predicate else {
+            panic!("expected an `after` filter");
+        };
+        let two_hours_ago = (OffsetDateTime::now_utc() - Duration::hours(2)).unix_timestamp_nanos();
+        assert!((i128::from(after) - two_hours_ago).abs() < 1_000_000_000);
+    }
+
+    #[test]
+    fn splits_filters_from_the_query() {
+        let (rest, filters) = split_filters("cwd:/src git exit:!0 r/pu(sh|ll)/ dur:>3 push");
+
+        assert_eq!(rest, "git r/pu(sh|ll)/ dur:>3 push");
+        assert_eq!(filters, [filter("cwd:/src"), filter("exit:!0")]);
+    }
+
+    #[rstest]
+    #[case("/home/ellie/src", "/home/ellie/src", true)]
+    #[case("/home/ellie/*", "/home/ellie/src", true)]
+    #[case("/home/ellie/*", "/home/ellie", false)]
+    #[case("/home/*/src", "/home/ellie/src", true)]
+    #[case("/home/?llie", "/home/ellie", true)]
+    #[case("*src*", "/home/ellie/src/atuin", true)]
+    #[case("/tmp/[a]", "/tmp/[a]", true)]
+    #[case("/tmp/[a]", "/tmp/a", false)]
+    fn globs(#[case] pattern: &str, #[case] text: &str, #[case] expected: bool) {
+        assert_eq!(glob_matches(pattern, text), expected);
+    }
+
+    /// Every filter must select exactly the same rows in SQL as it does in Rust.
+    #[rstest]
+    #[case("cwd:/home/ellie")]
+    #[case("cwd:/home/*")]
+    #[case("cwd:!/tmp")]
+    #[case("host:build-*")]
+    #[case("host:LAPTOP")]
+    #[case("exit:0")]
+    #[case("exit:!0")]
+    #[case("exit:>1")]
+    #[case("dur:>30s")]
+    #[case("dur:<=1s")]
+    #[case("after:1h")]
+    #[case("before:1h")]
+    #[case("author:$all-agent")]
+    #[case("author:$all-user")]
+    #[case("author:ellie")]
+    #[case("!author:claude-code")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn sql_agrees_with_matches(#[case] word: &str) {
+        let db = Sqlite::new("sqlite::memory:", test_local_timeout()).await.unwrap();
+        let now = OffsetDateTime::now_utc();
+
+        #[rustfmt::skip]
+        let rows = [
+            ("a", "/home/ellie", "laptop:ellie", "", None, 0, 1_000_000, 10),
+            ("b", "/home/ellie/src", "build-01:ci", "", None, 1, 45_000_000_000, 3600 * 3),
+            ("c", "/tmp", "Laptop:ellie", "claude-code", Some(AuthorKind::Agent), 2, 2_000_000_000, 60),
+            ("d", "/tmp", "build-02:ci", "ellie", None, -1, -1, 5),
+            ("e", "/home/ellie", "laptop:ellie", "", None, 127, 31_000_000_000, 3600 * 24),
+        ];
+
+        let mut history = Vec::new();
+        for (command, cwd, origin, author, author_kind, exit, duration, seconds_ago) in rows {
+            let h: History = History::import()
+                .timestamp(now - Duration::seconds(seconds_ago))
+                .command(command)
+                .cwd(cwd)
+                .cmd_origin(CmdOrigin::try_from(origin).unwrap())
+                .author(author)
+                .author_kind(author_kind)
+                .exit(exit)
+                .duration(duration)
+                .build()
+                .into();
+            db.save(&h).await.unwrap();
+            history.push(h);
+        }
+
+        let filter = filter(word);
+        let mut expected: Vec<&str> = history
+            .iter()
+            .filter(|h| filter.matches(&Invocation::from(*h)))
+            .map(|h| h.command.as_str())
+            .collect();
+        expected.sort_unstable();
+
+        let sql = format!("select * from history where {} order by command", filter.to_sql());
+        let found = db.query_history(&sql).await.unwrap();
+        let found: Vec<&str> = found.iter().map(|h| h.command.as_str()).collect();
+
+        assert_eq!(found, expected, "{word}");
+    }
+}
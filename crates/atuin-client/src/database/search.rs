@@ -0,0 +1,636 @@
+//! [`Sqlite::search`](super::Sqlite::search), evaluated without a database.
+//!
+//! With `[daemon] search` on, the daemon answers non-interactive searches from its in-memory index
+//! instead of SQLite. [`SearchQuery`] holds everything `Sqlite::search` puts in its `WHERE` clause,
+//! split into the part that looks at a command's text ([`SearchQuery::matches_command`]) and the
+//! part that looks at a single run of it ([`SearchQuery::matches_invocation`]). Deduplication,
+//! ordering and paging are up to the caller, which gets the options that control them.
+//!
+//! `Sqlite::search` builds its `LIKE` and `GLOB` conditions from [`command_patterns`] too, so the
+//! two can't disagree on those. The tests at the bottom of this file run everything else against
+//! SQLite, and `atuin-search-bench` compares whole searches.
+
+use atuin_common::filter::Items;
+use itertools::Itertools;
+use regex::Regex;
+use time::OffsetDateTime;
+
+use super::predicate::{Invocation, Predicate, QueryFilter, author_matches, split_filters};
+use super::{
+    Context, DbSearchMode, OptFilters, QueryToken, QueryTokenizer, get_session_start_time,
+};
+use crate::history::AuthorPattern;
+use crate::settings::FilterMode;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SearchQueryError {
+    /// The search is valid, but only `Sqlite::search` can run it.
+    #[error("{0} can only be searched for in the database")]
+    Unsupported(&'static str),
+    #[error("invalid `{field}` filter {value:?}: {reason}")]
+    Date {
+        field: &'static str,
+        value: String,
+        reason: interim::DateError,
+    },
+    #[error(transparent)]
+    Regex(#[from] regex::Error),
+}
+
+/// One `LIKE` or `GLOB` condition on the command, as `Sqlite::search` writes it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(super) struct CommandPattern {
+    pub pattern: String,
+    /// `GLOB`, which is case-sensitive, rather than `LIKE`.
+    pub glob: bool,
+    pub inverse: bool,
+    /// OR'd onto the condition before it, rather than AND'd.
+    pub or: bool,
+}
+
+/// The conditions `query` (with its inline filters already split off) puts on the command: `LIKE`
+/// and `GLOB` patterns, and regexes.
+pub(super) fn command_patterns(
+    search_mode: DbSearchMode,
+    query: &str,
+) -> (Vec<CommandPattern>, Vec<String>) {
+    let mut patterns = Vec::new();
+    let mut regexes = Vec::new();
+
+    if search_mode == DbSearchMode::Prefix {
+        patterns.push(CommandPattern {
+            pattern: format!("{}%", query.replace('*', "%")),
+            glob: false,
+            inverse: false,
+            or: false,
+        });
+        return (patterns, regexes);
+    }
+
+    let mut is_or = false;
+    for token in QueryTokenizer::new(query) {
+        // TODO smart case mode could be made configurable like in fzf
+        let (is_glob, glob) = if token.has_uppercase() {
+            (true, "*")
+        } else {
+            (false, "%")
+        };
+        let pattern = match token {
+            QueryToken::Regex(r) => {
+                regexes.push(String::from(r));
+                continue;
+            }
+            QueryToken::Filter(_) => continue,
+            QueryToken::Or => {
+                if !is_or {
+                    is_or = true;
+                    continue;
+                } else {
+                    format!("{glob}|{glob}")
+                }
+            }
+            QueryToken::MatchStart(term, _) => {
+                format!("{term}{glob}")
+            }
+            QueryToken::MatchEnd(term, _) => {
+                format!("{glob}{term}")
+            }
+            QueryToken::MatchFull(term, _) => {
+                format!("{glob}{term}{glob}")
+            }
+            QueryToken::Match(term, _) => {
+                if search_mode == DbSearchMode::FullText {
+                    format!("{glob}{term}{glob}")
+                } else {
+                    term.split("").join(glob)
+                }
+            }
+        };
+
+        patterns.push(CommandPattern {
+            pattern,
+            glob: is_glob,
+            inverse: token.is_inverse(),
+            or: is_or,
+        });
+        is_or = false;
+    }
+
+    (patterns, regexes)
+}
+
+/// Parse a `before` or `after` option the way `Sqlite::search` does, into unix nanoseconds.
+pub(super) fn parse_date(field: &'static str, value: &str) -> Result<i64, SearchQueryError> {
+    interim::parse_date_string(value, OffsetDateTime::now_utc(), interim::Dialect::Uk)
+        .map(|parsed| parsed.unix_timestamp_nanos() as i64)
+        .map_err(|reason| SearchQueryError::Date {
+            field,
+            value: value.to_string(),
+            reason,
+        })
+}
+
+/// A search's filter mode, with what it compares against taken from the context.
+#[derive(Clone, Debug)]
+enum Scope {
+    Global,
+    /// The lowercased host.
+    Host(String),
+    Session(String),
+    /// The session, or anything from before it started.
+    SessionPreload {
+        session: String,
+        start: Option<i64>,
+    },
+    Directory(String),
+    /// `cwd LIKE '<git root>%'`.
+    Workspace(LikePattern),
+}
+
+/// A `Sqlite::search`, ready to be checked against commands and the runs of them.
+#[derive(Clone, Debug)]
+pub struct SearchQuery {
+    /// Every group must match, and a group matches if any of its patterns do.
+    commands: Vec<Vec<(LikePattern, bool)>>,
+    regexes: Vec<Regex>,
+    scope: Scope,
+    exit: Option<i64>,
+    exclude_exit: Option<i64>,
+    only_failed: bool,
+    cwd: Option<String>,
+    exclude_cwd: Option<String>,
+    before: Option<i64>,
+    after: Option<i64>,
+    /// `None` when nothing is filtered on, like an inline author filter taking over.
+    authors: Option<Vec<AuthorPattern>>,
+    /// `None` for every shell. The empty string stands for no recorded shell.
+    shells: Option<Vec<String>>,
+    filters: Vec<QueryFilter>,
+    /// Keep every matching run, rather than only the latest run of each command.
+    pub include_duplicates: bool,
+    /// Oldest first, rather than newest first.
+    pub reverse: bool,
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+impl SearchQuery {
+    /// Take apart the search `Sqlite::search` would run for the same arguments.
+    ///
+    /// Fails with [`SearchQueryError::Unsupported`] for the few searches that only make sense
+    /// against the database: those restricted to a list of `ids`, and those whose first term is
+    /// OR'd onto the filter mode.
+    pub fn new(
+        search_mode: DbSearchMode,
+        filter: FilterMode,
+        context: &Context,
+        query: &str,
+        filter_options: &OptFilters<'_>,
+    ) -> Result<Self, SearchQueryError> {
+        if filter_options.ids.is_some() {
+            return Err(SearchQueryError::Unsupported("a list of history IDs"));
+        }
+
+        let (query, filters) = split_filters(query);
+
+        let scope = match filter {
+            FilterMode::Global => Scope::Global,
+            FilterMode::Host => Scope::Host(context.cmd_origin.host().into_inner().to_lowercase()),
+            FilterMode::Session => Scope::Session(context.session.clone()),
+            FilterMode::SessionPreload => Scope::SessionPreload {
+                session: context.session.clone(),
+                start: get_session_start_time(&context.session),
+            },
+            FilterMode::Directory => Scope::Directory(context.cwd.clone()),
+            FilterMode::Workspace => {
+                let git_root = match &context.git_root {
+                    Some(git_root) => git_root.to_str().unwrap_or("/").to_string(),
+                    None => context.cwd.clone(),
+                };
+                Scope::Workspace(LikePattern::like(&format!("{git_root}%")))
+            }
+        };
+
+        let (patterns, regexes) = command_patterns(search_mode, &query);
+        let mut commands: Vec<Vec<(LikePattern, bool)>> = Vec::new();
+        for pattern in patterns {
+            let compiled = if pattern.glob {
+                LikePattern::glob(&pattern.pattern)
+            } else {
+                LikePattern::like(&pattern.pattern)
+            };
+            match commands.last_mut() {
+                Some(group) if pattern.or => group.push((compiled, pattern.inverse)),
+                // Nothing to OR onto but the filter mode's own condition
+                None if pattern.or && !matches!(scope, Scope::Global) => {
+                    return Err(SearchQueryError::Unsupported("a query starting with `|`"));
+                }
+                _ => commands.push(vec![(compiled, pattern.inverse)]),
+            }
+        }
+        let regexes = regexes.iter().map(|r| Regex::new(r)).collect::<Result<_, _>>()?;
+
+        let before =
+            filter_options.before.map(|before| parse_date("before", before)).transpose()?;
+        let after = filter_options.after.map(|after| parse_date("after", after)).transpose()?;
+
+        // An inline author filter replaces the caller's, as it does in `Sqlite::search`.
+        let has_author_filter =
+            filters.iter().any(|f| !f.negated && matches!(f.predicate, Predicate::Author(_)));
+        let authors = match filter_options.authors.items() {
+            Items::Some(authors) if !has_author_filter => Some(authors.to_vec()),
+            _ => None,
+        };
+        let shells = match filter_options.shells.items() {
+            Items::All => None,
+            Items::Some(shells) => Some(shells.to_vec()),
+        };
+
+        Ok(Self {
+            commands,
+            regexes,
+            scope,
+            exit: filter_options.exit,
+            exclude_exit: filter_options.exclude_exit,
+            only_failed: filter_options.only_failed,
+            cwd: filter_options.cwd.map(str::to_string),
+            exclude_cwd: filter_options.exclude_cwd.map(str::to_string),
+            before,
+            after,
+            authors,
+            shells,
+            filters,
+            include_duplicates: filter_options.include_duplicates,
+            reverse: filter_options.reverse,
+            // SQLite reads a negative LIMIT as no limit, and a negative OFFSET as none.
+            limit: filter_options.limit.and_then(|limit| usize::try_from(limit).ok()),
+            offset: filter_options
+                .offset
+                .and_then(|offset| usize::try_from(offset).ok())
+                .unwrap_or(0),
+        })
+    }
+
+    /// Whether `command` matches the query text, regardless of when or where it was run.
+    pub fn matches_command(&self, command: &str) -> bool {
+        self.commands.iter().all(|group| {
+            group.iter().any(|(pattern, inverse)| pattern.matches(command) != *inverse)
+        }) && self.regexes.iter().all(|regex| regex.is_match(command))
+    }
+
+    /// Whether a single run of a command passes the filter mode, the options and the query's
+    /// inline filters.
+    pub fn matches_invocation(&self, invocation: &Invocation<'_>) -> bool {
+        let in_scope = match &self.scope {
+            Scope::Global => true,
+            Scope::Host(host) => {
+                let origin = invocation.origin.to_ascii_lowercase();
+                origin == *host
+                    || origin.strip_prefix(host.as_str()).is_some_and(|rest| rest.starts_with(':'))
+            }
+            Scope::Session(session) => invocation.session == session,
+            Scope::SessionPreload { session, start } => {
+                invocation.session == session
+                    || start.is_some_and(|start| invocation.timestamp < start)
+            }
+            Scope::Directory(cwd) => invocation.cwd == cwd,
+            Scope::Workspace(pattern) => pattern.matches(invocation.cwd),
+        };
+
+        in_scope
+            && self.exit.is_none_or(|exit| invocation.exit == exit)
+            && self.exclude_exit.is_none_or(|exit| invocation.exit != exit)
+            && (!self.only_failed || (invocation.exit != 0 && invocation.exit != -1))
+            && self.cwd.as_ref().is_none_or(|cwd| invocation.cwd == cwd)
+            && self.exclude_cwd.as_ref().is_none_or(|cwd| invocation.cwd != cwd)
+            && self.before.is_none_or(|before| invocation.timestamp < before)
+            && self.after.is_none_or(|after| invocation.timestamp > after)
+            && self.authors.as_ref().is_none_or(|authors| {
+                authors.iter().any(|author| author_matches(author, invocation))
+            })
+            && self.shells.as_ref().is_none_or(|shells| match invocation.shell {
+                None => shells.iter().any(String::is_empty),
+                Some(shell) => !shell.is_empty() && shells.iter().any(|s| s == shell),
+            })
+            && self.filters.iter().all(|filter| filter.matches(invocation))
+    }
+}
+
+/// A single-char element of a [`LikePattern`].
+#[derive(Clone, Debug)]
+enum Token {
+    /// `%` in `LIKE`, `*` in `GLOB`.
+    Any,
+    /// `_` in `LIKE`, `?` in `GLOB`.
+    One,
+    Char(char),
+    /// A `GLOB` character class, as inclusive ranges.
+    Class {
+        ranges: Vec<(char, char)>,
+        invert: bool,
+    },
+    /// A `GLOB` character class missing its `]`, which SQLite never matches.
+    Never,
+}
+
+/// A `LIKE` or `GLOB` pattern, matched the way SQLite matches them: `LIKE` folds ASCII case only,
+/// and neither has an escape character.
+#[derive(Clone, Debug)]
+struct LikePattern {
+    tokens: Vec<Token>,
+    ignore_ascii_case: bool,
+}
+
+impl LikePattern {
+    fn like(pattern: &str) -> Self {
+        let tokens = pattern
+            .chars()
+            .map(|c| match c {
+                '%' => Token::Any,
+                '_' => Token::One,
+                c => Token::Char(c),
+            })
+            .collect();
+        Self {
+            tokens,
+            ignore_ascii_case: true,
+        }
+    }
+
+    fn glob(pattern: &str) -> Self {
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            tokens.push(match c {
+                '*' => Token::Any,
+                '?' => Token::One,
+                '[' => Self::glob_class(&mut chars),
+                c => Token::Char(c),
+            });
+        }
+        Self {
+            tokens,
+            ignore_ascii_case: false,
+        }
+    }
+
+    /// Parse a character class up to its `]`, following SQLite's `patternCompare`.
+    fn glob_class(chars: &mut std::str::Chars<'_>) -> Token {
+        let mut ranges = Vec::new();
+        let mut invert = false;
+
+        let mut next = chars.next();
+        if next == Some('^') {
+            invert = true;
+            next = chars.next();
+        }
+        if next == Some(']') {
+            ranges.push((']', ']'));
+            next = chars.next();
+        }
+
+        // The char before a `-`, which starts a range. Not set by a leading `]`.
+        let mut prior: Option<char> = None;
+        loop {
+            match next {
+                None => return Token::Never,
+                Some(']') => return Token::Class { ranges, invert },
+                Some('-')
+                    if prior.is_some() && !matches!(chars.clone().next(), None | Some(']')) =>
+                {
+                    let end = chars.next().expect("checked above");
+                    ranges.push((prior.take().expect("checked above"), end));
+                }
+                Some(c) => {
+                    ranges.push((c, c));
+                    prior = Some(c);
+                }
+            }
+            next = chars.next();
+        }
+    }
+
+    fn matches_char(&self, token: &Token, c: char) -> bool {
+        match token {
+            Token::Any | Token::One => true,
+            Token::Char(p) if self.ignore_ascii_case => p.eq_ignore_ascii_case(&c),
+            Token::Char(p) => *p == c,
+            Token::Class { ranges, invert } => {
+                ranges.iter().any(|(lo, hi)| (*lo..=*hi).contains(&c)) != *invert
+            }
+            Token::Never => false,
+        }
+    }
+
+    fn matches(&self, text: &str) -> bool {
+        // Positions are a token index and a byte offset into `text`.
+        let (mut p, mut t) = (0, 0);
+        // Where to resume after the last `Any`: its token, and where the text after it started.
+        let mut star: Option<(usize, usize)> = None;
+
+        while let Some(c) = text[t..].chars().next() {
+            match self.tokens.get(p) {
+                Some(Token::Any) => {
+                    star = Some((p, t));
+                    p += 1;
+                }
+                Some(token) if self.matches_char(token, c) => {
+                    p += 1;
+                    t += c.len_utf8();
+                }
+                _ => match star {
+                    Some((star_p, star_t)) => {
+                        let skipped = text[star_t..].chars().next().expect("not past the end");
+                        let star_t = star_t + skipped.len_utf8();
+                        p = star_p + 1;
+                        t = star_t;
+                        star = Some((star_p, star_t));
+                    }
+                    None => return false,
+                },
+            }
+        }
+
+        self.tokens[p..].iter().all(|token| matches!(token, Token::Any))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use atuin_common::filter::OrFilter;
+    use atuin_domain::record::CmdOrigin;
+    use rstest::rstest;
+    use time::Duration;
+
+    use super::*;
+    use crate::database::Sqlite;
+    use crate::history::{AuthorKind, History};
+    use crate::settings::test_local_timeout;
+
+    #[rstest]
+    #[case("git%", "git status", true)]
+    #[case("git%", "GIT status", true)]
+    #[case("%STATUS", "git status", true)]
+    #[case("g_t", "git", true)]
+    #[case("g_t", "gt", false)]
+    #[case("%g%i%t%", "go install tar", true)]
+    #[case("%é%", "café", true)]
+    #[case("%É%", "café", false)]
+    #[case("", "", true)]
+    #[case("%", "", true)]
+    fn like(#[case] pattern: &str, #[case] text: &str, #[case] expected: bool) {
+        assert_eq!(LikePattern::like(pattern).matches(text), expected);
+    }
+
+    #[rstest]
+    #[case("*Git*", "run Git now", true)]
+    #[case("*Git*", "run git now", false)]
+    #[case("G?t", "Git", true)]
+    #[case("[a-c]x", "bx", true)]
+    #[case("[^a-c]x", "bx", false)]
+    #[case("[]]", "]", true)]
+    #[case("[]-a]", "-", true)]
+    #[case("[a-]", "-", true)]
+    #[case("*[ab", "xa", false)]
+    fn glob(#[case] pattern: &str, #[case] text: &str, #[case] expected: bool) {
+        assert_eq!(LikePattern::glob(pattern).matches(text), expected);
+    }
+
+    fn context(session: &str, cwd: &str, origin: &str) -> Context {
+        Context {
+            session: session.to_string(),
+            cwd: cwd.to_string(),
+            cmd_origin: CmdOrigin::try_from(origin).unwrap(),
+            host_id: String::new(),
+            git_root: None,
+        }
+    }
+
+    /// Every search must select exactly the same entries as `Sqlite::search` does.
+    #[rstest]
+    #[case::everything(DbSearchMode::Fuzzy, FilterMode::Global, "", OptFilters::default())]
+    #[case::fuzzy(DbSearchMode::Fuzzy, FilterMode::Global, "gst", OptFilters::default())]
+    #[case::smart_case(DbSearchMode::Fuzzy, FilterMode::Global, "Git", OptFilters::default())]
+    #[case::full_text(DbSearchMode::FullText, FilterMode::Global, "status", OptFilters::default())]
+    #[case::prefix(DbSearchMode::Prefix, FilterMode::Global, "git s*s", OptFilters::default())]
+    #[case::anchors(
+        DbSearchMode::Fuzzy,
+        FilterMode::Global,
+        "^git !push 'st us$",
+        OptFilters::default()
+    )]
+    #[case::or(DbSearchMode::FullText, FilterMode::Global, "push | pull", OptFilters::default())]
+    #[case::regex(DbSearchMode::Fuzzy, FilterMode::Global, "r/^(ls|cd) /", OptFilters::default())]
+    #[case::host(DbSearchMode::Fuzzy, FilterMode::Host, "", OptFilters::default())]
+    #[case::session(DbSearchMode::Fuzzy, FilterMode::Session, "", OptFilters::default())]
+    #[case::preload(DbSearchMode::Fuzzy, FilterMode::SessionPreload, "", OptFilters::default())]
+    #[case::directory(DbSearchMode::Fuzzy, FilterMode::Directory, "", OptFilters::default())]
+    #[case::workspace(DbSearchMode::Fuzzy, FilterMode::Workspace, "", OptFilters::default())]
+    #[case::exit(DbSearchMode::Fuzzy, FilterMode::Global, "", OptFilters { exit: Some(1), ..Default::default() })]
+    #[case::exclude_exit(DbSearchMode::Fuzzy, FilterMode::Global, "", OptFilters { exclude_exit: Some(0), ..Default::default() })]
+    #[case::only_failed(DbSearchMode::Fuzzy, FilterMode::Global, "", OptFilters { only_failed: true, ..Default::default() })]
+    #[case::cwd(DbSearchMode::Fuzzy, FilterMode::Global, "", OptFilters { cwd: Some("/tmp"), ..Default::default() })]
+    #[case::exclude_cwd(DbSearchMode::Fuzzy, FilterMode::Global, "", OptFilters { exclude_cwd: Some("/tmp"), ..Default::default() })]
+    #[case::after(DbSearchMode::Fuzzy, FilterMode::Global, "", OptFilters { after: Some("2 hours ago"), ..Default::default() })]
+    #[case::before(DbSearchMode::Fuzzy, FilterMode::Global, "", OptFilters { before: Some("2 hours ago"), ..Default::default() })]
+    #[case::users(DbSearchMode::Fuzzy, FilterMode::Global, "", OptFilters { authors: OrFilter::new_unchecked(&[AuthorPattern::AllUser]), ..Default::default() })]
+    #[case::inline_author(DbSearchMode::Fuzzy, FilterMode::Global, "author:$all-agent", OptFilters { authors: OrFilter::new_unchecked(&[AuthorPattern::AllUser]), ..Default::default() })]
+    #[case::inline_filters(
+        DbSearchMode::Fuzzy,
+        FilterMode::Global,
+        "g exit:0 cwd:/home/*",
+        OptFilters::default()
+    )]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn agrees_with_sqlite(
+        #[case] search_mode: DbSearchMode,
+        #[case] filter: FilterMode,
+        #[case] query: &str,
+        #[case] filter_options: OptFilters<'static>,
+    ) {
+        let db = Sqlite::new("sqlite::memory:", test_local_timeout()).await.unwrap();
+        let now = OffsetDateTime::now_utc();
+        let session = atuin_common::utils::uuid_v7().as_simple().to_string();
+
+        #[rustfmt::skip]
+        let rows = [
+            ("git status", "/home/ellie/src", "laptop:ellie", "", None, Some("zsh"), 0, 10),
+            ("git push", "/home/ellie/src", "laptop:ellie", "", None, Some("zsh"), 1, 60),
+            ("Git pull", "/tmp", "Laptop:ellie", "claude-code", Some(AuthorKind::Agent), None, 0, 120),
+            ("ls -la", "/home/ellie", "build-01:ci", "", None, Some("bash"), -1, 3600 * 3),
+            ("cd /tmp", "/tmp", "build-01:ci", "", None, Some(""), 127, 3600 * 24),
+            ("go install tar", "/home/ellie/src/atuin", "laptop:ellie", "", None, Some("fish"), 2, 30),
+        ];
+
+        let mut history = Vec::new();
+        for (i, (command, cwd, origin, author, author_kind, shell, exit, seconds_ago)) in
+            rows.into_iter().enumerate()
+        {
+            let h: History = History::import()
+                .timestamp(now - Duration::seconds(seconds_ago))
+                .command(command)
+                .cwd(cwd)
+                .cmd_origin(CmdOrigin::try_from(origin).unwrap())
+                .author(author)
+                .author_kind(author_kind)
+                .exit(exit)
+                .session(if i % 2 == 0 {
+                    session.clone()
+                } else {
+                    String::new()
+                })
+                .shell_opt(shell.map(str::to_string))
+                .build()
+                .into();
+            db.save(&h).await.unwrap();
+            history.push(h);
+        }
+
+        let mut context = context(&session, "/home/ellie/src", "laptop:ellie");
+        context.git_root = Some("/home/ellie".into());
+        let filter_options = OptFilters {
+            include_duplicates: true,
+            ..filter_options
+        };
+
+        let search =
+            SearchQuery::new(search_mode, filter, &context, query, &filter_options).unwrap();
+        let mut expected: Vec<&str> = history
+            .iter()
+            .filter(|h| search.matches_command(&h.command))
+            .filter(|h| search.matches_invocation(&Invocation::from(*h)))
+            .map(|h| h.command.as_str())
+            .collect();
+        expected.sort_unstable();
+
+        let found = db.search(search_mode, filter, &context, query, filter_options).await.unwrap();
+        let mut found: Vec<&str> = found.iter().map(|h| h.command.as_str()).collect();
+        found.sort_unstable();
+
+        assert_eq!(found, expected);
+    }
+
+    #[test]
+    fn leaves_database_only_searches_to_the_database() {
+        let context = context("", "/", "laptop:ellie");
+        let ids = [];
+        let by_ids = OptFilters {
+            ids: Some(&ids),
+            ..Default::default()
+        };
+        let mode = DbSearchMode::FullText;
+
+        assert!(matches!(
+            SearchQuery::new(mode, FilterMode::Global, &context, "", &by_ids),
+            Err(SearchQueryError::Unsupported(_))
+        ));
+        assert!(matches!(
+            SearchQuery::new(mode, FilterMode::Host, &context, "| ls", &OptFilters::default()),
+            Err(SearchQueryError::Unsupported(_))
+        ));
+        assert!(
+            SearchQuery::new(mode, FilterMode::Global, &context, "| ls", &OptFilters::default())
+                .is_ok()
+        );
+    }
+}
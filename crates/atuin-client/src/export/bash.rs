@@ -0,0 +1,58 @@
+use std::io::Write;
+
+use eyre::Result;
+
+use super::{Exporter, unix_seconds};
+use crate::history::History;
+
+/// bash history as written with `HISTTIMEFORMAT` set: a `#<start>` comment before each command.
+///
+/// Multi-line commands are written as they are, which is how bash writes them with `lithist`;
+/// bash reads every line up to the next timestamp back as one command.
+#[derive(Debug)]
+pub struct Bash<W> {
+    writer: W,
+}
+
+impl<W: Write> Exporter for Bash<W> {
+    const NAME: &'static str = "bash";
+    type Writer = W;
+
+    fn new(writer: W) -> Result<Self> {
+        Ok(Self { writer })
+    }
+
+    fn push(&mut self, hist: &History) -> Result<()> {
+        writeln!(self.writer, "#{}\n{}", unix_seconds(hist), hist.command)?;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::export::tests::{export, history};
+    use crate::import::Importer;
+    use crate::import::bash::Bash as BashImporter;
+    use crate::import::tests::TestLoader;
+
+    #[tokio::test]
+    async fn round_trips() {
+        let entries = [history("cargo build"), history("git push")];
+
+        let bytes = export::<Bash<_>>(&entries);
+        assert_eq!(bytes, b"#1706933106\ncargo build\n#1706933106\ngit push\n");
+
+        let mut loader = TestLoader::default();
+        BashImporter { bytes }.load(&mut loader).await.unwrap();
+
+        let commands: Vec<&str> = loader.buf.iter().map(|h| h.command.as_str()).collect();
+        assert_eq!(commands, ["cargo build", "git push"]);
+        assert_eq!(loader.buf[0].timestamp.unix_timestamp(), 1_706_933_106);
+    }
+}
@@ -0,0 +1,109 @@
+use std::io::Write;
+
+use eyre::Result;
+use time::format_description::well_known::Rfc3339;
+
+use super::{Entry, Exporter};
+use crate::history::History;
+
+const HEADER: [&str; 13] = [
+    "id",
+    "timestamp",
+    "duration",
+    "exit",
+    "command",
+    "cwd",
+    "session",
+    "hostname",
+    "author",
+    "author_kind",
+    "intent",
+    "shell",
+    "deleted_at",
+];
+
+/// RFC 4180 CSV with a header row, and the same columns as [`super::jsonl::Jsonl`]. Missing
+/// values are empty.
+#[derive(Debug)]
+pub struct Csv<W> {
+    writer: W,
+}
+
+impl<W: Write> Csv<W> {
+    fn write_row<'a>(&mut self, fields: impl IntoIterator<Item = &'a str>) -> Result<()> {
+        for (i, field) in fields.into_iter().enumerate() {
+            if i > 0 {
+                self.writer.write_all(b",")?;
+            }
+            if field.contains([',', '"', '\n', '\r']) {
+                write!(self.writer, "\"{}\"", field.replace('"', "\"\""))?;
+            } else {
+                self.writer.write_all(field.as_bytes())?;
+            }
+        }
+        self.writer.write_all(b"\r\n")?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Exporter for Csv<W> {
+    const NAME: &'static str = "csv";
+    type Writer = W;
+
+    fn new(writer: W) -> Result<Self> {
+        let mut csv = Self { writer };
+        csv.write_row(HEADER)?;
+        Ok(csv)
+    }
+
+    fn push(&mut self, hist: &History) -> Result<()> {
+        let entry = Entry::from(hist);
+        let timestamp = entry.timestamp.format(&Rfc3339)?;
+        let deleted_at = entry.deleted_at.map(|d| d.format(&Rfc3339)).transpose()?;
+
+        self.write_row([
+            entry.id.as_str(),
+            &timestamp,
+            &entry.duration.to_string(),
+            &entry.exit.to_string(),
+            &entry.command,
+            &entry.cwd,
+            &entry.session,
+            &entry.hostname,
+            &entry.author,
+            entry.author_kind.as_deref().unwrap_or_default(),
+            entry.intent.as_deref().unwrap_or_default(),
+            entry.shell.as_deref().unwrap_or_default(),
+            deleted_at.as_deref().unwrap_or_default(),
+        ])
+    }
+
+    fn finish(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::export::tests::{export, history};
+
+    #[test]
+    fn quotes_fields_that_need_it() {
+        let h = history(r#"git commit -m "one, two""#);
+        let out = String::from_utf8(export::<Csv<_>>(std::slice::from_ref(&h))).unwrap();
+
+        assert_eq!(
+            out,
+            format!(
+                "id,timestamp,duration,exit,command,cwd,session,hostname,author,author_kind,\
+                 intent,shell,deleted_at\r\n{},2024-02-03T04:05:06.789Z,2500000000,0,\"git commit \
+                 -m \"\"one, \
+                 two\"\"\",/home/ellie,b5ef2dd1a3a24e44a0a7c5d9e6bd4c3f,laptop:ellie,claude-code,\
+                 agent,check the build,zsh,\r\n",
+                h.id
+            )
+        );
+    }
+}
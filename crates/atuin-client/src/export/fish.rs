@@ -0,0 +1,66 @@
+use std::io::Write;
+
+use eyre::Result;
+
+use super::{Exporter, unix_seconds};
+use crate::history::History;
+
+/// fish's YAML-ish history file.
+///
+/// See <https://fishshell.com/docs/current/interactive.html#searchable-command-history>
+#[derive(Debug)]
+pub struct Fish<W> {
+    writer: W,
+}
+
+impl<W: Write> Exporter for Fish<W> {
+    const NAME: &'static str = "fish";
+    type Writer = W;
+
+    fn new(writer: W) -> Result<Self> {
+        Ok(Self { writer })
+    }
+
+    fn push(&mut self, hist: &History) -> Result<()> {
+        // the same escaping fish uses: backslashes first, so the newline escapes survive
+        let command = hist.command.replace('\\', r"\\").replace('\n', r"\n");
+        writeln!(self.writer, "- cmd: {command}\n  when: {}", unix_seconds(hist))?;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::export::tests::{export, history};
+    use crate::import::Importer;
+    use crate::import::fish::Fish as FishImporter;
+    use crate::import::tests::TestLoader;
+
+    #[tokio::test]
+    async fn round_trips() {
+        let entries = [history("cargo build"), history("cd 'C:\\dir'\nls")];
+
+        let bytes = export::<Fish<_>>(&entries);
+        assert_eq!(
+            bytes,
+            br"- cmd: cargo build
+  when: 1706933106
+- cmd: cd 'C:\\dir'\nls
+  when: 1706933106
+"
+        );
+
+        let mut loader = TestLoader::default();
+        FishImporter { bytes }.load(&mut loader).await.unwrap();
+
+        let commands: Vec<&str> = loader.buf.iter().map(|h| h.command.as_str()).collect();
+        assert_eq!(commands, ["cargo build", "cd 'C:\\dir'\nls"]);
+        assert_eq!(loader.buf[1].timestamp.unix_timestamp(), 1_706_933_106);
+    }
+}
@@ -0,0 +1,54 @@
+use std::io::Write;
+
+use eyre::Result;
+
+use super::{Entry, Exporter};
+use crate::history::History;
+
+/// One JSON object per line, with every field of the entry. See [`Entry`].
+#[derive(Debug)]
+pub struct Jsonl<W> {
+    writer: W,
+}
+
+impl<W: Write> Exporter for Jsonl<W> {
+    const NAME: &'static str = "jsonl";
+    type Writer = W;
+
+    fn new(writer: W) -> Result<Self> {
+        Ok(Self { writer })
+    }
+
+    fn push(&mut self, hist: &History) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, &Entry::from(hist))?;
+        self.writer.write_all(b"\n")?;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::export::tests::{export, history};
+
+    #[test]
+    fn writes_every_field() {
+        let h = history("cargo build\ncargo test");
+        let out = String::from_utf8(export::<Jsonl<_>>(std::slice::from_ref(&h))).unwrap();
+
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 1, "multi-line commands stay on one line");
+
+        let entry: Entry = serde_json::from_str(lines[0]).unwrap();
+        assert_eq!(entry, Entry::from(&h));
+        assert_eq!(entry.timestamp, h.timestamp);
+        assert_eq!(entry.hostname, "laptop:ellie");
+        assert_eq!(entry.author_kind.as_deref(), Some("agent"));
+        assert_eq!(entry.deleted_at, None);
+    }
+}
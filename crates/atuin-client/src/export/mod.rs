@@ -0,0 +1,121 @@
+// get history back out of atuin, in formats other tools (and shells) understand.
+// the inverse of `crate::import`
+
+use std::io::Write;
+
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::history::{AuthorKind, History};
+
+pub mod bash;
+pub mod csv;
+pub mod fish;
+pub mod jsonl;
+pub mod zsh;
+
+/// Writes history entries to `Self::Writer`, one at a time.
+///
+/// Entries are written in the order they're pushed, so push them oldest first when the output is
+/// meant to be read back by a shell.
+pub trait Exporter: Sized {
+    const NAME: &'static str;
+    type Writer: Write;
+
+    fn new(writer: Self::Writer) -> Result<Self>;
+    fn push(&mut self, hist: &History) -> Result<()>;
+    /// Flushes anything buffered, and hands the writer back.
+    fn finish(self) -> Result<Self::Writer>;
+}
+
+/// Every field of a [`History`], in the shape the JSONL and CSV exporters write it.
+///
+/// Timestamps are RFC 3339; `duration` is in nanoseconds, and it and `exit` are `-1` for a command
+/// that hadn't finished when it was recorded.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Entry {
+    pub id: String,
+    #[serde(with = "time::serde::rfc3339")]
+    pub timestamp: OffsetDateTime,
+    pub duration: i64,
+    pub exit: i64,
+    pub command: String,
+    pub cwd: String,
+    pub session: String,
+    /// The `host:user` the command was run as.
+    pub hostname: String,
+    pub author: String,
+    /// `user` or `agent`, if whatever captured the entry said which.
+    pub author_kind: Option<String>,
+    pub intent: Option<String>,
+    pub shell: Option<String>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub deleted_at: Option<OffsetDateTime>,
+}
+
+impl From<&History> for Entry {
+    fn from(h: &History) -> Self {
+        Self {
+            id: h.id.0.clone(),
+            timestamp: h.timestamp,
+            duration: h.duration,
+            exit: h.exit,
+            command: h.command.clone(),
+            cwd: h.cwd.clone(),
+            session: h.session.clone(),
+            hostname: h.cmd_origin.to_string(),
+            author: h.author.clone(),
+            author_kind: h.author_kind.map(author_kind_name),
+            intent: h.intent.clone(),
+            shell: h.shell.clone(),
+            deleted_at: h.deleted_at,
+        }
+    }
+}
+
+fn author_kind_name(kind: AuthorKind) -> String {
+    match kind {
+        AuthorKind::User => "user",
+        AuthorKind::Agent => "agent",
+    }
+    .to_owned()
+}
+
+/// Whole seconds since the epoch, which is all the shells keep.
+fn unix_seconds(h: &History) -> i64 {
+    h.timestamp.unix_timestamp()
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    pub fn history(command: &str) -> History {
+        History::import()
+            .timestamp(datetime!(2024-02-03 04:05:06.789 UTC))
+            .command(command)
+            .cwd("/home/ellie")
+            .exit(0)
+            .duration(2_500_000_000)
+            .session("b5ef2dd1a3a24e44a0a7c5d9e6bd4c3f")
+            .cmd_origin(atuin_domain::record::CmdOrigin::try_from("laptop:ellie").unwrap())
+            .author("claude-code")
+            .author_kind(Some(AuthorKind::Agent))
+            .intent("check the build")
+            .shell("zsh")
+            .build()
+            .into()
+    }
+
+    /// Pushes `entries` through `E` and returns what it wrote.
+    pub fn export<E: Exporter<Writer = Vec<u8>>>(entries: &[History]) -> Vec<u8> {
+        let mut exporter = E::new(Vec::new()).unwrap();
+        for h in entries {
+            exporter.push(h).unwrap();
+        }
+        exporter.finish().unwrap()
+    }
+}
@@ -0,0 +1,95 @@
+use std::io::Write;
+
+use eyre::Result;
+
+use super::{Exporter, unix_seconds};
+use crate::history::History;
+
+/// zsh's extended history format (`setopt extended_history`), `: <start>:<elapsed>;<command>`.
+#[derive(Debug)]
+pub struct Zsh<W> {
+    writer: W,
+}
+
+impl<W: Write> Exporter for Zsh<W> {
+    const NAME: &'static str = "zsh";
+    type Writer = W;
+
+    fn new(writer: W) -> Result<Self> {
+        Ok(Self { writer })
+    }
+
+    fn push(&mut self, hist: &History) -> Result<()> {
+        // zsh keeps whole seconds, and has no way to say a command is still running
+        let elapsed = hist.duration.max(0) / 1_000_000_000;
+        // each line of a multi-line command but the last ends in a backslash
+        let command = hist.command.replace('\n', "\\\n");
+        let line = format!(": {}:{elapsed};{command}\n", unix_seconds(hist));
+
+        self.writer.write_all(&metafy(line.as_bytes()))?;
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// The inverse of `import::zsh::unmetafy`.
+///
+/// zsh escapes the bytes it uses internally as tokens (NUL, and `0x83..=0xa2`) by prefixing them
+/// with `Meta` (`0x83`) and flipping bit 5.
+fn metafy(line: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(line.len());
+    for &b in line {
+        if b == 0 || (0x83..=0xa2).contains(&b) {
+            out.push(0x83);
+            out.push(b ^ 32);
+        } else {
+            out.push(b);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::export::tests::{export, history};
+    use crate::import::Importer;
+    use crate::import::tests::TestLoader;
+    use crate::import::zsh::Zsh as ZshImporter;
+
+    #[tokio::test]
+    async fn round_trips() {
+        let mut running = history("sleep 100");
+        running.duration = -1;
+        let entries =
+            [history("cargo build"), history("for i in 1 2 3\ndo\n  echo ∃ $i\ndone"), running];
+
+        let bytes = export::<Zsh<_>>(&entries);
+        assert!(
+            bytes.starts_with(
+                b": 1706933106:2;cargo build\n: 1706933106:2;for i in 1 2 3\\\ndo\\\n"
+            )
+        );
+
+        let mut loader = TestLoader::default();
+        ZshImporter { bytes }.load(&mut loader).await.unwrap();
+
+        let commands: Vec<&str> = loader.buf.iter().map(|h| h.command.as_str()).collect();
+        assert_eq!(commands, entries.iter().map(|h| h.command.as_str()).collect::<Vec<_>>());
+        for (imported, original) in loader.buf.iter().zip(&entries) {
+            assert_eq!(imported.timestamp.unix_timestamp(), original.timestamp.unix_timestamp());
+        }
+        assert_eq!(loader.buf[0].duration, 2_000_000_000);
+    }
+
+    #[test]
+    fn metafies_token_bytes() {
+        // `∃` is e2 88 83: the last two bytes are zsh tokens
+        assert_eq!(metafy("∃".as_bytes()), [0xe2, 0x83, 0xa8, 0x83, 0xa3]);
+        assert_eq!(metafy(b"ls"), b"ls");
+    }
+}
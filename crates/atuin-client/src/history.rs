@@ -10,10 +10,12 @@ use atuin_domain::record::{CmdOrigin, DecryptedData, UNKNOWN_USER};
 use eyre::{Result, bail};
 use time::OffsetDateTime;
 
-use crate::secrets::SECRET_PATTERNS_RE;
-use crate::settings::Settings;
+use crate::secrets::{has_secrets, redact_secrets};
+use crate::settings::{Settings, secrets};
 
+pub mod amend;
 pub(crate) mod builder;
+pub mod retention;
 pub mod store;
 
 /// Known AI agent author values. Used by [`History::is_agent`] to guess who ran a command when the
@@ -249,6 +251,10 @@ pub struct HistoryStats {
     pub day_of_week: Vec<(String, i64)>,
 
     pub duration_over_time: Vec<(String, i64)>,
+
+    /// What it printed, if `atuin pty-proxy` captured it and the output store still keeps it. Not
+    /// filled in by [`crate::database::Sqlite::stats`].
+    pub output: Option<String>,
 }
 
 impl History {
@@ -600,7 +606,30 @@ impl History {
             || self.command.is_empty()
             || settings.history_filter.is_match(&self.command)
             || settings.cwd_filter.is_match(&self.cwd)
-            || (settings.secrets_filter && SECRET_PATTERNS_RE.is_match(&self.command)))
+            || (settings.secrets_action == secrets::Action::Drop && self.has_secrets(settings)))
+    }
+
+    /// Whether this entry may leave this machine. With `secrets_action = "local"`, entries
+    /// holding secrets are only ever saved locally.
+    pub fn should_sync(&self, settings: &Settings) -> bool {
+        !(settings.secrets_action == secrets::Action::Local && self.has_secrets(settings))
+    }
+
+    /// Replace the secrets in this entry's command and intent with `[REDACTED]`, if that's what
+    /// `secrets_action` asks for.
+    pub fn redact_secrets(&mut self, settings: &Settings) {
+        if settings.secrets_action != secrets::Action::Redact {
+            return;
+        }
+
+        self.command = redact_secrets(&self.command, settings);
+        self.intent = self.intent.as_deref().map(|intent| redact_secrets(intent, settings));
+    }
+
+    /// Whether this entry's command or intent holds a secret.
+    pub fn has_secrets(&self, settings: &Settings) -> bool {
+        has_secrets(&self.command, settings)
+            || self.intent.as_deref().is_some_and(|intent| has_secrets(intent, settings))
     }
 }
 
@@ -614,7 +643,7 @@ mod tests {
 
     use super::{AuthorKind, AuthorPattern, History, all_user_author_filter, is_known_agent};
     use crate::history::Version;
-    use crate::settings::Settings;
+    use crate::settings::{Settings, secrets};
 
     /// Whether an author filter permits `history`, mirroring the SQL that
     /// [`apply_author_filter`](crate::database::OptFilters::authors) builds.
@@ -792,6 +821,44 @@ mod tests {
         assert!(stripe_key.should_save(&settings));
     }
 
+    #[rstest]
+    #[case::drop(
+        secrets::Action::Drop,
+        false,
+        true,
+        "curl -u ellie:sk_test_1234567890abcdefghijklmn"
+    )]
+    #[case::redact(secrets::Action::Redact, true, true, "curl -u ellie:[REDACTED]")]
+    #[case::local(
+        secrets::Action::Local,
+        true,
+        false,
+        "curl -u ellie:sk_test_1234567890abcdefghijklmn"
+    )]
+    fn secrets_action(
+        #[case] action: secrets::Action,
+        #[case] saved: bool,
+        #[case] synced: bool,
+        #[case] command: &str,
+    ) {
+        let settings = Settings {
+            secrets_action: action,
+            ..Settings::utc()
+        };
+
+        let mut history: History = History::capture()
+            .timestamp(time::OffsetDateTime::now_utc())
+            .command("curl -u ellie:sk_test_1234567890abcdefghijklmn")
+            .cwd("/")
+            .build()
+            .into();
+        history.redact_secrets(&settings);
+
+        assert_eq!(history.command, command);
+        assert_eq!(history.should_save(&settings), saved);
+        assert_eq!(history.should_sync(&settings), synced);
+    }
+
     #[rstest]
     #[case::basic(History {
         id: "66d16cbee7cd47538e5c5b8b44e9006e".to_owned().into(),
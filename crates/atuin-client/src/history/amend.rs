@@ -0,0 +1,380 @@
+//! Edits and redactions of history entries, made after they were recorded.
+//!
+//! Both are history records of their own, so they sync like the entries they amend, and every host
+//! applies them. They can arrive in any order, before or after the entry itself, so the history
+//! database keeps each entry's amendments apart, in `history_amendments`, and derives the text it
+//! shows from them: the latest edit wins, and every redaction blanks what it matches in it.
+//!
+//! What a redaction blanks is gone for good: only the redacted text of the entry and its latest
+//! edit is kept, and redactions that arrive later blank what they match in that. So hosts agree
+//! on the outcome whatever order amendments arrive in, except where two redactions match
+//! overlapping text, when they can blank slightly different spans.
+
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+use eyre::{Result, WrapErr};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use super::HistoryId;
+use crate::secrets::{token_hash, tokens};
+
+/// What a redaction blanks each match with.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// A replacement command and intent for a history entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEdit {
+    pub id: HistoryId,
+    /// When the edit was made. Of two edits to an entry, the later one wins.
+    pub timestamp: OffsetDateTime,
+    pub command: String,
+    pub intent: Option<String>,
+}
+
+/// What a redaction blanks.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Redacts {
+    /// Whatever a regular expression matches.
+    Pattern(String),
+    /// Some text, by its [`token_hash`] and length in bytes. For text picked out by hand, so it
+    /// isn't kept in the redaction. Comes before `Token`, whose JSON is a subset of this.
+    Text {
+        hash: String,
+        len: usize,
+    },
+    /// A token, as [`secrets`](crate::secrets) splits text into them, by its [`token_hash`]. For
+    /// secrets that can only be matched by themselves, so the secret isn't kept in the redaction.
+    Token {
+        hash: String,
+    },
+}
+
+impl Redacts {
+    /// A redaction of every occurrence of `text`, that doesn't keep `text` itself.
+    pub fn text(text: &str) -> Self {
+        Self::Text {
+            hash: token_hash(text),
+            len: text.len(),
+        }
+    }
+
+    /// Where it matches in `text`. A pattern from a newer regex syntax matches nothing.
+    fn ranges(&self, text: &str) -> Vec<Range<usize>> {
+        match self {
+            Self::Pattern(pattern) => Regex::new(pattern)
+                .map(|re| re.find_iter(text).map(|m| m.range()).collect())
+                .unwrap_or_default(),
+            Self::Text { hash, len } => text
+                .char_indices()
+                .map(|(start, _)| start..start + len)
+                .filter(|range| text.get(range.clone()).is_some_and(|s| token_hash(s) == *hash))
+                .collect(),
+            Self::Token { hash } => tokens(text)
+                .filter(|m| token_hash(m.as_str()) == *hash)
+                .map(|m| m.range())
+                .collect(),
+        }
+    }
+}
+
+/// Blank what `redacts` matches in a history entry's command and intent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryRedaction {
+    pub id: HistoryId,
+    pub redacts: Redacts,
+}
+
+impl HistoryRedaction {
+    /// A redaction of `redacts`, if any pattern is a valid regular expression.
+    pub fn new(id: HistoryId, redacts: Redacts) -> Result<Self> {
+        if let Redacts::Pattern(pattern) = &redacts {
+            Regex::new(pattern)
+                .wrap_err_with(|| format!("invalid redaction pattern {pattern:?}"))?;
+        }
+
+        Ok(Self { id, redacts })
+    }
+}
+
+/// An edit or a redaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Amendment {
+    Edit(HistoryEdit),
+    Redact(HistoryRedaction),
+}
+
+impl Amendment {
+    /// The entry amended.
+    pub fn id(&self) -> &HistoryId {
+        match self {
+            Self::Edit(edit) => &edit.id,
+            Self::Redact(redaction) => &redaction.id,
+        }
+    }
+}
+
+/// Blank everything any of `redactions` matches in `text`, with [`REDACTED`].
+///
+/// Every redaction is matched against `text` as given, and overlapping matches are blanked as one,
+/// so the result doesn't depend on the order of `redactions`. Matches that overlap text already
+/// blanked are blanked along with it, so redacting text again leaves it as it is.
+pub fn redact<'a>(text: &str, redactions: impl IntoIterator<Item = &'a Redacts>) -> String {
+    let mut matches = redactions.into_iter().flat_map(|redacts| redacts.ranges(text)).peekable();
+    if matches.peek().is_none() {
+        return text.to_string();
+    }
+
+    let blanked = text.match_indices(REDACTED).map(|(start, s)| start..start + s.len());
+
+    redact_ranges(text, matches.chain(blanked))
+}
+
+/// Blank the byte `ranges` of `text` with [`REDACTED`], as one where they overlap.
+pub fn redact_ranges(text: &str, ranges: impl IntoIterator<Item = Range<usize>>) -> String {
+    let mut matches: Vec<Range<usize>> =
+        ranges.into_iter().filter(|range| !range.is_empty()).collect();
+    matches.sort_by_key(|range| range.start);
+
+    let mut spans: Vec<Range<usize>> = Vec::with_capacity(matches.len());
+    for range in matches {
+        match spans.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => spans.push(range),
+        }
+    }
+
+    let mut redacted = String::with_capacity(text.len());
+    let mut pos = 0;
+    for span in spans {
+        redacted.push_str(&text[pos..span.start]);
+        redacted.push_str(REDACTED);
+        pos = span.end;
+    }
+    redacted.push_str(&text[pos..]);
+
+    redacted
+}
+
+/// Everything known about an amended entry, as `history_amendments` stores it. None of it is
+/// kept unredacted.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Amendments {
+    /// The entry's command and intent as recorded and redacted, once the entry itself has arrived.
+    pub recorded: Option<(String, Option<String>)>,
+    /// The latest edit, redacted.
+    pub edit: Option<HistoryEdit>,
+    /// What every redaction blanks.
+    pub redactions: BTreeSet<Redacts>,
+}
+
+impl Amendments {
+    /// Keep the entry's command and intent as recorded, redacted.
+    pub fn record(&mut self, command: &str, intent: Option<&str>) {
+        self.recorded = Some((
+            redact(command, &self.redactions),
+            intent.map(|intent| redact(intent, &self.redactions)),
+        ));
+    }
+
+    pub fn apply(&mut self, amendment: Amendment) {
+        match amendment {
+            Amendment::Edit(mut edit) => {
+                edit.command = redact(&edit.command, &self.redactions);
+                edit.intent = edit.intent.map(|intent| redact(&intent, &self.redactions));
+
+                // Ties are broken on the text, so every host picks the same edit.
+                let newer = self.edit.as_ref().is_none_or(|current| {
+                    (edit.timestamp, &edit.command, &edit.intent)
+                        > (current.timestamp, &current.command, &current.intent)
+                });
+
+                if newer {
+                    self.edit = Some(edit);
+                }
+            }
+            Amendment::Redact(redaction) => {
+                let redacts = [redaction.redacts];
+                let texts = self
+                    .recorded
+                    .iter_mut()
+                    .map(|recorded| (&mut recorded.0, &mut recorded.1))
+                    .chain(self.edit.iter_mut().map(|edit| (&mut edit.command, &mut edit.intent)));
+                for (command, intent) in texts {
+                    *command = redact(command, &redacts);
+                    if let Some(intent) = intent {
+                        *intent = redact(intent, &redacts);
+                    }
+                }
+
+                let [redacts] = redacts;
+                self.redactions.insert(redacts);
+            }
+        }
+    }
+
+    /// The command and intent the entry shows, or `None` if neither it nor an edit of it has
+    /// arrived yet.
+    pub fn current(&self) -> Option<(String, Option<String>)> {
+        match (&self.edit, &self.recorded) {
+            (Some(edit), _) => Some((edit.command.clone(), edit.intent.clone())),
+            (None, Some(recorded)) => Some(recorded.clone()),
+            (None, None) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+    use time::macros::datetime;
+
+    use super::*;
+
+    fn patterns(patterns: &[&str]) -> Vec<Redacts> {
+        patterns.iter().map(|p| Redacts::Pattern((*p).to_string())).collect()
+    }
+
+    #[rstest]
+    #[case("curl -H 'token: abc123'", &["abc123"], "curl -H 'token: [REDACTED]'")]
+    #[case("export A=1 B=2", &["A=\\d", "B=\\d"], "export [REDACTED] [REDACTED]")]
+    #[case("echo secret", &["secret", "sec"], "echo [REDACTED]")]
+    #[case("echo secret", &["sec", "cret"], "echo [REDACTED]")]
+    #[case("echo hi", &["nope"], "echo hi")]
+    #[case("echo hi", &["x*"], "echo hi")]
+    fn redacts_matches(#[case] text: &str, #[case] with: &[&str], #[case] expected: &str) {
+        assert_eq!(redact(text, &patterns(with)), expected);
+
+        let mut reversed = with.to_vec();
+        reversed.reverse();
+        assert_eq!(redact(text, &patterns(&reversed)), expected);
+    }
+
+    fn edit(at: OffsetDateTime, command: &str) -> Amendment {
+        Amendment::Edit(HistoryEdit {
+            id: "id".to_string().into(),
+            timestamp: at,
+            command: command.to_string(),
+            intent: None,
+        })
+    }
+
+    fn redaction(pattern: &str) -> Amendment {
+        let redacts = Redacts::Pattern(pattern.to_string());
+        Amendment::Redact(HistoryRedaction::new("id".to_string().into(), redacts).unwrap())
+    }
+
+    #[test]
+    fn amendments_converge_in_any_order() {
+        let amendments = [
+            edit(datetime!(2026-01-01 00:00 UTC), "git psuh hunter2"),
+            edit(datetime!(2026-01-02 00:00 UTC), "git push hunter2"),
+            redaction("hunter2"),
+            redaction("git"),
+        ];
+
+        let mut forwards = Amendments::default();
+        forwards.record("git psuh", None);
+        let mut backwards = forwards.clone();
+
+        for amendment in amendments.iter().cloned() {
+            forwards.apply(amendment);
+        }
+        for amendment in amendments.iter().rev().cloned() {
+            backwards.apply(amendment);
+        }
+
+        assert_eq!(forwards, backwards);
+        assert_eq!(forwards.current(), Some(("[REDACTED] push [REDACTED]".to_string(), None)));
+    }
+
+    #[test]
+    fn nothing_to_show_before_the_entry_arrives() {
+        let mut amendments = Amendments::default();
+        amendments.apply(redaction("hunter2"));
+        assert_eq!(amendments.current(), None);
+
+        amendments.record("echo hunter2", Some("hunter2"));
+        assert_eq!(
+            amendments.current(),
+            Some(("echo [REDACTED]".to_string(), Some("[REDACTED]".to_string())))
+        );
+    }
+
+    #[test]
+    fn redacted_text_isnt_kept() {
+        let mut amendments = Amendments::default();
+        amendments.record("mysql -p hunter2", None);
+        amendments.apply(edit(datetime!(2026-01-01 00:00 UTC), "mysql -u root -p hunter2"));
+        amendments.apply(redaction("hunter2"));
+        amendments.apply(edit(datetime!(2026-01-02 00:00 UTC), "mysql -u admin -p hunter2"));
+
+        assert!(!format!("{:?}", amendments.recorded).contains("hunter2"));
+        assert!(!format!("{:?}", amendments.edit).contains("hunter2"));
+        assert_eq!(amendments.current(), Some(("mysql -u admin -p [REDACTED]".to_string(), None)));
+    }
+
+    #[test]
+    fn redacting_again_changes_nothing() {
+        let patterns = patterns(&["E", "hunter2"]);
+        let once = redact("echo hunter2", &patterns);
+        assert_eq!(redact(&once, &patterns), once);
+    }
+
+    #[test]
+    fn invalid_patterns_are_rejected() {
+        let redacts = Redacts::Pattern("(".to_string());
+        assert!(HistoryRedaction::new("id".to_string().into(), redacts).is_err());
+    }
+
+    #[test]
+    fn tokens_are_redacted_by_their_hash() {
+        let secret = "9fQ2xLk7Vb3Rz8Wm1Tn5Yc4Hd6";
+        let redacts = [Redacts::Token {
+            hash: token_hash(secret),
+        }];
+
+        assert_eq!(
+            redact(&format!("curl -H 'X-Api-Key: {secret}' -d {secret}x"), &redacts),
+            format!("curl -H 'X-Api-Key: [REDACTED]' -d {secret}x")
+        );
+    }
+
+    #[rstest]
+    #[case("echo hunter2", "echo hunter2", "[REDACTED]")]
+    #[case("hunter2", "echo hunter2 && echo hunter2!", "echo [REDACTED] && echo [REDACTED]!")]
+    #[case("hunter2", "echo hunter", "echo hunter")]
+    #[case("é", "café é", "caf[REDACTED] [REDACTED]")]
+    fn text_is_redacted_by_its_hash(
+        #[case] secret: &str,
+        #[case] text: &str,
+        #[case] expected: &str,
+    ) {
+        let redacts = Redacts::text(secret);
+        assert!(!format!("{redacts:?}").contains(secret));
+
+        assert_eq!(redact(text, [&redacts]), expected);
+    }
+
+    #[test]
+    fn redactions_are_stored_as_patterns_or_hashes() {
+        let redactions = BTreeSet::from([
+            Redacts::Pattern("hunter2".to_string()),
+            Redacts::Text {
+                hash: "def".to_string(),
+                len: 7,
+            },
+            Redacts::Token {
+                hash: "abc".to_string(),
+            },
+        ]);
+        let json = serde_json::to_string(&redactions).unwrap();
+
+        assert_eq!(json, r#"["hunter2",{"hash":"def","len":7},{"hash":"abc"}]"#);
+        assert_eq!(serde_json::from_str::<BTreeSet<Redacts>>(&json).unwrap(), redactions);
+    }
+}
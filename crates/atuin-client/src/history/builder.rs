@@ -18,15 +18,15 @@ pub struct HistoryImported {
     exit: i64,
     #[builder(default = Self::DEFAULT_DURATION)]
     duration: i64,
-    #[builder(default, setter(strip_option, into))]
+    #[builder(default, setter(strip_option(fallback_suffix = "_opt"), into))]
     session: Option<String>,
-    #[builder(default, setter(strip_option, into))]
+    #[builder(default, setter(strip_option(fallback_suffix = "_opt"), into))]
     cmd_origin: Option<CmdOrigin>,
-    #[builder(default, setter(strip_option, into))]
+    #[builder(default, setter(strip_option(fallback_suffix = "_opt"), into))]
     author: Option<String>,
-    #[builder(default, setter(strip_option, into))]
+    #[builder(default, setter(strip_option(fallback_suffix = "_opt"), into))]
     intent: Option<String>,
-    #[builder(default, setter(strip_option, into))]
+    #[builder(default, setter(strip_option(fallback_suffix = "_opt"), into))]
     shell: Option<String>,
     #[builder(default)]
     author_kind: Option<AuthorKind>,
@@ -0,0 +1,273 @@
+//! Which history entries the `[retention]` settings no longer keep.
+//!
+//! Expired entries are deleted through the history store, like any other delete, so the deletes
+//! sync and every machine converges on the same history, whichever of them enforced the policy.
+
+use std::collections::HashSet;
+
+use eyre::Result;
+use time::OffsetDateTime;
+
+use super::store::HistoryStore;
+use super::{History, HistoryId};
+use crate::database::Sqlite;
+use crate::settings::retention::Settings;
+
+/// Why an entry isn't kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Reason {
+    /// The rule at this index of `retention.rules` matched it, and it's older than the rule keeps.
+    Rule(usize),
+    /// It's among the oldest of more than `retention.max_entries`.
+    MaxEntries,
+}
+
+/// An entry the retention settings no longer keep.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expired {
+    pub history: History,
+    pub reason: Reason,
+}
+
+/// How many entries each rule reads from the database at a time.
+const PAGE_SIZE: usize = 1000;
+
+/// The entries `settings` no longer keep, as of `now`. An entry more than one rule expires is only
+/// listed once, for the first.
+pub async fn expired(
+    db: &Sqlite,
+    settings: &Settings,
+    now: OffsetDateTime,
+) -> Result<Vec<Expired>> {
+    let mut expired = Vec::new();
+    let mut seen = HashSet::new();
+
+    for (i, rule) in settings.rules.iter().enumerate() {
+        // A rule keeping entries for longer than there's been time has nothing to expire.
+        let Some(cutoff) =
+            time::Duration::try_from(rule.keep.0).ok().and_then(|keep| now.checked_sub(keep))
+        else {
+            continue;
+        };
+
+        let mut after = None;
+        loop {
+            let page = db.expiring(cutoff, rule, after.as_ref(), PAGE_SIZE).await?;
+            let last_page = page.len() < PAGE_SIZE;
+            after = page.last().cloned();
+
+            for h in page {
+                if seen.insert(h.id.clone()) {
+                    expired.push(Expired {
+                        history: h,
+                        reason: Reason::Rule(i),
+                    });
+                }
+            }
+
+            if last_page {
+                break;
+            }
+        }
+    }
+
+    if let Some(max) = settings.max_entries {
+        let kept = u64::try_from(db.history_count(false).await?)?.saturating_sub(seen.len() as u64);
+
+        if kept > max {
+            let excess = usize::try_from(kept - max)?;
+
+            // The oldest entries may have expired already, so look past them.
+            let oldest = db.oldest(i64::try_from(excess + seen.len())?).await?;
+            expired.extend(oldest.into_iter().filter(|h| !seen.contains(&h.id)).take(excess).map(
+                |history| Expired {
+                    history,
+                    reason: Reason::MaxEntries,
+                },
+            ));
+        }
+    }
+
+    Ok(expired)
+}
+
+/// Delete the entries `settings` no longer keep, as of `now`, on every machine they sync to.
+/// Returns the ids deleted.
+pub async fn enforce(
+    history_store: &HistoryStore,
+    db: &Sqlite,
+    settings: &Settings,
+    now: OffsetDateTime,
+) -> Result<Vec<HistoryId>> {
+    let expired: Vec<History> =
+        expired(db, settings, now).await?.into_iter().map(|e| e.history).collect();
+    let ids = expired.iter().map(|h| h.id.clone()).collect();
+
+    let record_ids = history_store.delete_entries(expired).await?;
+    history_store.build_all(db, &record_ids).await?;
+
+    Ok(ids)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use atuin_domain::record::{CmdOrigin, HostId};
+    use pretty_assertions::assert_eq;
+    use regex::Regex;
+    use time::macros::datetime;
+
+    use super::*;
+    use crate::record::sqlite_store::SqliteStore;
+    use crate::settings::retention::{Age, Rule};
+    use crate::settings::test_local_timeout;
+
+    const NOW: OffsetDateTime = datetime!(2026-06-01 00:00 UTC);
+
+    fn entry(n: u32, days_ago: i64, command: &str, exit: i64, origin: &str) -> History {
+        History::import()
+            .timestamp(NOW - time::Duration::days(days_ago) + time::Duration::seconds(n.into()))
+            .command(command)
+            .cwd("/")
+            .exit(exit)
+            .duration(1)
+            .cmd_origin(CmdOrigin::try_from(origin.to_owned()).unwrap())
+            .build()
+            .into()
+    }
+
+    fn rule(days: u64) -> Rule {
+        Rule {
+            keep: Age(Duration::from_secs(days * 24 * 60 * 60)),
+            command: None,
+            cwd: None,
+            host: None,
+            failed: false,
+        }
+    }
+
+    fn commands(expired: &[Expired]) -> Vec<(&str, Reason)> {
+        expired.iter().map(|e| (e.history.command.as_str(), e.reason)).collect()
+    }
+
+    async fn db(entries: &[History]) -> Sqlite {
+        let db = Sqlite::new("sqlite::memory:", test_local_timeout()).await.unwrap();
+        db.save_bulk(entries).await.unwrap();
+        db
+    }
+
+    #[tokio::test]
+    async fn rules_expire_what_they_match() {
+        let db = db(&[
+            entry(0, 40, "make old-failure", 1, "laptop:ellie"),
+            entry(1, 10, "make new-failure", 1, "laptop:ellie"),
+            entry(2, 40, "make old-success", 0, "laptop:ellie"),
+            entry(3, 8, "curl old", 0, "laptop:ellie"),
+            entry(4, 400, "ls", 0, "old-laptop:ellie"),
+            entry(5, 400, "ls", 0, "desktop:ellie"),
+        ])
+        .await;
+
+        let settings = Settings {
+            rules: vec![
+                Rule {
+                    failed: true,
+                    ..rule(30)
+                },
+                Rule {
+                    command: Some(Regex::new("^curl .*").unwrap()),
+                    ..rule(7)
+                },
+                Rule {
+                    host: Some("old-laptop".to_string()),
+                    ..rule(365)
+                },
+            ],
+            ..Settings::default()
+        };
+
+        let expired = expired(&db, &settings, NOW).await.unwrap();
+        assert_eq!(commands(&expired), vec![
+            ("make old-failure", Reason::Rule(0)),
+            ("curl old", Reason::Rule(1)),
+            ("ls", Reason::Rule(2)),
+        ]);
+        assert_eq!(expired[2].history.cmd_origin.host().into_inner(), "old-laptop");
+    }
+
+    #[tokio::test]
+    async fn rules_read_every_page() {
+        let entries: Vec<History> = (0..=PAGE_SIZE as u32 * 2)
+            .map(|n| {
+                let command = if n % 2 == 0 {
+                    "make all"
+                } else {
+                    "ls"
+                };
+                entry(n, 40, command, 0, "laptop:ellie")
+            })
+            .collect();
+        let db = db(&entries).await;
+
+        let settings = Settings {
+            rules: vec![Rule {
+                command: Some(Regex::new("^make (all|test)$").unwrap()),
+                ..rule(30)
+            }],
+            ..Settings::default()
+        };
+
+        let expired = expired(&db, &settings, NOW).await.unwrap();
+        assert_eq!(expired.len(), PAGE_SIZE + 1);
+        assert!(expired.iter().all(|e| e.history.command == "make all"));
+    }
+
+    #[tokio::test]
+    async fn max_entries_expires_the_oldest_left() {
+        let db = db(&[
+            entry(0, 50, "first", 1, "laptop:ellie"),
+            entry(1, 40, "second", 0, "laptop:ellie"),
+            entry(2, 30, "third", 0, "laptop:ellie"),
+            entry(3, 20, "fourth", 0, "laptop:ellie"),
+        ])
+        .await;
+
+        let settings = Settings {
+            max_entries: Some(2),
+            rules: vec![Rule {
+                failed: true,
+                ..rule(30)
+            }],
+            ..Settings::default()
+        };
+
+        // "first" expires by the rule, leaving three: one too many.
+        assert_eq!(commands(&expired(&db, &settings, NOW).await.unwrap()), vec![
+            ("first", Reason::Rule(0)),
+            ("second", Reason::MaxEntries),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn enforcing_deletes_through_the_store() {
+        let db =
+            db(&[entry(0, 50, "old", 0, "laptop:ellie"), entry(1, 1, "new", 0, "laptop:ellie")])
+                .await;
+        let store = SqliteStore::new(":memory:", test_local_timeout()).await.unwrap();
+        let history_store =
+            HistoryStore::new(store, HostId(atuin_common::utils::uuid_v7()), [0u8; 32].into());
+
+        let settings = Settings {
+            rules: vec![rule(30)],
+            ..Settings::default()
+        };
+
+        let deleted = enforce(&history_store, &db, &settings, NOW).await.unwrap();
+        assert_eq!(deleted.len(), 1);
+
+        assert!(expired(&db, &settings, NOW).await.unwrap().is_empty());
+        assert_eq!(db.history_count(false).await.unwrap(), 1);
+        assert_eq!(history_store.history().await.unwrap().len(), 1);
+    }
+}
@@ -6,6 +6,7 @@ use std::time::Duration;
 use atuin_common::encryption::paseto_v4;
 use atuin_common::futures::stream::chunk_by_bounded;
 use atuin_common::rmp::decode::Bytes;
+use atuin_common::time::OffsetDateTimeExt;
 use atuin_domain::record::{
     DecryptedData, Host, HostId, Record, RecordId, RecordIdx, RecordSeriesKey, RecordTag,
     RecordVersion,
@@ -13,9 +14,11 @@ use atuin_domain::record::{
 use eyre::{Result, bail, eyre};
 use futures::{Stream, StreamExt, TryStreamExt, future, stream};
 use indicatif::{ProgressBar, ProgressState, ProgressStyle};
+use time::OffsetDateTime;
 use tracing::instrument;
 use tracing_futures::Instrument;
 
+use super::amend::{Amendment, HistoryEdit, HistoryRedaction, Redacts};
 use super::{History, HistoryId, Version};
 use crate::database::{Sqlite, current_context};
 use crate::record::sqlite_store::SqliteStore;
@@ -35,8 +38,10 @@ pub struct HistoryStore {
         would use more memory overall"
 )]
 pub enum HistoryRecord {
-    Create(History),   // Create a history record
-    Delete(HistoryId), // Delete a history record, identified by ID
+    Create(History),          // Create a history record
+    Delete(HistoryId),        // Delete a history record, identified by ID
+    Edit(HistoryEdit),        // Replace a history record's command and intent
+    Redact(HistoryRedaction), // Blank what a pattern or token hash matches in a history record
 }
 
 impl HistoryRecord {
@@ -46,12 +51,15 @@ impl HistoryRecord {
     ///
     /// HistoryRecord::Create -> 0
     /// HistoryRecord::Delete-> 1
+    /// HistoryRecord::Edit -> 2
+    /// HistoryRecord::Redact -> 3, or 4 for redactions of a token by its hash
     ///
     /// This numeric identifier is then written as the first byte to the buffer. For history, we
     /// append the serialized history right afterwards, to avoid having to handle serialization
     /// twice.
     ///
-    /// Deletion simply refers to the history by ID
+    /// Deletion simply refers to the history by ID. Edits and redactions refer to it by ID too,
+    /// followed by their fields.
     pub fn serialize(&self) -> Result<DecryptedData> {
         // probably don't actually need to use rmp here, but if we ever need to extend it, it's a
         // nice wrapper around raw byte stuff
@@ -73,6 +81,29 @@ impl HistoryRecord {
                 encode::write_u8(&mut output, 1)?;
                 encode::write_str(&mut output, id.0.as_str())?;
             }
+            Self::Edit(edit) => {
+                // 2 -> a history edit
+                encode::write_u8(&mut output, 2)?;
+                encode::write_str(&mut output, edit.id.0.as_str())?;
+                encode::write_u64(&mut output, edit.timestamp.unix_timestamp_nanos() as u64)?;
+                encode::write_str(&mut output, &edit.command)?;
+                encode::write_optional(&mut output, edit.intent.as_deref(), encode::write_str)?;
+            }
+            Self::Redact(redaction) => {
+                // 3 -> a history redaction of a pattern, 4 -> of a token by its hash, 5 -> of
+                // some text by its hash and length
+                let (kind, redacts, len) = match &redaction.redacts {
+                    Redacts::Pattern(pattern) => (3, pattern, None),
+                    Redacts::Token { hash } => (4, hash, None),
+                    Redacts::Text { hash, len } => (5, hash, Some(*len)),
+                };
+                encode::write_u8(&mut output, kind)?;
+                encode::write_str(&mut output, redaction.id.0.as_str())?;
+                encode::write_str(&mut output, redacts)?;
+                if let Some(len) = len {
+                    encode::write_u64(&mut output, len as u64)?;
+                }
+            }
         };
 
         Ok(DecryptedData(output))
@@ -115,6 +146,49 @@ impl HistoryRecord {
                 Ok(Self::Delete(id.to_string().into()))
             }
 
+            // 2 -> HistoryRecord::Edit
+            2 => {
+                let id = decode::read_string(&mut bytes)?;
+                let timestamp = decode::read_u64(&mut bytes).map_err(error_report)?;
+                let command = decode::read_string(&mut bytes)?;
+                let intent = decode::read_optional(&mut bytes, decode::read_string)?;
+
+                if !bytes.remaining_slice().is_empty() {
+                    bail!("trailing bytes decoding HistoryRecord::Edit - malformed?");
+                }
+
+                Ok(Self::Edit(HistoryEdit {
+                    id: id.into(),
+                    timestamp: OffsetDateTime::from_unix_nanos_u64(timestamp),
+                    command,
+                    intent,
+                }))
+            }
+
+            // 3, 4, 5 -> HistoryRecord::Redact
+            kind @ (3..=5) => {
+                let id = decode::read_string(&mut bytes)?;
+                let redacts = decode::read_string(&mut bytes)?;
+
+                let redacts = match kind {
+                    3 => Redacts::Pattern(redacts),
+                    4 => Redacts::Token { hash: redacts },
+                    _ => Redacts::Text {
+                        hash: redacts,
+                        len: usize::try_from(decode::read_u64(&mut bytes).map_err(error_report)?)?,
+                    },
+                };
+
+                if !bytes.remaining_slice().is_empty() {
+                    bail!("trailing bytes decoding HistoryRecord::Redact - malformed?");
+                }
+
+                Ok(Self::Redact(HistoryRedaction {
+                    id: id.into(),
+                    redacts,
+                }))
+            }
+
             n => {
                 bail!("unknown HistoryRecord type {n}");
             }
@@ -122,6 +196,17 @@ impl HistoryRecord {
     }
 }
 
+/// A batch of changes [`HistoryStore::incremental_build`] made to the history database.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Built {
+    /// New entries.
+    Created(Vec<History>),
+    /// Entries deleted.
+    Deleted(Vec<HistoryId>),
+    /// Entries edited or redacted, as they are now.
+    Amended(Vec<History>),
+}
+
 /// How many entries `incremental_build` holds in memory, and the most it puts into a single
 /// `save_bulk`/`delete_rows` transaction.
 const BUILD_BATCH_SIZE: NonZeroUsize = NonZeroUsize::new(5000).unwrap();
@@ -219,6 +304,41 @@ impl HistoryStore {
         Ok(record_ids)
     }
 
+    /// Replace the command and intent of the entry `id`, on every host it syncs to.
+    #[instrument(level = "trace", skip_all, fields(host = ?self.host_id, id = ?id), err)]
+    pub async fn edit(
+        &self,
+        id: HistoryId,
+        command: String,
+        intent: Option<String>,
+    ) -> Result<(RecordId, RecordIdx)> {
+        let record = HistoryRecord::Edit(HistoryEdit {
+            id,
+            timestamp: OffsetDateTime::now_utc(),
+            command,
+            intent,
+        });
+
+        self.push_record(record).await
+    }
+
+    /// Blank what `redacts` matches in the command and intent of each of `entries`, on every host
+    /// they sync to. Returns the record IDs so the caller can run incremental_build when ready.
+    #[instrument(level = "trace", skip_all, fields(host = ?self.host_id), err)]
+    pub async fn redact_entries(
+        &self,
+        entries: impl IntoIterator<Item = HistoryId>,
+        redacts: &Redacts,
+    ) -> Result<Vec<RecordId>> {
+        let mut record_ids = Vec::new();
+        for id in entries {
+            let record = HistoryRecord::Redact(HistoryRedaction::new(id, redacts.clone())?);
+            let (id, _) = self.push_record(record).await?;
+            record_ids.push(id);
+        }
+        Ok(record_ids)
+    }
+
     #[instrument(level = "trace", skip_all, fields(host = ?self.host_id), err)]
     pub async fn push(&self, history: History) -> Result<(RecordId, RecordIdx)> {
         // TODO(ellie): move the history store to its own file
@@ -285,6 +405,7 @@ impl HistoryStore {
         // create/delete before we even get to sqlite
         let mut creates = Vec::new();
         let mut deletes = Vec::new();
+        let mut amendments = Vec::new();
 
         for i in history {
             match i {
@@ -294,21 +415,28 @@ impl HistoryStore {
                 HistoryRecord::Delete(id) => {
                     deletes.push(id);
                 }
+                HistoryRecord::Edit(edit) => {
+                    amendments.push(Amendment::Edit(edit));
+                }
+                HistoryRecord::Redact(redaction) => {
+                    amendments.push(Amendment::Redact(redaction));
+                }
             }
         }
 
         database.save_bulk(&creates).await?;
         database.delete_rows(deletes).await?;
+        database.amend(amendments).await?;
 
         Ok(())
     }
 
-    /// Apply records to the history database, yielding each batch of created `History`.
+    /// Apply records to the history database, yielding each batch of changes made.
     pub fn incremental_build<'a>(
         &'a self,
         database: &'a Sqlite,
         ids: &'a [RecordId],
-    ) -> impl Stream<Item = Result<Vec<History>>> + 'a {
+    ) -> impl Stream<Item = Result<Built>> + 'a {
         // Group the concurrent per-record decodes under one span so the whole
         // decode fan-out shows as a single unit (each `decode` nests under it).
         let records = stream::iter(ids)
@@ -326,27 +454,41 @@ impl HistoryStore {
                 // `chunk_by_bounded` already collected.
                 match kind {
                     HistoryRecordKind::Create => {
-                        let creates: Vec<_> = chunk
+                        let mut creates: Vec<_> = chunk
                             .into_iter()
                             .map(|record| match record {
                                 HistoryRecord::Create(h) => h,
-                                HistoryRecord::Delete(_) => unreachable!(),
+                                _ => unreachable!(),
                             })
                             .collect();
 
+                        // Amendments may have arrived before the entries they amend.
+                        database.apply_amendments(&mut creates).await?;
                         database.save_bulk(&creates).await?;
 
-                        Ok(creates)
+                        Ok(Built::Created(creates))
                     }
                     HistoryRecordKind::Delete => {
-                        let deletes = chunk.into_iter().map(|record| match record {
-                            HistoryRecord::Delete(id) => id,
-                            HistoryRecord::Create(_) => unreachable!(),
-                        });
+                        let deletes: Vec<_> = chunk
+                            .into_iter()
+                            .map(|record| match record {
+                                HistoryRecord::Delete(id) => id,
+                                _ => unreachable!(),
+                            })
+                            .collect();
 
-                        database.delete_rows(deletes).await?;
+                        database.delete_rows(deletes.iter().cloned()).await?;
 
-                        Ok(Vec::new())
+                        Ok(Built::Deleted(deletes))
+                    }
+                    HistoryRecordKind::Edit | HistoryRecordKind::Redact => {
+                        let amendments = chunk.into_iter().map(|record| match record {
+                            HistoryRecord::Edit(edit) => Amendment::Edit(edit),
+                            HistoryRecord::Redact(redaction) => Amendment::Redact(redaction),
+                            _ => unreachable!(),
+                        });
+
+                        Ok(Built::Amended(database.amend(amendments).await?))
                     }
                 }
             },
@@ -397,9 +539,11 @@ impl HistoryStore {
     pub async fn history_ids(&self) -> Result<HashSet<HistoryId>> {
         let history = self.history().await?;
 
-        let ret = HashSet::from_iter(history.iter().map(|h| match h {
-            HistoryRecord::Create(h) => h.id.clone(),
-            HistoryRecord::Delete(id) => id.clone(),
+        // Amendments don't put an entry in the store; only its create or delete does.
+        let ret = HashSet::from_iter(history.iter().filter_map(|h| match h {
+            HistoryRecord::Create(h) => Some(h.id.clone()),
+            HistoryRecord::Delete(id) => Some(id.clone()),
+            HistoryRecord::Edit(_) | HistoryRecord::Redact(_) => None,
         }));
 
         Ok(ret)
@@ -425,6 +569,7 @@ impl HistoryStore {
 
         pb.set_message("Fetching history already in store");
         let store_ids = self.history_ids().await?;
+        let local_only = db.local_only_ids().await?;
 
         pb.set_message("Converting old history to new store");
         let mut records = Vec::new();
@@ -437,6 +582,11 @@ impl HistoryStore {
                 continue;
             }
 
+            if local_only.contains(&i.id) {
+                debug!("skipping {} - only kept locally", i.id);
+                continue;
+            }
+
             if i.deleted_at.is_some() {
                 records.push(HistoryRecord::Delete(i.id));
             } else {
@@ -466,9 +616,10 @@ mod tests {
     use time::Duration;
     use time::macros::datetime;
 
-    use super::{BUILD_BATCH_SIZE, History};
+    use super::{BUILD_BATCH_SIZE, Built, History};
     use crate::database::{Context, Sqlite};
     use crate::history::Version;
+    use crate::history::amend::{HistoryEdit, HistoryRedaction, Redacts};
     use crate::history::store::{HistoryRecord, HistoryStore};
     use crate::record::sqlite_store::SqliteStore;
     use crate::settings::test_local_timeout;
@@ -558,6 +709,51 @@ mod tests {
             97, 101, 101, 54, 53, 99, 100, 55, 56, 54, 49, 102, 57, 99, 56, 49,
         ]
     )]
+    #[case::edit(
+        HistoryRecord::Edit(HistoryEdit {
+            id: "018cd4fe81757cd2aee65cd7861f9c81".to_owned().into(),
+            timestamp: datetime!(2024-01-05 00:00:00.000000 +00:00),
+            command: "ls -l".to_owned(),
+            intent: Some("list".to_owned()),
+        }),
+        vec![
+            204, 2, 217, 32, 48, 49, 56, 99, 100, 52, 102, 101, 56, 49, 55, 53, 55, 99, 100, 50,
+            97, 101, 101, 54, 53, 99, 100, 55, 56, 54, 49, 102, 57, 99, 56, 49, 207, 23, 167, 74,
+            105, 70, 161, 0, 0, 165, 108, 115, 32, 45, 108, 164, 108, 105, 115, 116,
+        ]
+    )]
+    #[case::redact(
+        HistoryRecord::Redact(HistoryRedaction {
+            id: "018cd4fe81757cd2aee65cd7861f9c81".to_owned().into(),
+            redacts: Redacts::Pattern("hunter\\d".to_owned()),
+        }),
+        vec![
+            204, 3, 217, 32, 48, 49, 56, 99, 100, 52, 102, 101, 56, 49, 55, 53, 55, 99, 100, 50,
+            97, 101, 101, 54, 53, 99, 100, 55, 56, 54, 49, 102, 57, 99, 56, 49, 168, 104, 117, 110,
+            116, 101, 114, 92, 100,
+        ]
+    )]
+    #[case::redact_token(
+        HistoryRecord::Redact(HistoryRedaction {
+            id: "018cd4fe81757cd2aee65cd7861f9c81".to_owned().into(),
+            redacts: Redacts::Token { hash: "abc".to_owned() },
+        }),
+        vec![
+            204, 4, 217, 32, 48, 49, 56, 99, 100, 52, 102, 101, 56, 49, 55, 53, 55, 99, 100, 50,
+            97, 101, 101, 54, 53, 99, 100, 55, 56, 54, 49, 102, 57, 99, 56, 49, 163, 97, 98, 99,
+        ]
+    )]
+    #[case::redact_text(
+        HistoryRecord::Redact(HistoryRedaction {
+            id: "018cd4fe81757cd2aee65cd7861f9c81".to_owned().into(),
+            redacts: Redacts::Text { hash: "abc".to_owned(), len: 7 },
+        }),
+        vec![
+            204, 5, 217, 32, 48, 49, 56, 99, 100, 52, 102, 101, 56, 49, 55, 53, 55, 99, 100, 50,
+            97, 101, 101, 54, 53, 99, 100, 55, 56, 54, 49, 102, 57, 99, 56, 49, 163, 97, 98, 99,
+            207, 0, 0, 0, 0, 0, 0, 0, 7,
+        ]
+    )]
     fn test_serialize_deserialize(#[case] record: HistoryRecord, #[case] expected_bytes: Vec<u8>) {
         assert_record_roundtrip(&record, &expected_bytes);
     }
@@ -606,8 +802,7 @@ mod tests {
 
         let db = memory_db().await;
 
-        let created: Vec<History> =
-            history_store.incremental_build(&db, &[record_id]).try_concat().await.unwrap();
+        let created = created(&history_store, &db, &[record_id]).await;
 
         assert_eq!(created.len(), 1);
         assert_eq!(created[0], history);
@@ -637,20 +832,38 @@ mod tests {
         }
     }
 
-    /// Each yielded batch is one `save_bulk`, so batch sizes are the transaction shape.
-    async fn batch_sizes(
+    /// The entries each yielded batch created, or nothing for batches of deletes and amendments.
+    async fn built(
         history_store: &HistoryStore,
         db: &Sqlite,
         ids: &[atuin_domain::record::RecordId],
-    ) -> Vec<usize> {
+    ) -> Vec<Vec<History>> {
         history_store
             .incremental_build(db, ids)
-            .try_collect::<Vec<Vec<History>>>()
+            .map_ok(|built| match built {
+                Built::Created(created) => created,
+                Built::Deleted(_) | Built::Amended(_) => Vec::new(),
+            })
+            .try_collect()
             .await
             .unwrap()
-            .iter()
-            .map(Vec::len)
-            .collect()
+    }
+
+    async fn created(
+        history_store: &HistoryStore,
+        db: &Sqlite,
+        ids: &[atuin_domain::record::RecordId],
+    ) -> Vec<History> {
+        built(history_store, db, ids).await.concat()
+    }
+
+    /// Each yielded batch is one `save_bulk`, so batch sizes are the transaction shape.
+    async fn batch_sizes(
+        history_store: &HistoryStore,
+        db: &Sqlite,
+        ids: &[atuin_domain::record::RecordId],
+    ) -> Vec<usize> {
+        built(history_store, db, ids).await.iter().map(Vec::len).collect()
     }
 
     #[rstest]
@@ -748,11 +961,7 @@ mod tests {
 
         let missing = atuin_domain::record::RecordId(atuin_common::utils::uuid_v7());
 
-        let created: Vec<History> = history_store
-            .incremental_build(&db, &[missing, good, corrupt_id])
-            .try_concat()
-            .await
-            .unwrap();
+        let created = created(&history_store, &db, &[missing, good, corrupt_id]).await;
 
         assert_eq!(created, vec![history]);
     }
@@ -773,4 +982,75 @@ mod tests {
 
         assert!(history_store.build_all(&db, &[record_id]).await.is_err());
     }
+
+    async fn commands(db: &Sqlite) -> Vec<String> {
+        let stored = db.list([], &context(), None, false, true, None).await.unwrap();
+        stored.into_iter().map(|h| h.command).collect()
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn amendments_apply_whichever_arrives_first(
+        #[future(awt)]
+        #[from(stores)]
+        parts: (SqliteStore, HostId, HistoryStore),
+        #[from(sample_history)] history: History,
+    ) {
+        let (_store, _host_id, history_store) = parts;
+        let history = History {
+            command: "mysql -p hunter2".to_owned(),
+            ..history
+        };
+
+        let (create, _) = history_store.push(history.clone()).await.unwrap();
+        let redact = history_store
+            .redact_entries([history.id.clone()], &Redacts::Pattern("hunter2".to_owned()))
+            .await
+            .unwrap();
+        let (edit, _) = history_store
+            .edit(history.id.clone(), "mysql -u root -p hunter2".to_owned(), None)
+            .await
+            .unwrap();
+
+        let in_order = [create, redact[0], edit];
+        let backwards = [edit, redact[0], create];
+
+        for ids in [in_order, backwards] {
+            let db = memory_db().await;
+            history_store.build_all(&db, &ids).await.unwrap();
+            assert_eq!(commands(&db).await, vec!["mysql -u root -p [REDACTED]"]);
+        }
+
+        // a full build agrees
+        let db = memory_db().await;
+        history_store.build(&db).await.unwrap();
+        assert_eq!(commands(&db).await, vec!["mysql -u root -p [REDACTED]"]);
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn amending_yields_the_amended_entries(
+        #[future(awt)]
+        #[from(stores)]
+        parts: (SqliteStore, HostId, HistoryStore),
+        #[from(sample_history)] history: History,
+    ) {
+        let (_store, _host_id, history_store) = parts;
+        let db = memory_db().await;
+
+        let (create, _) = history_store.push(history.clone()).await.unwrap();
+        history_store.build_all(&db, &[create]).await.unwrap();
+
+        let redact = history_store
+            .redact_entries([history.id.clone()], &Redacts::Pattern("l".to_owned()))
+            .await
+            .unwrap();
+        let built: Vec<Built> =
+            history_store.incremental_build(&db, &redact).try_collect().await.unwrap();
+
+        assert_eq!(built, vec![Built::Amended(vec![History {
+            command: "[REDACTED]s".to_owned(),
+            ..history
+        }])]);
+    }
 }
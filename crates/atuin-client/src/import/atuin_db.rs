@@ -0,0 +1,264 @@
+// import another machine's atuin history, keeping its ids so that syncing with it later doesn't
+// duplicate anything
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use async_trait::async_trait;
+use atuin_common::encryption::paseto_v4;
+use atuin_common::utils::uuid_v7;
+use atuin_domain::record::HostId;
+use eyre::{Result, WrapErr, eyre};
+
+use super::{Importer, Loader, get_histfile_path, is_file};
+use crate::database::Sqlite;
+use crate::history::amend::{Amendment, Amendments};
+use crate::history::store::{HistoryRecord, HistoryStore};
+use crate::history::{History, HistoryId};
+use crate::record::sqlite_store::SqliteStore;
+
+const PAGE_SIZE: usize = 1000;
+
+/// The default `local_timeout`, for when we're opened without settings.
+const TIMEOUT: f64 = 2.0;
+
+/// Reads a `history.db`, and optionally the `records.db` it was built from.
+///
+/// The record store can hold entries the history database doesn't have yet (if it was never
+/// rebuilt after a sync), and deletions it does: both are taken into account when it's given.
+#[derive(Debug)]
+pub struct AtuinDb {
+    db: Sqlite,
+    records: Option<HistoryStore>,
+}
+
+impl AtuinDb {
+    /// Opens the databases at the given paths read-only: they're never migrated, so they must
+    /// have been written by this version of atuin.
+    pub async fn open(
+        db_path: &Path,
+        records: Option<(&Path, paseto_v4::Key)>,
+        timeout: f64,
+    ) -> Result<Self> {
+        let db = Sqlite::open_read_only(is_file(db_path.to_path_buf())?, timeout)
+            .await
+            .wrap_err_with(|| unreadable(db_path))?;
+
+        let records = match records {
+            Some((path, key)) => {
+                let store = SqliteStore::open_read_only(is_file(path.to_path_buf())?, timeout)
+                    .await
+                    .wrap_err_with(|| unreadable(path))?;
+                // only ever read from, so the host doesn't matter
+                Some(HistoryStore::new(store, HostId(uuid_v7()), key))
+            }
+            None => None,
+        };
+
+        Ok(Self { db, records })
+    }
+
+    /// Entries created in the record store, and ids deleted from it.
+    async fn records(&self) -> Result<(HashMap<HistoryId, History>, HashSet<HistoryId>)> {
+        let mut created = HashMap::new();
+        let mut deleted = HashSet::new();
+        let mut amended: HashMap<HistoryId, Amendments> = HashMap::new();
+
+        let Some(records) = &self.records else {
+            return Ok((created, deleted));
+        };
+
+        for record in records.history().await? {
+            match record {
+                HistoryRecord::Create(h) => {
+                    created.insert(h.id.clone(), h);
+                }
+                HistoryRecord::Delete(id) => {
+                    deleted.insert(id);
+                }
+                HistoryRecord::Edit(edit) => {
+                    amended.entry(edit.id.clone()).or_default().apply(Amendment::Edit(edit));
+                }
+                HistoryRecord::Redact(redaction) => {
+                    amended
+                        .entry(redaction.id.clone())
+                        .or_default()
+                        .apply(Amendment::Redact(redaction));
+                }
+            }
+        }
+
+        // Entries the database has were amended when it was built, but these weren't.
+        for (id, mut amendments) in amended {
+            if let Some(h) = created.get_mut(&id) {
+                amendments.record(&h.command, h.intent.as_deref());
+                if let Some((command, intent)) = amendments.current() {
+                    h.command = command;
+                    h.intent = intent;
+                }
+            }
+        }
+
+        Ok((created, deleted))
+    }
+}
+
+fn unreadable(path: &Path) -> String {
+    format!(
+        "can't read {}: it must be from the same version of atuin as this one, so upgrade \
+         whichever is older (and run it once, to update the database) before importing",
+        path.display()
+    )
+}
+
+#[async_trait]
+impl Importer for AtuinDb {
+    const NAME: &'static str = "atuin-db";
+
+    async fn new() -> Result<Self> {
+        let path = get_histfile_path(|| Err(eyre!("set $HISTFILE to the history.db to import")))?;
+        Self::open(&path, None, TIMEOUT).await
+    }
+
+    async fn entries(&mut self) -> Result<usize> {
+        Ok(usize::try_from(self.db.history_count(false).await?)?)
+    }
+
+    async fn load(self, loader: &mut impl Loader) -> Result<()> {
+        let (mut created, deleted) = self.records().await?;
+
+        let mut pages = self.db.all_paged(PAGE_SIZE, false, false);
+        while let Some(page) = pages.next().await? {
+            for h in page {
+                // the database is built from the store, so it's as new as the store's copy
+                created.remove(&h.id);
+                if !deleted.contains(&h.id) {
+                    loader.push(h).await?;
+                }
+            }
+        }
+
+        for (id, h) in created {
+            if !deleted.contains(&id) {
+                loader.push(h).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::*;
+    use crate::import::tests::TestLoader;
+    use crate::settings::test_local_timeout;
+
+    fn history(command: &str) -> History {
+        History::import()
+            .timestamp(datetime!(2024-02-03 04:05:06 UTC))
+            .command(command)
+            .build()
+            .into()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn merges_the_database_and_the_store() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("history.db");
+        let records_path = dir.path().join("records.db");
+        let key = paseto_v4::Key::generate();
+
+        let in_both = history("in both");
+        let only_in_db = history("only in the database");
+        let only_in_store = history("only in the store");
+        let deleted = history("deleted in the store");
+
+        let db = Sqlite::new(&db_path, test_local_timeout()).await.unwrap();
+        db.save_bulk([&in_both, &only_in_db, &deleted]).await.unwrap();
+
+        let store = SqliteStore::new(&records_path, test_local_timeout()).await.unwrap();
+        let history_store = HistoryStore::new(store, HostId(uuid_v7()), key.clone());
+        for h in [&in_both, &only_in_store, &deleted] {
+            history_store.push(h.clone()).await.unwrap();
+        }
+        history_store.delete(deleted.id.clone()).await.unwrap();
+
+        let importer = AtuinDb::open(&db_path, Some((&records_path, key)), test_local_timeout())
+            .await
+            .unwrap();
+        let mut loader = TestLoader::default();
+        importer.load(&mut loader).await.unwrap();
+
+        let mut imported = loader.buf;
+        imported.sort_by(|a, b| a.command.cmp(&b.command));
+        assert_eq!(imported, [in_both, only_in_db, only_in_store]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn the_store_is_optional() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("history.db");
+
+        let db = Sqlite::new(&db_path, test_local_timeout()).await.unwrap();
+        let entries = [history("ls"), history("pwd")];
+        db.save_bulk(&entries).await.unwrap();
+
+        let mut importer = AtuinDb::open(&db_path, None, test_local_timeout()).await.unwrap();
+        assert_eq!(importer.entries().await.unwrap(), 2);
+
+        let mut loader = TestLoader::default();
+        importer.load(&mut loader).await.unwrap();
+
+        let ids: HashSet<_> = loader.buf.into_iter().map(|h| h.id).collect();
+        assert_eq!(ids, entries.into_iter().map(|h| h.id).collect());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn a_missing_database_is_not_created() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("history.db");
+
+        assert!(AtuinDb::open(&db_path, None, test_local_timeout()).await.is_err());
+        assert!(!db_path.exists());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn the_source_is_not_written_to() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("history.db");
+
+        let db = Sqlite::new(&db_path, test_local_timeout()).await.unwrap();
+        db.save_bulk([&history("ls")]).await.unwrap();
+        db.pool.close().await;
+        let before = std::fs::read(&db_path).unwrap();
+
+        let mut importer = AtuinDb::open(&db_path, None, test_local_timeout()).await.unwrap();
+        assert_eq!(importer.entries().await.unwrap(), 1);
+        importer.load(&mut TestLoader::default()).await.unwrap();
+
+        assert_eq!(std::fs::read(&db_path).unwrap(), before);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn an_unknown_schema_is_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let db_path = dir.path().join("history.db");
+
+        let db = Sqlite::new(&db_path, test_local_timeout()).await.unwrap();
+        // as if a newer version had migrated it
+        sqlx::query(
+            "insert into _sqlx_migrations(version, description, success, checksum, execution_time)
+             values(99990101000000, 'from the future', 1, x'00', 0)",
+        )
+        .execute(&db.pool)
+        .await
+        .unwrap();
+        db.pool.close().await;
+
+        let err = AtuinDb::open(&db_path, None, test_local_timeout()).await.unwrap_err();
+        assert!(format!("{err:#}").contains("99990101000000"), "{err:#}");
+    }
+}
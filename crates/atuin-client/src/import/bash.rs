@@ -13,7 +13,7 @@ use crate::import::read_to_end;
 
 #[derive(Debug)]
 pub struct Bash {
-    bytes: Vec<u8>,
+    pub(crate) bytes: Vec<u8>,
 }
 
 fn default_histpath() -> Result<PathBuf> {
@@ -14,7 +14,7 @@ use crate::import::read_to_end;
 
 #[derive(Debug)]
 pub struct Fish {
-    bytes: Vec<u8>,
+    pub(crate) bytes: Vec<u8>,
 }
 
 /// see <https://fishshell.com/docs/current/interactive.html#searchable-command-history>
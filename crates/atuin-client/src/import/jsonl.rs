@@ -0,0 +1,383 @@
+// import any JSON Lines log, given which of its fields hold what.
+// the defaults read what `atuin history export` writes
+
+use std::path::Path;
+
+use async_trait::async_trait;
+use atuin_common::time::OffsetDateTimeExt;
+use atuin_domain::record::{CmdHost, CmdOrigin, CmdUser};
+use eyre::{Result, eyre};
+use serde_json::Value;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use super::{Importer, Loader, get_histfile_path, is_file, read_to_end};
+use crate::history::builder::HistoryImported;
+use crate::history::{AuthorKind, History};
+
+/// How timestamps are written in the log.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, clap::ValueEnum)]
+pub enum TimestampFormat {
+    /// A string like `2024-02-03T04:05:06.789Z`
+    Rfc3339,
+    /// Seconds since the epoch
+    Unix,
+    /// Milliseconds since the epoch
+    UnixMs,
+    /// Microseconds since the epoch
+    UnixUs,
+    /// Nanoseconds since the epoch
+    UnixNs,
+}
+
+/// What durations are measured in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, clap::ValueEnum)]
+pub enum DurationUnit {
+    S,
+    Ms,
+    Us,
+    Ns,
+}
+
+/// Which field of each JSON object holds each part of a history entry.
+///
+/// A name containing `.` looks inside nested objects, so `event.cmd` reads `{"event": {"cmd": ..}}`.
+/// A field that's empty or missing from an entry leaves that part of the entry at its default.
+#[derive(Clone, Debug, Eq, PartialEq, clap::Args)]
+pub struct FieldMapping {
+    /// The field holding the command
+    #[arg(long = "command-field", value_name = "FIELD", default_value = "command")]
+    pub command: String,
+    /// The field holding when the command was run
+    #[arg(long = "timestamp-field", value_name = "FIELD", default_value = "timestamp")]
+    pub timestamp: String,
+    /// How timestamps are written
+    #[arg(long, value_enum, default_value_t = TimestampFormat::Rfc3339)]
+    pub timestamp_format: TimestampFormat,
+    /// The field holding the working directory
+    #[arg(long = "cwd-field", value_name = "FIELD", default_value = "cwd")]
+    pub cwd: String,
+    /// The field holding the exit code
+    #[arg(long = "exit-field", value_name = "FIELD", default_value = "exit")]
+    pub exit: String,
+    /// The field holding how long the command took
+    #[arg(long = "duration-field", value_name = "FIELD", default_value = "duration")]
+    pub duration: String,
+    /// What durations are measured in
+    #[arg(long, value_enum, default_value_t = DurationUnit::Ns)]
+    pub duration_unit: DurationUnit,
+    /// The field holding the hostname, or `host:user`
+    #[arg(long = "hostname-field", value_name = "FIELD", default_value = "hostname")]
+    pub hostname: String,
+    /// The field holding the session id
+    #[arg(long = "session-field", value_name = "FIELD", default_value = "session")]
+    pub session: String,
+    /// The field holding who ran the command
+    #[arg(long = "author-field", value_name = "FIELD", default_value = "author")]
+    pub author: String,
+    /// The field holding whether a `user` or an `agent` ran the command
+    #[arg(long = "author-kind-field", value_name = "FIELD", default_value = "author_kind")]
+    pub author_kind: String,
+    /// The field holding why the command was run
+    #[arg(long = "intent-field", value_name = "FIELD", default_value = "intent")]
+    pub intent: String,
+    /// The field holding the shell the command was run in
+    #[arg(long = "shell-field", value_name = "FIELD", default_value = "shell")]
+    pub shell: String,
+}
+
+impl Default for FieldMapping {
+    fn default() -> Self {
+        Self {
+            command: "command".into(),
+            timestamp: "timestamp".into(),
+            timestamp_format: TimestampFormat::Rfc3339,
+            cwd: "cwd".into(),
+            exit: "exit".into(),
+            duration: "duration".into(),
+            duration_unit: DurationUnit::Ns,
+            hostname: "hostname".into(),
+            session: "session".into(),
+            author: "author".into(),
+            author_kind: "author_kind".into(),
+            intent: "intent".into(),
+            shell: "shell".into(),
+        }
+    }
+}
+
+impl FieldMapping {
+    /// The entry `value` describes, or `None` if it has no command or timestamp.
+    ///
+    /// Both are required, so that importing the same log again gives the same entries - which the
+    /// history table's unique `(timestamp, cwd, command)` constraint then ignores.
+    fn entry(&self, value: &Value) -> Option<HistoryImported> {
+        let command = string(field(value, &self.command)?)?;
+        let timestamp = parse_timestamp(field(value, &self.timestamp)?, self.timestamp_format)?;
+
+        let get = |name: &str| field(value, name);
+        let get_string = |name: &str| get(name).and_then(string);
+
+        let exit = get(&self.exit).and_then(integer);
+        let duration = get(&self.duration).and_then(|v| nanoseconds(v, self.duration_unit));
+        let cmd_origin = get_string(&self.hostname).map(|hostname| {
+            CmdOrigin::try_from(hostname.as_str()).unwrap_or_else(|_| {
+                CmdOrigin::new(&CmdHost::from(hostname), &CmdUser::probe_current())
+            })
+        });
+        let author_kind: Option<AuthorKind> = get_string(&self.author_kind)
+            .and_then(|kind| clap::ValueEnum::from_str(&kind, true).ok());
+
+        Some(
+            History::import()
+                .timestamp(timestamp)
+                .command(command)
+                .cwd(get_string(&self.cwd).unwrap_or_else(|| "unknown".into()))
+                .exit(exit.unwrap_or(HistoryImported::DEFAULT_EXIT))
+                .duration(duration.unwrap_or(HistoryImported::DEFAULT_DURATION))
+                .session_opt(get_string(&self.session))
+                .cmd_origin_opt(cmd_origin)
+                .author_opt(get_string(&self.author))
+                .intent_opt(get_string(&self.intent))
+                .shell_opt(get_string(&self.shell))
+                .author_kind(author_kind)
+                .build(),
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct Jsonl {
+    bytes: Vec<u8>,
+    mapping: FieldMapping,
+}
+
+impl Jsonl {
+    pub fn with_mapping(path: &Path, mapping: FieldMapping) -> Result<Self> {
+        let bytes = read_to_end(is_file(path.to_path_buf())?)?;
+        Ok(Self { bytes, mapping })
+    }
+
+    fn lines(&self) -> impl Iterator<Item = (usize, &[u8])> {
+        self.bytes
+            .split(|b| *b == b'\n')
+            .enumerate()
+            .filter(|(_, line)| !line.trim_ascii().is_empty())
+    }
+}
+
+#[async_trait]
+impl Importer for Jsonl {
+    const NAME: &'static str = "jsonl";
+
+    async fn new() -> Result<Self> {
+        let path = get_histfile_path(|| Err(eyre!("set $HISTFILE to the file to import")))?;
+        Self::with_mapping(&path, FieldMapping::default())
+    }
+
+    async fn entries(&mut self) -> Result<usize> {
+        Ok(self.lines().count())
+    }
+
+    async fn load(self, loader: &mut impl Loader) -> Result<()> {
+        for (i, line) in self.lines() {
+            let entry = match serde_json::from_slice::<Value>(line) {
+                Ok(value) => self.mapping.entry(&value),
+                Err(err) => {
+                    warn!("skipping line {}, which is not valid JSON: {err}", i + 1);
+                    continue;
+                }
+            };
+
+            match entry {
+                Some(entry) => loader.push(entry.into()).await?,
+                None => warn!("skipping line {}, which has no command or timestamp", i + 1),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn field<'a>(value: &'a Value, name: &str) -> Option<&'a Value> {
+    if name.is_empty() {
+        return None;
+    }
+    name.split('.').try_fold(value, |value, key| value.get(key)).filter(|v| !v.is_null())
+}
+
+fn string(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+fn integer(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// `value` multiplied by `scale`, for numbers that may have a fractional part (`1.5` seconds).
+fn scaled(value: &Value, scale: i64) -> Option<i128> {
+    let number = match value {
+        Value::Number(n) => n.clone(),
+        Value::String(s) => s.trim().parse().ok()?,
+        _ => return None,
+    };
+
+    match number.as_i64() {
+        Some(n) => Some(i128::from(n) * i128::from(scale)),
+        #[allow(clippy::cast_possible_truncation, clippy::cast_precision_loss)]
+        None => number.as_f64().map(|f| (f * scale as f64) as i128),
+    }
+}
+
+fn parse_timestamp(value: &Value, format: TimestampFormat) -> Option<OffsetDateTime> {
+    let scale = match format {
+        TimestampFormat::Rfc3339 => return OffsetDateTime::parse(value.as_str()?, &Rfc3339).ok(),
+        TimestampFormat::Unix => 1_000_000_000,
+        TimestampFormat::UnixMs => 1_000_000,
+        TimestampFormat::UnixUs => 1_000,
+        TimestampFormat::UnixNs => 1,
+    };
+    OffsetDateTime::from_unix_nanos(scaled(value, scale)?).ok()
+}
+
+fn nanoseconds(value: &Value, unit: DurationUnit) -> Option<i64> {
+    let scale = match unit {
+        DurationUnit::S => 1_000_000_000,
+        DurationUnit::Ms => 1_000_000,
+        DurationUnit::Us => 1_000,
+        DurationUnit::Ns => 1,
+    };
+    scaled(value, scale).and_then(|n| i64::try_from(n).ok())
+}
+
+#[cfg(test)]
+mod test {
+    use std::io::Write;
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::database::Sqlite;
+    use crate::export::Exporter;
+    use crate::export::jsonl::Jsonl as JsonlExporter;
+    use crate::import::tests::TestLoader;
+    use crate::settings::test_local_timeout;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        mapping: FieldMapping,
+    }
+
+    #[test]
+    fn default_mapping_matches_the_cli() {
+        assert_eq!(Cli::parse_from(["import"]).mapping, FieldMapping::default());
+    }
+
+    async fn load(bytes: &[u8], mapping: FieldMapping) -> Vec<History> {
+        let jsonl = Jsonl {
+            bytes: bytes.to_vec(),
+            mapping,
+        };
+        let mut loader = TestLoader::default();
+        jsonl.load(&mut loader).await.unwrap();
+        loader.buf
+    }
+
+    #[tokio::test]
+    async fn reads_mapped_fields() {
+        let bytes = br#"{"event": {"cmd": "cargo build", "dir": "/src"}, "ts": 1706933106.5, "took": 2.5, "rc": "1", "host": "laptop"}
+not json
+{"event": {"dir": "/src"}, "ts": 1706933107}
+
+{"event": {"cmd": "ls"}, "ts": 1706933108}"#;
+
+        let mapping = FieldMapping {
+            command: "event.cmd".into(),
+            timestamp: "ts".into(),
+            timestamp_format: TimestampFormat::Unix,
+            cwd: "event.dir".into(),
+            exit: "rc".into(),
+            duration: "took".into(),
+            duration_unit: DurationUnit::S,
+            hostname: "host".into(),
+            ..FieldMapping::default()
+        };
+        let history = load(bytes, mapping).await;
+
+        assert_eq!(history.len(), 2, "lines without a command or valid JSON are skipped");
+
+        let h = &history[0];
+        assert_eq!(h.command, "cargo build");
+        assert_eq!(h.timestamp.unix_timestamp_nanos(), 1_706_933_106_500_000_000);
+        assert_eq!(h.cwd, "/src");
+        assert_eq!(h.exit, 1);
+        assert_eq!(h.duration, 2_500_000_000);
+        assert_eq!(h.cmd_origin.host().as_ref(), "laptop");
+
+        // the trailing line has no newline, and the rest of its fields are defaults
+        let h = &history[1];
+        assert_eq!(h.command, "ls");
+        assert_eq!(h.cwd, "unknown");
+        assert_eq!(h.exit, -1);
+        assert_eq!(h.duration, -1);
+    }
+
+    #[tokio::test]
+    async fn reads_an_export() {
+        let original: History = History::import()
+            .timestamp(OffsetDateTime::from_unix_nanos_i64(1_706_933_106_789_000_000))
+            .command("cargo test")
+            .cwd("/src")
+            .exit(0)
+            .duration(1_000)
+            .session("b5ef2dd1a3a24e44a0a7c5d9e6bd4c3f")
+            .cmd_origin(CmdOrigin::try_from("laptop:ellie").unwrap())
+            .author("claude-code")
+            .author_kind(Some(AuthorKind::Agent))
+            .intent("run the tests")
+            .shell("zsh")
+            .build()
+            .into();
+
+        let mut exporter = JsonlExporter::new(Vec::new()).unwrap();
+        exporter.push(&original).unwrap();
+        let bytes = exporter.finish().unwrap();
+
+        let imported = load(&bytes, FieldMapping::default()).await;
+        // everything but the id, which the importer doesn't read
+        assert_eq!(imported, [History {
+            id: imported[0].id.clone(),
+            ..original
+        }]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn importing_twice_is_idempotent() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(
+            file,
+            r#"{{"command": "ls", "timestamp": "2024-02-03T04:05:06Z"}}
+{{"command": "ls", "timestamp": "2024-02-03T04:05:07Z", "cwd": "/src"}}"#
+        )
+        .unwrap();
+
+        let db = Sqlite::new("sqlite::memory:", test_local_timeout()).await.unwrap();
+        for _ in 0..2 {
+            let jsonl = Jsonl::with_mapping(file.path(), FieldMapping::default()).unwrap();
+            let mut loader = TestLoader::default();
+            jsonl.load(&mut loader).await.unwrap();
+            db.save_bulk(&loader.buf).await.unwrap();
+        }
+
+        assert_eq!(db.history_count(true).await.unwrap(), 2);
+    }
+}
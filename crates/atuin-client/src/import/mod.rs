@@ -8,8 +8,10 @@ use memchr::Memchr;
 
 use crate::history::History;
 
+pub mod atuin_db;
 pub mod bash;
 pub mod fish;
+pub mod jsonl;
 pub mod nu;
 pub mod nu_histdb;
 pub mod powershell;
@@ -116,7 +118,7 @@ fn is_dir(p: PathBuf) -> Result<PathBuf> {
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
 
     #[derive(Default)]
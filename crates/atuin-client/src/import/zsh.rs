@@ -16,7 +16,7 @@ use crate::import::read_to_end;
 
 #[derive(Debug)]
 pub struct Zsh {
-    bytes: Vec<u8>,
+    pub(crate) bytes: Vec<u8>,
 }
 
 impl Zsh {
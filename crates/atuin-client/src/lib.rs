@@ -13,17 +13,22 @@ pub mod hub;
 pub mod login;
 #[cfg(feature = "sync")]
 pub mod register;
+#[cfg(feature = "sync")]
+pub mod team;
 
 pub mod database;
 pub mod distro;
+pub mod export;
 pub mod history;
 pub mod import;
 pub mod logout;
 pub mod logs;
 pub mod meta;
 pub mod ordering;
+pub mod output;
 pub mod packfile;
 pub mod plugin;
+pub mod ranking;
 pub mod record;
 pub mod secrets;
 pub mod settings;
@@ -2,12 +2,13 @@ use eyre::Result;
 
 use crate::settings::Settings;
 
-pub async fn logout() -> Result<()> {
-    let meta = Settings::meta_store().await?;
+/// Log out of the account `settings` sync with.
+pub async fn logout(settings: &Settings) -> Result<()> {
+    let sessions = settings.sessions().await?;
 
-    if meta.logged_in().await? {
-        meta.delete_session().await?;
-        meta.delete_hub_session().await?;
+    if sessions.logged_in().await? {
+        sessions.delete_session().await?;
+        sessions.delete_hub_session().await?;
         println!("You have logged out!");
     } else {
         println!("You are not logged in");
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::path::Path;
 use std::str::FromStr;
 use std::time::Duration;
@@ -24,6 +25,7 @@ const KEY_LATEST_VERSION: &str = "latest_version";
 const KEY_SESSION: &str = "session";
 const KEY_HUB_SESSION: &str = "hub_session";
 const KEY_FILES_MIGRATED: &str = "files_migrated";
+const KEY_TEAM_HOSTS: &str = "team_hosts";
 
 pub struct MetaStore {
     pool: SqlitePool,
@@ -163,38 +165,100 @@ impl MetaStore {
         self.set(KEY_LATEST_VERSION, version).await
     }
 
+    /// This machine's host id in a team's store. Records written for the team go under it, so
+    /// they sync with the team rather than the user's own account.
+    pub async fn team_host_id(&self, team: &str) -> Result<HostId> {
+        let key = format!("team.{team}.host_id");
+
+        if let Some(id) = self.get(&key).await? {
+            let parsed =
+                Uuid::from_str(&id).map_err(|e| eyre!("failed to parse team host ID: {e}"))?;
+            return Ok(HostId(parsed));
+        }
+
+        let host = HostId(atuin_common::utils::uuid_v7());
+        self.add_team_hosts(team, [host]).await?;
+        self.set(&key, host.0.as_simple().to_string().as_ref()).await?;
+
+        Ok(host)
+    }
+
+    /// Every host known to belong to a team's store, and the team it belongs to. These are never
+    /// synced with the user's own account.
+    pub async fn team_hosts(&self) -> Result<HashMap<HostId, String>> {
+        match self.get(KEY_TEAM_HOSTS).await? {
+            Some(hosts) => Ok(serde_json::from_str(&hosts)?),
+            None => Ok(HashMap::new()),
+        }
+    }
+
+    pub async fn add_team_hosts(
+        &self,
+        team: &str,
+        hosts: impl IntoIterator<Item = HostId>,
+    ) -> Result<()> {
+        let mut known = self.team_hosts().await?;
+        known.extend(hosts.into_iter().map(|host| (host, team.to_owned())));
+
+        self.set(KEY_TEAM_HOSTS, &serde_json::to_string(&known)?).await
+    }
+
+    /// The public key `username` had on the sync server at `server` when they were first added to
+    /// a team from here. A different key later means either they changed it, or the server is
+    /// handing out one of its own.
+    pub async fn pinned_key(&self, server: &str, username: &str) -> Result<Option<String>> {
+        self.get(&format!("team.pinned_key.{server}.{username}")).await
+    }
+
+    pub async fn pin_key(&self, server: &str, username: &str, public_key: &str) -> Result<()> {
+        self.set(&format!("team.pinned_key.{server}.{username}"), public_key).await
+    }
+
+    /// The default sync account's sessions.
+    pub fn sessions(&self) -> Sessions<'_> {
+        self.profile_sessions(None)
+    }
+
+    /// The sessions of a named sync profile, or of the default account if there's no profile.
+    pub fn profile_sessions(&self, profile: Option<&str>) -> Sessions<'_> {
+        Sessions {
+            meta: self,
+            prefix: profile.map(|p| format!("profile.{p}.")).unwrap_or_default(),
+        }
+    }
+
     pub async fn session_token(&self) -> Result<Option<String>> {
-        self.get(KEY_SESSION).await
+        self.sessions().session_token().await
     }
 
     pub async fn save_session(&self, token: &str) -> Result<()> {
-        self.set(KEY_SESSION, token).await
+        self.sessions().save_session(token).await
     }
 
     pub async fn delete_session(&self) -> Result<()> {
-        self.delete(KEY_SESSION).await
+        self.sessions().delete_session().await
     }
 
     pub async fn logged_in(&self) -> Result<bool> {
-        Ok(self.session_token().await?.is_some() || self.hub_session_token().await?.is_some())
+        self.sessions().logged_in().await
     }
 
     // Hub session methods (separate from sync session, used for Hub-specific features like AI)
 
     pub async fn hub_session_token(&self) -> Result<Option<String>> {
-        self.get(KEY_HUB_SESSION).await
+        self.sessions().hub_session_token().await
     }
 
     pub async fn save_hub_session(&self, token: &str) -> Result<()> {
-        self.set(KEY_HUB_SESSION, token).await
+        self.sessions().save_hub_session(token).await
     }
 
     pub async fn delete_hub_session(&self) -> Result<()> {
-        self.delete(KEY_HUB_SESSION).await
+        self.sessions().delete_hub_session().await
     }
 
     pub async fn hub_logged_in(&self) -> Result<bool> {
-        Ok(self.hub_session_token().await?.is_some())
+        self.sessions().hub_logged_in().await
     }
 
     // File migration: on first open, migrate old plain-text files into the database.
@@ -282,6 +346,51 @@ impl MetaStore {
     }
 }
 
+/// The sync and hub sessions of one account. Named sync profiles keep theirs under
+/// `profile.<name>.`, so logging in to one doesn't log out of another.
+pub struct Sessions<'a> {
+    meta: &'a MetaStore,
+    prefix: String,
+}
+
+impl Sessions<'_> {
+    fn key(&self, key: &str) -> String {
+        format!("{}{key}", self.prefix)
+    }
+
+    pub async fn session_token(&self) -> Result<Option<String>> {
+        self.meta.get(&self.key(KEY_SESSION)).await
+    }
+
+    pub async fn save_session(&self, token: &str) -> Result<()> {
+        self.meta.set(&self.key(KEY_SESSION), token).await
+    }
+
+    pub async fn delete_session(&self) -> Result<()> {
+        self.meta.delete(&self.key(KEY_SESSION)).await
+    }
+
+    pub async fn logged_in(&self) -> Result<bool> {
+        Ok(self.session_token().await?.is_some() || self.hub_session_token().await?.is_some())
+    }
+
+    pub async fn hub_session_token(&self) -> Result<Option<String>> {
+        self.meta.get(&self.key(KEY_HUB_SESSION)).await
+    }
+
+    pub async fn save_hub_session(&self, token: &str) -> Result<()> {
+        self.meta.set(&self.key(KEY_HUB_SESSION), token).await
+    }
+
+    pub async fn delete_hub_session(&self) -> Result<()> {
+        self.meta.delete(&self.key(KEY_HUB_SESSION)).await
+    }
+
+    pub async fn hub_logged_in(&self) -> Result<bool> {
+        Ok(self.hub_session_token().await?.is_some())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use rstest::*;
@@ -353,6 +462,26 @@ mod tests {
         assert!(!store.logged_in().await.unwrap());
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_profile_sessions(#[future(awt)] store: MetaStore) {
+        store.save_session("default").await.unwrap();
+
+        let work = store.profile_sessions(Some("work"));
+        assert!(!work.logged_in().await.unwrap());
+
+        work.save_session("work").await.unwrap();
+        work.save_hub_session("atapi_work").await.unwrap();
+        assert_eq!(work.session_token().await.unwrap(), Some("work".to_string()));
+        assert_eq!(store.session_token().await.unwrap(), Some("default".to_string()));
+        assert!(!store.hub_logged_in().await.unwrap());
+
+        work.delete_session().await.unwrap();
+        work.delete_hub_session().await.unwrap();
+        assert!(!work.logged_in().await.unwrap());
+        assert!(store.logged_in().await.unwrap());
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_latest_version(#[future(awt)] store: MetaStore) {
@@ -361,4 +490,38 @@ mod tests {
         store.save_latest_version("1.2.3").await.unwrap();
         assert_eq!(store.latest_version().await.unwrap(), Some("1.2.3".to_string()));
     }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_team_hosts(#[future(awt)] store: MetaStore) {
+        let host = store.team_host_id("ops").await.unwrap();
+
+        assert_eq!(store.team_host_id("ops").await.unwrap(), host);
+        assert_ne!(store.team_host_id("dev").await.unwrap(), host);
+        assert_ne!(store.host_id().await.unwrap(), host);
+
+        let theirs = HostId(atuin_common::utils::uuid_v7());
+        store.add_team_hosts("ops", [theirs]).await.unwrap();
+
+        let hosts = store.team_hosts().await.unwrap();
+        assert_eq!(hosts.len(), 3);
+        assert_eq!(hosts[&host], "ops");
+        assert_eq!(hosts[&theirs], "ops");
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_pinned_keys(#[future(awt)] store: MetaStore) {
+        let server = "https://sync.example.com";
+        assert_eq!(store.pinned_key(server, "bob").await.unwrap(), None);
+
+        store.pin_key(server, "bob", "k4.public.bob").await.unwrap();
+        assert_eq!(
+            store.pinned_key(server, "bob").await.unwrap().as_deref(),
+            Some("k4.public.bob")
+        );
+
+        // the same username on another server is someone else
+        assert_eq!(store.pinned_key("https://other.example.com", "bob").await.unwrap(), None);
+    }
 }
@@ -0,0 +1,89 @@
+//! The output of commands captured by `atuin pty-proxy`, kept on disk.
+//!
+//! Output is kept zstd-compressed in its own database, by the history entry that printed it, and
+//! is only synced when `output.sync` is set, as records tagged `command-output`.
+
+use std::io;
+
+use time::OffsetDateTime;
+
+use crate::history::HistoryId;
+
+pub mod database;
+pub mod record;
+pub mod store;
+
+const ZSTD_LEVEL: i32 = 3;
+
+/// What a command printed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutput {
+    pub history_id: HistoryId,
+
+    /// When it was captured.
+    pub timestamp: OffsetDateTime,
+
+    pub output: String,
+
+    /// Whether capture stopped before the command did.
+    pub truncated: bool,
+
+    /// How much the command printed, including anything past truncation, in bytes.
+    pub observed_bytes: u64,
+}
+
+impl CommandOutput {
+    /// The first line containing `query`, ignoring case, or failing that every word of it, as
+    /// search matches whole words wherever they are.
+    pub fn matching_line(&self, query: &str) -> Option<&str> {
+        let query = query.to_lowercase();
+        let words: Vec<&str> =
+            query.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect();
+
+        let lines = || self.output.lines().map(str::trim);
+
+        lines().find(|line| line.to_lowercase().contains(&query)).or_else(|| {
+            lines().find(|line| {
+                let line = line.to_lowercase();
+                words.iter().all(|word| line.contains(word))
+            })
+        })
+    }
+}
+
+pub(crate) fn compress(output: &str) -> io::Result<Vec<u8>> {
+    zstd::stream::encode_all(output.as_bytes(), ZSTD_LEVEL)
+}
+
+pub(crate) fn decompress(data: &[u8]) -> io::Result<String> {
+    String::from_utf8(zstd::stream::decode_all(data)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn matching_line() {
+        let output = CommandOutput {
+            history_id: HistoryId("id".to_string()),
+            timestamp: datetime!(2026-06-01 00:00 UTC),
+            output: "Connecting...\n  Error: Connection  REFUSED (os error 111)\n".to_string(),
+            truncated: false,
+            observed_bytes: 0,
+        };
+
+        assert_eq!(
+            output.matching_line("error"),
+            Some("Error: Connection  REFUSED (os error 111)")
+        );
+        assert_eq!(
+            output.matching_line("connection refused"),
+            Some("Error: Connection  REFUSED (os error 111)")
+        );
+        assert_eq!(output.matching_line("timed out"), None);
+    }
+}
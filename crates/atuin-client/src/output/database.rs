@@ -0,0 +1,474 @@
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use atuin_common::time::OffsetDateTimeExt;
+use atuin_common::utils;
+use atuin_domain::record::RecordId;
+use fs_err as fs;
+use sqlx::sqlite::{
+    SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteRow,
+    SqliteSynchronous,
+};
+use sqlx::{Result, Row};
+use time::OffsetDateTime;
+use tracing::{debug, instrument};
+
+use super::{CommandOutput, compress, decompress};
+use crate::history::HistoryId;
+
+/// How much output [`SearchPages`] reads at a time.
+const SEARCH_PAGE_SIZE: usize = 200;
+
+#[derive(Debug, Clone)]
+pub struct Database {
+    pub pool: SqlitePool,
+}
+
+impl Database {
+    #[instrument(level = "trace", skip_all, fields(timeout), err)]
+    pub async fn new(path: impl AsRef<Path>, timeout: f64) -> Result<Self> {
+        let path = path.as_ref();
+        debug!("opening output sqlite database at {path:?}");
+
+        if utils::broken_symlink(path) {
+            eprintln!(
+                "Atuin: output sqlite db path ({path:?}) is a broken symlink. Unable to read or \
+                 create replacement."
+            );
+            std::process::exit(1);
+        }
+
+        if !path.exists()
+            && let Some(dir) = path.parent()
+        {
+            fs::create_dir_all(dir)?;
+        }
+
+        let opts = SqliteConnectOptions::from_str(path.as_os_str().to_str().unwrap())?
+            .journal_mode(SqliteJournalMode::Wal)
+            .optimize_on_close(true, None)
+            .synchronous(SqliteSynchronous::Normal)
+            .create_if_missing(true);
+
+        let pool = SqlitePoolOptions::new()
+            .acquire_timeout(Duration::try_from_secs_f64(timeout).map_err(|e| {
+                sqlx::Error::Decode(format!("invalid db timeout {timeout}: {e}").into())
+            })?)
+            .connect_with(opts)
+            .await?;
+
+        Self::setup_db(&pool).await?;
+        Ok(Self { pool })
+    }
+
+    async fn setup_db(pool: &SqlitePool) -> Result<()> {
+        debug!("running output sqlite database setup");
+
+        sqlx::migrate!("./output-migrations").run(pool).await?;
+        Self::index_unindexed(pool).await?;
+
+        Ok(())
+    }
+
+    /// Add output saved before there was a search index to it.
+    async fn index_unindexed(pool: &SqlitePool) -> Result<()> {
+        let rows: Vec<(i64, Vec<u8>)> =
+            sqlx::query_as("select rowid, data from output where indexed = 0")
+                .fetch_all(pool)
+                .await?;
+
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        debug!("indexing {} outputs for search", rows.len());
+
+        let mut tx = pool.begin().await?;
+
+        for (rowid, data) in rows {
+            let output = decompress(&data).map_err(|e| sqlx::Error::Decode(e.into()))?;
+
+            sqlx::query("insert into output_fts(rowid, output) values(?1, ?2)")
+                .bind(rowid)
+                .bind(output)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query("update output set indexed = 1 where rowid = ?1")
+                .bind(rowid)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Keep `output`, replacing whatever was kept for its history entry.
+    #[instrument(level = "trace", skip_all, fields(id = ?output.history_id), err)]
+    pub async fn save(&self, output: &CommandOutput) -> Result<()> {
+        let data = compress(&output.output).map_err(|e| sqlx::Error::Encode(e.into()))?;
+
+        let mut tx = self.pool.begin().await?;
+
+        // Delete rather than replace, as only deletes remove what's replaced from the index.
+        sqlx::query("delete from output where history_id = ?1")
+            .bind(output.history_id.0.as_str())
+            .execute(&mut *tx)
+            .await?;
+
+        let rowid = sqlx::query(
+            "insert into output(history_id, timestamp, truncated, observed_bytes, size, data, \
+             indexed)
+                values(?1, ?2, ?3, ?4, ?5, ?6, 1)",
+        )
+        .bind(output.history_id.0.as_str())
+        .bind(output.timestamp.unix_timestamp_nanos() as i64)
+        .bind(output.truncated)
+        .bind(i64::try_from(output.observed_bytes).unwrap_or(i64::MAX))
+        .bind(data.len() as i64)
+        .bind(data)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+
+        sqlx::query("insert into output_fts(rowid, output) values(?1, ?2)")
+            .bind(rowid)
+            .bind(output.output.as_str())
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// The output containing `query` as a phrase, ignoring case and punctuation, newest first.
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<CommandOutput>> {
+        let mut found = Vec::new();
+        for (_, id) in self.search_before(query, None, limit).await? {
+            // Unless it was deleted since
+            found.extend(self.load(&id).await?);
+        }
+
+        Ok(found)
+    }
+
+    /// The history entries whose output contains `query`, like [`Database::search`], a page at a
+    /// time. For picking some of them, e.g. those matching some filters, until there's enough.
+    /// Only the output of the ones picked needs loading.
+    pub fn search_pages<'a>(&'a self, query: &'a str) -> SearchPages<'a> {
+        SearchPages {
+            db: self,
+            query,
+            before: None,
+            done: false,
+        }
+    }
+
+    /// The timestamps and history ids of the output matching `query`, like [`Database::search`],
+    /// but only output older than `before`. The output itself isn't read.
+    #[instrument(level = "trace", skip_all, fields(limit), err)]
+    async fn search_before(
+        &self,
+        query: &str,
+        before: Option<&(i64, HistoryId)>,
+        limit: usize,
+    ) -> Result<Vec<(i64, HistoryId)>> {
+        let Some(phrase) = fts_phrase(query) else {
+            return Ok(Vec::new());
+        };
+
+        let rows: Vec<(i64, String)> = sqlx::query_as(
+            "select output.timestamp, output.history_id from output_fts
+                join output on output.rowid = output_fts.rowid
+                where output_fts match ?1
+                    and (?2 is null or (output.timestamp, output.history_id) < (?2, ?3))
+                order by output.timestamp desc, output.history_id desc
+                limit ?4",
+        )
+        .bind(phrase)
+        .bind(before.map(|(timestamp, _)| timestamp))
+        .bind(before.map(|(_, id)| id.0.as_str()))
+        .bind(i64::try_from(limit).unwrap_or(i64::MAX))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|(timestamp, id)| (timestamp, HistoryId(id))).collect())
+    }
+
+    #[instrument(level = "trace", skip_all, fields(id = ?id), err)]
+    pub async fn load(&self, id: &HistoryId) -> Result<Option<CommandOutput>> {
+        sqlx::query("select * from output where history_id = ?1")
+            .bind(id.0.as_str())
+            .fetch_optional(&self.pool)
+            .await?
+            .map(|row| Self::query_output(&row))
+            .transpose()
+    }
+
+    /// Whether any output is kept for this history entry.
+    pub async fn contains(&self, id: &HistoryId) -> Result<bool> {
+        sqlx::query_scalar("select exists(select 1 from output where history_id = ?1)")
+            .bind(id.0.as_str())
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    #[instrument(level = "trace", skip_all, fields(count = ids.len()), err)]
+    pub async fn delete(&self, ids: &[HistoryId]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        for id in ids {
+            sqlx::query("delete from output where history_id = ?1")
+                .bind(id.0.as_str())
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// How much output is kept, compressed, in bytes.
+    pub async fn size(&self) -> Result<u64> {
+        let size: i64 = sqlx::query_scalar("select coalesce(sum(size), 0) from output")
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(size as u64)
+    }
+
+    /// Delete output captured before `before`, then the oldest left until what's kept fits in
+    /// `max_size` bytes. Returns how many were deleted.
+    #[instrument(level = "trace", skip_all, fields(max_size), err)]
+    pub async fn prune(&self, before: OffsetDateTime, max_size: u64) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+
+        let expired = sqlx::query("delete from output where timestamp < ?1")
+            .bind(before.unix_timestamp_nanos() as i64)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        let oversized = sqlx::query(
+            "delete from output where history_id in (
+                select history_id from (
+                    select history_id,
+                        sum(size) over (order by timestamp desc, history_id desc) as kept
+                    from output
+                )
+                where kept > ?1
+            )",
+        )
+        .bind(i64::try_from(max_size).unwrap_or(i64::MAX))
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        tx.commit().await?;
+
+        Ok(expired + oversized)
+    }
+
+    /// Whether the output record with this id has been built into this database already.
+    pub async fn is_built(&self, id: RecordId) -> Result<bool> {
+        sqlx::query_scalar("select exists(select 1 from built_records where id = ?1)")
+            .bind(id.0.as_hyphenated().to_string())
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    pub async fn mark_built(&self, id: RecordId) -> Result<()> {
+        sqlx::query("insert or ignore into built_records(id) values(?1)")
+            .bind(id.0.as_hyphenated().to_string())
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    fn query_output(row: &SqliteRow) -> Result<CommandOutput> {
+        let data: Vec<u8> = row.try_get("data")?;
+        let observed_bytes: i64 = row.try_get("observed_bytes")?;
+
+        Ok(CommandOutput {
+            history_id: HistoryId(row.try_get("history_id")?),
+            timestamp: OffsetDateTime::from_unix_nanos_i64(row.try_get("timestamp")?),
+            output: decompress(&data).map_err(|e| sqlx::Error::Decode(e.into()))?,
+            truncated: row.try_get("truncated")?,
+            observed_bytes: observed_bytes as u64,
+        })
+    }
+}
+
+/// `query` as a single FTS5 phrase, or `None` if it has nothing to match.
+fn fts_phrase(query: &str) -> Option<String> {
+    // The tokenizer ignores anything that isn't a letter or digit.
+    if !query.chars().any(char::is_alphanumeric) {
+        return None;
+    }
+
+    Some(format!("\"{}\"", query.replace('"', "\"\"")))
+}
+
+/// The pages of an output search, from [`Database::search_pages`].
+pub struct SearchPages<'a> {
+    db: &'a Database,
+    query: &'a str,
+    /// The timestamp and history id of the last output found.
+    before: Option<(i64, HistoryId)>,
+    done: bool,
+}
+
+impl SearchPages<'_> {
+    /// The ids of the history entries on the next page, newest first, or `None` once there's no
+    /// more.
+    pub async fn next(&mut self) -> Result<Option<Vec<HistoryId>>> {
+        if self.done {
+            return Ok(None);
+        }
+
+        let page =
+            self.db.search_before(self.query, self.before.as_ref(), SEARCH_PAGE_SIZE).await?;
+        self.done = page.len() < SEARCH_PAGE_SIZE;
+        self.before = page.last().cloned();
+
+        Ok(Some(page.into_iter().map(|(_, id)| id).collect()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use time::macros::datetime;
+
+    use super::*;
+    use crate::settings::test_local_timeout;
+
+    async fn db() -> Database {
+        Database::new("sqlite::memory:", test_local_timeout()).await.unwrap()
+    }
+
+    fn output(id: &str, timestamp: OffsetDateTime, output: &str) -> CommandOutput {
+        CommandOutput {
+            history_id: HistoryId(id.to_string()),
+            timestamp,
+            output: output.to_string(),
+            truncated: false,
+            observed_bytes: output.len() as u64,
+        }
+    }
+
+    #[tokio::test]
+    async fn save_load_delete() {
+        let db = db().await;
+        let saved = CommandOutput {
+            truncated: true,
+            observed_bytes: 1 << 40,
+            ..output(
+                "id-1",
+                datetime!(2026-06-01 00:00 UTC),
+                &"error: connection refused\n".repeat(100),
+            )
+        };
+
+        db.save(&saved).await.unwrap();
+        assert_eq!(db.load(&saved.history_id).await.unwrap(), Some(saved.clone()));
+        assert!(db.size().await.unwrap() < saved.output.len() as u64, "output isn't compressed");
+
+        db.delete(std::slice::from_ref(&saved.history_id)).await.unwrap();
+        assert_eq!(db.load(&saved.history_id).await.unwrap(), None);
+        assert_eq!(db.size().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn search_finds_phrases_newest_first() {
+        let db = db().await;
+        let day = |d: u8| datetime!(2026-06-01 00:00 UTC).replace_day(d).unwrap();
+
+        db.save(&output("old", day(1), "curl: (7) Failed to connect: Connection refused\n"))
+            .await
+            .unwrap();
+        db.save(&output("new", day(2), "psql: error: connection refused\n")).await.unwrap();
+        db.save(&output("other", day(3), "refused, then a connection\n")).await.unwrap();
+
+        let ids = |found: Vec<CommandOutput>| {
+            found.into_iter().map(|o| o.history_id.0).collect::<Vec<_>>()
+        };
+
+        assert_eq!(ids(db.search("Connection refused", 10).await.unwrap()), ["new", "old"]);
+        assert_eq!(ids(db.search("connection refused", 1).await.unwrap()), ["new"]);
+        assert_eq!(ids(db.search("\"refused\"", 10).await.unwrap()), ["other", "new", "old"]);
+        assert!(db.search(" :: ", 10).await.unwrap().is_empty());
+
+        // Replaced and deleted output is gone from the index too.
+        db.save(&output("new", day(2), "ok\n")).await.unwrap();
+        db.delete(&[HistoryId("old".to_string())]).await.unwrap();
+        assert!(db.search("connection refused", 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn search_pages_read_everything_newest_first() {
+        let db = db().await;
+        let start = datetime!(2026-06-01 00:00 UTC);
+        for n in 0..=SEARCH_PAGE_SIZE * 2 {
+            let timestamp = start + time::Duration::seconds(n as i64);
+            db.save(&output(&format!("{n:03}"), timestamp, "error\n")).await.unwrap();
+        }
+
+        let mut pages = db.search_pages("error");
+        let mut found = Vec::new();
+        while let Some(page) = pages.next().await.unwrap() {
+            assert!(page.len() <= SEARCH_PAGE_SIZE);
+            found.extend(page.into_iter().map(|id| id.0));
+        }
+
+        let expected: Vec<String> =
+            (0..=SEARCH_PAGE_SIZE * 2).rev().map(|n| format!("{n:03}")).collect();
+        assert_eq!(found, expected);
+    }
+
+    #[tokio::test]
+    async fn indexes_output_saved_before_the_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("output.db");
+
+        let db = Database::new(&path, test_local_timeout()).await.unwrap();
+        db.save(&output("id-1", datetime!(2026-06-01 00:00 UTC), "panicked at src/main.rs"))
+            .await
+            .unwrap();
+
+        // As if it were saved by a version without the index.
+        sqlx::query("delete from output_fts").execute(&db.pool).await.unwrap();
+        sqlx::query("update output set indexed = 0").execute(&db.pool).await.unwrap();
+        db.pool.close().await;
+
+        let db = Database::new(&path, test_local_timeout()).await.unwrap();
+        assert_eq!(db.search("panicked at", 10).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn prune_by_age_then_size() {
+        let db = db().await;
+        let day = |d: u8| datetime!(2026-06-01 00:00 UTC).replace_day(d).unwrap();
+
+        db.save(&output("id-0", day(10), &"x".repeat(1000))).await.unwrap();
+        let one = db.size().await.unwrap();
+
+        db.save(&output("old", day(1), "old")).await.unwrap();
+        db.save(&output("id-1", day(11), &"x".repeat(1000))).await.unwrap();
+        db.save(&output("id-2", day(12), &"x".repeat(1000))).await.unwrap();
+
+        // Room for one and a half of them keeps the newest only.
+        let pruned = db.prune(day(5), one + one / 2).await.unwrap();
+
+        assert_eq!(pruned, 3);
+        for (id, kept) in [("old", false), ("id-0", false), ("id-1", false), ("id-2", true)] {
+            assert_eq!(db.contains(&HistoryId(id.to_string())).await.unwrap(), kept, "{id}");
+        }
+    }
+}
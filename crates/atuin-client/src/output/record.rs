@@ -0,0 +1,156 @@
+//! How [`CommandOutput`], and the deletes that forget it, are encoded in `command-output` records.
+
+use atuin_common::rmp::decode::{self, Bytes, DecodeError};
+use atuin_common::rmp::encode::{self, ByteBuf};
+use atuin_common::time::OffsetDateTimeExt;
+use atuin_domain::record::{DecryptedData, RecordVersion};
+use eyre::{Result, bail, ensure};
+use time::OffsetDateTime;
+
+use super::{CommandOutput, compress, decompress};
+use crate::history::HistoryId;
+
+pub const OUTPUT_RECORD_VERSION: RecordVersion = RecordVersion::V0;
+
+const FIELDS: u32 = 5;
+
+/// What a `command-output` record says.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OutputRecord {
+    /// Keep this output.
+    Create(CommandOutput),
+    /// Forget the output of these history entries, which were deleted.
+    Delete(Vec<HistoryId>),
+}
+
+impl OutputRecord {
+    /// Encode for a record, with any output compressed.
+    ///
+    /// The first byte says what the record is: 0 for a create, followed by the output, or 1 for
+    /// a delete, followed by the history ids.
+    pub fn serialize(&self) -> Result<DecryptedData> {
+        let mut output = ByteBuf::new();
+
+        match self {
+            Self::Create(command_output) => {
+                encode::write_u8(&mut output, 0)?;
+                command_output.write(&mut output)?;
+            }
+            Self::Delete(ids) => {
+                encode::write_u8(&mut output, 1)?;
+                encode::write_array_len(&mut output, u32::try_from(ids.len())?)?;
+                for id in ids {
+                    encode::write_str(&mut output, &id.0)?;
+                }
+            }
+        }
+
+        Ok(DecryptedData(output.into_vec()))
+    }
+
+    pub fn deserialize(data: &DecryptedData, version: &RecordVersion) -> Result<Self> {
+        let mut bytes = Bytes::new(&data.0);
+
+        if *version != OUTPUT_RECORD_VERSION {
+            bail!("unknown output record version {version:?}");
+        }
+
+        match decode::read_u8(&mut bytes).map_err(DecodeError::from)? {
+            0 => CommandOutput::read(&mut bytes).map(Self::Create),
+            1 => {
+                let len = decode::read_array_len(&mut bytes).map_err(DecodeError::from)?;
+                let ids = (0..len)
+                    .map(|_| decode::read_string(&mut bytes).map(HistoryId))
+                    .collect::<Result<_, _>>()?;
+
+                ensure!(bytes.remaining_slice().is_empty(), "malformed output delete record");
+                Ok(Self::Delete(ids))
+            }
+            kind => bail!("unknown output record type {kind}"),
+        }
+    }
+}
+
+impl CommandOutput {
+    fn write(&self, output: &mut ByteBuf) -> Result<()> {
+        encode::write_array_len(output, FIELDS)?;
+        encode::write_str(output, &self.history_id.0)?;
+        encode::write_u64(output, self.timestamp.unix_timestamp_nanos() as u64)?;
+        encode::write_bool(output, self.truncated)?;
+        encode::write_u64(output, self.observed_bytes)?;
+        encode::write_bin(output, &compress(&self.output)?)?;
+
+        Ok(())
+    }
+
+    /// Read output written by [`CommandOutput::write`], which ends the record.
+    fn read(bytes: &mut Bytes<'_>) -> Result<Self> {
+        let nfields = decode::read_array_len(bytes).map_err(DecodeError::from)?;
+        ensure!(nfields == FIELDS, "expected {FIELDS} fields in output record, found {nfields}");
+
+        let history_id = HistoryId(decode::read_string(bytes)?);
+        let timestamp = decode::read_u64(bytes).map_err(DecodeError::from)?;
+        let truncated = decode::read_bool(bytes).map_err(DecodeError::from)?;
+        let observed_bytes = decode::read_u64(bytes).map_err(DecodeError::from)?;
+        let len = decode::read_bin_len(bytes).map_err(DecodeError::from)?;
+
+        let compressed = bytes.remaining_slice();
+        ensure!(compressed.len() == len as usize, "malformed output record");
+
+        Ok(Self {
+            history_id,
+            timestamp: OffsetDateTime::from_unix_nanos_u64(timestamp),
+            output: decompress(compressed)?,
+            truncated,
+            observed_bytes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn serialize_deserialize_roundtrip() {
+        let output = CommandOutput {
+            history_id: HistoryId("018f2cbe0e0f7cc48e9c7fd0b9c1c0a1".to_string()),
+            timestamp: datetime!(2026-06-01 12:30 UTC),
+            output: "Compiling atuin\nerror[E0433]: failed to resolve\n".repeat(50),
+            truncated: true,
+            observed_bytes: 1 << 20,
+        };
+
+        let record = OutputRecord::Create(output);
+        let serialized = record.serialize().unwrap();
+        assert!(serialized.0.len() < 50 * 50, "output isn't compressed");
+
+        let deserialized = OutputRecord::deserialize(&serialized, &OUTPUT_RECORD_VERSION).unwrap();
+        assert_eq!(deserialized, record);
+
+        let delete =
+            OutputRecord::Delete(vec![HistoryId("a".to_string()), HistoryId("b".to_string())]);
+        let serialized = delete.serialize().unwrap();
+        let deserialized = OutputRecord::deserialize(&serialized, &OUTPUT_RECORD_VERSION).unwrap();
+        assert_eq!(deserialized, delete);
+    }
+
+    #[test]
+    fn rejects_trailing_bytes() {
+        let output = CommandOutput {
+            history_id: HistoryId("id".to_string()),
+            timestamp: datetime!(2026-06-01 12:30 UTC),
+            output: "ok".to_string(),
+            truncated: false,
+            observed_bytes: 2,
+        };
+
+        let mut serialized = OutputRecord::Create(output).serialize().unwrap();
+        serialized.0.push(0);
+
+        assert!(OutputRecord::deserialize(&serialized, &OUTPUT_RECORD_VERSION).is_err());
+    }
+}
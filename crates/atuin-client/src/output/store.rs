@@ -0,0 +1,321 @@
+use atuin_common::encryption::paseto_v4;
+use atuin_domain::record::{Host, HostId, Record, RecordId, RecordSeriesKey, RecordTag};
+use eyre::Result;
+use time::OffsetDateTime;
+use tracing::instrument;
+
+use super::CommandOutput;
+use super::database::Database;
+use super::record::{OUTPUT_RECORD_VERSION, OutputRecord};
+use crate::database::Sqlite;
+use crate::history::HistoryId;
+use crate::record::sqlite_store::SqliteStore;
+use crate::settings::output::Settings;
+use crate::settings::sync;
+
+/// Command output, kept in its own database, and synced through the record store when
+/// `output.sync` is set.
+#[derive(Debug, Clone)]
+pub struct OutputStore {
+    pub store: SqliteStore,
+    pub db: Database,
+    pub host_id: HostId,
+    pub encryption_key: paseto_v4::Key,
+}
+
+impl OutputStore {
+    pub fn new(
+        store: SqliteStore,
+        db: Database,
+        host_id: HostId,
+        encryption_key: paseto_v4::Key,
+    ) -> Self {
+        Self {
+            store,
+            db,
+            host_id,
+            encryption_key,
+        }
+    }
+
+    /// Keep `output` here. It only syncs once [`OutputStore::share`] says so.
+    #[instrument(level = "trace", skip_all, fields(id = ?output.history_id), err)]
+    pub async fn save(&self, output: &CommandOutput) -> Result<()> {
+        Ok(self.db.save(output).await?)
+    }
+
+    /// Push `output` to the record store, when `settings` sync output and its history syncs too:
+    /// the entry isn't kept local-only in `history`, and `filter` syncs this machine's history.
+    /// Returns whether it was pushed.
+    #[instrument(level = "trace", skip_all, fields(id = ?output.history_id), err)]
+    pub async fn share(
+        &self,
+        output: &CommandOutput,
+        history: &Sqlite,
+        settings: &Settings,
+        filter: &sync::Settings,
+    ) -> Result<bool> {
+        if !settings.sync
+            || !filter.syncs(&RecordSeriesKey::new(self.host_id, RecordTag::History))
+            || history.is_local_only(&output.history_id).await?
+        {
+            return Ok(false);
+        }
+
+        let id = self.push_record(&OutputRecord::Create(output.clone())).await?;
+        self.db.mark_built(id).await?;
+
+        Ok(true)
+    }
+
+    pub async fn load(&self, id: &HistoryId) -> Result<Option<CommandOutput>> {
+        Ok(self.db.load(id).await?)
+    }
+
+    /// The output containing `query` as a phrase, newest first.
+    pub async fn search(&self, query: &str, limit: usize) -> Result<Vec<CommandOutput>> {
+        Ok(self.db.search(query, limit).await?)
+    }
+
+    /// Forget the output of these history entries, here and, when `settings` sync output, on the
+    /// machines it synced to.
+    pub async fn delete(&self, ids: &[HistoryId], settings: &Settings) -> Result<()> {
+        if settings.sync {
+            let mut kept = Vec::new();
+            for id in ids {
+                if self.db.contains(id).await? {
+                    kept.push(id.clone());
+                }
+            }
+
+            if !kept.is_empty() {
+                let id = self.push_record(&OutputRecord::Delete(kept)).await?;
+                self.db.mark_built(id).await?;
+            }
+        }
+
+        Ok(self.db.delete(ids).await?)
+    }
+
+    /// Delete what `settings` don't keep, as of `now`. Returns how many were deleted.
+    pub async fn prune(&self, settings: &Settings, now: OffsetDateTime) -> Result<u64> {
+        let before = time::Duration::try_from(settings.max_age.0)
+            .ok()
+            .and_then(|max_age| now.checked_sub(max_age))
+            .unwrap_or(OffsetDateTime::UNIX_EPOCH);
+
+        Ok(self.db.prune(before, settings.max_size()).await?)
+    }
+
+    async fn push_record(&self, record: &OutputRecord) -> Result<RecordId> {
+        let idx = self
+            .store
+            .last(&RecordSeriesKey::new(self.host_id, RecordTag::CommandOutput))
+            .await?
+            .map_or(0, |p| p.idx + 1);
+
+        let record = Record::builder()
+            .host(Host::new(self.host_id))
+            .version(OUTPUT_RECORD_VERSION)
+            .tag(RecordTag::CommandOutput)
+            .idx(idx)
+            .data(record.serialize()?)
+            .build();
+
+        let id = record.id;
+
+        self.store.push(&record.encrypt(&self.encryption_key)).await?;
+
+        Ok(id)
+    }
+
+    /// Keep the output synced from elsewhere since the last build, as far as `settings` keep it,
+    /// as of `now`, and only while its entry in `history` is there and not deleted.
+    #[instrument(level = "trace", skip_all, err)]
+    pub async fn build(
+        &self,
+        history: &Sqlite,
+        settings: &Settings,
+        now: OffsetDateTime,
+    ) -> Result<()> {
+        let mut built = 0;
+        let mut skipped = 0;
+
+        for id in self.store.all_tagged_ids(&RecordTag::CommandOutput).await? {
+            if self.db.is_built(id).await? {
+                continue;
+            }
+
+            // Skip records we can't decrypt or decode, rather than failing the entire build.
+            let record = self.store.get(id).await.and_then(|record| {
+                let decrypted = record.decrypt(&self.encryption_key)?;
+                OutputRecord::deserialize(&decrypted.data, &decrypted.version)
+            });
+
+            match record {
+                Ok(OutputRecord::Create(output)) => match history.load(&output.history_id.0).await?
+                {
+                    // Its history may sync later, so look again at the next build.
+                    None => continue,
+                    Some(h) if h.deleted_at.is_some() => {}
+                    // Output kept here is newer, or what was captured here in the first place.
+                    Some(_) => {
+                        if !self.db.contains(&output.history_id).await? {
+                            self.db.save(&output).await?;
+                            built += 1;
+                        }
+                    }
+                },
+                Ok(OutputRecord::Delete(ids)) => self.db.delete(&ids).await?,
+                Err(e) => {
+                    tracing::warn!("failed to decode output record, skipping: {e}");
+                    skipped += 1;
+                }
+            }
+
+            self.db.mark_built(id).await?;
+        }
+
+        if skipped > 0 {
+            // library code that may run under the TUI or shell hooks, so no stderr here
+            tracing::warn!(
+                "skipped {skipped} output records that could not be decrypted or decoded"
+            );
+        }
+
+        if built > 0 {
+            // What's too old or too much to keep from elsewhere goes straight away.
+            self.prune(settings, now).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+    use time::macros::datetime;
+
+    use super::*;
+    use crate::history::History;
+    use crate::settings::test_local_timeout;
+
+    const NOW: OffsetDateTime = datetime!(2026-06-01 00:00 UTC);
+
+    async fn store(record_store: &SqliteStore, host_id: HostId) -> OutputStore {
+        let db = Database::new("sqlite::memory:", test_local_timeout()).await.unwrap();
+        OutputStore::new(record_store.clone(), db, host_id, [0u8; 32].into())
+    }
+
+    fn entry(id: &str) -> History {
+        let mut h: History = History::import().timestamp(NOW).command(id).build().into();
+        h.id = HistoryId(id.to_string());
+        h
+    }
+
+    /// A history database holding an entry for each id, deleted when `deleted` says so.
+    async fn history(ids: &[(&str, bool)]) -> Sqlite {
+        let db = Sqlite::new("sqlite::memory:", test_local_timeout()).await.unwrap();
+        for (id, deleted) in ids {
+            let mut h = entry(id);
+            h.deleted_at = deleted.then_some(NOW);
+            db.save(&h).await.unwrap();
+        }
+        db
+    }
+
+    fn output(id: &str, days_ago: i64) -> CommandOutput {
+        CommandOutput {
+            history_id: HistoryId(id.to_string()),
+            timestamp: NOW - time::Duration::days(days_ago),
+            output: format!("output of {id}\n"),
+            truncated: false,
+            observed_bytes: 0,
+        }
+    }
+
+    fn syncing() -> Settings {
+        Settings {
+            sync: true,
+            ..Settings::default()
+        }
+    }
+
+    /// Keep `output` and share it, as the daemon does once its history has ended.
+    async fn capture(store: &OutputStore, history: &Sqlite, output: &CommandOutput) {
+        store.save(output).await.unwrap();
+        store.share(output, history, &syncing(), &sync::Settings::default()).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn only_shares_when_the_history_syncs() {
+        let record_store = SqliteStore::new(":memory:", test_local_timeout()).await.unwrap();
+        let store = store(&record_store, HostId(atuin_common::utils::uuid_v7())).await;
+        let history = history(&[("synced", false), ("local", false)]).await;
+        history.mark_local_only(&HistoryId("local".to_string())).await.unwrap();
+
+        let (synced, local) = (output("synced", 0), output("local", 0));
+        let everything = sync::Settings::default();
+        let no_history = sync::Settings {
+            ignore_tags: vec![RecordTag::History],
+            ..sync::Settings::default()
+        };
+
+        let shared = [
+            store.share(&synced, &history, &Settings::default(), &everything).await.unwrap(),
+            store.share(&local, &history, &syncing(), &everything).await.unwrap(),
+            store.share(&synced, &history, &syncing(), &no_history).await.unwrap(),
+            store.share(&synced, &history, &syncing(), &everything).await.unwrap(),
+        ];
+        assert_eq!(shared, [false, false, false, true]);
+
+        assert_eq!(record_store.len_tag(&RecordTag::CommandOutput).await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn builds_output_synced_from_elsewhere() {
+        // Two machines' stores, after a sync: the same records, and their own output and history
+        // databases.
+        let record_store = SqliteStore::new(":memory:", test_local_timeout()).await.unwrap();
+        let laptop = store(&record_store, HostId(atuin_common::utils::uuid_v7())).await;
+        let desktop = store(&record_store, HostId(atuin_common::utils::uuid_v7())).await;
+        let laptop_history =
+            history(&[("recent", false), ("ancient", false), ("deleted", false)]).await;
+        let desktop_history =
+            history(&[("recent", false), ("ancient", false), ("deleted", true)]).await;
+
+        for output in
+            [output("recent", 7), output("ancient", 365), output("deleted", 1), output("later", 1)]
+        {
+            capture(&laptop, &laptop_history, &output).await;
+        }
+        // Its history is yet to sync, so its output isn't shared yet either.
+        laptop.save(&output("unsynced", 1)).await.unwrap();
+
+        desktop.build(&desktop_history, &Settings::default(), NOW).await.unwrap();
+
+        let recent = HistoryId("recent".to_string());
+        let later = HistoryId("later".to_string());
+        assert_eq!(desktop.load(&recent).await.unwrap(), Some(output("recent", 7)));
+        assert_eq!(desktop.load(&HistoryId("ancient".to_string())).await.unwrap(), None);
+        assert_eq!(desktop.load(&HistoryId("deleted".to_string())).await.unwrap(), None);
+        assert_eq!(desktop.load(&later).await.unwrap(), None);
+        assert_eq!(desktop.load(&HistoryId("unsynced".to_string())).await.unwrap(), None);
+
+        // Output is built once its history is there.
+        desktop_history.save(&entry("later")).await.unwrap();
+        desktop.build(&desktop_history, &Settings::default(), NOW).await.unwrap();
+        assert_eq!(desktop.load(&later).await.unwrap(), Some(output("later", 1)));
+
+        // Records are only built once, so output deleted here stays deleted.
+        desktop.delete(std::slice::from_ref(&recent), &Settings::default()).await.unwrap();
+        desktop.build(&desktop_history, &Settings::default(), NOW).await.unwrap();
+        assert_eq!(desktop.load(&recent).await.unwrap(), None);
+
+        // Output deleted where it synced from is deleted everywhere.
+        laptop.delete(std::slice::from_ref(&later), &syncing()).await.unwrap();
+        desktop.build(&desktop_history, &Settings::default(), NOW).await.unwrap();
+        assert_eq!(desktop.load(&later).await.unwrap(), None);
+    }
+}
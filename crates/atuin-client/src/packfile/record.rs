@@ -305,12 +305,14 @@ impl<'a> PackManifestRecordView<'a> {
         Ok(run)
     }
 
-    /// Asynchronously pack the records enclosed by this manifest.
+    /// Asynchronously pack the records enclosed by this manifest, which are encrypted with `key`
+    /// locally, into a blob encrypted with `blob_key`.
     #[instrument(level = "trace", skip_all, fields(id = ?self.record.id), err)]
     pub async fn pack_records(
         &self,
         store: &SqliteStore,
         key: paseto_v4::Key,
+        blob_key: paseto_v4::Key,
     ) -> Result<(Vec<u8>, Vec<RecordId>), PackingError> {
         let encrypted_records = self.load_encrypted_packed_records(store).await?;
         let ia = self.ia().json();
@@ -341,7 +343,7 @@ impl<'a> PackManifestRecordView<'a> {
             let encrypted_data = paseto_v4::encrypt_sync(
                 &compressed,
                 Some(paseto_v4::ImplicitAssertion::from(ia.as_str())),
-                &key,
+                &blob_key,
             )?;
 
             let mut buf = ByteBuf::new();
@@ -355,10 +357,13 @@ impl<'a> PackManifestRecordView<'a> {
         .map_err(PackingError::Pack)
     }
 
+    /// Unpack a blob encrypted with `blob_key` into records encrypted with `key`, for the local
+    /// store.
     #[instrument(level = "trace", skip_all, fields(id = ?self.record.id), err)]
     pub async fn unpack_records(
         &self,
         packed_bytes: impl AsRef<[u8]> + Send + 'static,
+        blob_key: paseto_v4::Key,
         key: paseto_v4::Key,
     ) -> Result<Vec<Record<EncryptedData>>, UnpackError> {
         let ia = self.ia().json();
@@ -370,7 +375,7 @@ impl<'a> PackManifestRecordView<'a> {
             let decrypted = paseto_v4::decrypt_sync(
                 &encrypted,
                 Some(paseto_v4::ImplicitAssertion::from(ia.as_str())),
-                &key,
+                &blob_key,
             )?;
 
             let decompressed = zstd::stream::decode_all(decrypted.as_slice())?;
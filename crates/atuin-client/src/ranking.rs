@@ -0,0 +1,486 @@
+//! Context-aware ranking of search results.
+//!
+//! A [`Ranker`] takes the rows a search matched and reorders them. The
+//! [`Pipeline`] ranker is built from a set of weighted [`Scorer`]s, each of which
+//! looks at one signal (does the command match the query as a prefix, was it run
+//! in this directory, how often is it used...) and returns a score that is
+//! roughly in `0.0..=1.0`. The pipeline sums the weighted scores and sorts by
+//! the total, keeping the incoming order for ties.
+//!
+//! Some signals need data that isn't on the [`History`] rows themselves. Those
+//! are carried by [`RankContext`], which the caller fills in once per search;
+//! see [`Ranker::uses_frecency`] and [`Ranker::uses_affinity`].
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use tracing::instrument;
+
+use crate::database::Context;
+use crate::history::History;
+
+/// The score [`FrecencyData::compute`] gives a command used a hundred times in the last hour,
+/// used to bring frecency into the same range as the other scorers.
+const MAX_FRECENCY: f64 = 200.0;
+
+/// Pre-computed frecency data for O(1) lookup.
+#[derive(Debug, Clone, Default)]
+pub struct FrecencyData {
+    /// Total number of times this command was used.
+    pub count: u32,
+    /// Most recent usage timestamp (unix seconds).
+    pub last_used: i64,
+}
+
+impl FrecencyData {
+    /// Record a new usage of this command.
+    pub fn record_use(&mut self, timestamp: i64) {
+        self.count += 1;
+        if timestamp > self.last_used {
+            self.last_used = timestamp;
+        }
+    }
+
+    /// Compute frecency score based on count and recency.
+    ///
+    /// Uses a decay function where more recent commands score higher.
+    /// The formula balances frequency (how often) with recency (how recent).
+    ///
+    /// Multipliers allow tuning the relative weights:
+    /// - `recency_mul`: Multiplier for recency score (default: 1.0)
+    /// - `frequency_mul`: Multiplier for frequency score (default: 1.0)
+    ///
+    /// A multiplier of 0.0 disables that component, 1.0 is unchanged, 2.0 doubles weight.
+    /// Values like 0.5 reduce weight by half, 1.5 increases by 50%, etc.
+    #[instrument(level = tracing::Level::TRACE, name = "index_frecency_compute")]
+    pub fn compute(&self, now: i64, recency_mul: f64, frequency_mul: f64) -> u32 {
+        if self.count == 0 {
+            return 0;
+        }
+
+        // Time-based decay: score decreases as time passes
+        let age_seconds = (now - self.last_used).max(0) as u64;
+        let age_hours = age_seconds / 3600;
+
+        // Decay factor: recent commands get higher scores
+        // - Last hour: multiplier ~1.0
+        // - Last day: multiplier ~0.5
+        // - Last week: multiplier ~0.1
+        // - Older: multiplier approaches 0
+        let recency_score: f64 = match age_hours {
+            0 => 100.0,
+            1..=6 => 90.0,
+            7..=24 => 70.0,
+            25..=72 => 50.0,
+            73..=168 => 30.0,
+            169..=720 => 15.0,
+            _ => 5.0,
+        };
+
+        // Frequency boost: more uses = higher score (with diminishing returns)
+        let frequency_score = (f64::from(self.count).ln() * 20.0).min(100.0);
+
+        // Apply multipliers and combine scores, then round to u32
+        ((recency_score * recency_mul) + (frequency_score * frequency_mul)).round() as u32
+    }
+}
+
+/// Weights for each of the scorers in the default ranking [`Pipeline`].
+///
+/// Configured under `[search.ranking]`. A weight of 0.0 disables that scorer entirely, which also
+/// skips any extra queries it would need.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(default)]
+pub struct RankingWeights {
+    /// How strongly to prefer commands that start with, or contain, the query.
+    pub query_match: f64,
+    /// How strongly to prefer commands run very recently.
+    pub recency: f64,
+    /// Boost for commands run in the current directory.
+    pub cwd: f64,
+    /// Boost for commands run inside the current git repository.
+    pub git_root: f64,
+    /// Boost for commands run on the current host.
+    pub host: f64,
+    /// Boost for commands run in the current shell session.
+    pub session: f64,
+    /// Boost for commands that exited successfully.
+    pub exit_success: f64,
+    /// Boost for commands that are used often and recently.
+    pub frecency: f64,
+    /// Boost for commands that usually follow the last command run in this session.
+    pub affinity: f64,
+}
+
+impl Default for RankingWeights {
+    fn default() -> Self {
+        Self {
+            query_match: 1.0,
+            recency: 1.0,
+            cwd: 0.5,
+            git_root: 0.25,
+            host: 0.1,
+            session: 0.25,
+            exit_success: 0.2,
+            frecency: 0.5,
+            affinity: 0.5,
+        }
+    }
+}
+
+/// Everything a [`Scorer`] may look at besides the row being scored.
+pub struct RankContext<'a> {
+    /// The raw query string, as typed.
+    pub query: &'a str,
+    /// Where the search is being run from.
+    pub context: &'a Context,
+    /// The current time, as a unix timestamp in seconds.
+    pub now: i64,
+    /// Frecency of each command, keyed by command. Only filled in if the ranker
+    /// [uses frecency](Ranker::uses_frecency).
+    pub frecency: HashMap<String, FrecencyData>,
+    /// How many times each command directly followed the last command run in this session.
+    /// Only filled in if the ranker [uses affinity](Ranker::uses_affinity).
+    pub followers: HashMap<String, u32>,
+}
+
+impl<'a> RankContext<'a> {
+    pub fn new(query: &'a str, context: &'a Context) -> Self {
+        Self {
+            query,
+            context,
+            now: OffsetDateTime::now_utc().unix_timestamp(),
+            frecency: HashMap::new(),
+            followers: HashMap::new(),
+        }
+    }
+}
+
+/// A single ranking signal.
+pub trait Scorer: Send + Sync {
+    /// Score a single row. Scores should be roughly in `0.0..=1.0` so that
+    /// weights are comparable between scorers.
+    fn score(&self, ctx: &RankContext<'_>, history: &History) -> f64;
+
+    /// Whether this scorer reads [`RankContext::frecency`].
+    fn uses_frecency(&self) -> bool {
+        false
+    }
+
+    /// Whether this scorer reads [`RankContext::followers`].
+    fn uses_affinity(&self) -> bool {
+        false
+    }
+}
+
+/// Reorders search results.
+pub trait Ranker: Send + Sync {
+    fn rank(&self, ctx: &RankContext<'_>, results: Vec<History>) -> Vec<History>;
+
+    /// Whether [`RankContext::frecency`] needs to be filled in for this ranker.
+    fn uses_frecency(&self) -> bool {
+        false
+    }
+
+    /// Whether [`RankContext::followers`] needs to be filled in for this ranker.
+    fn uses_affinity(&self) -> bool {
+        false
+    }
+}
+
+/// Prefers commands that start with the query over ones that merely contain it.
+pub struct QueryMatch;
+
+impl Scorer for QueryMatch {
+    fn score(&self, ctx: &RankContext<'_>, history: &History) -> f64 {
+        if history.command.starts_with(ctx.query) {
+            1.0
+        } else if history.command.contains(ctx.query) {
+            0.75
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Prefers newer history, but falls off quickly so it mostly breaks ties.
+pub struct Recency;
+
+impl Scorer for Recency {
+    fn score(&self, ctx: &RankContext<'_>, history: &History) -> f64 {
+        let diff = std::cmp::max(1, ctx.now - history.timestamp.unix_timestamp()); // no /0 please
+
+        #[allow(clippy::cast_precision_loss)]
+        let score = 1.0 / diff as f64;
+        score
+    }
+}
+
+/// Prefers commands run in the current directory.
+pub struct SameCwd;
+
+impl Scorer for SameCwd {
+    fn score(&self, ctx: &RankContext<'_>, history: &History) -> f64 {
+        f64::from(u8::from(history.cwd == ctx.context.cwd))
+    }
+}
+
+/// Prefers commands run somewhere inside the current git repository.
+pub struct SameGitRoot;
+
+impl Scorer for SameGitRoot {
+    fn score(&self, ctx: &RankContext<'_>, history: &History) -> f64 {
+        let Some(root) = ctx.context.git_root.as_deref() else {
+            return 0.0;
+        };
+
+        f64::from(u8::from(Path::new(&history.cwd).starts_with(root)))
+    }
+}
+
+/// Prefers commands run on the current host.
+pub struct SameHost;
+
+impl Scorer for SameHost {
+    fn score(&self, ctx: &RankContext<'_>, history: &History) -> f64 {
+        let host = history.cmd_origin.host();
+        let current = ctx.context.cmd_origin.host();
+        f64::from(u8::from(host.as_ref().eq_ignore_ascii_case(current.as_ref())))
+    }
+}
+
+/// Prefers commands run in the current shell session.
+pub struct SameSession;
+
+impl Scorer for SameSession {
+    fn score(&self, ctx: &RankContext<'_>, history: &History) -> f64 {
+        let session = &ctx.context.session;
+        f64::from(u8::from(!session.is_empty() && history.session == *session))
+    }
+}
+
+/// Prefers commands that exited successfully.
+pub struct ExitSuccess;
+
+impl Scorer for ExitSuccess {
+    fn score(&self, _ctx: &RankContext<'_>, history: &History) -> f64 {
+        f64::from(u8::from(history.exit == 0))
+    }
+}
+
+/// Prefers commands that are used often and recently, using the same formula
+/// as the daemon's search index.
+pub struct Frecency {
+    pub recency_mul: f64,
+    pub frequency_mul: f64,
+}
+
+impl Scorer for Frecency {
+    fn score(&self, ctx: &RankContext<'_>, history: &History) -> f64 {
+        ctx.frecency.get(&history.command).map_or(0.0, |data| {
+            let frecency = data.compute(ctx.now, self.recency_mul, self.frequency_mul);
+            (f64::from(frecency) / MAX_FRECENCY).min(1.0)
+        })
+    }
+
+    fn uses_frecency(&self) -> bool {
+        true
+    }
+}
+
+/// Prefers commands that usually come right after the last command run in this session.
+pub struct Affinity;
+
+impl Scorer for Affinity {
+    fn score(&self, ctx: &RankContext<'_>, history: &History) -> f64 {
+        let Some(max) = ctx.followers.values().copied().max() else {
+            return 0.0;
+        };
+
+        ctx.followers.get(&history.command).map_or(0.0, |&count| f64::from(count) / f64::from(max))
+    }
+
+    fn uses_affinity(&self) -> bool {
+        true
+    }
+}
+
+/// A [`Ranker`] that sums weighted [`Scorer`]s.
+#[derive(Default)]
+pub struct Pipeline {
+    scorers: Vec<(f64, Box<dyn Scorer>)>,
+}
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a scorer to the pipeline. Scorers with a weight of zero are dropped.
+    #[must_use]
+    pub fn with(mut self, weight: f64, scorer: impl Scorer + 'static) -> Self {
+        if weight != 0.0 {
+            self.scorers.push((weight, Box::new(scorer)));
+        }
+        self
+    }
+
+    /// Build the default pipeline from the `[search]` settings.
+    pub fn from_settings(settings: &crate::settings::Search) -> Self {
+        let weights = &settings.ranking;
+
+        Self::new()
+            .with(weights.query_match, QueryMatch)
+            .with(weights.recency, Recency)
+            .with(weights.cwd, SameCwd)
+            .with(weights.git_root, SameGitRoot)
+            .with(weights.host, SameHost)
+            .with(weights.session, SameSession)
+            .with(weights.exit_success, ExitSuccess)
+            .with(weights.frecency, Frecency {
+                recency_mul: settings.recency_score_multiplier.max(0.0),
+                frequency_mul: settings.frequency_score_multiplier.max(0.0),
+            })
+            .with(weights.affinity, Affinity)
+    }
+
+    fn score(&self, ctx: &RankContext<'_>, history: &History) -> f64 {
+        self.scorers.iter().map(|(weight, scorer)| weight * scorer.score(ctx, history)).sum()
+    }
+}
+
+impl Ranker for Pipeline {
+    fn rank(&self, ctx: &RankContext<'_>, results: Vec<History>) -> Vec<History> {
+        if self.scorers.is_empty() {
+            return results;
+        }
+
+        let mut scored: Vec<(f64, History)> =
+            results.into_iter().map(|h| (self.score(ctx, &h), h)).collect();
+
+        // Stable, so rows that tie keep the order the search returned them in.
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        scored.into_iter().map(|(_, h)| h).collect()
+    }
+
+    fn uses_frecency(&self) -> bool {
+        self.scorers.iter().any(|(_, scorer)| scorer.uses_frecency())
+    }
+
+    fn uses_affinity(&self) -> bool {
+        self.scorers.iter().any(|(_, scorer)| scorer.uses_affinity())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use atuin_domain::record::CmdOrigin;
+    use rstest::rstest;
+
+    use super::*;
+
+    fn context() -> Context {
+        Context {
+            session: "s1".to_string(),
+            cwd: "/src/atuin/crates".to_string(),
+            cmd_origin: CmdOrigin::try_from("laptop:ellie").unwrap(),
+            host_id: String::new(),
+            git_root: Some(PathBuf::from("/src/atuin")),
+        }
+    }
+
+    fn hist(command: &str, cwd: &str, session: &str, exit: i64) -> History {
+        History::import()
+            .timestamp(OffsetDateTime::now_utc() - time::Duration::days(1))
+            .command(command)
+            .cwd(cwd)
+            .session(session)
+            .exit(exit)
+            .cmd_origin(CmdOrigin::try_from("laptop:ellie").unwrap())
+            .build()
+            .into()
+    }
+
+    fn commands(res: Vec<History>) -> Vec<String> {
+        res.into_iter().map(|h| h.command).collect()
+    }
+
+    #[rstest]
+    #[case::cwd(SameCwd, hist("a", "/src/atuin/crates", "s2", 1), 1.0)]
+    #[case::cwd_elsewhere(SameCwd, hist("a", "/tmp", "s2", 1), 0.0)]
+    #[case::git_root(SameGitRoot, hist("a", "/src/atuin/docs", "s2", 1), 1.0)]
+    #[case::git_root_sibling(SameGitRoot, hist("a", "/src/atuin-other", "s2", 1), 0.0)]
+    #[case::host(SameHost, hist("a", "/tmp", "s2", 1), 1.0)]
+    #[case::session(SameSession, hist("a", "/tmp", "s1", 1), 1.0)]
+    #[case::session_other(SameSession, hist("a", "/tmp", "s2", 1), 0.0)]
+    #[case::exit_success(ExitSuccess, hist("a", "/tmp", "s2", 0), 1.0)]
+    #[case::exit_failure(ExitSuccess, hist("a", "/tmp", "s2", 1), 0.0)]
+    fn scorer_scores(#[case] scorer: impl Scorer, #[case] history: History, #[case] expected: f64) {
+        let context = context();
+        let ctx = RankContext::new("", &context);
+        assert!((scorer.score(&ctx, &history) - expected).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn pipeline_prefers_context() {
+        let context = context();
+        let ctx = RankContext::new("cargo", &context);
+        let pipeline = Pipeline::new().with(1.0, QueryMatch).with(0.2, SameCwd);
+
+        let results = vec![
+            hist("cargo build", "/tmp", "s2", 0),
+            hist("cargo test", "/src/atuin/crates", "s2", 0),
+            hist("echo cargo", "/src/atuin/crates", "s2", 0),
+        ];
+
+        assert_eq!(commands(pipeline.rank(&ctx, results)), vec![
+            "cargo test",
+            "cargo build",
+            "echo cargo"
+        ]);
+    }
+
+    #[test]
+    fn pipeline_uses_frecency_and_affinity() {
+        let context = context();
+        let mut ctx = RankContext::new("", &context);
+        ctx.frecency.insert("git push".to_string(), FrecencyData {
+            count: 50,
+            last_used: ctx.now,
+        });
+        ctx.followers.insert("git commit".to_string(), 10);
+
+        let frecency = Pipeline::new().with(1.0, Frecency {
+            recency_mul: 1.0,
+            frequency_mul: 1.0,
+        });
+        let affinity = Pipeline::new().with(1.0, Affinity);
+        let results =
+            || vec![hist("git commit", "/tmp", "s2", 0), hist("git push", "/tmp", "s2", 0)];
+
+        assert_eq!(commands(frecency.rank(&ctx, results())), vec!["git push", "git commit"]);
+        assert_eq!(commands(affinity.rank(&ctx, results())), vec!["git commit", "git push"]);
+    }
+
+    #[test]
+    fn zero_weights_are_skipped() {
+        let settings = crate::settings::Search {
+            ranking: RankingWeights {
+                frecency: 0.0,
+                affinity: 0.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let pipeline = Pipeline::from_settings(&settings);
+
+        assert!(!pipeline.uses_frecency());
+        assert!(!pipeline.uses_affinity());
+        assert_eq!(pipeline.scorers.len(), 7);
+    }
+}
@@ -22,6 +22,8 @@ use sqlx::sqlite::{
 use tracing::instrument;
 use uuid::Uuid;
 
+use crate::database::check_schema;
+
 #[derive(Debug, Clone)]
 pub struct SqliteStore {
     pool: SqlitePool,
@@ -101,6 +103,26 @@ impl SqliteStore {
         Ok(Self { pool })
     }
 
+    /// Opens someone else's record store to read from, without creating, migrating or otherwise
+    /// writing to it.
+    ///
+    /// Refuses a store whose schema isn't exactly the one this version would migrate it to.
+    pub async fn open_read_only(path: impl AsRef<Path>, timeout: f64) -> Result<Self> {
+        let path = path.as_ref();
+        debug!("opening sqlite database at {path:?} read-only");
+
+        let opts =
+            SqliteConnectOptions::from_str(path.as_os_str().to_str().unwrap())?.read_only(true);
+
+        let pool = SqlitePoolOptions::new()
+            .acquire_timeout(Duration::try_from_secs_f64(timeout)?)
+            .connect_with(opts)
+            .await?;
+
+        check_schema(&pool, &sqlx::migrate!("./record-migrations")).await?;
+        Ok(Self { pool })
+    }
+
     #[instrument(level = "trace", skip_all, err)]
     async fn setup_db(pool: &SqlitePool) -> Result<()> {
         debug!("running sqlite database setup");
@@ -354,6 +376,18 @@ impl SqliteStore {
         Ok(res.into_iter().map(Into::into).collect())
     }
 
+    /// The ids of every record with this tag, oldest first, without loading the records themselves.
+    #[instrument(level = "trace", skip_all, fields(tag = ?tag), err)]
+    pub async fn all_tagged_ids(&self, tag: &RecordTag) -> Result<Vec<RecordId>> {
+        let ids: Vec<String> =
+            sqlx::query_scalar("select id from store where tag = ?1 order by timestamp asc")
+                .bind(tag.as_str())
+                .fetch_all(&self.pool)
+                .await?;
+
+        Ok(ids.iter().map(|id| Uuid::from_str(id).map(RecordId)).collect::<Result<_, _>>()?)
+    }
+
     /// Reencrypt every single item in this store with a new key
     /// Be careful - this may mess with sync.
     #[instrument(level = "trace", skip_all, err)]
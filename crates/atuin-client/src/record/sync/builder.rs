@@ -1,24 +1,36 @@
+use std::collections::HashMap;
 use std::sync::Arc;
 
 use atuin_domain::caps::CapClient;
 use eyre::Result;
 use tracing::instrument;
 use typed_builder::TypedBuilder;
+use url::Url;
 
-use super::{DEFAULT_PAGE_SIZE, SyncEngine, SyncError};
+use super::{DEFAULT_PAGE_SIZE, SyncEngine, SyncError, Transport};
 use crate::api_client::{Client, caps_client};
 use crate::record::sqlite_store::SqliteStore;
-use crate::settings::Settings;
+use crate::settings::{Settings, sync};
 
-/// Where a [`SyncEngine`]'s API client comes from.
+/// Where a [`SyncEngine`]'s remote comes from.
 pub enum ClientSource<'a> {
     /// Wrap an already-built [`Client`].
     FromClient(Client),
-    /// Build from settings, fetching capabilities during `connect`, unless supplied here.
+    /// Build from settings, fetching capabilities during `connect`, unless supplied here. The
+    /// engine syncs what the `[sync]` settings allow.
     FromSettings {
         settings: &'a Settings,
         caps: Option<Arc<CapClient>>,
     },
+    /// Another of this account's machines, whose daemon listens at `address`, authenticating by
+    /// answering its challenges with the shared `peer.secret`. The engine syncs what the `[sync]` settings allow.
+    FromPeer {
+        settings: &'a Settings,
+        address: Url,
+    },
+    /// Any other [`Transport`], such as a [`FileTransport`](super::FileTransport). The engine
+    /// syncs everything, unless given a filter with [`SyncEngine::with_filter`].
+    FromTransport(Arc<dyn Transport>),
 }
 
 /// Inputs for constructing a [`SyncEngine`]. See [`SyncEngine::builder`].
@@ -33,8 +45,9 @@ impl SyncEngineInit<'_> {
     /// Resolve the configured inputs into a live [`SyncEngine`].
     #[instrument(level = "trace", skip_all, err)]
     pub async fn connect(self) -> Result<SyncEngine, SyncError> {
-        let client = match self.client_source {
-            ClientSource::FromClient(client) => client,
+        let (transport, filter): (Arc<dyn Transport>, _) = match self.client_source {
+            ClientSource::FromClient(client) => (Arc::new(client), sync::Settings::default()),
+            ClientSource::FromTransport(transport) => (transport, sync::Settings::default()),
             ClientSource::FromSettings { settings, caps } => {
                 let caps = match caps {
                     Some(caps) => caps,
@@ -42,7 +55,7 @@ impl SyncEngineInit<'_> {
                         .map_err(|e| SyncError::OperationalError { msg: e.to_string() })?,
                 };
 
-                Client::new(
+                let client = Client::new(
                     settings.sync_address.clone(),
                     &settings
                         .sync_auth_token()
@@ -53,14 +66,49 @@ impl SyncEngineInit<'_> {
                     &settings.extra_headers,
                     caps,
                 )
-                .map_err(|e| SyncError::OperationalError { msg: e.to_string() })?
+                .map_err(|e| SyncError::OperationalError { msg: e.to_string() })?;
+
+                // teams' stores are synced on their own, by `crate::team::sync`
+                let filter = settings
+                    .sync_filter()
+                    .await
+                    .map_err(|e| SyncError::LocalStoreError { msg: e.to_string() })?;
+
+                (Arc::new(client), filter)
+            }
+            ClientSource::FromPeer { settings, address } => {
+                let secret =
+                    settings.peer.secret.clone().ok_or_else(|| SyncError::OperationalError {
+                        msg: "set peer.secret in the config to sync with a peer".to_string(),
+                    })?;
+
+                // `extra_headers` are for the sync server, and shouldn't go to peers
+                let caps = caps_client(&address, &HashMap::new())
+                    .map_err(|e| SyncError::OperationalError { msg: e.to_string() })?;
+
+                let client = Client::peer(
+                    address,
+                    &secret,
+                    settings.network_connect_timeout,
+                    settings.network_timeout,
+                    caps,
+                )
+                .map_err(|e| SyncError::OperationalError { msg: e.to_string() })?;
+
+                let filter = settings
+                    .sync_filter()
+                    .await
+                    .map_err(|e| SyncError::LocalStoreError { msg: e.to_string() })?;
+
+                (Arc::new(client), filter)
             }
         };
 
         Ok(SyncEngine {
-            client,
+            transport,
             store: self.store,
             page_size: DEFAULT_PAGE_SIZE,
+            filter,
         })
     }
 }
@@ -0,0 +1,544 @@
+//! A [`Transport`] that keeps the record log as plain files, in a local directory (a USB stick, an
+//! NFS mount) or in a directory on any host reachable over SSH. No server is involved.
+//!
+//! The remote holds a directory per host, and one per tag within that, of chunk files:
+//! `<host>/<tag>/<start>-<end>.jsonl` holds that series' records `start..end`, one JSON record per
+//! line. Chunks are never rewritten. They're written under a temporary name and renamed into place,
+//! so a half-written chunk is never read. Records stay encrypted, as on a sync server.
+
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::str::FromStr;
+
+use async_stream::try_stream;
+use async_trait::async_trait;
+use atuin_common::range::Chunks;
+use atuin_common::utils::uuid_v7;
+use atuin_domain::record::{
+    EncryptedData, HostId, Record, RecordIdx, RecordSeriesKey, RecordStatus, RecordTag,
+};
+use eyre::{Context, Result, bail, eyre};
+use futures::StreamExt;
+use futures::stream::BoxStream;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use uuid::Uuid;
+
+use super::Transport;
+
+/// Where a [`FileTransport`] keeps its files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileRemote {
+    /// A directory on this machine.
+    Local(PathBuf),
+    /// A directory on `host`, reached with the `ssh` command, so `~/.ssh/config` applies. A
+    /// relative `path` is relative to the home directory there.
+    Ssh {
+        host: String,
+        path: String,
+    },
+}
+
+impl FromStr for FileRemote {
+    type Err = eyre::Report;
+
+    /// Parse an scp-like `[user@]host:path`, or anything else as a local path. As with scp, a
+    /// path with a slash before its first colon is local, and so is a Windows drive like `C:`.
+    fn from_str(s: &str) -> Result<Self> {
+        if s.is_empty() {
+            bail!("the remote can't be empty");
+        }
+
+        match s.split_once(':') {
+            Some((host, path)) if host.len() > 1 && !host.contains(['/', '\\']) => {
+                if host.starts_with('-') {
+                    bail!("invalid ssh host {host}");
+                }
+
+                let path = match path.strip_prefix("~/").unwrap_or(path) {
+                    "" => ".",
+                    path => path,
+                };
+
+                Ok(Self::Ssh {
+                    host: host.to_string(),
+                    path: path.to_string(),
+                })
+            }
+            _ => Ok(Self::Local(PathBuf::from(s))),
+        }
+    }
+}
+
+impl fmt::Display for FileRemote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Local(path) => write!(f, "{}", path.display()),
+            Self::Ssh { host, path } => write!(f, "{host}:{path}"),
+        }
+    }
+}
+
+/// A chunk file on the remote.
+#[derive(Debug)]
+struct Chunk {
+    series: RecordSeriesKey,
+    range: Range<RecordIdx>,
+    /// The path of the file, relative to the remote's directory.
+    path: String,
+}
+
+impl Chunk {
+    /// The chunk a `<host>/<tag>/<start>-<end>.jsonl` path names, if it names one.
+    fn parse(path: &str) -> Option<Self> {
+        let mut parts = path.split('/');
+        let (Some(host), Some(tag), Some(file), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return None;
+        };
+
+        let host = HostId(Uuid::parse_str(host).ok()?);
+        let tag = decode_tag(tag)?;
+        let (start, end) = file.strip_suffix(".jsonl")?.split_once('-')?;
+        let range = start.parse().ok()?..end.parse().ok()?;
+
+        if range.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            series: RecordSeriesKey::new(host, tag),
+            range,
+            path: path.to_string(),
+        })
+    }
+
+    fn path(series: &RecordSeriesKey, range: &Range<RecordIdx>) -> String {
+        format!(
+            "{}/{}/{}-{}.jsonl",
+            series.host_id,
+            encode_tag(&series.tag),
+            range.start,
+            range.end
+        )
+    }
+}
+
+/// Tags are free-form, so anything but ASCII letters, digits, `-` and `_` is percent-encoded to
+/// keep them to a single, portable path component.
+fn encode_tag(tag: &RecordTag) -> String {
+    let mut encoded = String::new();
+
+    for b in tag.as_str().bytes() {
+        if b.is_ascii_alphanumeric() || b == b'-' || b == b'_' {
+            encoded.push(char::from(b));
+        } else {
+            let _ = write!(encoded, "%{b:02X}");
+        }
+    }
+
+    encoded
+}
+
+fn decode_tag(encoded: &str) -> Option<RecordTag> {
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut rest = encoded.as_bytes();
+
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+
+    String::from_utf8(bytes).ok().map(RecordTag::from)
+}
+
+/// Quote `s` for a POSIX shell.
+fn quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+/// Syncs with a directory of chunk files. See the [module docs](self) for the layout.
+#[derive(Debug, Clone)]
+pub struct FileTransport {
+    remote: FileRemote,
+}
+
+impl FileTransport {
+    pub const fn new(remote: FileRemote) -> Self {
+        Self { remote }
+    }
+
+    /// Every chunk on the remote. A remote directory that doesn't exist yet has none.
+    async fn list(&self) -> Result<Vec<Chunk>> {
+        let paths = match &self.remote {
+            FileRemote::Local(root) => list_local(root).await?,
+            FileRemote::Ssh { host, path } => {
+                let script = format!(
+                    "cd {} 2>/dev/null || exit 0; find . -type f -name '*.jsonl'",
+                    quote(path)
+                );
+                let out = ssh(host, &script, None).await?;
+
+                String::from_utf8_lossy(&out)
+                    .lines()
+                    .filter_map(|line| line.strip_prefix("./"))
+                    .map(str::to_string)
+                    .collect()
+            }
+        };
+
+        Ok(paths.iter().filter_map(|path| Chunk::parse(path)).collect())
+    }
+
+    /// The records in the chunk files at `paths`.
+    async fn read(&self, paths: &[&str]) -> Result<Vec<Record<EncryptedData>>> {
+        let contents = match &self.remote {
+            FileRemote::Local(root) => {
+                let mut contents = Vec::new();
+                for path in paths {
+                    let path = root.join(path);
+                    contents.extend(
+                        tokio::fs::read(&path)
+                            .await
+                            .wrap_err_with(|| format!("could not read {}", path.display()))?,
+                    );
+                }
+                contents
+            }
+            FileRemote::Ssh { host, path } => {
+                let files: Vec<_> = paths.iter().map(|p| quote(p)).collect();
+                let script = format!("cd {} && cat {}", quote(path), files.join(" "));
+                ssh(host, &script, None).await?
+            }
+        };
+
+        contents
+            .split(|&b| b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).wrap_err("invalid record in a chunk file"))
+            .collect()
+    }
+
+    /// Write a chunk file at `path`, relative to the remote's directory.
+    async fn write(&self, path: &str, contents: Vec<u8>) -> Result<()> {
+        let tmp = format!("{path}.{}.tmp", uuid_v7().simple());
+
+        match &self.remote {
+            FileRemote::Local(root) => {
+                let (path, tmp) = (root.join(path), root.join(tmp));
+
+                if let Some(dir) = path.parent() {
+                    tokio::fs::create_dir_all(dir)
+                        .await
+                        .wrap_err_with(|| format!("could not create {}", dir.display()))?;
+                }
+
+                tokio::fs::write(&tmp, contents)
+                    .await
+                    .wrap_err_with(|| format!("could not write {}", tmp.display()))?;
+                tokio::fs::rename(&tmp, &path)
+                    .await
+                    .wrap_err_with(|| format!("could not write {}", path.display()))?;
+            }
+            FileRemote::Ssh { host, path: root } => {
+                let dir = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+                let script = format!(
+                    "mkdir -p {root}/{dir} && cd {root} && cat > {tmp} && mv {tmp} {path}",
+                    root = quote(root),
+                    dir = quote(dir),
+                    tmp = quote(&tmp),
+                    path = quote(path),
+                );
+                ssh(host, &script, Some(contents)).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The paths of the `<host>/<tag>/<file>.jsonl` files under `root`, relative to it.
+async fn list_local(root: &Path) -> Result<Vec<String>> {
+    async fn entries(dir: &Path) -> Result<Vec<(String, PathBuf)>> {
+        let mut read_dir = match tokio::fs::read_dir(dir).await {
+            Ok(read_dir) => read_dir,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e).wrap_err_with(|| format!("could not list {}", dir.display())),
+        };
+
+        let mut entries = Vec::new();
+        while let Some(entry) = read_dir.next_entry().await? {
+            if let Some(name) = entry.file_name().to_str() {
+                entries.push((name.to_string(), entry.path()));
+            }
+        }
+
+        Ok(entries)
+    }
+
+    let mut paths = Vec::new();
+
+    for (host, host_dir) in entries(root).await? {
+        for (tag, tag_dir) in entries(&host_dir).await? {
+            for (file, _) in entries(&tag_dir).await? {
+                if file.ends_with(".jsonl") {
+                    paths.push(format!("{host}/{tag}/{file}"));
+                }
+            }
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Run `script` on `host` over SSH, with `stdin` as its input, and return its output.
+async fn ssh(host: &str, script: &str, stdin: Option<Vec<u8>>) -> Result<Vec<u8>> {
+    let mut child = Command::new("ssh")
+        .arg("--")
+        .arg(host)
+        .arg(script)
+        .stdin(if stdin.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .wrap_err("could not run ssh")?;
+
+    if let Some(input) = stdin
+        && let Some(mut pipe) = child.stdin.take()
+    {
+        pipe.write_all(&input).await.wrap_err("could not write to ssh")?;
+    }
+
+    let output = child.wait_with_output().await.wrap_err("could not run ssh")?;
+
+    if !output.status.success() {
+        return Err(eyre!(
+            "ssh to {host} failed ({}): {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    Ok(output.stdout)
+}
+
+#[async_trait]
+impl Transport for FileTransport {
+    async fn status(&self) -> Result<RecordStatus> {
+        let mut status = RecordStatus::new();
+
+        for chunk in self.list().await? {
+            let tail = chunk.range.end - 1;
+            if status.get(&chunk.series).is_none_or(|idx| idx < tail) {
+                status.set_raw(chunk.series, tail);
+            }
+        }
+
+        Ok(status)
+    }
+
+    fn pages(
+        &self,
+        series: &RecordSeriesKey,
+        chunks: Chunks<RecordIdx>,
+    ) -> BoxStream<'static, Result<Vec<Record<EncryptedData>>>> {
+        let this = self.clone();
+        let series = series.clone();
+
+        try_stream! {
+            let files: Vec<_> =
+                this.list().await?.into_iter().filter(|c| c.series == series).collect();
+
+            for page in chunks {
+                let paths: Vec<_> = files
+                    .iter()
+                    .filter(|c| c.range.start < page.end && page.start < c.range.end)
+                    .map(|c| c.path.as_str())
+                    .collect();
+
+                if paths.is_empty() {
+                    break;
+                }
+
+                // Chunks can overlap, if two machines uploaded the same records.
+                let mut records = BTreeMap::new();
+                for record in this.read(&paths).await? {
+                    if page.contains(&record.idx) {
+                        records.entry(record.idx).or_insert(record);
+                    }
+                }
+
+                // Only hand back the records that follow on from the page's start.
+                let records: Vec<_> = records
+                    .into_values()
+                    .zip(page.clone())
+                    .take_while(|(record, idx)| record.idx == *idx)
+                    .map(|(record, _)| record)
+                    .collect();
+
+                let short = (records.len() as u64) < page.end - page.start;
+
+                if records.is_empty() {
+                    break;
+                }
+
+                yield records;
+
+                if short {
+                    break;
+                }
+            }
+        }
+        .boxed()
+    }
+
+    async fn push(&self, records: &[Record<EncryptedData>]) -> Result<()> {
+        let mut by_series: BTreeMap<RecordSeriesKey, Vec<&Record<EncryptedData>>> = BTreeMap::new();
+        for record in records {
+            by_series
+                .entry(RecordSeriesKey::new(record.host.id, record.tag.clone()))
+                .or_default()
+                .push(record);
+        }
+
+        for (series, mut records) in by_series {
+            records.sort_by_key(|r| r.idx);
+            records.dedup_by_key(|r| r.idx);
+
+            // One chunk per contiguous run.
+            for run in records.chunk_by(|a, b| a.idx + 1 == b.idx) {
+                let range = run[0].idx..run[run.len() - 1].idx + 1;
+
+                let mut contents = Vec::new();
+                for record in run {
+                    serde_json::to_writer(&mut contents, record)?;
+                    contents.push(b'\n');
+                }
+
+                self.write(&Chunk::path(&series, &range), contents).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use atuin_common::range::RangeExt;
+    use pretty_assertions::assert_eq;
+    use rstest::rstest;
+
+    use super::*;
+    use crate::record::sync::packfile_sync_tests::{key, memory_store, seed_history};
+    use crate::record::sync::{ClientSource, SyncEngine};
+
+    #[rstest]
+    #[case("/mnt/usb/atuin", FileRemote::Local("/mnt/usb/atuin".into()))]
+    #[case("./backup:old", FileRemote::Local("./backup:old".into()))]
+    #[case("C:\\atuin", FileRemote::Local("C:\\atuin".into()))]
+    #[case("nas:atuin", FileRemote::Ssh { host: "nas".into(), path: "atuin".into() })]
+    #[case("me@nas:~/atuin", FileRemote::Ssh { host: "me@nas".into(), path: "atuin".into() })]
+    #[case("nas:/srv/atuin", FileRemote::Ssh { host: "nas".into(), path: "/srv/atuin".into() })]
+    #[case("nas:", FileRemote::Ssh { host: "nas".into(), path: ".".into() })]
+    fn parses_remotes(#[case] remote: &str, #[case] expected: FileRemote) {
+        assert_eq!(remote.parse::<FileRemote>().unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("")]
+    #[case("-oProxyCommand=sh:x")]
+    fn rejects_remotes(#[case] remote: &str) {
+        assert!(remote.parse::<FileRemote>().is_err());
+    }
+
+    #[rstest]
+    #[case(RecordTag::History, "history")]
+    #[case(RecordTag::DotfilesVar, "dotfiles-var")]
+    #[case(RecordTag::Other("team/ops:kv".into()), "team%2Fops%3Akv")]
+    fn encodes_tags(#[case] tag: RecordTag, #[case] encoded: &str) {
+        assert_eq!(encode_tag(&tag), encoded);
+        assert_eq!(decode_tag(encoded), Some(tag));
+    }
+
+    async fn engine(store: crate::record::sqlite_store::SqliteStore, dir: &Path) -> SyncEngine {
+        let transport = FileTransport::new(FileRemote::Local(dir.to_path_buf()));
+
+        SyncEngine::builder()
+            .store(store)
+            .client_source(ClientSource::FromTransport(std::sync::Arc::new(transport)))
+            .build()
+            .connect()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn syncs_stores_through_a_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = key();
+
+        let (a, b) = (memory_store().await, memory_store().await);
+        let (host_a, host_b) = (HostId(uuid_v7()), HostId(uuid_v7()));
+        seed_history(&a, host_a, &key, 250).await;
+        seed_history(&b, host_b, &key, 3).await;
+
+        let (uploaded, downloaded) =
+            engine(a.clone(), dir.path()).await.keyed(&key).sync().await.unwrap();
+        assert_eq!((uploaded, downloaded.len()), (250, 0));
+
+        let (uploaded, downloaded) =
+            engine(b.clone(), dir.path()).await.keyed(&key).sync().await.unwrap();
+        assert_eq!((uploaded, downloaded.len()), (3, 250));
+
+        let (uploaded, downloaded) =
+            engine(a.clone(), dir.path()).await.keyed(&key).sync().await.unwrap();
+        assert_eq!((uploaded, downloaded.len()), (0, 3));
+
+        assert_eq!(a.status().await.unwrap().hosts, b.status().await.unwrap().hosts);
+
+        let series = RecordSeriesKey::new(host_a, RecordTag::History);
+        let status = engine(a, dir.path()).await.record_status().await.unwrap();
+        assert_eq!(status.get(&series), Some(249));
+    }
+
+    #[tokio::test]
+    async fn overlapping_chunks_are_read_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let key = key();
+        let host = HostId(uuid_v7());
+
+        let up = memory_store().await;
+        seed_history(&up, host, &key, 10).await;
+
+        let series = RecordSeriesKey::new(host, RecordTag::History);
+        let records = up.next(&series, 0, 10).await.unwrap();
+
+        let transport = FileTransport::new(FileRemote::Local(dir.path().to_path_buf()));
+        transport.push(&records[..6]).await.unwrap();
+        transport.push(&records[4..]).await.unwrap();
+
+        let pages: Vec<_> = transport
+            .pages(&series, (0..10).chunks(4))
+            .map(|page| page.unwrap().iter().map(|r| r.idx).collect::<Vec<_>>())
+            .collect()
+            .await;
+
+        assert_eq!(pages, vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7], vec![8, 9]]);
+    }
+}
@@ -12,14 +12,15 @@
 //!                      records.
 //!
 //! > do a sync :O
+use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::fmt::Write;
 use std::num::NonZeroU64;
+use std::sync::Arc;
 
 use atuin_common::encryption::paseto_v4;
 use atuin_common::range::{Chunks, RangeExt};
 use atuin_common::sync::MutEagerFutureCell;
-use atuin_domain::caps::PackfileCap;
 use atuin_domain::record::{
     Diff, EncryptedData, Record, RecordId, RecordIdx, RecordSeriesKey, RecordStatus, RecordTag,
 };
@@ -31,18 +32,24 @@ use tokio::runtime::Handle;
 use tracing::instrument;
 
 use super::sqlite_store::SqliteStore;
-use crate::api_client::Client;
+use crate::api_client::QuotaExceeded;
 use crate::packfile::PackedPackfile;
 use crate::packfile::record::{PackManifestRecordView, ParsingError, UnpackError};
+use crate::settings::sync;
 
 mod builder;
+mod files;
+pub mod peer;
+mod transport;
 pub use builder::{ClientSource, SyncEngineBuilder, SyncEngineInit};
+pub use files::{FileRemote, FileTransport};
+pub use transport::Transport;
 
 /// How many packfile blobs to download concurrently within a single page. (Uploads are batched by
-/// [`Client::upload_packfiles`](crate::api_client::Client::upload_packfiles).)
+/// the [`Transport`].)
 const MAX_CONCURRENT_PACKFILE_TRANSFERS: usize = 16;
 
-/// How many packfile manifests to pack concurrently before handing a page's blobs to the client.
+/// How many packfile manifests to pack concurrently before handing a page's blobs to the transport.
 const MAX_CONCURRENT_PACKS: usize = 16;
 
 /// Records requested per sync page unless overridden with [`SyncEngine::with_page_size`].
@@ -73,6 +80,14 @@ pub enum SyncError {
         msg: String,
     },
 
+    #[error(
+        "the sync server refused to store more records, as your account is over its storage \
+         quota: {msg}"
+    )]
+    QuotaExceeded {
+        msg: String,
+    },
+
     #[error(
         "the encryption key on this machine does not match the data on the server. this usually \
          means a new machine was set up without copying the existing key. to fix: run `atuin key` \
@@ -128,18 +143,23 @@ pub enum Operation {
 /// Drives atuin's sync.
 #[derive(Clone)]
 pub struct SyncEngine {
-    client: Client,
+    transport: Arc<dyn Transport>,
     store: SqliteStore,
     /// How many records each sync page requests. Set via [`Self::with_page_size`].
     page_size: NonZeroU64,
+    /// Which series to sync. Set via [`Self::with_filter`].
+    filter: sync::Settings,
 }
 
 /// A [`SyncEngine`] paired with an encryption key, for the operations that encrypt or decrypt.
-/// Obtained from [`SyncEngine::keyed`].
+/// Obtained from [`SyncEngine::keyed`] or [`SyncEngine::keyed_with_remote`].
 pub struct Keyed<'k> {
     engine: &'k SyncEngine,
+    /// The key the local store is encrypted with.
     key: &'k paseto_v4::Key,
-    /// The result of verifying `key` against the remote.
+    /// The key the remote is encrypted with. The same as `key`, unless syncing a profile.
+    remote_key: &'k paseto_v4::Key,
+    /// The result of verifying `remote_key` against the remote.
     key_check: MutEagerFutureCell<Option<SyncError>>,
 }
 
@@ -151,10 +171,28 @@ impl SyncEngine {
         self
     }
 
+    /// Only sync the series `filter` allows (default: everything, or the `[sync]` settings when
+    /// built from settings).
+    #[must_use]
+    pub fn with_filter(mut self, filter: sync::Settings) -> Self {
+        self.filter = filter;
+        self
+    }
+
     /// Pair this engine with an encryption `key` to run the crypto-touching sync operations.
     pub fn keyed<'k>(&'k self, key: &'k paseto_v4::Key) -> Keyed<'k> {
+        self.keyed_with_remote(key, key)
+    }
+
+    /// As [`Self::keyed`], for a remote encrypted with another key than the local store, such as a
+    /// sync profile's server. Records are re-wrapped from one key to the other as they pass.
+    pub fn keyed_with_remote<'k>(
+        &'k self,
+        key: &'k paseto_v4::Key,
+        remote_key: &'k paseto_v4::Key,
+    ) -> Keyed<'k> {
         let engine = self.clone();
-        let key_for_check = key.clone();
+        let key_for_check = remote_key.clone();
         let key_check = MutEagerFutureCell::new(
             async move { engine.check_encryption_key(&key_for_check).await },
             &Handle::current(),
@@ -163,6 +201,7 @@ impl SyncEngine {
         Keyed {
             engine: self,
             key,
+            remote_key,
             key_check,
         }
     }
@@ -195,9 +234,10 @@ impl SyncEngine {
 
         let series = sample?;
 
-        let record = match self.client.records(&series).one().await {
-            Ok(Some(record)) => record,
-            Ok(None) => return None,
+        let pages = self.transport.pages(&series, (0..1).chunks(1));
+        futures::pin_mut!(pages);
+        let record = match pages.next().await? {
+            Ok(page) => page.into_iter().next()?,
             Err(e) => return Some(SyncError::RemoteRequestError { msg: e.to_string() }),
         };
 
@@ -207,12 +247,13 @@ impl SyncEngine {
     /// Fetch the remote's record status index.
     #[instrument(level = "trace", skip_all, err)]
     pub async fn record_status(&self) -> Result<RecordStatus, SyncError> {
-        self.client
-            .record_status()
+        self.transport
+            .status()
             .await
             .map_err(|e| SyncError::RemoteRequestError { msg: e.to_string() })
     }
 
+    /// Diff the local store against the remote, leaving out the series the filter skips.
     #[instrument(level = "trace", skip_all, err)]
     pub async fn diff(&self) -> Result<(Vec<Diff>, RecordStatus), SyncError> {
         let local_index = self
@@ -222,12 +263,17 @@ impl SyncEngine {
             .map_err(|e| SyncError::LocalStoreError { msg: e.to_string() })?;
 
         let remote_index = self
-            .client
-            .record_status()
+            .transport
+            .status()
             .await
             .map_err(|e| SyncError::RemoteRequestError { msg: e.to_string() })?;
 
-        let diff = local_index.diff(&remote_index);
+        let (diff, skipped): (Vec<_>, Vec<_>) =
+            local_index.diff(&remote_index).into_iter().partition(|d| self.filter.syncs(&d.series));
+
+        for diff in skipped {
+            debug!(host = %diff.series.host_id, tag = %diff.series.tag, "not syncing filtered series");
+        }
 
         Ok((diff, remote_index))
     }
@@ -302,6 +348,23 @@ impl SyncEngine {
 }
 
 impl Keyed<'_> {
+    /// Re-wrap `page` for the remote, if it uses another key than the local store.
+    fn to_remote<'p>(
+        &self,
+        page: &'p [Record<EncryptedData>],
+    ) -> Result<Cow<'p, [Record<EncryptedData>]>, SyncError> {
+        rewrap(page, self.key, self.remote_key)
+            .map_err(|e| SyncError::LocalStoreError { msg: e.to_string() })
+    }
+
+    /// Re-wrap `page` from the remote for the local store, if they use different keys.
+    fn to_local<'p>(
+        &self,
+        page: &'p [Record<EncryptedData>],
+    ) -> Result<Cow<'p, [Record<EncryptedData>]>, SyncError> {
+        rewrap(page, self.remote_key, self.key).map_err(|_| SyncError::WrongKey)
+    }
+
     // TODO(markovejnovic): Seriously revisit the syncing logic and coupling.
     #[instrument(
         level = "trace",
@@ -317,7 +380,7 @@ impl Keyed<'_> {
     ) -> Result<u64, SyncError> {
         let page_size = self.engine.page_size.get();
         let store = &self.engine.store;
-        let client = &self.engine.client;
+        let transport = &self.engine.transport;
         // The first record the remote *doesn't* have.
         let first_missing_remote = remote.map_or(0, |n| n + 1);
         let expected = local + 1 - first_missing_remote;
@@ -358,14 +421,14 @@ impl Keyed<'_> {
             }
 
             if series.tag == RecordTag::Packfile {
-                let key = self.key.clone();
                 let store = store.clone();
                 let packed: Vec<_> = stream::iter(page.iter().cloned())
                     .map(|manifest| {
-                        let (store, key) = (store.clone(), key.clone());
+                        let (store, key, remote_key) =
+                            (store.clone(), self.key.clone(), self.remote_key.clone());
                         async move {
                             let view = PackManifestRecordView::new(&manifest)?;
-                            let (blob, ids) = view.pack_records(&store, key).await?;
+                            let (blob, ids) = view.pack_records(&store, key, remote_key).await?;
                             Ok::<_, eyre::Report>(PackedPackfile {
                                 manifest_id: view.record.id,
                                 records: ids,
@@ -380,19 +443,21 @@ impl Keyed<'_> {
                         error!("failed to pack packfile: {e}");
                         SyncError::RemoteRequestError { msg: e.to_string() }
                     })?;
-                client
-                    .upload_packfiles(stream::iter(packed.into_iter().map(Ok::<_, eyre::Report>)))
-                    .await
-                    .map_err(|e| {
-                        error!("failed to upload packfile: {e}");
-                        SyncError::RemoteRequestError { msg: e.to_string() }
-                    })?;
+                transport.upload_packfiles(packed).await.map_err(|e| {
+                    error!("failed to upload packfile: {e}");
+                    SyncError::RemoteRequestError { msg: e.to_string() }
+                })?;
             }
 
-            client.post_records(&page).await.map_err(|e| {
+            transport.push(&self.to_remote(&page)?).await.map_err(|e| {
                 error!("failed to post records: {e:?}");
 
-                SyncError::RemoteRequestError { msg: e.to_string() }
+                match e.downcast_ref::<QuotaExceeded>() {
+                    Some(quota) => SyncError::QuotaExceeded {
+                        msg: quota.reason.clone(),
+                    },
+                    None => SyncError::RemoteRequestError { msg: e.to_string() },
+                }
             })?;
 
             progress += page.len() as u64;
@@ -508,12 +573,12 @@ impl Keyed<'_> {
 
         let blob = self
             .engine
-            .client
+            .transport
             .download_packfile(view.record.id)
             .await
             .map_err(PackfileDownloadError::Api)?;
 
-        let records = view.unpack_records(blob, self.key.clone()).await?;
+        let records = view.unpack_records(blob, self.remote_key.clone(), self.key.clone()).await?;
         let ids: Vec<RecordId> = records.iter().map(|record| record.id).collect();
 
         store.push_batch(records.iter()).await.map_err(PackfileDownloadError::Store)?;
@@ -533,10 +598,11 @@ impl Keyed<'_> {
         let mut ret = Vec::new();
         let mut progress = 0u64;
 
-        let pages = self.engine.client.records(series).stream(chunks);
+        let pages = self.engine.transport.pages(series, chunks);
         futures::pin_mut!(pages);
         while let Some(page) = pages.next().await {
             let page = page.map_err(|e| SyncError::RemoteRequestError { msg: e.to_string() })?;
+            let page = self.to_local(&page)?;
 
             // A packfile manifest's history must land in the store *before* the manifest itself, so
             // a stored manifest always has its records. Loose pages have no such step.
@@ -600,10 +666,7 @@ impl Keyed<'_> {
         let mut uploaded = 0;
         let mut downloaded = Vec::new();
 
-        let packfiles_enabled = matches!(
-            self.engine.client.caps().get_server::<PackfileCap>().await,
-            Ok(Some(cap)) if cap.record_count > 0
-        );
+        let packfiles_enabled = self.engine.transport.packfiles_enabled().await;
 
         // this can totally run in parallel, but lets get it working first
         for i in operations {
@@ -654,7 +717,7 @@ impl Keyed<'_> {
     /// Subsequent calls to [`Self::key_valid`] will return whether the key is valid against this
     /// new `remote_index`.
     pub async fn key_valid_against(&self, remote_index: &RecordStatus) -> Option<SyncError> {
-        let verdict = self.engine.check_key_against_index(self.key, remote_index).await;
+        let verdict = self.engine.check_key_against_index(self.remote_key, remote_index).await;
         self.key_check.overwrite(verdict.clone());
         verdict
     }
@@ -674,6 +737,26 @@ impl Keyed<'_> {
     }
 }
 
+/// Re-wrap the content keys of `page` from `from` to `to`, leaving the payloads alone. Packfile
+/// manifests aren't encrypted, and pass through as they are.
+fn rewrap<'p>(
+    page: &'p [Record<EncryptedData>],
+    from: &paseto_v4::Key,
+    to: &paseto_v4::Key,
+) -> Result<Cow<'p, [Record<EncryptedData>]>, paseto_v4::ReencryptionError> {
+    if from == to {
+        return Ok(Cow::Borrowed(page));
+    }
+
+    page.iter()
+        .map(|record| match record.tag {
+            RecordTag::Packfile => Ok(record.clone()),
+            _ => Ok(record.clone().with_data(paseto_v4::reencrypt_sync(&record.data, from, to)?)),
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(Cow::Owned)
+}
+
 #[cfg(test)]
 mod tests {
     use atuin_common::utils::uuid_v7;
@@ -923,6 +1006,7 @@ mod packfile_sync_tests {
 
     use atuin_common::encryption::paseto_v4;
     use atuin_common::utils::uuid_v7;
+    use atuin_domain::caps::PackfileCap;
     use atuin_domain::record::{
         DecryptedData, EncryptedData, Host, HostId, Record, RecordId, RecordVersion,
     };
@@ -1025,7 +1109,7 @@ mod packfile_sync_tests {
         let manifest =
             up.last(&RecordSeriesKey::new(host, RecordTag::Packfile)).await.unwrap().unwrap();
         let view = PackManifestRecordView::new(&manifest).unwrap();
-        let (blob, ids) = view.pack_records(&up, key.clone()).await.unwrap();
+        let (blob, ids) = view.pack_records(&up, key.clone(), key.clone()).await.unwrap();
         (manifest, blob, ids)
     }
 
@@ -1352,7 +1436,7 @@ mod packfile_sync_tests {
         let mut packs = Vec::new();
         for manifest in &manifests {
             let view = PackManifestRecordView::new(manifest).unwrap();
-            let (blob, _ids) = view.pack_records(&up, key.clone()).await.unwrap();
+            let (blob, _ids) = view.pack_records(&up, key.clone(), key.clone()).await.unwrap();
             packs.push((manifest.clone(), blob));
         }
 
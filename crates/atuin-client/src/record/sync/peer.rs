@@ -0,0 +1,130 @@
+//! Authenticating with a peer's daemon, for `atuin sync peer`.
+//!
+//! Peers usually talk plain HTTP, so `peer.secret` never goes over the wire, and nothing that does
+//! can be replayed. Before each request the client fetches a fresh challenge from
+//! [`CHALLENGE_PATH`], and answers it in the `Authorization` header with a MAC, keyed by the
+//! secret, over the challenge and the request itself. The daemon only accepts each challenge once,
+//! and only for a short while.
+
+use async_trait::async_trait;
+use blake2::digest::consts::U32;
+use blake2::digest::{Digest, Mac};
+use blake2::{Blake2b, Blake2bMac};
+use http::Extensions;
+use reqwest::header::{AUTHORIZATION, HeaderValue};
+use reqwest::{Method, Request, Response, Url};
+use reqwest_middleware::{Middleware, Next};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+/// Where a peer hands out challenges. It's the only record API route that needs no answer.
+pub const CHALLENGE_PATH: &str = "/api/v0/peer/challenge";
+
+/// The `Authorization` scheme peers answer challenges with: `Peer <challenge> <response>`.
+pub const SCHEME: &str = "Peer";
+
+/// A challenge from a peer, to be answered with [`response`].
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Challenge {
+    pub challenge: String,
+}
+
+/// The answer to `challenge` for a request, which only someone with `secret` can give. It covers
+/// the method, path, query and body, so it can't be used for any other request.
+pub fn response(
+    secret: &str,
+    challenge: &str,
+    method: &Method,
+    path_and_query: &str,
+    body: &[u8],
+) -> String {
+    // BLAKE2b keys are at most 64 bytes, and secrets can be any length.
+    let key = Zeroizing::new(
+        Blake2b::<U32>::new().chain_update(b"atuin-peer").chain_update(secret).finalize(),
+    );
+    let mut mac = <Blake2bMac<U32> as Mac>::new_from_slice(key.as_slice())
+        .expect("BLAKE2b takes 32 byte keys");
+
+    for part in [challenge.as_bytes(), method.as_str().as_bytes(), path_and_query.as_bytes()] {
+        mac.update(part);
+        mac.update(b"\n");
+    }
+    mac.update(body);
+
+    mac.finalize().into_bytes().iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// The path and query of `url`, as [`response`] covers them.
+pub fn path_and_query(url: &Url) -> String {
+    match url.query() {
+        Some(query) => format!("{}?{query}", url.path()),
+        None => url.path().to_string(),
+    }
+}
+
+/// Reqwest middleware answering a fresh challenge from the peer for each request.
+pub(crate) struct PeerAuth {
+    secret: Zeroizing<String>,
+    challenge_url: Url,
+    http: reqwest::Client,
+}
+
+impl PeerAuth {
+    /// Authenticate with the peer at `address`, fetching challenges over `http`.
+    pub(crate) fn new(address: &Url, secret: &str, http: reqwest::Client) -> eyre::Result<Self> {
+        Ok(Self {
+            secret: Zeroizing::new(secret.to_string()),
+            challenge_url: address.join(CHALLENGE_PATH)?,
+            http,
+        })
+    }
+}
+
+#[async_trait]
+impl Middleware for PeerAuth {
+    async fn handle(
+        &self,
+        mut req: Request,
+        ext: &mut Extensions,
+        next: Next<'_>,
+    ) -> reqwest_middleware::Result<Response> {
+        let Challenge { challenge } = self
+            .http
+            .get(self.challenge_url.clone())
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let body = req.body().and_then(|body| body.as_bytes()).unwrap_or_default();
+        let response =
+            response(&self.secret, &challenge, req.method(), &path_and_query(req.url()), body);
+
+        let value = HeaderValue::from_str(&format!("{SCHEME} {challenge} {response}"))
+            .map_err(reqwest_middleware::Error::middleware)?;
+        req.headers_mut().insert(AUTHORIZATION, value);
+
+        next.run(req, ext).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn responses_cover_the_whole_request() {
+        let answer = |secret, challenge, method, path, body: &[u8]| {
+            response(secret, challenge, &method, path, body)
+        };
+        let base = answer("secret", "abc", Method::POST, "/api/v0/record", b"[]");
+
+        assert_eq!(base, answer("secret", "abc", Method::POST, "/api/v0/record", b"[]"));
+        assert_ne!(base, answer("other", "abc", Method::POST, "/api/v0/record", b"[]"));
+        assert_ne!(base, answer("secret", "abd", Method::POST, "/api/v0/record", b"[]"));
+        assert_ne!(base, answer("secret", "abc", Method::GET, "/api/v0/record", b"[]"));
+        assert_ne!(base, answer("secret", "abc", Method::POST, "/api/v0/record/next", b"[]"));
+        assert_ne!(base, answer("secret", "abc", Method::POST, "/api/v0/record", b"[{}]"));
+    }
+}
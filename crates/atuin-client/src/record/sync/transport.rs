@@ -0,0 +1,86 @@
+//! What a [`SyncEngine`](super::SyncEngine) syncs with.
+//!
+//! The engine only needs a remote that can report the tail of each series, hand back pages of
+//! records, and append more. [`Client`] does so over the sync server's HTTP API, and
+//! [`FileTransport`](super::files::FileTransport) with plain files.
+
+use async_trait::async_trait;
+use atuin_common::range::Chunks;
+use atuin_domain::caps::PackfileCap;
+use atuin_domain::record::{
+    EncryptedData, Record, RecordId, RecordIdx, RecordSeriesKey, RecordStatus,
+};
+use eyre::{Result, bail};
+use futures::StreamExt;
+use futures::stream::{self, BoxStream};
+
+use crate::api_client::Client;
+use crate::packfile::PackedPackfile;
+
+/// A remote record log for a [`SyncEngine`](super::SyncEngine) to sync with.
+#[async_trait]
+pub trait Transport: Send + Sync {
+    /// The index of the last record of every series the remote has.
+    async fn status(&self) -> Result<RecordStatus>;
+
+    /// Stream the record pages the `chunks` plan covers for `series`. A page only comes up short,
+    /// or empty, when the remote runs out of records.
+    fn pages(
+        &self,
+        series: &RecordSeriesKey,
+        chunks: Chunks<RecordIdx>,
+    ) -> BoxStream<'static, Result<Vec<Record<EncryptedData>>>>;
+
+    /// Add `records` to the remote. Records it already has are left as they are.
+    async fn push(&self, records: &[Record<EncryptedData>]) -> Result<()>;
+
+    /// Whether the remote stores packfiles. When it doesn't, packfile series aren't synced with
+    /// it, and the loose history records cover the history they pack.
+    async fn packfiles_enabled(&self) -> bool {
+        false
+    }
+
+    /// Upload packfile blobs, for the manifests about to be pushed.
+    async fn upload_packfiles(&self, _packfiles: Vec<PackedPackfile>) -> Result<()> {
+        bail!("this remote doesn't store packfiles")
+    }
+
+    /// Download the blob of the packfile `manifest_id` describes.
+    async fn download_packfile(&self, _manifest_id: RecordId) -> Result<Vec<u8>> {
+        bail!("this remote doesn't store packfiles")
+    }
+}
+
+#[async_trait]
+impl Transport for Client {
+    async fn status(&self) -> Result<RecordStatus> {
+        self.record_status().await
+    }
+
+    fn pages(
+        &self,
+        series: &RecordSeriesKey,
+        chunks: Chunks<RecordIdx>,
+    ) -> BoxStream<'static, Result<Vec<Record<EncryptedData>>>> {
+        self.records(series).stream(chunks).boxed()
+    }
+
+    async fn push(&self, records: &[Record<EncryptedData>]) -> Result<()> {
+        self.post_records(records).await
+    }
+
+    async fn packfiles_enabled(&self) -> bool {
+        matches!(
+            self.caps().get_server::<PackfileCap>().await,
+            Ok(Some(cap)) if cap.record_count > 0
+        )
+    }
+
+    async fn upload_packfiles(&self, packfiles: Vec<PackedPackfile>) -> Result<()> {
+        Self::upload_packfiles(self, stream::iter(packfiles.into_iter().map(Ok))).await
+    }
+
+    async fn download_packfile(&self, manifest_id: RecordId) -> Result<Vec<u8>> {
+        Self::download_packfile(self, manifest_id).await
+    }
+}
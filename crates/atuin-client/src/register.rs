@@ -15,6 +15,7 @@ pub async fn register_classic(
         &username,
         &email,
         &password,
+        None,
         &settings.extra_headers,
     )
     .await?;
@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
@@ -21,6 +22,8 @@ use tokio::sync::OnceCell;
 use tracing::instrument;
 use url::Url;
 
+use crate::ranking::RankingWeights;
+
 static EXAMPLE_CONFIG: &str = include_str!("../config.toml");
 
 static DATA_DIR: OnceLock<PathBuf> = OnceLock::new();
@@ -31,8 +34,12 @@ pub mod daemon;
 mod dotfiles;
 mod kv;
 pub(crate) mod meta;
+pub mod output;
+pub mod retention;
 mod scripts;
+pub mod secrets;
 pub mod shells;
+pub mod sync;
 pub mod watcher;
 
 pub use daemon::Daemon;
@@ -538,6 +545,14 @@ pub struct Search {
     ///
     /// One of: `"all"`, `"auto"`, or an array of strings.
     pub shells: Shells,
+
+    /// Weights for the scorers that rank search results when `smart_sort` is enabled.
+    #[serde(default)]
+    pub ranking: RankingWeights,
+
+    /// How many predicted next commands to show at the top of interactive search
+    /// while the query is empty (default: 0, disabled).
+    pub predictions: usize,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -752,6 +767,8 @@ impl Default for Search {
             frequency_score_multiplier: 1.0,
             frecency_score_multiplier: 1.0,
             shells: Default::default(),
+            ranking: RankingWeights::default(),
+            predictions: 0,
         }
     }
 }
@@ -1041,6 +1058,14 @@ pub struct Settings {
     pub cwd_filter: RegexSet,
 
     pub secrets_filter: bool,
+    pub secrets_action: secrets::Action,
+
+    #[serde(default)]
+    pub secrets: secrets::Settings,
+
+    #[serde(default)]
+    pub retention: retention::Settings,
+
     pub workspaces: bool,
     pub ctrl_n_shortcuts: bool,
 
@@ -1074,6 +1099,20 @@ pub struct Settings {
     #[serde(default)]
     pub dotfiles: dotfiles::Settings,
 
+    #[serde(default)]
+    pub sync: sync::Settings,
+
+    /// Named sync accounts to use with `--profile`, next to the default one.
+    #[serde(default)]
+    pub profiles: HashMap<String, sync::Profile>,
+
+    #[serde(default)]
+    pub peer: sync::Peer,
+
+    /// The profile these settings were switched to with [`Self::with_profile`], if any.
+    #[serde(skip)]
+    pub profile: Option<String>,
+
     #[serde(default)]
     pub daemon: Daemon,
 
@@ -1095,6 +1134,9 @@ pub struct Settings {
     #[serde(default)]
     pub kv: kv::Settings,
 
+    #[serde(default)]
+    pub output: output::Settings,
+
     #[serde(default)]
     pub tmux: Tmux,
 
@@ -1162,6 +1204,21 @@ impl Settings {
         Self::meta_store().await?.host_id().await
     }
 
+    pub async fn team_host_id(team: &str) -> Result<HostId> {
+        Self::meta_store().await?.team_host_id(team).await
+    }
+
+    /// What this machine syncs with its own account: the `[sync]` settings, less the hosts of team
+    /// stores, which are synced on their own.
+    pub async fn sync_filter(&self) -> Result<sync::Settings> {
+        let team_hosts = Self::meta_store().await?.team_hosts().await?;
+
+        let mut filter = self.sync.clone();
+        filter.ignore_hosts.extend(team_hosts.into_keys());
+
+        Ok(filter)
+    }
+
     pub async fn last_sync() -> Result<OffsetDateTime> {
         Self::meta_store().await?.last_sync().await
     }
@@ -1178,8 +1235,77 @@ impl Settings {
         Self::meta_store().await?.save_version_check_time().await
     }
 
+    /// These settings, switched to sync with the named profile: its server, protocol and filters,
+    /// along with its session and key.
+    pub fn with_profile(&self, name: &str) -> Result<Self> {
+        let Some(profile) = self.profiles.get(name) else {
+            let mut names: Vec<_> = self.profiles.keys().map(String::as_str).collect();
+            names.sort_unstable();
+
+            return Err(eyre!(
+                "there is no sync profile called {name:?} (configured profiles: {})",
+                if names.is_empty() {
+                    "none".to_owned()
+                } else {
+                    names.join(", ")
+                }
+            ));
+        };
+
+        if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(eyre!(
+                "sync profile names may only contain letters, digits, '-' and '_': {name:?}"
+            ));
+        }
+
+        Ok(Self {
+            sync_address: profile.sync_address.clone(),
+            sync_protocol: profile.sync_protocol,
+            sync: profile.sync.clone(),
+            profile: Some(name.to_owned()),
+            ..self.clone()
+        })
+    }
+
+    /// The key for records on the sync server. That's [`Self::key_path`] for the default account,
+    /// while each profile has its own; the local store is always encrypted with `key_path`.
+    pub fn sync_key_path(&self) -> Result<PathBuf> {
+        let Some(name) = &self.profile else {
+            return Ok(self.key_path.clone());
+        };
+
+        match self.profiles.get(name).and_then(|p| p.key_path.as_ref()) {
+            Some(path) => {
+                let path = path.to_str().ok_or_else(|| eyre!("key path is not valid UTF-8"))?;
+                Ok(PathBuf::from(Self::expand_path(path)?))
+            }
+            None => Ok(Self::effective_data_dir().join("profiles").join(name).join("key")),
+        }
+    }
+
+    /// [`Self::with_profile`], or these settings as they are without a profile.
+    pub fn for_profile(&self, name: Option<&str>) -> Result<Cow<'_, Self>> {
+        match name {
+            Some(name) => Ok(Cow::Owned(self.with_profile(name)?)),
+            None => Ok(Cow::Borrowed(self)),
+        }
+    }
+
+    /// How to run the atuin `command` for the account these settings sync with, for hints.
+    pub fn profile_command(&self, command: &str) -> String {
+        match &self.profile {
+            Some(name) => format!("atuin {command} --profile {name}"),
+            None => format!("atuin {command}"),
+        }
+    }
+
+    /// The sessions of the account these settings sync with.
+    pub async fn sessions(&self) -> Result<crate::meta::Sessions<'static>> {
+        Ok(Self::meta_store().await?.profile_sessions(self.profile.as_deref()))
+    }
+
     pub async fn should_sync(&self) -> Result<bool> {
-        if !self.auto_sync || !Self::meta_store().await?.logged_in().await? {
+        if !self.auto_sync || !self.sessions().await?.logged_in().await? {
             return Ok(false);
         }
 
@@ -1197,18 +1323,18 @@ impl Settings {
     }
 
     pub async fn logged_in(&self) -> Result<bool> {
-        Self::meta_store().await?.logged_in().await
+        self.sessions().await?.logged_in().await
     }
 
     pub async fn session_token(&self) -> Result<String> {
-        match Self::meta_store().await?.session_token().await? {
+        match self.sessions().await?.session_token().await? {
             Some(token) => Ok(token),
             None => Err(eyre!("Tried to load session; not logged in")),
         }
     }
 
     pub async fn hub_session_token(&self) -> Result<String> {
-        match Self::meta_store().await?.hub_session_token().await? {
+        match self.sessions().await?.hub_session_token().await? {
             Some(token) => Ok(token),
             None => Err(eyre!("Tried to load hub session; not logged in")),
         }
@@ -1267,7 +1393,7 @@ impl Settings {
     /// (e.g. a CLI token incorrectly saved in the Hub session slot).
     #[cfg(feature = "sync")]
     pub async fn resolve_sync_auth(&self) -> SyncAuth {
-        let meta = match Self::meta_store().await {
+        let meta = match self.sessions().await {
             Ok(m) => m,
             Err(e) => {
                 return SyncAuth::NotLoggedIn {
@@ -1281,9 +1407,10 @@ impl Settings {
             return match meta.session_token().await {
                 Ok(Some(token)) => SyncAuth::Legacy { token },
                 _ => SyncAuth::NotLoggedIn {
-                    reason: "Not logged in. Run 'atuin login' to authenticate with your sync \
-                             server."
-                        .into(),
+                    reason: format!(
+                        "Not logged in. Run '{}' to authenticate with your sync server.",
+                        self.profile_command("login")
+                    ),
                 },
             };
         }
@@ -1313,8 +1440,10 @@ impl Settings {
         match meta.session_token().await {
             Ok(Some(token)) => SyncAuth::HubViaCli { token },
             _ => SyncAuth::NotLoggedIn {
-                reason: "Not logged in. Run 'atuin login' or 'atuin register' to authenticate."
-                    .into(),
+                reason: format!(
+                    "Not logged in. Run '{}' or 'atuin register' to authenticate.",
+                    self.profile_command("login")
+                ),
             },
         }
     }
@@ -1430,6 +1559,7 @@ impl Settings {
         let db_path = data_dir.join("history.db");
         let record_store_path = data_dir.join("records.db");
         let kv_path = data_dir.join("kv.db");
+        let output_path = data_dir.join("output.db");
         let scripts_path = data_dir.join("scripts.db");
         let ai_sessions_path = data_dir.join("ai_sessions.db");
         let pidfile_path = data_dir.join("atuin-daemon.pid");
@@ -1473,6 +1603,7 @@ impl Settings {
             .set_default("workspaces", false)?
             .set_default("ctrl_n_shortcuts", false)?
             .set_default("secrets_filter", true)?
+            .set_default("secrets_action", "drop")?
             .set_default("strip_trailing_whitespace", true)?
             .set_default("network_connect_timeout", 5)?
             .set_default("network_timeout", 30)?
@@ -1499,6 +1630,7 @@ impl Settings {
             .set_default("daemon.sync_frequency", 300)?
             .set_default("daemon.enabled", false)?
             .set_default("daemon.autostart", false)?
+            .set_default("daemon.search", false)?
             .set_default("daemon.socket_path", None::<String>)?
             .set_default("daemon.pidfile_path", pidfile_path.to_str())?
             .set_default("daemon.systemd_socket", false)?
@@ -1510,10 +1642,12 @@ impl Settings {
             .set_default("logs.daemon.file", "daemon.log")?
             .set_default("logs.ai.file", "ai.log")?
             .set_default("kv.db_path", kv_path.to_str())?
+            .set_default("output.db_path", output_path.to_str())?
             .set_default("scripts.db_path", scripts_path.to_str())?
             .set_default("search.recency_score_multiplier", 1.0)?
             .set_default("search.frequency_score_multiplier", 1.0)?
             .set_default("search.frecency_score_multiplier", 1.0)?
+            .set_default("search.predictions", 0)?
             .set_default("search.shells", "auto")?
             .set_default("meta.db_path", meta_path.to_str())?
             .set_default("ai.db_path", ai_sessions_path.to_str())?
@@ -1633,6 +1767,7 @@ impl Settings {
             "key_path",
             "daemon.socket_path",
             "daemon.pidfile_path",
+            "output.db_path",
             "logs.dir",
             "logs.search.file",
             "logs.daemon.file",
@@ -1951,6 +2086,7 @@ mod tests {
         let key_path: String = config.get("key_path")?;
         let record_store_path: String = config.get("record_store_path")?;
         let kv_db_path: String = config.get("kv.db_path")?;
+        let output_db_path: String = config.get("output.db_path")?;
         let scripts_db_path: String = config.get("scripts.db_path")?;
         let meta_db_path: String = config.get("meta.db_path")?;
         let daemon_socket_path: Option<String> = config.get("daemon.socket_path")?;
@@ -1961,6 +2097,7 @@ mod tests {
         assert_eq!(key_path, custom_dir.join("key").to_str().unwrap());
         assert_eq!(record_store_path, custom_dir.join("records.db").to_str().unwrap());
         assert_eq!(kv_db_path, custom_dir.join("kv.db").to_str().unwrap());
+        assert_eq!(output_db_path, custom_dir.join("output.db").to_str().unwrap());
         assert_eq!(scripts_db_path, custom_dir.join("scripts.db").to_str().unwrap());
         assert_eq!(meta_db_path, custom_dir.join("meta.db").to_str().unwrap());
         assert_eq!(daemon_socket_path, None);
@@ -1974,6 +2111,10 @@ mod tests {
     #[case::valid_config("search_mode = \"fuzzy\"\n")]
     #[case::empty_config("")]
     #[case::plain_data_dir("data_dir = \"/tmp/atuin-test\"\n")]
+    #[case::retention(
+        "[retention]\nmax_entries = 500000\n[[retention.rules]]\nkeep = \"30d\"\nfailed = true\n"
+    )]
+    #[case::secrets("secrets_action = \"redact\"\n[secrets.patterns]\nInternal = \"itk_\\\\w+\"\n")]
     fn validate_accepts(#[case] toml: &str) {
         assert!(Settings::validate_str(toml).is_ok());
     }
@@ -1983,6 +2124,9 @@ mod tests {
     #[case::invalid_enum_variant("search_mode = \"invalid\"\n", "search_mode")]
     #[case::value_of_the_wrong_type("auto_sync = \"banana\"\n", "auto_sync")]
     #[case::invalid_nested_value("[search]\nfilters = [\"nope\"]\n", "search.filters")]
+    #[case::invalid_retention_age("[[retention.rules]]\nkeep = \"a while\"\n", "retention.rules")]
+    #[case::zero_retention_interval("[retention]\ninterval = \"0s\"\n", "retention.interval")]
+    #[case::invalid_secret_pattern("[secrets.patterns]\nBroken = \"(\"\n", "secrets.patterns")]
     #[case::data_dir_with_an_unexpandable_variable(
         "data_dir = \"${DEFINITELY_UNSET_VAR_XYZ}/atuin\"\n",
         "data_dir"
@@ -1998,6 +2142,38 @@ mod tests {
         assert!(err.contains(expected_err), "error should mention `{expected_err}`, got: {err}");
     }
 
+    #[test]
+    fn with_profile_switches_the_sync_account() -> Result<()> {
+        let toml = r#"
+            sync_address = "https://api.atuin.sh"
+
+            [profiles.work]
+            sync_address = "https://atuin.example.com"
+            key_path = "/keys/work"
+            sync.tags = ["history"]
+        "#;
+        let settings: Settings =
+            Settings::builder_with_data_dir(&std::path::PathBuf::from("/data"))?
+                .add_source(ConfigFile::from_str(toml, FileFormat::Toml))
+                .build()?
+                .try_deserialize()?;
+
+        assert_eq!(settings.profile, None);
+        assert_eq!(settings.sync_key_path()?, settings.key_path);
+
+        let work = settings.with_profile("work")?;
+        assert_eq!(work.profile.as_deref(), Some("work"));
+        assert_eq!(work.sync_address.as_str(), "https://atuin.example.com/");
+        assert_eq!(work.sync.tags.len(), 1);
+        assert_eq!(work.sync_key_path()?, std::path::PathBuf::from("/keys/work"));
+        assert_eq!(work.key_path, settings.key_path);
+
+        let err = settings.with_profile("home").expect_err("there is no home profile");
+        assert!(err.to_string().contains("work"), "error should list the profiles, got: {err}");
+
+        Ok(())
+    }
+
     #[test]
     fn effective_data_dir_returns_default_when_not_set() {
         let effective = super::Settings::effective_data_dir();
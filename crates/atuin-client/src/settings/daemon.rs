@@ -20,6 +20,10 @@ pub struct Daemon {
     /// The daemon will handle sync on an interval. How often to sync, in seconds.
     pub sync_frequency: u64,
 
+    /// Answer non-interactive searches (`atuin search`, `atuin history list`, the AI history tool)
+    /// from the daemon's search index when it can, instead of the database.
+    pub search: bool,
+
     /// The path to the unix socket used by the daemon.
     /// When unset, [`Daemon::socket_path`] picks the default location.
     pub socket_path: Option<PathBuf>,
@@ -40,6 +44,7 @@ impl Default for Daemon {
             enabled: false,
             autostart: false,
             sync_frequency: 300,
+            search: false,
             socket_path: None,
             pidfile_path: "".to_string(),
             systemd_socket: false,
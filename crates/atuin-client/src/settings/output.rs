@@ -0,0 +1,46 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::retention::Age;
+
+/// The output of commands run through `atuin pty-proxy`, kept on disk, compressed, so it outlives
+/// the daemon. The oldest output goes first when it's over `max_size_mb`, and anything older than
+/// `max_age` goes regardless.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct Settings {
+    pub db_path: String,
+
+    /// How much compressed output to keep, in megabytes.
+    pub max_size_mb: u64,
+
+    /// How long to keep output for.
+    pub max_age: Age,
+
+    /// Also sync output, encrypted, to the account's other machines. Synced output records are
+    /// never removed, from the record store or the server, even once `max_size_mb` or `max_age`
+    /// delete the output here.
+    pub sync: bool,
+}
+
+impl Settings {
+    /// [`Self::max_size_mb`], in bytes.
+    pub fn max_size(&self) -> u64 {
+        self.max_size_mb.saturating_mul(1024 * 1024)
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        let dir = atuin_common::utils::data_dir();
+        let path = dir.join("output.db");
+
+        Self {
+            db_path: path.to_string_lossy().to_string(),
+            max_size_mb: 512,
+            max_age: Age(Duration::from_secs(30 * 24 * 60 * 60)),
+            sync: false,
+        }
+    }
+}
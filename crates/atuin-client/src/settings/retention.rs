@@ -0,0 +1,119 @@
+use std::fmt;
+use std::time::Duration;
+
+use regex::Regex;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// A length of time, written like `30d` or `1y 6months`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Age(pub Duration);
+
+impl TryFrom<String> for Age {
+    type Error = humantime::DurationError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        humantime::parse_duration(&value).map(Self)
+    }
+}
+
+impl From<Age> for String {
+    fn from(age: Age) -> Self {
+        age.to_string()
+    }
+}
+
+impl fmt::Display for Age {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        humantime::format_duration(self.0).fmt(f)
+    }
+}
+
+/// How long history is kept. The daemon deletes what these settings don't keep, with deletes that
+/// sync, so every machine ends up without it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    /// Keep at most this many entries, deleting the oldest first.
+    pub max_entries: Option<u64>,
+
+    /// How long the entries each rule matches are kept.
+    pub rules: Vec<Rule>,
+
+    /// How often the daemon applies these settings. Never zero.
+    #[serde(deserialize_with = "nonzero")]
+    pub interval: Age,
+}
+
+impl Settings {
+    /// Whether these settings would ever delete anything.
+    pub fn is_empty(&self) -> bool {
+        self.max_entries.is_none() && self.rules.is_empty()
+    }
+}
+
+fn nonzero<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Age, D::Error> {
+    let age = Age::deserialize(deserializer)?;
+    if age.0.is_zero() {
+        return Err(D::Error::custom("must be longer than zero"));
+    }
+    Ok(age)
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            max_entries: None,
+            rules: Vec::new(),
+            interval: Age(Duration::from_secs(60 * 60)),
+        }
+    }
+}
+
+/// Delete the entries matching every filter given, once they're older than `keep`. A rule without
+/// filters matches everything.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Rule {
+    /// How long matching entries are kept.
+    pub keep: Age,
+
+    /// Only entries whose command matches this.
+    #[serde(default, with = "serde_regex")]
+    pub command: Option<Regex>,
+
+    /// Only entries run in a directory matching this.
+    #[serde(default, with = "serde_regex")]
+    pub cwd: Option<Regex>,
+
+    /// Only entries from this host.
+    pub host: Option<String>,
+
+    /// Only entries that failed, with a non-zero exit code.
+    #[serde(default)]
+    pub failed: bool,
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut filters = Vec::new();
+        if self.failed {
+            filters.push("failed".to_string());
+        }
+        if let Some(command) = &self.command {
+            filters.push(format!("command ~ {command}"));
+        }
+        if let Some(cwd) = &self.cwd {
+            filters.push(format!("cwd ~ {cwd}"));
+        }
+        if let Some(host) = &self.host {
+            filters.push(format!("host = {host}"));
+        }
+
+        if filters.is_empty() {
+            write!(f, "everything, kept {}", self.keep)
+        } else {
+            write!(f, "{}, kept {}", filters.join(", "), self.keep)
+        }
+    }
+}
@@ -0,0 +1,53 @@
+use std::collections::BTreeMap;
+
+use clap::ValueEnum;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// What to do with a command that holds a secret, when `secrets_filter` is on.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    /// Don't save it at all.
+    #[default]
+    Drop,
+
+    /// Save it with each secret replaced by `[REDACTED]`.
+    Redact,
+
+    /// Save it as it is, but only on this machine: it's never synced.
+    Local,
+}
+
+/// A user-defined secret pattern.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Pattern(#[serde(with = "serde_regex")] pub Regex);
+
+/// How secrets are recognised, next to the built-in patterns.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Settings {
+    /// More patterns to treat as secrets, by name.
+    pub patterns: BTreeMap<String, Pattern>,
+
+    /// Treat long, random-looking tokens as secrets too, going by their Shannon entropy.
+    pub entropy: bool,
+
+    /// The entropy, in bits per character, a token needs to be treated as a secret.
+    pub entropy_threshold: f64,
+
+    /// How long a token needs to be to be considered.
+    pub entropy_min_length: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            patterns: BTreeMap::new(),
+            entropy: false,
+            // Above any hex string, so commit hashes and UUIDs aren't caught.
+            entropy_threshold: 4.0,
+            entropy_min_length: 20,
+        }
+    }
+}
@@ -0,0 +1,165 @@
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+
+use atuin_domain::record::{HostId, RecordSeriesKey, RecordTag};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::SyncProtocol;
+
+/// Which record series this machine syncs. Each allow list syncs everything when it's empty, and
+/// the `ignore_*` lists win over them.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct Settings {
+    /// Only sync these record tags, e.g. `history` or `dotfiles-var`.
+    pub tags: Vec<RecordTag>,
+
+    /// Never sync these record tags.
+    pub ignore_tags: Vec<RecordTag>,
+
+    /// Only sync records from these hosts, by host id.
+    pub hosts: Vec<HostId>,
+
+    /// Never sync records from these hosts.
+    pub ignore_hosts: Vec<HostId>,
+}
+
+impl Settings {
+    /// Whether records in `series` are synced, in either direction.
+    pub fn syncs(&self, series: &RecordSeriesKey) -> bool {
+        self.skip_reason(series).is_none()
+    }
+
+    /// Why records in `series` aren't synced, naming the setting that leaves them out, or `None`
+    /// if they are.
+    pub fn skip_reason(&self, series: &RecordSeriesKey) -> Option<&'static str> {
+        // Packfiles carry history, so they go wherever history goes.
+        let tag = match &series.tag {
+            RecordTag::Packfile => &RecordTag::History,
+            tag => tag,
+        };
+
+        if self.ignore_tags.contains(tag) {
+            Some("its tag is in `ignore_tags`")
+        } else if !self.tags.is_empty() && !self.tags.contains(tag) {
+            Some("its tag isn't in `tags`")
+        } else if self.ignore_hosts.contains(&series.host_id) {
+            Some("its host is in `ignore_hosts`")
+        } else if !self.hosts.is_empty() && !self.hosts.contains(&series.host_id) {
+            Some("its host isn't in `hosts`")
+        } else {
+            None
+        }
+    }
+}
+
+/// A named sync account, e.g. a company server next to the hosted one. Each profile has its own
+/// session and key, and syncs the same local store.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Profile {
+    pub sync_address: Url,
+
+    #[serde(default)]
+    pub sync_protocol: SyncProtocol,
+
+    /// The key records are encrypted with on this profile's server. Defaults to
+    /// `profiles/<name>/key` in the data dir.
+    pub key_path: Option<PathBuf>,
+
+    /// Which record series this profile syncs.
+    #[serde(default)]
+    pub sync: Settings,
+}
+
+/// Syncing directly with another of this account's machines, with `atuin sync peer`, rather than
+/// through a server.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Peer {
+    /// Where the daemon listens for peers. Only this machine can reach the default, so other
+    /// machines come in through an SSH tunnel.
+    pub listen: SocketAddr,
+
+    /// The secret peers share. Both machines need the same one, as well as the same key. Nothing
+    /// listens for peers when unset.
+    pub secret: Option<String>,
+}
+
+impl Default for Peer {
+    fn default() -> Self {
+        Self {
+            listen: SocketAddr::from((Ipv4Addr::LOCALHOST, 8890)),
+            secret: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use atuin_common::utils::uuid_v7;
+    use rstest::rstest;
+
+    use super::*;
+
+    #[rstest]
+    #[case::everything(Settings::default(), RecordTag::History, true)]
+    #[case::allowed_tag(tags(&["history"], &[]), RecordTag::History, true)]
+    #[case::other_tag(tags(&["history"], &[]), RecordTag::Kv, false)]
+    #[case::ignored_tag(tags(&[], &["history"]), RecordTag::History, false)]
+    #[case::ignore_wins(tags(&["history"], &["history"]), RecordTag::History, false)]
+    #[case::packfile_follows_history(tags(&[], &["history"]), RecordTag::Packfile, false)]
+    #[case::packfile_allowed(tags(&["history"], &[]), RecordTag::Packfile, true)]
+    fn filters_tags(#[case] settings: Settings, #[case] tag: RecordTag, #[case] expected: bool) {
+        let series = RecordSeriesKey::new(HostId(uuid_v7()), tag);
+
+        assert_eq!(settings.syncs(&series), expected);
+    }
+
+    #[test]
+    fn filters_hosts() {
+        let (mine, theirs) = (HostId(uuid_v7()), HostId(uuid_v7()));
+        let history = |host| RecordSeriesKey::new(host, RecordTag::History);
+
+        let allow = Settings {
+            hosts: vec![mine],
+            ..Settings::default()
+        };
+        assert!(allow.syncs(&history(mine)));
+        assert!(!allow.syncs(&history(theirs)));
+
+        let ignore = Settings {
+            ignore_hosts: vec![theirs],
+            ..Settings::default()
+        };
+        assert!(ignore.syncs(&history(mine)));
+        assert!(!ignore.syncs(&history(theirs)));
+    }
+
+    #[test]
+    fn names_what_skips_a_series() {
+        let host = HostId(uuid_v7());
+        let settings = Settings {
+            ignore_tags: vec![RecordTag::Kv],
+            hosts: vec![host],
+            ..tags(&["history", "kv"], &[])
+        };
+
+        let reason = |host, tag| settings.skip_reason(&RecordSeriesKey::new(host, tag));
+        assert_eq!(reason(host, RecordTag::History), None);
+        assert_eq!(reason(host, RecordTag::Kv), Some("its tag is in `ignore_tags`"));
+        assert_eq!(reason(host, RecordTag::from("script")), Some("its tag isn't in `tags`"));
+        assert_eq!(
+            reason(HostId(uuid_v7()), RecordTag::History),
+            Some("its host isn't in `hosts`")
+        );
+    }
+
+    fn tags(tags: &[&str], ignore_tags: &[&str]) -> Settings {
+        Settings {
+            tags: tags.iter().map(|t| RecordTag::from(*t)).collect(),
+            ignore_tags: ignore_tags.iter().map(|t| RecordTag::from(*t)).collect(),
+            ..Settings::default()
+        }
+    }
+}
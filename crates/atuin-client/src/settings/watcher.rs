@@ -1,7 +1,8 @@
 //! Config file watching for automatic settings reload.
 //!
-//! This module provides a `SettingsWatcher` that monitors the config file
-//! for changes and broadcasts updated settings via a `tokio::sync::watch` channel.
+//! This module provides a `SettingsWatcher` that monitors the config file and
+//! theme files for changes and broadcasts updated settings via a
+//! `tokio::sync::watch` channel, along with any error that kept them from loading.
 //!
 //! # Example
 //!
@@ -32,6 +33,7 @@ use tokio::sync::watch;
 use tracing::{debug, error, info, warn};
 
 use super::Settings;
+use crate::theme::ThemeManager;
 
 /// Global singleton for the settings watcher.
 static SETTINGS_WATCHER: OnceLock<Result<SettingsWatcher, String>> = OnceLock::new();
@@ -56,6 +58,8 @@ pub fn global_settings_watcher() -> Result<&'static SettingsWatcher> {
 pub struct SettingsWatcher {
     /// Receiver for settings updates. Clone this to subscribe.
     rx: watch::Receiver<Arc<Settings>>,
+    /// Receiver for what went wrong with the config, if anything.
+    errors_rx: watch::Receiver<Option<String>>,
     /// Keeps the file watcher alive for the lifetime of this struct.
     _watcher: RecommendedWatcher,
 }
@@ -66,16 +70,18 @@ impl SettingsWatcher {
     /// Loads initial settings and starts watching the config file for changes.
     /// Changes are debounced (500ms) to avoid multiple reloads during saves.
     pub fn new() -> Result<Self> {
-        let initial_settings = Arc::new(Settings::new()?);
-        let (tx, rx) = watch::channel(initial_settings);
+        let initial_settings = Settings::new()?;
+        let (errors_tx, errors_rx) = watch::channel(check_theme(&initial_settings).err());
+        let (tx, rx) = watch::channel(Arc::new(initial_settings));
 
         let config_path = Self::config_path();
         info!("starting config file watcher: {:?}", config_path);
 
-        let watcher = Self::create_watcher(tx, &config_path)?;
+        let watcher = Self::create_watcher(tx, errors_tx, &config_path)?;
 
         Ok(Self {
             rx,
+            errors_rx,
             _watcher: watcher,
         })
     }
@@ -94,6 +100,15 @@ impl SettingsWatcher {
         self.rx.borrow().clone()
     }
 
+    /// Subscribe to config errors.
+    ///
+    /// The value is why the config last failed to load, in which case the
+    /// settings from before stay current, or why the configured theme doesn't
+    /// load. It goes back to `None` once a reload succeeds cleanly.
+    pub fn subscribe_errors(&self) -> watch::Receiver<Option<String>> {
+        self.errors_rx.clone()
+    }
+
     /// Get the config file path.
     fn config_path() -> PathBuf {
         let config_dir = if let Ok(p) = std::env::var("ATUIN_CONFIG_DIR") {
@@ -107,6 +122,7 @@ impl SettingsWatcher {
     /// Create the file watcher with debouncing.
     fn create_watcher(
         tx: watch::Sender<Arc<Settings>>,
+        errors_tx: watch::Sender<Option<String>>,
         config_path: &Path,
     ) -> Result<RecommendedWatcher> {
         // Channel for debouncing file events
@@ -115,9 +131,18 @@ impl SettingsWatcher {
         // Spawn debounce thread
         let config_path_clone = config_path.to_path_buf();
         std::thread::spawn(move || {
-            Self::debounce_loop(&debounce_rx, &tx, &config_path_clone);
+            Self::debounce_loop(&debounce_rx, &tx, &errors_tx, &config_path_clone);
         });
 
+        // Theme files are only read when a theme is loaded, but a change to
+        // the configured one is a change to the config too. Only a directory
+        // that exists now is watched.
+        let theme_dir = ThemeManager::new(None, None)
+            .theme_dir()
+            .and_then(|dir| dir.canonicalize().ok())
+            .filter(|dir| dir.is_dir());
+        let theme_dir_for_watcher = theme_dir.clone();
+
         // Clone config_path for use in the watcher callback
         let config_path_for_watcher = config_path.to_path_buf();
 
@@ -142,8 +167,8 @@ impl SettingsWatcher {
                             return;
                         }
 
-                        // Only react to events for our specific config file
-                        // (filter out editor temp files, backups, etc.)
+                        // Only react to events for our specific config file, or for
+                        // theme files (filter out editor temp files, backups, etc.)
                         let is_config_file = event.paths.iter().any(|path| {
                             // Canonicalize for reliable comparison (handles macOS symlinks)
                             let canonical_event_path =
@@ -153,6 +178,7 @@ impl SettingsWatcher {
                             // (either exact match or the file was renamed to our config)
                             canonical_event_path == canonical_config_path
                                 || path.file_name() == config_path_for_watcher.file_name()
+                                || is_theme_file(path, theme_dir_for_watcher.as_deref())
                         });
 
                         if !is_config_file {
@@ -193,6 +219,13 @@ impl SettingsWatcher {
             .watch(watch_path, RecursiveMode::NonRecursive)
             .wrap_err_with(|| format!("failed to watch config directory: {watch_path:?}"))?;
 
+        if let Some(theme_dir) = &theme_dir
+            && let Err(e) = watcher.watch(theme_dir, RecursiveMode::NonRecursive)
+        {
+            // The config file is still watched; only theme edits go unnoticed.
+            warn!("failed to watch theme directory {:?}: {}", theme_dir, e);
+        }
+
         info!("config file watcher initialized for: {:?}", watch_path);
         Ok(watcher)
     }
@@ -201,6 +234,7 @@ impl SettingsWatcher {
     fn debounce_loop(
         rx: &std::sync::mpsc::Receiver<()>,
         tx: &watch::Sender<Arc<Settings>>,
+        errors_tx: &watch::Sender<Option<String>>,
         config_path: &Path,
     ) {
         const DEBOUNCE_DURATION: Duration = Duration::from_millis(500);
@@ -229,18 +263,45 @@ impl SettingsWatcher {
             info!("config file changed, reloading settings: {:?}", config_path);
             match Settings::new() {
                 Ok(settings) => {
+                    let theme_error = check_theme(&settings).err();
+                    if let Some(e) = &theme_error {
+                        warn!("{}", e);
+                    }
+
                     if tx.send(Arc::new(settings)).is_err() {
                         // All receivers dropped
                         debug!("all settings subscribers dropped, exiting");
                         return;
                     }
+                    errors_tx.send_replace(theme_error);
                     info!("settings reloaded successfully");
                 }
                 Err(e) => {
                     warn!("failed to reload settings: {}", e);
-                    // Keep the old settings, don't broadcast the error
+                    // Keep the old settings, but let subscribers know why
+                    errors_tx.send_replace(Some(format!("{e:#}")));
                 }
             }
         }
     }
 }
+
+/// Check that the configured theme loads, rather than falling back to no theme.
+pub fn check_theme(settings: &Settings) -> Result<(), String> {
+    ThemeManager::new(settings.theme.debug, None)
+        .check_theme(&settings.theme.name, settings.theme.max_depth)
+        .map_err(|e| format!("could not load theme {:?}: {e}", settings.theme.name))
+}
+
+/// Whether `path` is a theme file directly in `theme_dir` (already canonicalized).
+fn is_theme_file(path: &Path, theme_dir: Option<&Path>) -> bool {
+    let Some(theme_dir) = theme_dir else {
+        return false;
+    };
+
+    path.extension().is_some_and(|ext| ext == "toml")
+        && path
+            .parent()
+            .and_then(|parent| parent.canonicalize().ok())
+            .is_some_and(|parent| parent == theme_dir)
+}
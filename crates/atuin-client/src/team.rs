@@ -0,0 +1,178 @@
+//! Teams share a store on a self-hosted sync server, for kv entries, scripts and dotfiles.
+//!
+//! Each team has its own key, which the server never sees: it's sealed to each member's public
+//! key, which is derived from the key their account syncs with (see
+//! [`paseto_v4::Key::identity`]). Records written for a team go under a team host id in the local
+//! store (see [`MetaStore::team_host_id`]), encrypted with the local key like any other record, and
+//! are re-wrapped with the team key as they sync with the team's store.
+//!
+//! [`MetaStore::team_host_id`]: crate::meta::MetaStore::team_host_id
+
+use atuin_common::encryption::paseto_v4;
+use atuin_domain::caps::TeamsCap;
+use atuin_domain::record::RecordId;
+use eyre::{Context, Result, eyre};
+
+use crate::api_client::Client;
+use crate::record::sqlite_store::SqliteStore;
+use crate::record::sync::{ClientSource, SyncEngine};
+use crate::settings::Settings;
+
+/// A team the account is in.
+pub struct Membership {
+    pub team: String,
+    pub admin: bool,
+    pub key: paseto_v4::Key,
+}
+
+/// Whether the server `client` talks to has team stores.
+pub async fn supported(client: &Client) -> bool {
+    matches!(client.caps().get_server::<TeamsCap>().await, Ok(Some(_)))
+}
+
+/// Publish the public key of `account_key`, the key the account syncs with, so that team admins
+/// can add the account to their teams.
+pub async fn publish_key(client: &Client, account_key: &paseto_v4::Key) -> Result<()> {
+    client.put_public_key(&account_key.identity().public_key()).await
+}
+
+/// The teams the account is in, with their keys unsealed.
+pub async fn memberships(client: &Client, account_key: &paseto_v4::Key) -> Result<Vec<Membership>> {
+    let identity = account_key.identity();
+
+    client
+        .teams()
+        .await?
+        .members
+        .into_iter()
+        .map(|member| {
+            let sealed = member
+                .sealed_key
+                .ok_or_else(|| eyre!("the server didn't send the key of team {}", member.team))?;
+            let key = identity
+                .unseal(&sealed)
+                .wrap_err_with(|| format!("could not unseal the key of team {}", member.team))?;
+
+            Ok(Membership {
+                team: member.team,
+                admin: member.admin,
+                key,
+            })
+        })
+        .collect()
+}
+
+/// The account's membership of `team`.
+pub async fn membership(
+    client: &Client,
+    account_key: &paseto_v4::Key,
+    team: &str,
+) -> Result<Membership> {
+    memberships(client, account_key)
+        .await?
+        .into_iter()
+        .find(|m| m.team == team)
+        .ok_or_else(|| eyre!("you aren't in a team called {team}"))
+}
+
+/// Create a team with a new key, with the account as its admin.
+pub async fn create(client: &Client, account_key: &paseto_v4::Key, team: &str) -> Result<()> {
+    let identity = account_key.identity();
+    let sealed = paseto_v4::Key::generate().seal(&identity.public_key())?;
+
+    // make sure the account can be added to other teams, too
+    client.put_public_key(&identity.public_key()).await?;
+    client.create_team(team, &sealed).await
+}
+
+/// `username`'s public key, as the server gives it. They have to have synced with the server
+/// first, so that it has it.
+///
+/// A malicious server could hand out a key of its own, so check its [`paseto_v4::fingerprint`]
+/// with them before sealing anything to it.
+pub async fn member_key(client: &Client, username: &str) -> Result<String> {
+    client.public_key(username).await
+}
+
+/// Add `username` to the team, sealing its key to `public_key`, which has been checked to be
+/// theirs (see [`member_key`]).
+pub async fn add_member(
+    client: &Client,
+    account_key: &paseto_v4::Key,
+    team: &str,
+    username: &str,
+    public_key: &str,
+    admin: bool,
+) -> Result<()> {
+    let membership = membership(client, account_key, team).await?;
+    let sealed = membership.key.seal(public_key)?;
+
+    client.add_team_member(team, username, &sealed, admin).await
+}
+
+/// Sync the store of every team the account is in with the local store, which is encrypted with
+/// `key`. Returns how many records were uploaded, and the ids of those downloaded.
+pub async fn sync(
+    settings: &Settings,
+    client: &Client,
+    store: &SqliteStore,
+    key: &paseto_v4::Key,
+    account_key: &paseto_v4::Key,
+) -> Result<(u64, Vec<RecordId>)> {
+    let meta = Settings::meta_store().await?;
+    let my_host = Settings::host_id().await?;
+
+    let mut uploaded = 0;
+    let mut downloaded = Vec::new();
+
+    for membership in memberships(client, account_key).await? {
+        let team = membership.team.as_str();
+        let team_client = client.for_team(team)?;
+
+        // Register the hosts in the team's store, so they aren't synced with the account's own.
+        // Hosts the local store already has under the account are left out: a team member could
+        // otherwise claim one of this account's hosts, and have its records synced to the team.
+        let known = meta.team_hosts().await?;
+        let local = store.status().await?;
+        let remote = team_client.record_status().await?;
+        let new_hosts = remote.hosts.keys().copied().filter(|host| {
+            let claimed =
+                *host == my_host || (local.hosts.contains_key(host) && !known.contains_key(host));
+            if claimed {
+                warn!(%host, team, "not syncing a team host that belongs to this account");
+            }
+            !claimed && !known.contains_key(host)
+        });
+        meta.add_team_hosts(team, new_hosts).await?;
+        meta.team_host_id(team).await?;
+
+        let mut filter = settings.sync.clone();
+        filter.hosts = meta
+            .team_hosts()
+            .await?
+            .into_iter()
+            .filter_map(|(host, t)| (t == team).then_some(host))
+            .collect();
+
+        let engine = SyncEngine::builder()
+            .store(store.clone())
+            .client_source(ClientSource::FromClient(team_client))
+            .build()
+            .connect()
+            .await?
+            .with_filter(filter);
+
+        let (up, mut down) = engine
+            .keyed_with_remote(key, &membership.key)
+            .sync()
+            .await
+            .wrap_err_with(|| format!("could not sync team {team}"))?;
+
+        debug!(team, uploaded = up, downloaded = down.len(), "synced team");
+
+        uploaded += up;
+        downloaded.append(&mut down);
+    }
+
+    Ok((uploaded, downloaded))
+}
@@ -357,32 +357,34 @@ impl ThemeManager {
         }
     }
 
-    // Try to load a theme from a `{name}.toml` file in the theme directory. If an override is set
-    // for the theme dir (via ATUIN_THEME_DIR env) we should load the theme from there
+    // The directory theme files are loaded from: the override for the theme dir (via
+    // ATUIN_THEME_DIR env) if there is one, or `themes` in the config dir. `None` for an empty
+    // override, which means no theme files at all
+    pub fn theme_dir(&self) -> Option<PathBuf> {
+        if let Some(p) = &self.override_theme_dir {
+            return (!p.is_empty()).then(|| PathBuf::from(p));
+        }
+
+        let mut theme_dir = if let Ok(p) = std::env::var("ATUIN_CONFIG_DIR") {
+            PathBuf::from(p)
+        } else {
+            atuin_common::utils::config_dir()
+        };
+        theme_dir.push("themes");
+        Some(theme_dir)
+    }
+
+    // Try to load a theme from a `{name}.toml` file in the theme directory
     pub fn load_theme_from_file(
         &mut self,
         name: &str,
         max_depth: u8,
     ) -> Result<&Theme, Box<dyn error::Error>> {
-        let mut theme_file = if let Some(p) = &self.override_theme_dir {
-            if p.is_empty() {
-                return Err(Box::new(Error::new(
-                    ErrorKind::NotFound,
-                    "Empty theme directory override and could not find theme elsewhere",
-                )));
-            }
-            PathBuf::from(p)
-        } else {
-            let config_dir = atuin_common::utils::config_dir();
-            let mut theme_file = if let Ok(p) = std::env::var("ATUIN_CONFIG_DIR") {
-                PathBuf::from(p)
-            } else {
-                let mut theme_file = PathBuf::new();
-                theme_file.push(config_dir);
-                theme_file
-            };
-            theme_file.push("themes");
-            theme_file
+        let Some(mut theme_file) = self.theme_dir() else {
+            return Err(Box::new(Error::new(
+                ErrorKind::NotFound,
+                "Empty theme directory override and could not find theme elsewhere",
+            )));
         };
 
         let theme_toml = format!["{name}.toml"];
@@ -449,6 +451,19 @@ impl ThemeManager {
         Ok(theme)
     }
 
+    // Check that the requested theme is a builtin or loads from file, where `load_theme` would
+    // quietly fall back to no theme at all
+    pub fn check_theme(
+        &mut self,
+        name: &str,
+        max_depth: Option<u8>,
+    ) -> Result<(), Box<dyn error::Error>> {
+        if self.loaded_themes.contains_key(name) || BUILTIN_THEMES.contains_key(name) {
+            return Ok(());
+        }
+        self.load_theme_from_file(name, max_depth.unwrap_or(DEFAULT_MAX_DEPTH)).map(|_| ())
+    }
+
     // Check if the requested theme is loaded and, if not, then attempt to get it
     // from the builtins or, if not there, from file
     pub fn load_theme(&mut self, name: &str, max_depth: Option<u8>) -> &Theme {
@@ -491,6 +506,13 @@ mod theme_tests {
         assert_eq!(theme.as_style(Meaning::Guidance).foreground_color, from_string("brown").ok());
     }
 
+    #[rstest]
+    fn test_check_theme_reports_themes_that_would_not_load(mut manager: ThemeManager) {
+        assert!(manager.check_theme("autumn", None).is_ok());
+        // `load_theme` would silently fall back to "(none)" for this one.
+        assert!(manager.check_theme("not-a-theme", None).is_err());
+    }
+
     #[rstest]
     fn test_can_create_theme(mut manager: ThemeManager) {
         let mytheme = Theme::new(
@@ -5,11 +5,15 @@ use std::array::TryFromSliceError;
 use std::fs;
 use std::io::{Read, Write};
 use std::path::Path;
+use std::str::FromStr;
 
 use base64::Engine;
 use base64::engine::general_purpose::{
     STANDARD as B64_STANDARD, URL_SAFE_NO_PAD as B64_URL_SAFE_NO_PAD,
 };
+use blake2::digest::consts::U32;
+use blake2::digest::{Digest, Mac};
+use blake2::{Blake2b, Blake2bMac};
 use crypto_secretbox::{KeyInit, XSalsa20Poly1305, aead};
 use rusty_paseto::{Paseto, core as rusty_paseto};
 use serde::{Deserialize, Serialize};
@@ -140,6 +144,26 @@ impl Key {
         p_self.wrap_pie(&p_wrapping)
     }
 
+    /// The [`Identity`] of the account this key belongs to.
+    pub fn identity(&self) -> Identity {
+        let mut mac =
+            <Blake2bMac<U32> as Mac>::new_from_slice(&self.0).expect("BLAKE2b takes 32 byte keys");
+        mac.update(b"atuin-identity");
+        let mut seed: [u8; 32] = mac.finalize().into_bytes().into();
+
+        let identity = Identity(rusty_paserk::Key::from_secret_key(seed));
+        seed.zeroize();
+        identity
+    }
+
+    /// Seal this key to the holder of `public_key`, which [`Identity::public_key`] gave, as a
+    /// `k4.seal.` PASERK only they can unseal.
+    pub fn seal(&self, public_key: &str) -> Result<String, SealingError> {
+        let key: rusty_paserk::Key<rusty_paserk::V4, rusty_paserk::Local> = self.into();
+
+        Ok(key.seal(&parse_public_key(public_key)?).to_string())
+    }
+
     /// Generate a new key with the XSalsa20Poly1305 algorithm.
     pub fn generate() -> Self {
         <[u8; 32]>::from(XSalsa20Poly1305::generate_key(&mut aead::OsRng)).into()
@@ -441,6 +465,69 @@ pub struct EncryptedData {
     pub cek: String,
 }
 
+/// An account's key pair for sharing keys with it: anyone can [`Key::seal`] a key to the public
+/// half, and only the account can unseal it. It's derived from the account's [`Key`], so every
+/// machine with that key has the same identity.
+pub struct Identity(rusty_paserk::Key<rusty_paserk::V4, rusty_paserk::Secret>);
+
+impl Identity {
+    /// The public half, as a `k4.public.` PASERK.
+    pub fn public_key(&self) -> String {
+        rusty_paserk::PlaintextKey(self.0.public_key()).to_string()
+    }
+
+    /// The [`fingerprint`] of the public half, for others to check before sealing keys to it.
+    pub fn fingerprint(&self) -> String {
+        fingerprint(&self.public_key()).expect("an identity's own public key is valid")
+    }
+
+    /// Unseal a key [`Key::seal`] sealed to this identity.
+    pub fn unseal(&self, sealed: &str) -> Result<Key, SealingError> {
+        let sealed = rusty_paserk::SealedKey::<rusty_paserk::V4>::from_str(sealed)
+            .map_err(|_| SealingError::Sealed)?;
+
+        Ok(sealed.unseal(&self.0).map_err(SealingError::Unseal)?.into())
+    }
+}
+
+/// Check `public_key` is a `k4.public.` PASERK that keys can be sealed to.
+pub fn validate_public_key(public_key: &str) -> Result<(), SealingError> {
+    parse_public_key(public_key).map(|_| ())
+}
+
+/// A short fingerprint of `public_key`, for people to compare out of band: the server hands out
+/// public keys, so only that shows a key really is the person's own.
+pub fn fingerprint(public_key: &str) -> Result<String, SealingError> {
+    let key = rusty_paserk::PlaintextKey(parse_public_key(public_key)?).to_string();
+    let digest = Blake2b::<U32>::digest(key.as_bytes());
+
+    Ok(digest[..16]
+        .chunks(2)
+        .map(|pair| format!("{:02x}{:02x}", pair[0], pair[1]))
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+fn parse_public_key(
+    public_key: &str,
+) -> Result<rusty_paserk::Key<rusty_paserk::V4, rusty_paserk::Public>, SealingError> {
+    // Parsing the PASERK doesn't check the bytes are a point on the curve, and sealing to one
+    // that isn't panics, so go through `from_public_key` too.
+    rusty_paserk::PlaintextKey::<rusty_paserk::V4, rusty_paserk::Public>::from_str(public_key)
+        .and_then(|key| rusty_paserk::Key::from_public_key(key.0.as_ref()))
+        .map_err(|_| SealingError::PublicKey)
+}
+
+#[derive(Debug, Error)]
+pub enum SealingError {
+    #[error("not a valid k4.public key")]
+    PublicKey,
+    #[error("not a valid k4.seal key")]
+    Sealed,
+    #[error("could not unseal the key, which was sealed to someone else: {_0}")]
+    Unseal(rusty_paserk::PasetoError),
+}
+
 #[derive(Debug, Error)]
 pub enum EncryptionError {
     #[error("unexpected paseto error creating new CEK: {_0}")]
@@ -736,4 +823,26 @@ mod test {
 
         let _ = fs::remove_dir_all(&dir);
     }
+
+    #[rstest]
+    fn sealed_keys_unseal_only_for_their_identity(key: Key) {
+        let team = Key::generate();
+        let sealed = team.seal(&key.identity().public_key()).unwrap();
+
+        assert!(sealed.starts_with("k4.seal."));
+        assert!(key.identity().unseal(&sealed).unwrap() == team);
+        assert!(Key::generate().identity().unseal(&sealed).is_err());
+        assert!(matches!(team.seal("k4.public.nope"), Err(SealingError::PublicKey)));
+    }
+
+    #[rstest]
+    fn fingerprints_identify_public_keys(key: Key) {
+        let identity = key.identity();
+        let fingerprint = identity.fingerprint();
+
+        assert_eq!(fingerprint.len(), 39);
+        assert_eq!(fingerprint, super::fingerprint(&identity.public_key()).unwrap());
+        assert_ne!(fingerprint, Key::generate().identity().fingerprint());
+        assert!(matches!(super::fingerprint("k4.public.nope"), Err(SealingError::PublicKey)));
+    }
 }
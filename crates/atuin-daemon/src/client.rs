@@ -1,11 +1,14 @@
 #[cfg(unix)]
 use std::path::PathBuf;
 
-use atuin_client::database::Context;
-use atuin_client::history::History;
+use atuin_client::database::{Context, DbSearchMode, OptFilters, Sqlite};
+use atuin_client::history::{
+    AUTHOR_FILTER_ALL_AGENT, AUTHOR_FILTER_ALL_USER, AuthorPattern, History,
+};
 use atuin_client::settings::{FilterMode, Settings};
 use atuin_common::filter::{self, OrFilter};
 use eyre::{Context as EyreContext, Result};
+use futures::{Stream, TryStreamExt};
 use hyper_util::rt::TokioIo;
 #[cfg(windows)]
 use tokio::net::TcpStream;
@@ -18,7 +21,8 @@ use tracing::{Level, instrument, span};
 
 use crate::control::control_client::ControlClient as ControlServiceClient;
 use crate::control::{
-    ForceSyncEvent, HistoryDeletedEvent, HistoryPrunedEvent, HistoryRebuiltEvent, SendEventRequest,
+    ConfigStatusRequest, ConfigStatusResponse, ForceSyncEvent, HistoryAmendedEvent,
+    HistoryDeletedEvent, HistoryPrunedEvent, HistoryRebuiltEvent, SendEventRequest,
     SettingsReloadedEvent, ShutdownEvent,
 };
 use crate::events::DaemonEvent;
@@ -30,8 +34,9 @@ use crate::history::{
 };
 use crate::search::search_client::SearchClient as SearchServiceClient;
 use crate::search::{
-    FilterMode as RpcFilterMode, PrepareIndexRequest, SearchContext as RpcSearchContext,
-    SearchRequest, SearchResponse,
+    FilterMode as RpcFilterMode, PredictRequest, Prediction, PrepareIndexRequest, QueryOptions,
+    QueryRequest, SearchContext as RpcSearchContext, SearchMode as RpcSearchMode, SearchRequest,
+    SearchResponse,
 };
 use crate::semantic::semantic_client::SemanticClient as SemanticServiceClient;
 use crate::semantic::{
@@ -245,6 +250,16 @@ impl SearchClient {
         Ok(SearchClient { client })
     }
 
+    #[cfg(unix)]
+    pub async fn from_settings(settings: &Settings) -> Result<Self> {
+        Self::new(settings.daemon.existing_socket_path().into_owned()).await
+    }
+
+    #[cfg(not(unix))]
+    pub async fn from_settings(settings: &Settings) -> Result<Self> {
+        Self::new(settings.daemon.tcp_port).await
+    }
+
     #[instrument(
         skip_all,
         level = Level::TRACE,
@@ -276,6 +291,96 @@ impl SearchClient {
         self.client.prepare_index(request).await?;
         Ok(())
     }
+
+    /// Ask the daemon which commands are most likely to be run next in `context`.
+    pub async fn predict(&mut self, context: Context, limit: u32) -> Result<Vec<Prediction>> {
+        let request = PredictRequest {
+            context: Some(RpcSearchContext::from(context)),
+            limit,
+        };
+        Ok(self.client.predict(request).await?.into_inner().predictions)
+    }
+
+    /// Run a non-interactive search against the daemon's index.
+    ///
+    /// The results are those `Sqlite::search` would find, in timestamp order, before any
+    /// reordering; `filter_options.ranker` is not sent, and `filter_options.ids` is not supported.
+    /// They stream in as the daemon sends them, so a caller that has enough can stop reading.
+    #[instrument(skip_all, level = Level::TRACE, name = "daemon_client_query", fields(query = %query))]
+    pub async fn query(
+        &mut self,
+        search_mode: DbSearchMode,
+        filter_mode: FilterMode,
+        context: Context,
+        query: &str,
+        filter_options: &OptFilters<'_>,
+    ) -> Result<impl Stream<Item = Result<History>> + use<>> {
+        if filter_options.ids.is_some() {
+            eyre::bail!("searching specific history ids is not supported by the daemon");
+        }
+
+        let request = QueryRequest {
+            search_mode: RpcSearchMode::from(search_mode).into(),
+            filter_mode: RpcFilterMode::from(filter_mode).into(),
+            context: Some(RpcSearchContext::from(context)),
+            query: query.to_owned(),
+            options: Some(QueryOptions::from(filter_options)),
+        };
+        let stream = self.client.query(request).await?.into_inner();
+
+        Ok(stream
+            .map_ok(|response| {
+                futures::stream::iter(
+                    response.history.into_iter().map(|h| Ok::<_, tonic::Status>(History::from(h))),
+                )
+            })
+            .try_flatten()
+            .map_err(eyre::Report::from))
+    }
+}
+
+impl From<DbSearchMode> for RpcSearchMode {
+    fn from(search_mode: DbSearchMode) -> Self {
+        match search_mode {
+            DbSearchMode::Prefix => Self::Prefix,
+            DbSearchMode::FullText => Self::FullText,
+            DbSearchMode::Fuzzy => Self::Fuzzy,
+        }
+    }
+}
+
+impl From<&OptFilters<'_>> for QueryOptions {
+    fn from(options: &OptFilters<'_>) -> Self {
+        Self {
+            exit: options.exit,
+            exclude_exit: options.exclude_exit,
+            only_failed: options.only_failed,
+            cwd: options.cwd.map(str::to_owned),
+            exclude_cwd: options.exclude_cwd.map(str::to_owned),
+            before: options.before.map(str::to_owned),
+            after: options.after.map(str::to_owned),
+            limit: options.limit,
+            offset: options.offset,
+            reverse: options.reverse,
+            include_duplicates: options.include_duplicates,
+            // Empty lists mean "all", as in `SearchRequest::shells`.
+            authors: match options.authors.into_list() {
+                filter::Items::All => vec![],
+                filter::Items::Some(authors) => authors
+                    .iter()
+                    .map(|author| match author {
+                        AuthorPattern::AllUser => AUTHOR_FILTER_ALL_USER.to_owned(),
+                        AuthorPattern::AllAgent => AUTHOR_FILTER_ALL_AGENT.to_owned(),
+                        AuthorPattern::Name(name) => name.clone(),
+                    })
+                    .collect(),
+            },
+            shells: match options.shells.into_list() {
+                filter::Items::All => vec![],
+                filter::Items::Some(shells) => shells.to_vec(),
+            },
+        }
+    }
 }
 
 impl From<FilterMode> for RpcFilterMode {
@@ -469,6 +574,12 @@ impl ControlClient {
         self.client.send_event(request).await?;
         Ok(())
     }
+
+    /// Why the daemon's config last failed to load, if it did, and which changed settings only
+    /// apply once it restarts.
+    pub async fn config_status(&mut self) -> Result<ConfigStatusResponse> {
+        Ok(self.client.config_status(ConfigStatusRequest {}).await?.into_inner())
+    }
 }
 
 /// Convert a daemon event to its proto representation.
@@ -481,6 +592,9 @@ fn daemon_event_to_proto(event: DaemonEvent) -> crate::control::send_event_reque
         DaemonEvent::HistoryDeleted { ids } => Event::HistoryDeleted(HistoryDeletedEvent {
             ids: ids.into_iter().map(|id| id.0).collect(),
         }),
+        DaemonEvent::HistoryAmended { ids } => Event::HistoryAmended(HistoryAmendedEvent {
+            ids: ids.into_iter().map(|id| id.0).collect(),
+        }),
         DaemonEvent::ForceSync => Event::ForceSync(ForceSyncEvent {}),
         DaemonEvent::SettingsReloaded => Event::SettingsReloaded(SettingsReloadedEvent {}),
         DaemonEvent::ShutdownRequested => Event::Shutdown(ShutdownEvent {}),
@@ -559,6 +673,52 @@ pub async fn emit_event_with_settings(
     Ok(())
 }
 
+/// Search history the way [`Sqlite::search`] does, answered from the daemon's index when
+/// `daemon.search` is enabled.
+///
+/// Falls back to the database whenever the daemon can't answer: it isn't running, its index is
+/// still loading, or the search is one only the database can run.
+pub async fn search_history(
+    settings: &Settings,
+    db: &Sqlite,
+    search_mode: DbSearchMode,
+    filter_mode: FilterMode,
+    context: &Context,
+    query: &str,
+    filter_options: OptFilters<'_>,
+) -> Result<Vec<History>> {
+    if settings.daemon.enabled && settings.daemon.search && filter_options.ids.is_none() {
+        match query_daemon(settings, search_mode, filter_mode, context, query, &filter_options)
+            .await
+        {
+            Ok(results) => {
+                return Ok(db
+                    .arrange(search_mode, query, context, filter_options.ranker, results)
+                    .await?);
+            }
+            Err(e) => tracing::debug!(?e, "daemon search failed, searching the database"),
+        }
+    }
+
+    Ok(db.search(search_mode, filter_mode, context, query, filter_options).await?)
+}
+
+async fn query_daemon(
+    settings: &Settings,
+    search_mode: DbSearchMode,
+    filter_mode: FilterMode,
+    context: &Context,
+    query: &str,
+    filter_options: &OptFilters<'_>,
+) -> Result<Vec<History>> {
+    let mut client = SearchClient::from_settings(settings).await?;
+    client
+        .query(search_mode, filter_mode, context.clone(), query, filter_options)
+        .await?
+        .try_collect()
+        .await
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -23,9 +23,9 @@ use crate::daemon::{Component, DaemonHandle};
 use crate::events::DaemonEvent;
 use crate::history::history_server::{History as HistorySvc, HistoryServer};
 use crate::history::{
-    AuthorKind, CancelHistoryReply, CancelHistoryRequest, EndHistoryReply, EndHistoryRequest,
-    HistoryEntry, HistoryEventKind, ShutdownReply, ShutdownRequest, StartHistoryReply,
-    StartHistoryRequest, StatusReply, StatusRequest, TailHistoryReply, TailHistoryRequest,
+    CancelHistoryReply, CancelHistoryRequest, EndHistoryReply, EndHistoryRequest, HistoryEventKind,
+    ShutdownReply, ShutdownRequest, StartHistoryReply, StartHistoryRequest, StatusReply,
+    StatusRequest, TailHistoryReply, TailHistoryRequest,
 };
 
 const DAEMON_PROTOCOL_VERSION: u32 = 1;
@@ -119,20 +119,7 @@ pub struct HistoryGrpcService {
 fn history_to_tail_reply(kind: HistoryEventKind, history: History) -> TailHistoryReply {
     TailHistoryReply {
         kind: kind as i32,
-        history: Some(HistoryEntry {
-            timestamp: history.timestamp.unix_timestamp_nanos() as u64,
-            id: history.id.0,
-            command: history.command,
-            cwd: history.cwd,
-            session: history.session,
-            hostname: history.cmd_origin.into_string(),
-            author: history.author,
-            intent: history.intent.unwrap_or_default(),
-            exit: history.exit,
-            duration: history.duration,
-            shell: history.shell.unwrap_or_default(),
-            author_kind: AuthorKind::from(history.author_kind) as i32,
-        }),
+        history: Some(history.into()),
     }
 }
 
@@ -221,6 +208,19 @@ impl HistorySvc for HistoryGrpcService {
                 .clone()
                 .ok_or_else(|| Status::internal("component not initialized"))?;
 
+            // The filters were checked when the command started, but the settings may have been
+            // reloaded with new ones while it ran.
+            if !history.should_save(&*handle.settings().await) {
+                tracing::info!(id = id.0.to_string(), "end history: filtered out, not saving");
+
+                return Ok(Response::new(EndHistoryReply {
+                    id: String::new(),
+                    idx: 0,
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                    protocol: DAEMON_PROTOCOL_VERSION,
+                }));
+            }
+
             // Save to database
             handle
                 .history_db()
@@ -230,6 +230,21 @@ impl HistorySvc for HistoryGrpcService {
 
             tracing::info!(id = id.0.to_string(), duration = history.duration, "end history");
 
+            if !history.should_sync(&*handle.settings().await) {
+                handle.history_db().mark_local_only(&history.id).await.map_err(|e| {
+                    Status::internal(format!("failed to keep history local: {e:?}"))
+                })?;
+
+                handle.emit(DaemonEvent::HistoryEnded(history));
+
+                return Ok(Response::new(EndHistoryReply {
+                    id: String::new(),
+                    idx: 0,
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                    protocol: DAEMON_PROTOCOL_VERSION,
+                }));
+            }
+
             // Push to record store
             let (record_id, idx) = history_store
                 .push(history.clone())
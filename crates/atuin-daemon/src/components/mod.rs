@@ -10,16 +10,22 @@
 //! Available components:
 //!
 //! - [`history::HistoryComponent`]: Command history lifecycle management
+//! - [`peer::PeerComponent`]: Direct sync with the account's other machines
+//! - [`retention::RetentionComponent`]: Deletes history the retention settings don't keep
 //! - [`search::SearchComponent`]: Fuzzy search over history
 //! - [`semantic::SemanticComponent`]: In-memory semantic command captures
 //! - [`sync::SyncComponent`]: Cloud sync
 
 pub mod history;
+pub mod peer;
+pub mod retention;
 pub mod search;
 pub mod semantic;
 pub mod sync;
 
 pub use history::HistoryComponent;
+pub use peer::PeerComponent;
+pub use retention::RetentionComponent;
 pub use search::SearchComponent;
 pub use semantic::SemanticComponent;
 pub use sync::SyncComponent;
@@ -0,0 +1,367 @@
+//! Peer component.
+//!
+//! Lets this account's other machines sync with this one directly, over the LAN or an SSH tunnel,
+//! with `atuin sync peer <address>`. It serves the sync server's record API over the local record
+//! store to peers that know the shared `peer.secret`, so a peer syncs with it like with a server.
+//!
+//! The secret itself is never sent: peers answer a single-use challenge with each request (see
+//! [`atuin_client::record::sync::peer`]), so a request seen on the network can't be replayed.
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use atuin_client::history::store::HistoryStore;
+use atuin_client::record::sync::peer;
+use atuin_client::settings::Settings;
+use atuin_client::settings::sync::Peer as PeerSettings;
+use atuin_domain::api::{ATUIN_CARGO_VERSION, ATUIN_HEADER_VERSION, ErrorResponse};
+use atuin_domain::caps::CapServer;
+use atuin_domain::caps::axum::get as capabilities_endpoint;
+use atuin_domain::record::{
+    EncryptedData, HostId, Record, RecordIdx, RecordSeriesKey, RecordStatus, RecordTag,
+};
+use atuin_dotfiles::store::AliasStore;
+use atuin_dotfiles::store::var::VarStore;
+use axum::body::Body;
+use axum::extract::{Query, Request, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::{HeaderValue, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use eyre::{Context, Result};
+use parking_lot::Mutex;
+use serde::Deserialize;
+use tokio::net::TcpListener;
+
+use crate::components::sync::build_downloaded;
+use crate::daemon::{Component, DaemonHandle};
+use crate::events::DaemonEvent;
+
+/// The most records a peer gets in one page, however many it asks for.
+const MAX_PAGE_SIZE: u64 = 1000;
+
+/// How long a peer has to answer a challenge.
+const CHALLENGE_TTL: Duration = Duration::from_secs(60);
+
+/// The most challenges that can be waiting for an answer at once.
+const MAX_CHALLENGES: usize = 1024;
+
+/// The largest request body a peer can send, the same as axum's default.
+const MAX_BODY_SIZE: usize = 2 * 1024 * 1024;
+
+/// Peer component - serves the local record store to this account's other machines.
+///
+/// This component:
+/// - Listens on `peer.listen`, when `peer.secret` is set
+/// - Rejects requests that don't answer one of its challenges with the secret
+/// - Only serves and accepts the records that the `[sync]` settings sync
+/// - Builds history, aliases and vars from the records peers upload, emitting `HistorySynced`
+/// - Listens again when the `[peer]` settings change
+pub struct PeerComponent {
+    task_handle: Option<tokio::task::JoinHandle<()>>,
+    handle: Option<DaemonHandle>,
+    /// The `[peer]` settings the component last started listening with.
+    peer: Option<PeerSettings>,
+}
+
+impl PeerComponent {
+    /// Create a new peer component.
+    pub fn new() -> Self {
+        Self {
+            task_handle: None,
+            handle: None,
+            peer: None,
+        }
+    }
+
+    /// Listen for peers with the current `[peer]` settings, if they set a secret.
+    async fn listen(&mut self, handle: DaemonHandle) -> Result<()> {
+        let peer = handle.settings().await.peer.clone();
+        self.peer = Some(peer.clone());
+        let addr = peer.listen;
+
+        let Some(secret) = peer.secret else {
+            tracing::debug!("peer.secret is not set, not listening for peers");
+            return Ok(());
+        };
+
+        // A misconfigured listener shouldn't take the rest of the daemon down with it.
+        let listener = match TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                tracing::error!("failed to listen for peers on {addr}: {e}");
+                return Ok(());
+            }
+        };
+
+        let router = router(handle, &secret).await?;
+
+        self.task_handle = Some(tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, router).await {
+                tracing::error!("peer listener failed: {e}");
+            }
+        }));
+
+        tracing::info!(%addr, "listening for peers");
+        Ok(())
+    }
+
+    /// Stop listening for peers, waiting for the listener to close so its address is free again.
+    async fn stop_listening(&mut self) {
+        if let Some(task) = self.task_handle.take() {
+            task.abort();
+            let _ = task.await;
+        }
+    }
+}
+
+impl Default for PeerComponent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Component for PeerComponent {
+    fn name(&self) -> &'static str {
+        "peer"
+    }
+
+    async fn start(&mut self, handle: DaemonHandle) -> Result<()> {
+        self.handle = Some(handle.clone());
+        self.listen(handle).await?;
+
+        tracing::info!("peer component started");
+        Ok(())
+    }
+
+    async fn handle_event(&mut self, event: &DaemonEvent) -> Result<()> {
+        if let DaemonEvent::SettingsReloaded = event
+            && let Some(handle) = self.handle.clone()
+        {
+            let changed = self.peer.as_ref() != Some(&handle.settings().await.peer);
+            if changed {
+                tracing::info!("peer settings changed, listening again");
+                self.stop_listening().await;
+                self.listen(handle).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        if let Some(handle) = self.task_handle.take() {
+            handle.abort();
+        }
+        tracing::info!("peer component stopped");
+        Ok(())
+    }
+}
+
+#[derive(Clone)]
+struct PeerState {
+    handle: DaemonHandle,
+    secret: Arc<str>,
+    /// The challenges handed out and not yet answered, with when.
+    challenges: Arc<Mutex<HashMap<String, Instant>>>,
+    history_store: HistoryStore,
+    alias_store: AliasStore,
+    var_store: VarStore,
+}
+
+/// The record API the peer component serves, for peers presenting `secret`.
+pub async fn router(handle: DaemonHandle, secret: &str) -> Result<Router> {
+    let host_id = Settings::host_id().await.context("failed to get host id")?;
+
+    let encryption_key = handle.encryption_key().clone();
+    let store = handle.store().clone();
+
+    let state = PeerState {
+        history_store: HistoryStore::new(store.clone(), host_id, encryption_key.clone()),
+        alias_store: AliasStore::new(store.clone(), host_id, encryption_key.clone()),
+        var_store: VarStore::new(store, host_id, encryption_key),
+        secret: secret.into(),
+        challenges: Arc::default(),
+        handle,
+    };
+
+    // Peers advertise no capabilities, so packfiles aren't exchanged: the history they carry is
+    // still in the store as loose records.
+    let caps = Router::new()
+        .route("/api/v0/capabilities", get(capabilities_endpoint))
+        .with_state(Arc::new(CapServer::new()));
+
+    let records = Router::new()
+        .route("/api/v0/record", get(status).post(post))
+        .route("/api/v0/record/next", get(next))
+        .layer(middleware::from_fn_with_state(state.clone(), authenticate))
+        .route(peer::CHALLENGE_PATH, get(challenge))
+        .with_state(state);
+
+    Ok(records.merge(caps).layer(middleware::map_response(version)))
+}
+
+/// Hand out a challenge, for the peer to answer with its next request.
+async fn challenge(State(state): State<PeerState>) -> Result<Json<peer::Challenge>, PeerError> {
+    let challenge: String = rand::random::<[u8; 32]>().iter().map(|b| format!("{b:02x}")).collect();
+
+    let mut challenges = state.challenges.lock();
+    if challenges.len() >= MAX_CHALLENGES {
+        challenges.retain(|_, issued| issued.elapsed() < CHALLENGE_TTL);
+    }
+    if challenges.len() >= MAX_CHALLENGES {
+        return Err(error(StatusCode::SERVICE_UNAVAILABLE, "too many unanswered challenges"));
+    }
+    challenges.insert(challenge.clone(), Instant::now());
+    drop(challenges);
+
+    Ok(Json(peer::Challenge { challenge }))
+}
+
+async fn authenticate(State(state): State<PeerState>, request: Request, next: Next) -> Response {
+    let unauthorized =
+        || error(StatusCode::UNAUTHORIZED, "the peer secret doesn't match").into_response();
+
+    let Some((challenge, answer)) = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix(peer::SCHEME)?.strip_prefix(' ')?.split_once(' '))
+        .map(|(challenge, answer)| (challenge.to_string(), answer.to_string()))
+    else {
+        return unauthorized();
+    };
+
+    // Each challenge is only good for one request, and only for a while.
+    let issued = state.challenges.lock().remove(&challenge);
+    if issued.is_none_or(|issued| issued.elapsed() >= CHALLENGE_TTL) {
+        return unauthorized();
+    }
+
+    let (parts, body) = request.into_parts();
+    let Ok(body) = axum::body::to_bytes(body, MAX_BODY_SIZE).await else {
+        return error(StatusCode::PAYLOAD_TOO_LARGE, "the request is too large").into_response();
+    };
+
+    let path_and_query = parts.uri.path_and_query().map_or("", |p| p.as_str());
+    let expected = peer::response(&state.secret, &challenge, &parts.method, path_and_query, &body);
+
+    if secrets_match(&answer, &expected) {
+        next.run(Request::from_parts(parts, Body::from(body))).await
+    } else {
+        unauthorized()
+    }
+}
+
+/// Compare secrets in constant time, so their contents can't be guessed from response timings.
+fn secrets_match(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+/// Peers check they're on the same major version, like they do with servers.
+async fn version(mut response: Response) -> Response {
+    response
+        .headers_mut()
+        .insert(ATUIN_HEADER_VERSION, HeaderValue::from_static(ATUIN_CARGO_VERSION));
+    response
+}
+
+/// An error response, shaped like the sync server's.
+type PeerError = (StatusCode, Json<ErrorResponse<'static>>);
+
+const fn error(status: StatusCode, reason: &'static str) -> PeerError {
+    (
+        status,
+        Json(ErrorResponse {
+            reason: Cow::Borrowed(reason),
+        }),
+    )
+}
+
+async fn filter(settings: &Settings) -> Result<atuin_client::settings::sync::Settings, PeerError> {
+    settings.sync_filter().await.map_err(|e| {
+        tracing::error!("failed to load the sync filter: {e}");
+        error(StatusCode::INTERNAL_SERVER_ERROR, "failed to load the sync settings")
+    })
+}
+
+async fn status(State(state): State<PeerState>) -> Result<Json<RecordStatus>, PeerError> {
+    let filter = filter(&*state.handle.settings().await).await?;
+
+    let mut status = state.handle.store().status().await.map_err(|e| {
+        tracing::error!("failed to get the record status: {e}");
+        error(StatusCode::INTERNAL_SERVER_ERROR, "failed to calculate record index")
+    })?;
+
+    status.hosts.retain(|host, tags| {
+        tags.retain(|tag, _| filter.syncs(&RecordSeriesKey::new(*host, tag.clone())));
+        !tags.is_empty()
+    });
+
+    Ok(Json(status))
+}
+
+#[derive(Deserialize)]
+struct NextParams {
+    host: HostId,
+    tag: RecordTag,
+    start: Option<RecordIdx>,
+    count: u64,
+}
+
+async fn next(
+    State(state): State<PeerState>,
+    Query(params): Query<NextParams>,
+) -> Result<Json<Vec<Record<EncryptedData>>>, PeerError> {
+    let series = RecordSeriesKey::new(params.host, params.tag);
+
+    if !filter(&*state.handle.settings().await).await?.syncs(&series) {
+        return Ok(Json(Vec::new()));
+    }
+
+    let records = state
+        .handle
+        .store()
+        .next(&series, params.start.unwrap_or(0), params.count.min(MAX_PAGE_SIZE))
+        .await
+        .map_err(|e| {
+            tracing::error!("failed to get records: {e}");
+            error(StatusCode::INTERNAL_SERVER_ERROR, "failed to get records")
+        })?;
+
+    Ok(Json(records))
+}
+
+async fn post(
+    State(state): State<PeerState>,
+    Json(records): Json<Vec<Record<EncryptedData>>>,
+) -> Result<(), PeerError> {
+    let filter = filter(&*state.handle.settings().await).await?;
+
+    let records: Vec<_> = records
+        .into_iter()
+        .filter(|r| filter.syncs(&RecordSeriesKey::new(r.host.id, r.tag.clone())))
+        .collect();
+
+    state.handle.store().push_batch(records.iter()).await.map_err(|e| {
+        tracing::error!("failed to add records from a peer: {e}");
+        error(StatusCode::INTERNAL_SERVER_ERROR, "failed to add records")
+    })?;
+
+    tracing::debug!(count = records.len(), "added records from a peer");
+
+    let ids: Vec<_> = records.iter().map(|r| r.id).collect();
+    build_downloaded(
+        &state.handle,
+        &state.history_store,
+        &state.alias_store,
+        &state.var_store,
+        &ids,
+    )
+    .await;
+
+    Ok(())
+}
@@ -0,0 +1,134 @@
+//! Retention component.
+//!
+//! Applies the `[retention]` settings on a schedule, deleting the history they no longer keep.
+//! The deletes go through the history store, so they sync, and every machine converges.
+
+use std::sync::Arc;
+
+use atuin_client::history::retention;
+use atuin_client::history::store::HistoryStore;
+use atuin_client::settings::Settings;
+use eyre::Result;
+use time::OffsetDateTime;
+use tokio::sync::Notify;
+use tokio::time::{Instant, MissedTickBehavior, interval, interval_at};
+
+use crate::daemon::{Component, DaemonHandle};
+use crate::events::DaemonEvent;
+
+/// Retention component - deletes history the retention settings no longer keep.
+///
+/// This component:
+/// - Wakes every `retention.interval`, and does nothing while no rules or limit are set
+/// - Deletes expired history through the history store, so the deletes sync
+/// - Emits `HistoryDeleted` for what it deleted
+/// - Picks up a new `retention.interval` when the settings are reloaded
+pub struct RetentionComponent {
+    task_handle: Option<tokio::task::JoinHandle<()>>,
+    settings_reloaded: Arc<Notify>,
+}
+
+impl RetentionComponent {
+    /// Create a new retention component.
+    pub fn new() -> Self {
+        Self {
+            task_handle: None,
+            settings_reloaded: Arc::new(Notify::new()),
+        }
+    }
+}
+
+impl Default for RetentionComponent {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Component for RetentionComponent {
+    fn name(&self) -> &'static str {
+        "retention"
+    }
+
+    async fn start(&mut self, handle: DaemonHandle) -> Result<()> {
+        let host_id = Settings::host_id().await?;
+        let history_store =
+            HistoryStore::new(handle.store().clone(), host_id, handle.encryption_key().clone());
+
+        self.task_handle = Some(tokio::spawn(retention_loop(
+            handle,
+            history_store,
+            self.settings_reloaded.clone(),
+        )));
+
+        tracing::info!("retention component started");
+        Ok(())
+    }
+
+    async fn handle_event(&mut self, event: &DaemonEvent) -> Result<()> {
+        if let DaemonEvent::SettingsReloaded = event {
+            self.settings_reloaded.notify_one();
+        }
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<()> {
+        if let Some(handle) = self.task_handle.take() {
+            handle.abort();
+        }
+        tracing::info!("retention component stopped");
+        Ok(())
+    }
+}
+
+/// Apply the retention settings every `retention.interval`, as they are at the time, so changes
+/// to the rules apply without a restart. A new interval starts counting when the settings are
+/// reloaded.
+async fn retention_loop(
+    handle: DaemonHandle,
+    history_store: HistoryStore,
+    settings_reloaded: Arc<Notify>,
+) {
+    let period = handle.settings().await.retention.interval.0;
+
+    let mut ticker = interval(period);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick() => {}
+            () = settings_reloaded.notified() => {
+                let period = handle.settings().await.retention.interval.0;
+                if ticker.period() != period {
+                    tracing::info!(?period, "retention interval changed, rescheduling");
+                    ticker = interval_at(Instant::now() + period, period);
+                    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+                }
+                continue;
+            }
+        }
+
+        let settings = handle.settings().await.retention.clone();
+        if settings.is_empty() {
+            continue;
+        }
+
+        match retention::enforce(
+            &history_store,
+            handle.history_db(),
+            &settings,
+            OffsetDateTime::now_utc(),
+        )
+        .await
+        {
+            Ok(ids) if ids.is_empty() => {}
+            Ok(ids) => {
+                tracing::info!(
+                    count = ids.len(),
+                    "deleted history the retention settings don't keep"
+                );
+                handle.emit(DaemonEvent::HistoryDeleted { ids });
+            }
+            Err(e) => tracing::error!("failed to apply retention settings: {e}"),
+        }
+    }
+}
@@ -4,27 +4,43 @@
 //! with frecency-based ranking and dynamic filtering.
 
 use std::ops::Deref;
+use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::Arc;
 
+use atuin_client::database::search::{SearchQuery, SearchQueryError};
+use atuin_client::database::{Context, DbSearchMode, OptFilters};
+use atuin_client::history::AuthorPattern;
+use atuin_client::settings::FilterMode as DbFilterMode;
 use atuin_common::filter::OrFilter;
 use atuin_common::path::DisplayRichExt;
+use atuin_domain::record::CmdOrigin;
+use atuin_history::predict::{MODEL_HISTORY_LIMIT, Predictor};
 use eyre::Result;
+use time::OffsetDateTime;
 use tokio::sync::RwLock;
 use tokio_stream::Stream;
 use tonic::{Request, Response, Status, Streaming};
 use tracing::{Level, debug, error, info, instrument, span, trace};
+use uuid::Uuid;
 
 use crate::daemon::{Component, DaemonHandle};
 use crate::events::DaemonEvent;
+use crate::history::HistoryEntry;
 use crate::search::search_server::{Search as SearchSvc, SearchServer};
 use crate::search::{
-    FilterMode, IndexFilterMode, PrepareIndexRequest, PrepareIndexResponse, SearchIndex,
-    SearchRequest, SearchResponse,
+    FilterMode, IndexFilterMode, MatchRange, PredictRequest, PredictResponse, Prediction,
+    PrepareIndexRequest, PrepareIndexResponse, QueryOptions, QueryRequest, QueryResponse,
+    ScoreComponents, SearchContext, SearchHit, SearchIndex, SearchMode, SearchOrigin,
+    SearchRequest, SearchResponse, SearchResult,
 };
 
 const PAGE_SIZE: usize = 5000;
 const RESULTS_LIMIT: u32 = 200;
+/// How many history entries go in each `QueryResponse`.
+const QUERY_CHUNK_SIZE: usize = 500;
+/// The most predictions a single `Predict` request can ask for.
+const PREDICTIONS_LIMIT: u32 = 50;
 /// How often to rebuild the frecency map (in seconds).
 const FRECENCY_REFRESH_INTERVAL_SECS: u64 = 60;
 
@@ -45,7 +61,11 @@ where
 {
     info!("Loading history into search index; page size = {}", PAGE_SIZE);
     let db = handle.history_db();
-    let mut pager = db.all_paged(PAGE_SIZE, false, true);
+    // Non-interactive searches can filter on anything about a run, and list every one of them, so
+    // an index answering them needs every run of every command. Interactive search only needs one
+    // per place the command was run.
+    let unique = !index().await.is_queryable();
+    let mut pager = db.all_paged(PAGE_SIZE, false, unique);
     loop {
         match pager.next().await {
             Ok(Some(histories)) => {
@@ -53,10 +73,9 @@ where
                 index().await.add_histories(&histories);
             }
             Ok(None) => {
-                info!(
-                    "History load complete; {} unique commands indexed",
-                    index().await.command_count()
-                );
+                let index = index().await;
+                index.mark_loaded();
+                info!("History load complete; {} unique commands indexed", index.command_count());
                 return Ok(());
             }
             Err(e) => {
@@ -84,7 +103,7 @@ where
 {
     {
         let settings = handle.settings().await;
-        index().await.rebuild_frecency(&settings.search);
+        index().await.rebuild_frecency(&settings.search, settings.smart_sort);
     }
     info!("Frecency map built");
 }
@@ -108,15 +127,35 @@ where
     Ok(())
 }
 
+/// Build the next-command prediction model from recent history.
+#[instrument(skip_all, level = Level::TRACE)]
+async fn build_predictor(handle: &DaemonHandle) -> Result<Predictor, ()> {
+    let db = handle.history_db();
+    match db.before(OffsetDateTime::now_utc(), MODEL_HISTORY_LIMIT).await {
+        Ok(mut histories) => {
+            // `before` returns newest first, but the model needs to see each session in order.
+            histories.reverse();
+            info!("Prediction model built from {} history entries", histories.len());
+            Ok(Predictor::from_history(&histories))
+        }
+        Err(e) => {
+            error!("Failed to load history for prediction model: {}", e);
+            Err(())
+        }
+    }
+}
+
 /// Search component - provides fuzzy search over command history.
 ///
 /// This component:
 /// - Maintains a deduplicated search index with frecency ranking
 /// - Loads history from the database on startup
 /// - Updates the index when history events occur
+/// - Maintains a model of which commands usually follow which, for predictions
 /// - Provides the Search gRPC service
 pub struct SearchComponent {
     index: Arc<RwLock<SearchIndex>>,
+    predictor: Arc<RwLock<Predictor>>,
     handle: Option<DaemonHandle>,
     loader_handle: Option<tokio::task::JoinHandle<()>>,
     frecency_handle: Option<tokio::task::JoinHandle<()>>,
@@ -127,6 +166,7 @@ impl SearchComponent {
     pub fn new() -> Self {
         Self {
             index: Arc::new(RwLock::new(SearchIndex::default())),
+            predictor: Arc::new(RwLock::new(Predictor::new())),
             handle: None,
             loader_handle: None,
             frecency_handle: None,
@@ -137,6 +177,7 @@ impl SearchComponent {
     pub fn grpc_service(&self) -> SearchGrpcServiceBuilder {
         SearchGrpcServiceBuilder {
             index: self.index.clone(),
+            predictor: self.predictor.clone(),
         }
     }
 
@@ -149,7 +190,8 @@ impl SearchComponent {
         info!("Rebuilding search index from database");
 
         // Create a new index
-        let new_index = SearchIndex::new(self.index.read().await.shells.clone());
+        let queryable = handle.settings().await.daemon.search;
+        let new_index = SearchIndex::new(self.index.read().await.shells.clone(), queryable);
         if build_index_only(async || &new_index, handle).await.is_err() {
             return;
         }
@@ -157,6 +199,18 @@ impl SearchComponent {
         info!("Search index rebuild complete; {} unique commands", new_index.command_count());
         *self.index.write().await = new_index;
     }
+
+    /// Rebuild the prediction model from the database.
+    async fn rebuild_predictor(&self) {
+        let Some(handle) = self.handle.as_ref() else {
+            error!("Component not initialized");
+            return;
+        };
+
+        if let Ok(predictor) = build_predictor(handle).await {
+            *self.predictor.write().await = predictor;
+        }
+    }
 }
 
 impl Default for SearchComponent {
@@ -175,6 +229,7 @@ impl Component for SearchComponent {
 
         // Spawn background task to load history into index
         let index = self.index.clone();
+        let predictor = self.predictor.clone();
         let handle_for_loader = handle.clone();
 
         self.loader_handle = Some(tokio::spawn(async move {
@@ -182,10 +237,16 @@ impl Component for SearchComponent {
             // will use the value of the `ATUIN_SHELL` environment variable. This variable might be
             // correct if the daemon was autostarted by the shell hooks, but if it's unset or
             // incorrect, we'll simply rebuild the index upon receipt of the first request.
-            let shells =
-                handle_for_loader.settings().await.search.shells.to_filter().to_vec_filter();
-            index.write().await.shells = shells;
+            let (shells, queryable) = {
+                let settings = handle_for_loader.settings().await;
+                (settings.search.shells.to_filter().to_vec_filter(), settings.daemon.search)
+            };
+            *index.write().await = SearchIndex::new(shells, queryable);
             let _ = build_index(|| index.read(), &handle_for_loader).await;
+
+            if let Ok(built) = build_predictor(&handle_for_loader).await {
+                *predictor.write().await = built;
+            }
         }));
 
         // Spawn background task to periodically refresh frecency
@@ -214,8 +275,14 @@ impl Component for SearchComponent {
                     return Ok(());
                 };
 
-                let histories = handle.history_db().load_active(ids.iter().cloned()).await?;
+                let mut histories = handle.history_db().load_active(ids.iter().cloned()).await?;
                 self.index.read().await.add_histories(&histories);
+
+                histories.sort_by_key(|h| h.timestamp);
+                let mut predictor = self.predictor.write().await;
+                for history in &histories {
+                    predictor.observe(history);
+                }
             }
             DaemonEvent::HistoryStarted(history) => {
                 debug!(id = %history.id, command = %history.command, "History started (no index action)");
@@ -226,19 +293,28 @@ impl Component for SearchComponent {
                         self.index.read().await.add_history(history);
                     })
                     .await;
+                self.predictor.write().await.observe(history);
             }
             DaemonEvent::HistoryPruned | DaemonEvent::HistoryRebuilt => {
                 info!("History store pruned or rebuilt, rebuilding search index");
                 self.rebuild_index_only().await;
+                self.rebuild_predictor().await;
             }
-            DaemonEvent::HistoryDeleted { ids } => {
-                info!(count = ids.len(), "History deleted, rebuilding search index");
+            DaemonEvent::HistoryDeleted { ids } | DaemonEvent::HistoryAmended { ids } => {
+                info!(count = ids.len(), "History deleted or amended, rebuilding search index");
                 // For now, just rebuild the entire index. A more efficient implementation
                 // would remove specific items from the index.
                 self.rebuild_index_only().await;
+                self.rebuild_predictor().await;
             }
             DaemonEvent::SettingsReloaded => {
                 if let Some(handle) = self.handle.as_ref() {
+                    if handle.settings().await.daemon.search
+                        != self.index.read().await.is_queryable()
+                    {
+                        info!("daemon.search changed, rebuilding search index");
+                        self.rebuild_index_only().await;
+                    }
                     info!("Rebuilding frecency map after settings update");
                     build_frecency(|| self.index.read(), handle).await;
                 }
@@ -266,12 +342,14 @@ impl Component for SearchComponent {
 
 pub struct SearchGrpcServiceBuilder {
     index: Arc<RwLock<SearchIndex>>,
+    predictor: Arc<RwLock<Predictor>>,
 }
 
 impl SearchGrpcServiceBuilder {
     pub fn build(self, handle: DaemonHandle) -> SearchServer<SearchGrpcService> {
         SearchServer::new(SearchGrpcService {
             index: self.index,
+            predictor: self.predictor,
             handle,
         })
     }
@@ -281,6 +359,7 @@ impl SearchGrpcServiceBuilder {
 #[derive(Clone)]
 pub struct SearchGrpcService {
     index: Arc<RwLock<SearchIndex>>,
+    predictor: Arc<RwLock<Predictor>>,
     handle: DaemonHandle,
 }
 
@@ -295,7 +374,8 @@ impl SearchGrpcService {
 
         info!("Rebuilding search index from database after shell filter change");
 
-        let new_index = SearchIndex::new(shells);
+        let queryable = self.handle.settings().await.daemon.search;
+        let new_index = SearchIndex::new(shells, queryable);
         build_index(async || &new_index, &self.handle).await?;
 
         info!("Search index rebuild complete; {} unique commands", new_index.command_count());
@@ -306,6 +386,7 @@ impl SearchGrpcService {
 #[tonic::async_trait]
 impl SearchSvc for SearchGrpcService {
     type SearchStream = Pin<Box<dyn Stream<Item = Result<SearchResponse, Status>> + Send>>;
+    type QueryStream = Pin<Box<dyn Stream<Item = Result<QueryResponse, Status>> + Send>>;
 
     #[instrument(skip_all, level = Level::TRACE, name = "search_rpc")]
     async fn search(
@@ -345,6 +426,7 @@ impl SearchSvc for SearchGrpcService {
 
                 // Convert proto FilterMode + context to IndexFilterMode
                 let index_filter = convert_filter_mode(filter_mode, proto_context.as_ref());
+                let origin = convert_origin(proto_context.as_ref());
 
                 // An empty list in `SearchRequest::shells` means "all".
                 let shells = OrFilter::from_list(search_req.shells).unwrap_or_default();
@@ -362,16 +444,21 @@ impl SearchSvc for SearchGrpcService {
                 };
 
                 // Perform the search
-                let history_ids: Vec<Vec<u8>> =
-                    span!(Level::TRACE, "daemon_search_query", %query, query_id).in_scope(|| {
-                        index.search(&query, &index_filter, RESULTS_LIMIT).map(Vec::from).collect()
-                    });
+                let hits = span!(Level::TRACE, "daemon_search_query", %query, query_id)
+                    .in_scope(|| index.search_hits(&query, &index_filter, &origin, RESULTS_LIMIT));
                 drop(index);
 
+                // A hit whose ID isn't a UUID can't be sent, so it's dropped.
+                let (ids, results) = hits
+                    .into_iter()
+                    .filter_map(|hit| Some((hit_id(&hit)?.to_vec(), search_result(hit))))
+                    .unzip();
+
                 if tx
                     .send(Ok(SearchResponse {
                         query_id,
-                        ids: history_ids,
+                        ids,
+                        results,
                     }))
                     .await
                     .is_err()
@@ -401,13 +488,177 @@ impl SearchSvc for SearchGrpcService {
         }
         Ok(Response::new(PrepareIndexResponse {}))
     }
+
+    #[instrument(skip_all, level = Level::TRACE, name = "predict_rpc")]
+    async fn predict(
+        &self,
+        request: Request<PredictRequest>,
+    ) -> Result<Response<PredictResponse>, Status> {
+        let request = request.into_inner();
+        let context = request.context.unwrap_or_default();
+        let limit = request.limit.min(PREDICTIONS_LIMIT) as usize;
+
+        let predicted =
+            self.predictor.read().await.predict(&context.session_id, &context.cwd, limit);
+
+        let index = self.index.read().await;
+        let predictions = predicted
+            .into_iter()
+            .map(|p| Prediction {
+                id: index.most_recent_id(&p.command).map(Vec::from).unwrap_or_default(),
+                command: p.command,
+                score: p.score,
+            })
+            .collect();
+
+        Ok(Response::new(PredictResponse { predictions }))
+    }
+
+    #[instrument(skip_all, level = Level::TRACE, name = "query_rpc")]
+    async fn query(
+        &self,
+        request: Request<QueryRequest>,
+    ) -> Result<Response<Self::QueryStream>, Status> {
+        let request = request.into_inner();
+        let search_mode = match request.search_mode() {
+            SearchMode::Prefix => DbSearchMode::Prefix,
+            SearchMode::FullText => DbSearchMode::FullText,
+            SearchMode::Fuzzy => DbSearchMode::Fuzzy,
+        };
+        let filter_mode = db_filter_mode(request.filter_mode());
+        let context = query_context(request.context.unwrap_or_default());
+
+        let options = request.options.unwrap_or_default();
+        // Empty lists mean "all", as in `QueryOptions`.
+        let authors = OrFilter::from_list(
+            options.authors.iter().map(String::as_str).map(AuthorPattern::from).collect::<Vec<_>>(),
+        )
+        .unwrap_or_default();
+        let shells = OrFilter::from_list(options.shells.clone()).unwrap_or_default();
+        let filter_options = OptFilters {
+            authors: authors.as_slice_filter(),
+            shells: shells.as_slice_filter(),
+            ..opt_filters(&options)
+        };
+
+        debug!(query = %request.query, ?search_mode, ?filter_mode, "query request");
+
+        let query =
+            SearchQuery::new(search_mode, filter_mode, &context, &request.query, &filter_options)
+                .map_err(|e| match e {
+                SearchQueryError::Unsupported(_) => Status::failed_precondition(e.to_string()),
+                SearchQueryError::Date { .. } | SearchQueryError::Regex(_) => {
+                    Status::invalid_argument(e.to_string())
+                }
+            })?;
+
+        let index = self.index.read().await;
+        if !index.is_queryable() {
+            return Err(Status::failed_precondition("daemon.search is disabled"));
+        }
+        if !index.is_loaded() {
+            return Err(Status::unavailable("the search index is still loading"));
+        }
+        let results = index.query(&query);
+        drop(index);
+
+        // Sent in chunks, each converted as the client reads it.
+        let mut results = results.into_iter();
+        let chunks = std::iter::from_fn(move || {
+            let history: Vec<HistoryEntry> =
+                results.by_ref().take(QUERY_CHUNK_SIZE).map(HistoryEntry::from).collect();
+            (!history.is_empty()).then_some(Ok(QueryResponse { history }))
+        });
+        Ok(Response::new(Box::pin(tokio_stream::iter(chunks))))
+    }
+}
+
+/// The options of a `QueryRequest`, apart from the author and shell filters, which need owned
+/// lists to borrow from.
+fn opt_filters(options: &QueryOptions) -> OptFilters<'_> {
+    OptFilters {
+        exit: options.exit,
+        exclude_exit: options.exclude_exit,
+        only_failed: options.only_failed,
+        cwd: options.cwd.as_deref(),
+        exclude_cwd: options.exclude_cwd.as_deref(),
+        before: options.before.as_deref(),
+        after: options.after.as_deref(),
+        limit: options.limit,
+        offset: options.offset,
+        reverse: options.reverse,
+        include_duplicates: options.include_duplicates,
+        ..OptFilters::default()
+    }
+}
+
+fn db_filter_mode(mode: FilterMode) -> DbFilterMode {
+    match mode {
+        FilterMode::Global => DbFilterMode::Global,
+        FilterMode::Host => DbFilterMode::Host,
+        FilterMode::Session => DbFilterMode::Session,
+        FilterMode::Directory => DbFilterMode::Directory,
+        FilterMode::Workspace => DbFilterMode::Workspace,
+        FilterMode::SessionPreload => DbFilterMode::SessionPreload,
+    }
+}
+
+/// The client's [`Context`], back from the proto it was sent as.
+fn query_context(context: SearchContext) -> Context {
+    Context {
+        session: context.session_id,
+        cwd: context.cwd,
+        // Whatever the client's own context held, so it needs no more validating than it had there.
+        #[allow(deprecated)]
+        cmd_origin: CmdOrigin::parse_lenient(context.hostname),
+        host_id: context.host_id,
+        git_root: context.git_root.map(PathBuf::from),
+    }
+}
+
+/// The 16-byte form of a hit's history ID, as sent in `SearchResponse::ids`.
+fn hit_id(hit: &SearchHit) -> Option<[u8; 16]> {
+    Uuid::parse_str(&hit.history.id.0).ok().map(|id| *id.as_bytes())
+}
+
+fn search_result(hit: SearchHit) -> SearchResult {
+    SearchResult {
+        history: Some(hit.history.into()),
+        score: Some(ScoreComponents {
+            match_score: u32::from(hit.match_score),
+            frecency: hit.frecency,
+            context_boost: hit.context_boost,
+        }),
+        ranges: hit
+            .ranges
+            .into_iter()
+            .map(|range| MatchRange {
+                start: range.start,
+                end: range.end,
+            })
+            .collect(),
+    }
+}
+
+/// Convert the proto context to the place the search is run from, for context boosts.
+fn convert_origin(context: Option<&SearchContext>) -> SearchOrigin {
+    let Some(ctx) = context else {
+        return SearchOrigin::default();
+    };
+    let non_empty = |s: &str| (!s.is_empty()).then(|| s.to_owned());
+    SearchOrigin {
+        cwd: (!ctx.cwd.is_empty()).then(|| ctx.cwd.display_rich().trailing_slash(true).to_string()),
+        git_root: ctx
+            .git_root
+            .as_ref()
+            .map(|root| root.display_rich().trailing_slash(true).to_string()),
+        host: non_empty(&ctx.hostname),
+        session: non_empty(&ctx.session_id),
+    }
 }
 
 /// Convert proto FilterMode and context to IndexFilterMode.
-fn convert_filter_mode(
-    mode: FilterMode,
-    context: Option<&crate::search::SearchContext>,
-) -> IndexFilterMode {
+fn convert_filter_mode(mode: FilterMode, context: Option<&SearchContext>) -> IndexFilterMode {
     match (mode, context) {
         (FilterMode::Global, _) => IndexFilterMode::Global,
         (FilterMode::Directory, Some(ctx)) => {
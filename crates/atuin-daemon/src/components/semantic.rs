@@ -1,15 +1,25 @@
 //! Semantic command capture component.
 //!
-//! This is a prototype in-memory store for completed command captures emitted
-//! by atuin-pty-proxy. It keeps recent captures per Atuin session and indexes
-//! them by history ID for AI tool lookup.
+//! This keeps completed command captures emitted by atuin-pty-proxy. Recent
+//! captures are kept in memory per Atuin session and indexed by history ID for
+//! AI tool lookup, and every capture is kept in the output store on disk too, so
+//! older output outlives both the in-memory limits and the daemon.
 
 use std::collections::{HashMap, VecDeque};
 use std::sync::Arc;
+use std::time::Duration;
 
+use atuin_client::database::Sqlite as HistoryDatabase;
 use atuin_client::history::{History, HistoryId};
+use atuin_client::output::CommandOutput;
+use atuin_client::output::database::Database as OutputDatabase;
+use atuin_client::output::store::OutputStore;
+use atuin_client::settings::{Settings, output, sync};
 use eyre::Result;
-use tokio::sync::Mutex;
+use parking_lot::RwLock;
+use time::OffsetDateTime;
+use tokio::sync::{Mutex, OnceCell};
+use tokio::time::{MissedTickBehavior, interval};
 use tonic::{Request, Response, Status, Streaming};
 use tracing::{Level, instrument};
 
@@ -25,13 +35,29 @@ const MAX_COMMANDS_PER_SESSION: usize = 128;
 const MAX_BYTES_PER_SESSION: usize = 32 * 1024 * 1024;
 const MAX_PENDING_HISTORIES: usize = 128;
 
+/// How often output the `[output]` settings no longer keep is deleted.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 /// Stores completed command captures and associates them with history events.
 pub struct SemanticComponent {
     inner: Arc<SemanticComponentInner>,
+    handle: Option<DaemonHandle>,
+    task_handle: Option<tokio::task::JoinHandle<()>>,
 }
 
 struct SemanticComponentInner {
     state: Mutex<SemanticState>,
+    /// Where captures are kept on disk, once the component has started and opened it.
+    output: OnceCell<PersistedOutput>,
+}
+
+struct PersistedOutput {
+    store: OutputStore,
+    /// The `[output]` settings, as of the last reload. The store stays where it was opened.
+    settings: RwLock<output::Settings>,
+    /// Where history is kept, to only sync the output of history that syncs.
+    history: HistoryDatabase,
+    sync: RwLock<sync::Settings>,
 }
 
 #[derive(Default)]
@@ -86,7 +112,10 @@ impl SemanticComponent {
         Self {
             inner: Arc::new(SemanticComponentInner {
                 state: Mutex::new(SemanticState::default()),
+                output: OnceCell::new(),
             }),
+            handle: None,
+            task_handle: None,
         }
     }
 
@@ -108,20 +137,52 @@ impl Component for SemanticComponent {
         "semantic"
     }
 
-    async fn start(&mut self, _handle: DaemonHandle) -> Result<()> {
+    async fn start(&mut self, handle: DaemonHandle) -> Result<()> {
+        self.handle = Some(handle.clone());
+
+        match open_output_store(&handle).await {
+            Ok(output) => {
+                let _ = self.inner.output.set(output);
+                self.task_handle = Some(tokio::spawn(prune_loop(self.inner.clone())));
+            }
+            Err(e) => {
+                tracing::warn!("failed to open the output store, keeping output in memory: {e}");
+            }
+        }
+
         tracing::info!("semantic component started");
         Ok(())
     }
 
     async fn handle_event(&mut self, event: &DaemonEvent) -> Result<()> {
-        if let DaemonEvent::HistoryEnded(history) = event {
-            self.inner.record_history(history.clone()).await;
+        match event {
+            DaemonEvent::HistoryEnded(history) => {
+                self.inner.record_history(history.clone()).await;
+            }
+            DaemonEvent::HistorySynced(_) | DaemonEvent::SyncCompleted { .. } => {
+                // Output synced from elsewhere may be large, so don't hold up other events.
+                let inner = self.inner.clone();
+                tokio::spawn(async move { inner.build_output().await });
+            }
+            DaemonEvent::HistoryDeleted { ids } => {
+                self.inner.delete_output(ids).await;
+            }
+            DaemonEvent::SettingsReloaded => {
+                if let Some(handle) = &self.handle {
+                    self.inner.reload_settings(handle).await;
+                }
+            }
+            _ => {}
         }
 
         Ok(())
     }
 
     async fn stop(&mut self) -> Result<()> {
+        if let Some(handle) = self.task_handle.take() {
+            handle.abort();
+        }
+
         let state = self.inner.state.lock().await;
         tracing::info!(
             sessions = state.sessions.len(),
@@ -136,21 +197,186 @@ impl Component for SemanticComponent {
 
 impl SemanticComponentInner {
     async fn record_capture(&self, capture: CommandCapture) -> bool {
-        let mut state = self.state.lock().await;
-        state.record_capture(capture)
+        // Copied before the state takes the capture, to keep on disk once it's accepted.
+        let output = self.output.get().and_then(|_| captured_output(&capture));
+
+        let (recorded, history_ended) = {
+            let mut state = self.state.lock().await;
+            let recorded = state.record_capture(capture);
+            let ended = output.as_ref().is_some_and(|output| state.has_history(&output.history_id));
+            drop(state);
+            (recorded, ended)
+        };
+
+        if recorded
+            && let Some(output) = output
+            && let Some(persisted) = self.output.get()
+        {
+            if let Err(e) = persisted.store.save(&output).await {
+                tracing::warn!(history_id = %output.history_id, "failed to keep command output: {e}");
+            } else if history_ended {
+                persisted.share(&output).await;
+            }
+        }
+
+        recorded
     }
 
     async fn record_history(&self, history: History) {
-        let mut state = self.state.lock().await;
-        state.record_history(history);
+        let output = self.state.lock().await.record_history(history);
+
+        // Output is only shared once its history has ended, as whether that syncs is known then.
+        if let (Some(output), Some(persisted)) = (output, self.output.get()) {
+            persisted.share(&output).await;
+        }
     }
 
     async fn command_output(&self, request: &CommandOutputRequest) -> CommandOutputReply {
-        let mut state = self.state.lock().await;
-        state.command_output(request)
+        let reply = self.state.lock().await.command_output(request);
+        if reply.found {
+            return reply;
+        }
+
+        // Output that's no longer in memory may still be on disk.
+        let (Some(persisted), Some(history_id)) =
+            (self.output.get(), history_id_from_str(Some(&request.history_id)))
+        else {
+            return reply;
+        };
+
+        match persisted.store.load(&history_id).await {
+            Ok(Some(output)) => output_reply(
+                &output.output,
+                &request.ranges,
+                output.truncated,
+                output.observed_bytes,
+            ),
+            Ok(None) => reply,
+            Err(e) => {
+                tracing::warn!(history_id = %history_id, "failed to load command output: {e}");
+                reply
+            }
+        }
+    }
+
+    async fn build_output(&self) {
+        let Some(persisted) = self.output.get() else {
+            return;
+        };
+
+        if let Err(e) = persisted
+            .store
+            .build(&persisted.history, &persisted.settings(), OffsetDateTime::now_utc())
+            .await
+        {
+            tracing::error!("failed to build command output synced from elsewhere: {e}");
+        }
+    }
+
+    async fn delete_output(&self, ids: &[HistoryId]) {
+        let Some(persisted) = self.output.get() else {
+            return;
+        };
+
+        if let Err(e) = persisted.store.delete(ids, &persisted.settings()).await {
+            tracing::error!("failed to delete the output of deleted history: {e}");
+        }
+    }
+
+    /// Pick up changes to the `[output]` and `[sync]` settings. A new `output.db_path` only
+    /// applies once the daemon restarts.
+    async fn reload_settings(&self, handle: &DaemonHandle) {
+        let Some(persisted) = self.output.get() else {
+            return;
+        };
+
+        let (settings, sync) = {
+            let settings = handle.settings().await;
+            (settings.output.clone(), settings.sync_filter().await)
+        };
+        let sync = match sync {
+            Ok(sync) => sync,
+            Err(e) => {
+                tracing::warn!("failed to load the sync settings, keeping the old ones: {e}");
+                return;
+            }
+        };
+
+        *persisted.settings.write() = settings;
+        *persisted.sync.write() = sync;
     }
 }
 
+impl PersistedOutput {
+    fn settings(&self) -> output::Settings {
+        self.settings.read().clone()
+    }
+
+    /// Sync `output`, if `[output]` syncs and so does its history.
+    async fn share(&self, output: &CommandOutput) {
+        let (settings, sync) = (self.settings(), self.sync.read().clone());
+        if let Err(e) = self.store.share(output, &self.history, &settings, &sync).await {
+            tracing::warn!(history_id = %output.history_id, "failed to sync command output: {e}");
+        }
+    }
+}
+
+async fn open_output_store(handle: &DaemonHandle) -> Result<PersistedOutput> {
+    let (settings, timeout, sync) = {
+        let settings = handle.settings().await;
+        (settings.output.clone(), settings.local_timeout, settings.sync_filter().await?)
+    };
+
+    let db = OutputDatabase::new(&settings.db_path, timeout).await?;
+    let host_id = Settings::host_id().await?;
+
+    Ok(PersistedOutput {
+        store: OutputStore::new(
+            handle.store().clone(),
+            db,
+            host_id,
+            handle.encryption_key().clone(),
+        ),
+        settings: RwLock::new(settings),
+        history: handle.history_db().clone(),
+        sync: RwLock::new(sync),
+    })
+}
+
+/// Build output synced while the daemon wasn't running, then delete what the `[output]` settings
+/// no longer keep, every [`PRUNE_INTERVAL`].
+async fn prune_loop(inner: Arc<SemanticComponentInner>) {
+    let mut ticker = interval(PRUNE_INTERVAL);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    inner.build_output().await;
+
+    loop {
+        ticker.tick().await;
+
+        let Some(persisted) = inner.output.get() else {
+            return;
+        };
+
+        match persisted.store.prune(&persisted.settings(), OffsetDateTime::now_utc()).await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!(count, "deleted command output past the output limits"),
+            Err(e) => tracing::error!("failed to delete old command output: {e}"),
+        }
+    }
+}
+
+/// The output of an accepted capture, as it's kept on disk.
+fn captured_output(capture: &CommandCapture) -> Option<CommandOutput> {
+    Some(CommandOutput {
+        history_id: history_id_from_str(capture.history_id.as_deref())?,
+        timestamp: OffsetDateTime::now_utc(),
+        output: capture.output.clone(),
+        truncated: capture.output_truncated,
+        observed_bytes: capture.output_observed_bytes.max(capture.output.len() as u64),
+    })
+}
+
 impl SemanticState {
     fn record_capture(&mut self, mut capture: CommandCapture) -> bool {
         let Some(history_id) = history_id_from_str(capture.history_id.as_deref()) else {
@@ -198,14 +424,16 @@ impl SemanticState {
         true
     }
 
-    fn record_history(&mut self, history: History) {
+    /// Associate `history` with its capture, or keep it until the capture arrives. Returns the
+    /// output of the capture it was associated with.
+    fn record_history(&mut self, history: History) -> Option<CommandOutput> {
         let history_id = history.id.clone();
 
         if let Some(capture_ref) = self.history_index.get(&history_id).cloned() {
             if let Some(stored) = self.stored_capture_mut(&capture_ref) {
                 stored.record.history = Some(history);
                 log_record(&stored.record, "associated semantic command capture with history");
-                return;
+                return captured_output(&stored.record.capture);
             }
 
             self.history_index.remove(&history_id);
@@ -217,6 +445,17 @@ impl SemanticState {
             "history ended before semantic capture arrived"
         );
         push_pending_history(&mut self.pending_histories, history);
+        None
+    }
+
+    /// Whether the capture of `history_id` has been associated with its history.
+    fn has_history(&self, history_id: &HistoryId) -> bool {
+        self.history_index
+            .get(history_id)
+            .and_then(|capture_ref| {
+                self.sessions.get(&capture_ref.session_id)?.stored_capture(capture_ref.capture_id)
+            })
+            .is_some_and(|stored| stored.record.history.is_some())
     }
 
     fn command_output(&mut self, request: &CommandOutputRequest) -> CommandOutputReply {
@@ -243,19 +482,13 @@ impl SemanticState {
     ) -> Option<CommandOutputReply> {
         let stored =
             self.sessions.get(&capture_ref.session_id)?.stored_capture(capture_ref.capture_id)?;
-        let output = &stored.record.capture.output;
-        let output_observed_bytes =
-            stored.record.capture.output_observed_bytes.max(output.len() as u64);
-
-        Some(CommandOutputReply {
-            found: true,
-            output: String::new(),
-            total_bytes: output.len() as u64,
-            total_lines: output.lines().count() as u64,
-            lines: select_output_ranges(output, ranges),
-            output_truncated: stored.record.capture.output_truncated,
-            output_observed_bytes,
-        })
+
+        Some(output_reply(
+            &stored.record.capture.output,
+            ranges,
+            stored.record.capture.output_truncated,
+            stored.record.capture.output_observed_bytes,
+        ))
     }
 
     fn push_record(
@@ -476,6 +709,23 @@ fn trim_front<T>(records: &mut VecDeque<T>, max_len: usize) {
     }
 }
 
+fn output_reply(
+    output: &str,
+    ranges: &[crate::semantic::OutputRange],
+    output_truncated: bool,
+    output_observed_bytes: u64,
+) -> CommandOutputReply {
+    CommandOutputReply {
+        found: true,
+        output: String::new(),
+        total_bytes: output.len() as u64,
+        total_lines: output.lines().count() as u64,
+        lines: select_output_ranges(output, ranges),
+        output_truncated,
+        output_observed_bytes: output_observed_bytes.max(output.len() as u64),
+    }
+}
+
 fn command_output_not_found() -> CommandOutputReply {
     CommandOutputReply {
         found: false,
@@ -577,7 +827,9 @@ fn log_record(record: &SemanticCommandRecord, message: &'static str) {
 
 #[cfg(test)]
 mod tests {
-    use atuin_domain::record::CmdOrigin;
+    use atuin_client::record::sqlite_store::SqliteStore;
+    use atuin_common::utils::uuid_v7;
+    use atuin_domain::record::{CmdOrigin, HostId, RecordTag};
     use rstest::rstest;
     use time::OffsetDateTime;
 
@@ -628,6 +880,89 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn falls_back_to_output_kept_on_disk() {
+        let record_store = SqliteStore::new(":memory:", 1.0).await.unwrap();
+        let db = OutputDatabase::new("sqlite::memory:", 1.0).await.unwrap();
+        let component = SemanticComponent::new();
+        let _ = component.inner.output.set(PersistedOutput {
+            store: OutputStore::new(record_store, db, HostId(uuid_v7()), [0u8; 32].into()),
+            settings: RwLock::new(output::Settings::default()),
+            history: HistoryDatabase::new("sqlite::memory:", 1.0).await.unwrap(),
+            sync: RwLock::new(sync::Settings::default()),
+        });
+
+        assert!(
+            component
+                .inner
+                .record_capture(capture(Some("id-1"), Some("session-1"), "one\ntwo"))
+                .await
+        );
+
+        // As if the daemon restarted.
+        *component.inner.state.lock().await = SemanticState::default();
+
+        let reply = component
+            .inner
+            .command_output(&CommandOutputRequest {
+                history_id: "id-1".to_string(),
+                ranges: Vec::new(),
+            })
+            .await;
+        assert!(reply.found);
+        assert_eq!(reply.lines, vec![output_line(1, "one"), output_line(2, "two")]);
+        assert!(
+            !component
+                .inner
+                .command_output(&CommandOutputRequest {
+                    history_id: "id-2".to_string(),
+                    ranges: Vec::new(),
+                })
+                .await
+                .found
+        );
+    }
+
+    #[tokio::test]
+    async fn syncs_output_once_its_history_ends() {
+        let record_store = SqliteStore::new(":memory:", 1.0).await.unwrap();
+        let history_db = HistoryDatabase::new("sqlite::memory:", 1.0).await.unwrap();
+        history_db.mark_local_only(&HistoryId("local".to_string())).await.unwrap();
+
+        let component = SemanticComponent::new();
+        let _ = component.inner.output.set(PersistedOutput {
+            store: OutputStore::new(
+                record_store.clone(),
+                OutputDatabase::new("sqlite::memory:", 1.0).await.unwrap(),
+                HostId(uuid_v7()),
+                [0u8; 32].into(),
+            ),
+            settings: RwLock::new(output::Settings {
+                sync: true,
+                ..output::Settings::default()
+            }),
+            history: history_db,
+            sync: RwLock::new(sync::Settings::default()),
+        });
+        let synced = || record_store.len_tag(&RecordTag::CommandOutput);
+
+        // Captured before its history ended.
+        component.inner.record_capture(capture(Some("before"), Some("session-1"), "one")).await;
+        assert_eq!(synced().await.unwrap(), 0);
+        component.inner.record_history(history("before", "session-1", "make")).await;
+        assert_eq!(synced().await.unwrap(), 1);
+
+        // Captured after.
+        component.inner.record_history(history("after", "session-1", "make")).await;
+        component.inner.record_capture(capture(Some("after"), Some("session-1"), "two")).await;
+        assert_eq!(synced().await.unwrap(), 2);
+
+        // History kept local keeps its output local too.
+        component.inner.record_history(history("local", "session-1", "make")).await;
+        component.inner.record_capture(capture(Some("local"), Some("session-1"), "three")).await;
+        assert_eq!(synced().await.unwrap(), 2);
+    }
+
     #[test]
     fn drops_capture_without_history_id() {
         let mut state = SemanticState::default();
@@ -6,9 +6,10 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use atuin_client::history::HistoryId;
-use atuin_client::history::store::HistoryStore;
+use atuin_client::history::store::{Built, HistoryStore};
 use atuin_client::record::sync::{ClientSource, SyncEngine};
 use atuin_client::settings::Settings;
+use atuin_domain::record::RecordId;
 use atuin_dotfiles::store::AliasStore;
 use atuin_dotfiles::store::var::VarStore;
 use eyre::Result;
@@ -24,6 +25,8 @@ use crate::events::DaemonEvent;
 enum SyncCommand {
     /// Trigger an immediate sync.
     ForceSync,
+    /// Settings were reloaded; pick up a new `sync_frequency`.
+    SettingsReloaded,
     /// Stop the sync loop.
     Stop,
 }
@@ -44,6 +47,7 @@ enum SyncState {
 /// - Runs a background sync loop on a configurable interval
 /// - Implements exponential backoff on sync failures
 /// - Responds to ForceSync events for immediate sync
+/// - Reschedules when a settings reload changes the sync frequency
 /// - Emits SyncCompleted/SyncFailed events
 pub struct SyncComponent {
     task_handle: Option<tokio::task::JoinHandle<()>>,
@@ -83,11 +87,16 @@ impl Component for SyncComponent {
     }
 
     async fn handle_event(&mut self, event: &DaemonEvent) -> Result<()> {
-        if let DaemonEvent::ForceSync = event {
-            tracing::info!("force sync requested");
-            if let Some(tx) = &self.command_tx {
-                let _ = tx.send(SyncCommand::ForceSync).await;
+        let command = match event {
+            DaemonEvent::ForceSync => {
+                tracing::info!("force sync requested");
+                SyncCommand::ForceSync
             }
+            DaemonEvent::SettingsReloaded => SyncCommand::SettingsReloaded,
+            _ => return Ok(()),
+        };
+        if let Some(tx) = &self.command_tx {
+            let _ = tx.send(command).await;
         }
         Ok(())
     }
@@ -177,6 +186,15 @@ async fn sync_loop(handle: DaemonHandle, mut cmd_rx: mpsc::Receiver<SyncCommand>
                             &settings,
                         ).await;
                     }
+                    Some(SyncCommand::SettingsReloaded) => {
+                        let frequency = handle.settings().await.daemon.sync_frequency;
+                        // While backing off, the backoff keeps its schedule; the new
+                        // frequency takes over once a sync succeeds.
+                        if sync_state == SyncState::Idle && ticker.period().as_secs() != frequency {
+                            tracing::info!(frequency, "sync frequency changed, rescheduling");
+                            reschedule(&mut ticker, frequency);
+                        }
+                    }
                     Some(SyncCommand::Stop) | None => {
                         tracing::info!("sync loop stopping");
                         break;
@@ -265,27 +283,8 @@ async fn do_sync_tick(
                 "sync complete"
             );
 
-            // `incremental_build` already yields in bounded batches - an initial sync (on
-            // backfill, eg.) risks being dozens of GB of RAM otherwise.
-            let batches = history_store.incremental_build(handle.history_db(), &downloaded_records);
-            futures::pin_mut!(batches);
-
-            while let Some(batch) = batches.next().await {
-                match batch {
-                    Ok(histories) if !histories.is_empty() => {
-                        // Only the IDs go on the bus; the rows themselves are already in sqlite.
-                        let ids: Arc<[HistoryId]> =
-                            histories.iter().map(|h| h.id.clone()).collect();
-                        handle.emit(DaemonEvent::HistorySynced(ids));
-                    }
-                    Ok(_) => {}
-                    // Legacy behavior was to abort on the first error.
-                    Err(e) => {
-                        tracing::error!("failed to build history from downloaded records: {e}");
-                        break;
-                    }
-                }
-            }
+            build_downloaded(handle, history_store, alias_store, var_store, &downloaded_records)
+                .await;
 
             // Emit sync completed event
             handle.emit(DaemonEvent::SyncCompleted {
@@ -293,22 +292,9 @@ async fn do_sync_tick(
                 downloaded: downloaded_records.len(),
             });
 
-            // Rebuild alias and var stores
-            if let Err(e) = alias_store.build().await {
-                tracing::error!("failed to rebuild alias store: {e}");
-            }
-            if let Err(e) = var_store.build().await {
-                tracing::error!("failed to rebuild var store: {e}");
-            }
-
             // Reset backoff on success
             if ticker.period().as_secs() != settings.daemon.sync_frequency {
-                *ticker = time::interval_at(
-                    tokio::time::Instant::now()
-                        + Duration::from_secs(settings.daemon.sync_frequency),
-                    time::Duration::from_secs(settings.daemon.sync_frequency),
-                );
-                ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+                reschedule(ticker, settings.daemon.sync_frequency);
             }
 
             // Store sync time
@@ -320,3 +306,55 @@ async fn do_sync_tick(
         }
     }
 }
+
+/// Sync every `frequency` seconds, starting `frequency` seconds from now.
+fn reschedule(ticker: &mut time::Interval, frequency: u64) {
+    *ticker = time::interval_at(
+        tokio::time::Instant::now() + Duration::from_secs(frequency),
+        time::Duration::from_secs(frequency),
+    );
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+}
+
+/// Build the history, aliases and vars of records that arrived in the store from elsewhere,
+/// telling the rest of the daemon about the new history.
+pub(crate) async fn build_downloaded(
+    handle: &DaemonHandle,
+    history_store: &HistoryStore,
+    alias_store: &AliasStore,
+    var_store: &VarStore,
+    downloaded: &[RecordId],
+) {
+    // `incremental_build` already yields in bounded batches - an initial sync (on
+    // backfill, eg.) risks being dozens of GB of RAM otherwise.
+    let batches = history_store.incremental_build(handle.history_db(), downloaded);
+    futures::pin_mut!(batches);
+
+    while let Some(batch) = batches.next().await {
+        match batch {
+            Ok(Built::Created(histories)) if !histories.is_empty() => {
+                // Only the IDs go on the bus; the rows themselves are already in sqlite.
+                let ids: Arc<[HistoryId]> = histories.iter().map(|h| h.id.clone()).collect();
+                handle.emit(DaemonEvent::HistorySynced(ids));
+            }
+            Ok(Built::Amended(histories)) if !histories.is_empty() => {
+                let ids = histories.into_iter().map(|h| h.id).collect();
+                handle.emit(DaemonEvent::HistoryAmended { ids });
+            }
+            Ok(_) => {}
+            // Legacy behavior was to abort on the first error.
+            Err(e) => {
+                tracing::error!("failed to build history from downloaded records: {e}");
+                break;
+            }
+        }
+    }
+
+    // Rebuild alias and var stores
+    if let Err(e) = alias_store.build().await {
+        tracing::error!("failed to rebuild alias store: {e}");
+    }
+    if let Err(e) = var_store.build().await {
+        tracing::error!("failed to rebuild var store: {e}");
+    }
+}
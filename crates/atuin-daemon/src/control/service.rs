@@ -9,7 +9,7 @@ use tracing::{Level, info, instrument};
 
 use super::control_server::{Control, ControlServer};
 use super::send_event_request::Event;
-use super::{SendEventRequest, SendEventResponse};
+use super::{ConfigStatusRequest, ConfigStatusResponse, SendEventRequest, SendEventResponse};
 use crate::daemon::DaemonHandle;
 use crate::events::DaemonEvent;
 
@@ -47,10 +47,29 @@ impl Control for ControlService {
         let daemon_event = proto_event_to_daemon_event(event);
 
         info!(?daemon_event, "received control event");
-        self.handle.emit(daemon_event);
+        if let DaemonEvent::SettingsReloaded = daemon_event {
+            // Re-read the config first, so components see what changed; this
+            // emits the event itself.
+            self.handle.reload_settings().await.map_err(|e| {
+                Status::failed_precondition(format!("failed to reload settings: {e:#}"))
+            })?;
+        } else {
+            self.handle.emit(daemon_event);
+        }
 
         Ok(Response::new(SendEventResponse {}))
     }
+
+    #[instrument(skip_all, level = Level::DEBUG, name = "control_config_status")]
+    async fn config_status(
+        &self,
+        _request: Request<ConfigStatusRequest>,
+    ) -> Result<Response<ConfigStatusResponse>, Status> {
+        Ok(Response::new(ConfigStatusResponse {
+            error: self.handle.config_error().await,
+            restart_required: self.handle.restart_required().await,
+        }))
+    }
 }
 
 /// Convert a proto event to a daemon event.
@@ -61,6 +80,9 @@ fn proto_event_to_daemon_event(event: Event) -> DaemonEvent {
         Event::HistoryDeleted(e) => DaemonEvent::HistoryDeleted {
             ids: e.ids.into_iter().map(HistoryId).collect(),
         },
+        Event::HistoryAmended(e) => DaemonEvent::HistoryAmended {
+            ids: e.ids.into_iter().map(HistoryId).collect(),
+        },
         Event::ForceSync(_) => DaemonEvent::ForceSync,
         Event::SettingsReloaded(_) => DaemonEvent::SettingsReloaded,
         Event::Shutdown(_) => DaemonEvent::ShutdownRequested,
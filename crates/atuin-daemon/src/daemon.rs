@@ -15,13 +15,17 @@ use atuin_client::api_client::caps_client;
 use atuin_client::database::Sqlite as HistoryDatabase;
 use atuin_client::record::sqlite_store::SqliteStore;
 use atuin_client::settings::Settings;
+use atuin_client::settings::watcher::check_theme;
 use atuin_common::encryption::paseto_v4;
 use atuin_domain::caps::CapClient;
 use enum_dispatch::enum_dispatch;
 use eyre::{Context, Result};
 use tokio::sync::{RwLock, broadcast};
 
-use crate::components::{HistoryComponent, SearchComponent, SemanticComponent, SyncComponent};
+use crate::components::{
+    HistoryComponent, PeerComponent, RetentionComponent, SearchComponent, SemanticComponent,
+    SyncComponent,
+};
 use crate::events::DaemonEvent;
 
 // ============================================================================
@@ -39,6 +43,12 @@ pub struct DaemonState {
     // Configuration (mutable - can be reloaded)
     settings: RwLock<Settings>,
 
+    // Why the config last failed to load or validate, if it did
+    config_error: RwLock<Option<String>>,
+
+    // The settings the daemon started with, to tell which changes need a restart
+    initial_settings: Settings,
+
     // Encryption key (immutable - derived at startup)
     encryption_key: paseto_v4::Key,
 
@@ -50,6 +60,15 @@ pub struct DaemonState {
     caps: Arc<CapClient>,
 }
 
+/// Check for settings the daemon can't run with, though the config itself loads.
+pub fn check_settings(settings: &Settings) -> Result<(), String> {
+    if settings.daemon.sync_frequency == 0 {
+        return Err("daemon.sync_frequency must be longer than zero".to_string());
+    }
+
+    Ok(())
+}
+
 // ============================================================================
 // DaemonHandle
 // ============================================================================
@@ -124,21 +143,78 @@ impl DaemonHandle {
     /// Reload settings from disk and emit a SettingsReloaded event.
     ///
     /// Components listening for `SettingsReloaded` can then re-read settings
-    /// via `handle.settings()` to pick up the changes.
+    /// via `handle.settings()` to pick up the changes. If the config doesn't
+    /// load, the current settings are kept and the error is recorded for
+    /// [`Self::config_error`].
     pub async fn reload_settings(&self) -> Result<()> {
-        let new_settings = Settings::new()?;
-        self.apply_settings(new_settings).await;
-        Ok(())
+        let new_settings = match Settings::new() {
+            Ok(settings) => settings,
+            Err(e) => {
+                self.set_config_error(Some(format!("{e:#}"))).await;
+                return Err(e);
+            }
+        };
+        self.set_config_error(check_theme(&new_settings).err()).await;
+        self.apply_settings(new_settings).await
     }
 
     /// Apply already-loaded settings and emit a SettingsReloaded event.
     ///
     /// Use this when settings have already been loaded (e.g., from a file watcher)
-    /// to avoid parsing the config file twice.
-    pub async fn apply_settings(&self, settings: Settings) {
+    /// to avoid parsing the config file twice. Settings the daemon can't run with
+    /// (see [`check_settings`]) are not applied, and are recorded for
+    /// [`Self::config_error`] instead.
+    pub async fn apply_settings(&self, settings: Settings) -> Result<()> {
+        if let Err(e) = check_settings(&settings) {
+            self.set_config_error(Some(e.clone())).await;
+            eyre::bail!(e);
+        }
+
         *self.state.settings.write().await = settings;
         self.emit(DaemonEvent::SettingsReloaded);
         tracing::info!("settings applied");
+        Ok(())
+    }
+
+    /// The settings that have changed since the daemon started, but that it only reads when it
+    /// starts, by key.
+    pub async fn restart_required(&self) -> Vec<String> {
+        let settings = self.state.settings.read().await;
+        let initial = &self.state.initial_settings;
+
+        let changed = [
+            ("db_path", settings.db_path != initial.db_path),
+            ("record_store_path", settings.record_store_path != initial.record_store_path),
+            ("key_path", settings.key_path != initial.key_path),
+            ("sync_address", settings.sync_address != initial.sync_address),
+            ("output.db_path", settings.output.db_path != initial.output.db_path),
+            ("daemon.socket_path", settings.daemon.socket_path != initial.daemon.socket_path),
+            (
+                "daemon.systemd_socket",
+                settings.daemon.systemd_socket != initial.daemon.systemd_socket,
+            ),
+            ("daemon.tcp_port", settings.daemon.tcp_port != initial.daemon.tcp_port),
+        ];
+        drop(settings);
+
+        changed
+            .into_iter()
+            .filter(|(_, changed)| *changed)
+            .map(|(key, _)| key.to_string())
+            .collect()
+    }
+
+    /// Why the config last failed to load or validate, or `None` if it's fine.
+    pub async fn config_error(&self) -> Option<String> {
+        self.state.config_error.read().await.clone()
+    }
+
+    /// Record the outcome of the latest config load; `None` clears any earlier error.
+    pub async fn set_config_error(&self, error: Option<String>) {
+        if let Some(error) = &error {
+            tracing::warn!(%error, "config error");
+        }
+        *self.state.config_error.write().await = error;
     }
 
     /// Get the encryption key.
@@ -256,6 +332,8 @@ pub trait Component: Send + Sync + Into<AnyComponent> {
 #[enum_dispatch(Component)]
 pub enum AnyComponent {
     History(HistoryComponent),
+    Peer(PeerComponent),
+    Retention(RetentionComponent),
     Search(SearchComponent),
     Semantic(SemanticComponent),
     Sync(SyncComponent),
@@ -448,6 +526,7 @@ impl DaemonBuilder {
     pub fn build(self) -> Result<Daemon> {
         let store = self.store.ok_or_else(|| eyre::eyre!("store is required"))?;
         let history_db = self.history_db.ok_or_else(|| eyre::eyre!("history_db is required"))?;
+        check_settings(&self.settings).map_err(|e| eyre::eyre!(e))?;
 
         // Load encryption key
         let encryption_key = paseto_v4::Key::try_load_or_generate(&self.settings.key_path)
@@ -465,7 +544,9 @@ impl DaemonBuilder {
         // Create the shared state
         let state = Arc::new(DaemonState {
             event_tx,
+            initial_settings: self.settings.clone(),
             settings: RwLock::new(self.settings),
+            config_error: RwLock::new(None),
             encryption_key,
             history_db,
             store,
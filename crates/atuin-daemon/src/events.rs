@@ -67,6 +67,14 @@ pub enum DaemonEvent {
         ids: Vec<HistoryId>,
     },
 
+    /// Specific history items were edited or redacted, here or on another host.
+    ///
+    /// The search component should reindex these.
+    HistoryAmended {
+        /// IDs of the amended history entries.
+        ids: Vec<HistoryId>,
+    },
+
     /// Settings have changed, components should reload if needed.
     SettingsReloaded,
 
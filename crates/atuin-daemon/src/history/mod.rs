@@ -2,6 +2,10 @@
 //!
 //! This module contains the proto-generated types for the history gRPC service.
 
+use atuin_client::history::History;
+use atuin_common::time::OffsetDateTimeExt;
+use time::OffsetDateTime;
+
 // Include the generated proto code
 tonic::include_proto!("history");
 
@@ -25,6 +29,47 @@ impl From<AuthorKind> for Option<atuin_client::history::AuthorKind> {
     }
 }
 
+impl From<History> for HistoryEntry {
+    fn from(history: History) -> Self {
+        Self {
+            timestamp: history.timestamp.unix_timestamp_nanos() as u64,
+            id: history.id.0,
+            command: history.command,
+            cwd: history.cwd,
+            session: history.session,
+            hostname: history.cmd_origin.into_string(),
+            author: history.author,
+            intent: history.intent.unwrap_or_default(),
+            exit: history.exit,
+            duration: history.duration,
+            shell: history.shell.unwrap_or_default(),
+            author_kind: AuthorKind::from(history.author_kind) as i32,
+        }
+    }
+}
+
+impl From<HistoryEntry> for History {
+    fn from(entry: HistoryEntry) -> Self {
+        let author_kind = entry.author_kind().into();
+        Self::from_db()
+            .id(entry.id)
+            .timestamp(OffsetDateTime::from_unix_nanos_u64(entry.timestamp))
+            .command(entry.command)
+            .cwd(entry.cwd)
+            .exit(entry.exit)
+            .duration(entry.duration)
+            .session(entry.session)
+            .hostname(entry.hostname)
+            .author(entry.author)
+            .intent(Some(entry.intent).filter(|intent| !intent.is_empty()))
+            .deleted_at(None)
+            .shell(Some(entry.shell).filter(|shell| !shell.is_empty()))
+            .author_kind(author_kind)
+            .build()
+            .into()
+    }
+}
+
 /// Trait for reply types that include the daemon version and protocol version.
 pub trait VersionedReply {
     fn version(&self) -> &str;
@@ -16,15 +16,20 @@ pub mod server;
 
 // Re-export core daemon types for convenience
 // Re-export client helpers
-pub use client::{ControlClient, SemanticClient, emit_event, emit_event_with_settings};
+pub use client::{
+    ControlClient, SemanticClient, emit_event, emit_event_with_settings, search_history,
+};
 // Re-export components
-pub use components::{HistoryComponent, SearchComponent, SemanticComponent, SyncComponent};
+pub use components::{
+    HistoryComponent, PeerComponent, RetentionComponent, SearchComponent, SemanticComponent,
+    SyncComponent,
+};
 pub use daemon::{AnyComponent, Daemon, DaemonBuilder, DaemonHandle};
 pub use events::DaemonEvent;
 
 /// Boot the daemon using the new component-based architecture.
 ///
-/// This creates a daemon with the standard components (history, search, sync),
+/// This creates a daemon with the standard components (history, search, sync, peer, retention),
 /// starts the gRPC server with their services, and runs the event loop.
 pub async fn boot(
     settings: Settings,
@@ -36,6 +41,8 @@ pub async fn boot(
     let search_component = SearchComponent::new();
     let semantic_component = SemanticComponent::new();
     let sync_component = SyncComponent::new();
+    let peer_component = PeerComponent::new();
+    let retention_component = RetentionComponent::new();
 
     // Get the gRPC services before moving components into the daemon
     // (The services share state with the components via Arc)
@@ -51,6 +58,8 @@ pub async fn boot(
         .component(search_component)
         .component(semantic_component)
         .component(sync_component)
+        .component(peer_component)
+        .component(retention_component)
         .build()?;
 
     // Get a handle for the control service and gRPC server shutdown
@@ -65,14 +74,36 @@ pub async fn boot(
     // Spawn config file watcher to reload settings on changes
     if let Ok(watcher) = global_settings_watcher() {
         let mut settings_rx = watcher.subscribe();
+        let mut errors_rx = watcher.subscribe_errors();
         let watcher_handle = handle.clone();
+        let error = errors_rx.borrow_and_update().clone();
+        watcher_handle.set_config_error(error).await;
         tokio::spawn(async move {
             tracing::info!("config file watcher started");
-            while settings_rx.changed().await.is_ok() {
-                // Use the already-loaded settings from the watcher
-                // (avoids parsing the config file twice)
-                let new_settings = (*settings_rx.borrow()).clone();
-                watcher_handle.apply_settings((*new_settings).clone()).await;
+            loop {
+                tokio::select! {
+                    changed = settings_rx.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                        // Use the already-loaded settings from the watcher
+                        // (avoids parsing the config file twice). Settings the daemon
+                        // can't run with are kept out, and reported as a config error.
+                        let new_settings = (*settings_rx.borrow_and_update()).clone();
+                        let _ = watcher_handle.apply_settings((*new_settings).clone()).await;
+                    }
+                    changed = errors_rx.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                        // The watcher only knows whether the config loads, not whether the
+                        // daemon can run with it.
+                        let error = errors_rx.borrow_and_update().clone().or_else(|| {
+                            daemon::check_settings(&settings_rx.borrow()).err()
+                        });
+                        watcher_handle.set_config_error(error).await;
+                    }
+                }
             }
             tracing::debug!("config file watcher stopped");
         });
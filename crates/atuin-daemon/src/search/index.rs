@@ -6,16 +6,24 @@
 //! - Efficient fuzzy matching (fewer items to match)
 //! - Frecency-based ranking (frequency + recency)
 //! - Dynamic filtering by directory, host, session, etc.
+//! - Inline query filters (`exit:!0`, `dur:>30s`, ...), checked against each invocation
 
 use std::borrow::Cow;
 use std::cmp::Ordering;
 use std::collections::HashSet;
+use std::ops::Range;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
 
-use atuin_client::history::History;
+use atuin_client::database::predicate::{Invocation, QueryFilter, split_filters};
+use atuin_client::database::search::SearchQuery;
+use atuin_client::history::{AuthorKind, History};
+pub use atuin_client::ranking::FrecencyData;
+use atuin_client::ranking::RankingWeights;
 use atuin_client::settings::Search;
 use atuin_common::filter::OrFilter;
 use atuin_common::path::DisplayRichExt;
+use atuin_common::time::OffsetDateTimeExt;
 use dashmap::DashMap;
 use lasso::{Spur, ThreadedRodeo};
 use parking_lot::RwLock;
@@ -31,74 +39,96 @@ fn parse_uuid_bytes(s: &str) -> Option<[u8; 16]> {
     Uuid::parse_str(s).ok().map(|u| *u.as_bytes())
 }
 
-/// Pre-computed frecency data for O(1) lookup.
-#[derive(Debug, Clone, Default)]
-pub struct FrecencyData {
-    /// Total number of times this command was used.
-    pub count: u32,
-    /// Most recent usage timestamp (unix seconds).
-    pub last_used: i64,
+/// A single run of a command: the parts inline query filters look at, plus enough to hand the
+/// whole history entry back with a search result.
+struct IndexedInvocation {
+    id: [u8; 16],
+    /// Whether the ID was stored hyphenated, as some older and imported entries are.
+    hyphenated: bool,
+    /// In unix nanoseconds.
+    timestamp: i64,
+    exit: i64,
+    duration: i64,
+    cwd: Spur,
+    host: Spur,
+    author: Spur,
+    /// The full `host:user` origin; `host` above is only its host part.
+    origin: Spur,
+    session: Spur,
+    shell: Option<Spur>,
+    intent: Option<Spur>,
+    author_kind: Option<AuthorKind>,
+    is_agent: bool,
 }
 
-impl FrecencyData {
-    /// Record a new usage of this command.
-    pub fn record_use(&mut self, timestamp: i64) {
-        self.count += 1;
-        if timestamp > self.last_used {
-            self.last_used = timestamp;
+impl IndexedInvocation {
+    fn new(history: &History, id: [u8; 16], interner: &ThreadedRodeo) -> Self {
+        let invocation = Invocation::from(history);
+        Self {
+            id,
+            hyphenated: history.id.0.contains('-'),
+            timestamp: invocation.timestamp,
+            exit: invocation.exit,
+            duration: invocation.duration,
+            cwd: interner.get_or_intern(invocation.cwd),
+            host: interner.get_or_intern(invocation.host),
+            author: interner.get_or_intern(invocation.author),
+            origin: interner.get_or_intern(history.cmd_origin.as_str()),
+            session: interner.get_or_intern(&history.session),
+            shell: history.shell.as_deref().map(|shell| interner.get_or_intern(shell)),
+            intent: history.intent.as_deref().map(|intent| interner.get_or_intern(intent)),
+            author_kind: history.author_kind,
+            is_agent: invocation.is_agent,
         }
     }
 
-    /// Compute frecency score based on count and recency.
-    ///
-    /// Uses a decay function where more recent commands score higher.
-    /// The formula balances frequency (how often) with recency (how recent).
-    ///
-    /// Multipliers allow tuning the relative weights:
-    /// - `recency_mul`: Multiplier for recency score (default: 1.0)
-    /// - `frequency_mul`: Multiplier for frequency score (default: 1.0)
-    ///
-    /// A multiplier of 0.0 disables that component, 1.0 is unchanged, 2.0 doubles weight.
-    /// Values like 0.5 reduce weight by half, 1.5 increases by 50%, etc.
-    #[instrument(level = tracing::Level::TRACE, name = "index_frecency_compute")]
-    pub fn compute(&self, now: i64, recency_mul: f64, frequency_mul: f64) -> u32 {
-        if self.count == 0 {
-            return 0;
-        }
-
-        // Time-based decay: score decreases as time passes
-        let age_seconds = (now - self.last_used).max(0) as u64;
-        let age_hours = age_seconds / 3600;
-
-        // Decay factor: recent commands get higher scores
-        // - Last hour: multiplier ~1.0
-        // - Last day: multiplier ~0.5
-        // - Last week: multiplier ~0.1
-        // - Older: multiplier approaches 0
-        let recency_score: f64 = match age_hours {
-            0 => 100.0,
-            1..=6 => 90.0,
-            7..=24 => 70.0,
-            25..=72 => 50.0,
-            73..=168 => 30.0,
-            169..=720 => 15.0,
-            _ => 5.0,
+    /// Rebuild the history entry for this run of `command`.
+    fn to_history(&self, command: &str, interner: &ThreadedRodeo) -> History {
+        let resolve = |spur: Spur| interner.resolve(&spur).to_owned();
+        let id = Uuid::from_bytes(self.id);
+        let id = if self.hyphenated {
+            id.as_hyphenated().to_string()
+        } else {
+            id.as_simple().to_string()
         };
+        History::from_db()
+            .id(id)
+            .timestamp(OffsetDateTime::from_unix_nanos_i64(self.timestamp))
+            .command(command.to_owned())
+            .cwd(resolve(self.cwd))
+            .exit(self.exit)
+            .duration(self.duration)
+            .session(resolve(self.session))
+            .hostname(resolve(self.origin))
+            .author(resolve(self.author))
+            .intent(self.intent.map(resolve))
+            .deleted_at(None)
+            .shell(self.shell.map(resolve))
+            .author_kind(self.author_kind)
+            .build()
+            .into()
+    }
 
-        // Frequency boost: more uses = higher score (with diminishing returns)
-        let frequency_score = (f64::from(self.count).ln() * 20.0).min(100.0);
-
-        // Apply multipliers and combine scores, then round to u32
-        ((recency_score * recency_mul) + (frequency_score * frequency_mul)).round() as u32
+    fn resolve<'a>(&self, interner: &'a ThreadedRodeo) -> Invocation<'a> {
+        Invocation {
+            cwd: interner.resolve(&self.cwd),
+            host: interner.resolve(&self.host),
+            origin: interner.resolve(&self.origin),
+            session: interner.resolve(&self.session),
+            shell: self.shell.map(|shell| interner.resolve(&shell)),
+            author: interner.resolve(&self.author),
+            is_agent: self.is_agent,
+            exit: self.exit,
+            duration: self.duration,
+            timestamp: self.timestamp,
+        }
     }
 }
 
 /// Data for a unique command.
 pub struct CommandData {
-    /// History ID of the most recent invocation (16-byte UUID).
-    most_recent_id: [u8; 16],
-    /// Timestamp of the most recent invocation.
-    most_recent_timestamp: i64,
+    /// Position of the most recent invocation in `invocations`.
+    latest: usize,
     /// Pre-computed global frecency.
     pub global_frecency: FrecencyData,
 
@@ -110,6 +140,8 @@ pub struct CommandData {
     hosts: HashSet<Spur>,
     /// All sessions where this command has been run (as 16-byte UUIDs).
     sessions: HashSet<[u8; 16]>,
+    /// Every run of this command, for inline query filters.
+    invocations: Vec<IndexedInvocation>,
     /// Position of this command in `SearchIndex::haystack`, so filtered
     /// searches can walk the command map without hashing command strings.
     haystack_index: u32,
@@ -118,7 +150,7 @@ pub struct CommandData {
 impl CommandData {
     /// Create a new CommandData from a history entry.
     ///
-    /// Returns [`None`] if the history entry has invalid UUIDs or if the haystack index exceeds
+    /// Returns [`None`] if the history entry's ID isn't a UUID or if the haystack index exceeds
     /// 2^32.
     pub fn new(history: &History, haystack_index: usize, interner: &ThreadedRodeo) -> Option<Self> {
         let Ok(haystack_index) = u32::try_from(haystack_index) else {
@@ -129,7 +161,6 @@ impl CommandData {
         };
 
         let history_id = parse_uuid_bytes(&history.id.0)?;
-        let session = parse_uuid_bytes(&history.session)?;
         let timestamp = history.timestamp.unix_timestamp();
 
         let dir_key =
@@ -140,25 +171,22 @@ impl CommandData {
         global_frecency.record_use(timestamp);
 
         Some(Self {
-            most_recent_id: history_id,
-            most_recent_timestamp: timestamp,
+            latest: 0,
             global_frecency,
             directories: HashSet::from([dir_key]),
             hosts: HashSet::from([host_key]),
-            sessions: HashSet::from([session]),
+            sessions: parse_uuid_bytes(&history.session).into_iter().collect(),
+            invocations: vec![IndexedInvocation::new(history, history_id, interner)],
             haystack_index,
         })
     }
 
     /// Add an invocation from a history entry.
-    /// Returns false if the history entry has invalid UUIDs.
+    /// Returns false if the history entry's ID isn't a UUID.
     pub fn add_invocation(&mut self, history: &History, interner: &ThreadedRodeo) -> bool {
         let Some(history_id) = parse_uuid_bytes(&history.id.0) else {
             return false;
         };
-        let Some(session) = parse_uuid_bytes(&history.session) else {
-            return false;
-        };
 
         let timestamp = history.timestamp.unix_timestamp();
 
@@ -170,20 +198,46 @@ impl CommandData {
             interner.get_or_intern(history.cwd.display_rich().trailing_slash(true).to_string());
         self.directories.insert(dir_key);
         self.hosts.insert(interner.get_or_intern(history.cmd_origin.as_str()));
-        self.sessions.insert(session);
+        // Only sessions atuin started can be searched for, and those are always UUIDs.
+        self.sessions.extend(parse_uuid_bytes(&history.session));
+        let invocation = IndexedInvocation::new(history, history_id, interner);
 
         // Update most recent if this invocation is newer
-        if timestamp > self.most_recent_timestamp {
-            self.most_recent_id = history_id;
-            self.most_recent_timestamp = timestamp;
+        if invocation.timestamp > self.invocations[self.latest].timestamp {
+            self.latest = self.invocations.len();
         }
+        self.invocations.push(invocation);
 
         true
     }
 
     /// Get the most recent history ID for this command.
     pub fn most_recent_id(&self) -> [u8; 16] {
-        self.most_recent_id
+        self.invocations[self.latest].id
+    }
+
+    /// Get the position of the most recent invocation that passes all of `filters`, for
+    /// [`Self::invocation`].
+    pub fn latest_matching(
+        &self,
+        filters: &[QueryFilter],
+        interner: &ThreadedRodeo,
+    ) -> Option<usize> {
+        self.invocations
+            .iter()
+            .enumerate()
+            .filter(|(_, invocation)| {
+                let invocation = invocation.resolve(interner);
+                filters.iter().all(|filter| filter.matches(&invocation))
+            })
+            .max_by_key(|(_, invocation)| invocation.timestamp)
+            .map(|(i, _)| i)
+    }
+
+    /// Get the run of this command at a position given by [`Self::latest_matching`], or by
+    /// [`Ranked`]. Runs are only ever added, so positions hold.
+    fn invocation(&self, position: usize) -> Option<&IndexedInvocation> {
+        self.invocations.get(position)
     }
 
     /// Check if any invocation matches an interned directory (exact match).
@@ -258,6 +312,166 @@ impl IndexFilterMode {
     }
 }
 
+/// Where a search is being run from. Commands that have been run in the same place rank higher
+/// than equally good matches that haven't.
+///
+/// Directories are in the same form as [`IndexFilterMode::Directory`] and
+/// [`IndexFilterMode::Workspace`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchOrigin {
+    pub cwd: Option<String>,
+    pub git_root: Option<String>,
+    pub host: Option<String>,
+    pub session: Option<String>,
+}
+
+/// A "compiled" form of [`SearchOrigin`]. Anything the index has never seen is dropped, since no
+/// command can have been run there.
+struct CompiledOrigin<'a> {
+    cwd: Option<Spur>,
+    git_root: Option<&'a str>,
+    host: Option<Spur>,
+    session: Option<[u8; 16]>,
+}
+
+impl SearchOrigin {
+    fn compile(&self, interner: &ThreadedRodeo) -> CompiledOrigin<'_> {
+        CompiledOrigin {
+            cwd: self.cwd.as_deref().and_then(|cwd| interner.get(cwd)),
+            git_root: self.git_root.as_deref(),
+            host: self.host.as_deref().and_then(|host| interner.get(host)),
+            session: self.session.as_deref().and_then(parse_uuid_bytes),
+        }
+    }
+}
+
+impl CompiledOrigin<'_> {
+    /// The boost `command` gets for having been run here.
+    fn boost(
+        &self,
+        command: &CommandData,
+        weights: &ContextWeights,
+        interner: &ThreadedRodeo,
+    ) -> u32 {
+        let mut boost = 0;
+        if self.cwd.is_some_and(|dir| command.has_invocation_in_dir(dir)) {
+            boost += weights.cwd;
+        }
+        if self.git_root.is_some_and(|root| command.has_invocation_in_workspace(root, interner)) {
+            boost += weights.git_root;
+        }
+        if self.host.is_some_and(|host| command.has_invocation_on_host(host)) {
+            boost += weights.host;
+        }
+        if self.session.is_some_and(|session| command.has_invocation_in_session(&session)) {
+            boost += weights.session;
+        }
+        boost
+    }
+}
+
+/// How much a command is boosted for having been run in the searcher's directory, repo, host or
+/// session, on the same scale as frecency.
+#[derive(Debug, Clone, Copy)]
+struct ContextWeights {
+    cwd: u32,
+    git_root: u32,
+    host: u32,
+    session: u32,
+}
+
+impl ContextWeights {
+    /// A weight of 1.0 in `[search.ranking]` is worth as much frecency as a command run in the
+    /// last hour.
+    const SCALE: f64 = 100.0;
+
+    fn new(weights: &RankingWeights) -> Self {
+        let scale = |weight: f64| (weight.max(0.0) * Self::SCALE).round() as u32;
+        Self {
+            cwd: scale(weights.cwd),
+            git_root: scale(weights.git_root),
+            host: scale(weights.host),
+            session: scale(weights.session),
+        }
+    }
+}
+
+/// A search result, with what went into its ranking.
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    /// The command's most recent run that passed the query's inline filters.
+    pub history: History,
+    /// How closely the command matched the query; 0 for an empty query.
+    pub match_score: u16,
+    /// The command's precomputed global frecency.
+    pub frecency: u32,
+    /// How much the command was boosted for having been run where the search is run from.
+    pub context_boost: u32,
+    /// The matched chars of the command, as ranges of char (not byte) positions.
+    pub ranges: Vec<Range<u32>>,
+}
+
+/// A command that made the cut in [`SearchIndex::rank`].
+struct Ranked {
+    id: [u8; 16],
+    /// Where the run with `id` is among the command's invocations.
+    invocation: usize,
+    haystack_index: u32,
+    fuzzy_score: u16,
+    frecency: u32,
+}
+
+/// A run of a command that passed a [`SearchQuery`], in the index holding it.
+struct Run<'a> {
+    timestamp: i64,
+    id: [u8; 16],
+    command: Arc<str>,
+    /// The run's position in its command's invocations.
+    invocation: usize,
+    index: &'a SearchIndex,
+}
+
+impl Run<'_> {
+    /// The order `Sqlite::search` keeps and sorts runs in.
+    fn key(&self) -> (i64, [u8; 16]) {
+        (self.timestamp, self.id)
+    }
+
+    fn to_history(&self) -> Option<History> {
+        let history = self
+            .index
+            .commands
+            .get(self.command.as_ref())?
+            .invocation(self.invocation)?
+            .to_history(&self.command, &self.index.interner);
+        Some(history)
+    }
+}
+
+/// Collapse frizbee's matched byte offsets into `text` into runs of char positions.
+fn char_ranges(text: &str, mut byte_indices: Vec<u32>) -> Vec<Range<u32>> {
+    byte_indices.sort_unstable();
+    let mut byte_indices = byte_indices.into_iter().peekable();
+    let mut ranges: Vec<Range<u32>> = Vec::new();
+    for (char_idx, (byte_idx, ch)) in text.char_indices().enumerate() {
+        // frizbee reports every byte of a matched multibyte char
+        let end = byte_idx + ch.len_utf8();
+        let mut matched = false;
+        while byte_indices.next_if(|&i| (i as usize) < end).is_some() {
+            matched = true;
+        }
+        if !matched {
+            continue;
+        }
+        let char_idx = char_idx as u32;
+        match ranges.last_mut() {
+            Some(last) if last.end == char_idx => last.end += 1,
+            _ => ranges.push(char_idx..char_idx + 1),
+        }
+    }
+    ranges
+}
+
 /// Shareable frecency map: command -> frecency score.
 type FrecencyMap = Arc<Vec<u32>>;
 
@@ -346,34 +560,84 @@ pub struct SearchIndex {
     /// the last rebuild sit past the end and score 0 until the next rebuild.
     frecency_map: RwLock<Option<FrecencyMap>>,
 
+    /// Context boost weights, taken from the settings along with the frecency map. `None`, so
+    /// nothing is boosted, unless `smart_sort` is enabled.
+    context_weights: RwLock<Option<ContextWeights>>,
+
     /// String interner for deduplicating cwd, hostname, and directory paths.
     interner: Arc<ThreadedRodeo>,
 
     /// Controls which shells' commands are included.
     pub shells: OrFilter<Vec<String>>,
+
+    /// Commands interactive search leaves out: agents' commands, and commands from shells that
+    /// `shells` doesn't include. [`Self::query`] looks here too, so it sees the whole history.
+    /// `None` when the index only serves interactive search, and in this index itself.
+    excluded: Option<Box<Self>>,
+
+    /// Whether this index keeps every command it's given, as [`Self::excluded`] does.
+    keeps_everything: bool,
+
+    /// Whether the whole history has been loaded, as opposed to the index still filling up.
+    loaded: AtomicBool,
 }
 
 impl SearchIndex {
     /// Create a new empty search index.
-    pub fn new(shells: OrFilter<Vec<String>>) -> Self {
+    ///
+    /// A `queryable` index also keeps the commands interactive search leaves out, so that
+    /// [`Self::query`] can answer non-interactive searches.
+    pub fn new(shells: OrFilter<Vec<String>>, queryable: bool) -> Self {
+        Self {
+            shells,
+            excluded: queryable.then(|| Box::new(Self::with_everything())),
+            keeps_everything: false,
+            ..Self::with_everything()
+        }
+    }
+
+    /// An index that keeps every command, like [`SearchIndex::excluded`].
+    fn with_everything() -> Self {
         Self {
             commands: Arc::new(DashMap::new()),
             haystack: RwLock::new(Vec::new()),
             frecency_map: RwLock::new(None),
+            context_weights: RwLock::new(None),
             interner: Arc::new(ThreadedRodeo::new()),
-            shells,
+            shells: OrFilter::all(),
+            excluded: None,
+            keeps_everything: true,
+            loaded: AtomicBool::new(false),
         }
     }
 
+    /// Whether this index can answer [`Self::query`].
+    pub fn is_queryable(&self) -> bool {
+        self.excluded.is_some()
+    }
+
+    /// Mark the whole history as loaded.
+    pub fn mark_loaded(&self) {
+        self.loaded.store(true, AtomicOrdering::Release);
+    }
+
+    /// Whether the whole history has been loaded. Until it has, [`Self::query`] would miss
+    /// entries the database has.
+    pub fn is_loaded(&self) -> bool {
+        self.loaded.load(AtomicOrdering::Acquire)
+    }
+
     /// Add a history entry to the index.
     ///
     /// If the command already exists, updates its invocation data.
     /// If it's a new command, adds it to both the map and Nucleo.
     pub fn add_history(&self, history: &History) {
-        if history.is_agent() {
-            return;
-        }
-        if !self.shells.contains(history.shell.as_deref().unwrap_or_default()) {
+        let searchable = !history.is_agent()
+            && self.shells.contains(history.shell.as_deref().unwrap_or_default());
+        if !searchable && !self.keeps_everything {
+            if let Some(excluded) = &self.excluded {
+                excluded.add_history(history);
+            }
             return;
         }
 
@@ -414,10 +678,19 @@ impl SearchIndex {
         self.commands.len()
     }
 
+    /// Get the history ID of the most recent invocation of `command`, if it's indexed.
+    pub fn most_recent_id(&self, command: &str) -> Option<[u8; 16]> {
+        self.commands.get(command).map(|data| data.most_recent_id())
+    }
+
     /// Search for commands matching a query.
     ///
     /// Returns an iterator of history IDs as parsed UUIDs (most recent invocation per command).
     /// Uses precomputed global frecency for scoring if available.
+    ///
+    /// Inline filters in the query (see [`atuin_client::database::predicate`]) are checked against
+    /// each invocation, and the ID returned for a command is its most recent invocation that
+    /// passes them. The filter mode is still checked per command.
     #[instrument(skip_all, level = tracing::Level::TRACE, name = "index_search", fields(query = %query))]
     pub fn search(
         &self,
@@ -425,10 +698,141 @@ impl SearchIndex {
         filter_mode: &IndexFilterMode,
         limit: u32,
     ) -> impl Iterator<Item = [u8; 16]> {
+        self.rank(query, filter_mode, limit).into_iter().map(|ranked| ranked.id)
+    }
+
+    /// Search for commands matching a query, like [`Self::search`], but return each command's
+    /// history entry along with its score components and the chars of it that matched.
+    ///
+    /// When `smart_sort` is enabled, commands that have been run where the search is run from
+    /// (see [`SearchOrigin`]) are boosted ahead of equally good matches. The boost only reorders
+    /// the top `limit` matches.
+    #[instrument(skip_all, level = tracing::Level::TRACE, name = "index_search_hits", fields(query = %query))]
+    pub fn search_hits(
+        &self,
+        query: &str,
+        filter_mode: &IndexFilterMode,
+        origin: &SearchOrigin,
+        limit: u32,
+    ) -> Vec<SearchHit> {
+        let ranked = self.rank(query, filter_mode, limit);
+
+        let (text, _) = split_filters(query);
+        let text = normalize_diacritics(super::truncate_query(&text));
+        let config = frizbee::Config::default().casing(frizbee::CaseMatching::Smart);
+        let mut matcher = frizbee::Matcher::from_query(&text, &config);
+        let has_pattern = !matcher.patterns().is_empty();
+
+        let origin = origin.compile(&self.interner);
+        let weights = *self.context_weights.read();
+        let haystack = self.haystack.read();
+
+        let mut hits: Vec<SearchHit> = ranked
+            .into_iter()
+            .filter_map(|ranked| {
+                let entry = haystack.get(ranked.haystack_index as usize)?;
+                let ranges = if has_pattern {
+                    matcher
+                        .match_one_indices(&entry.normalized, 0)
+                        .map(|m| char_ranges(&entry.normalized, m.indices))
+                        .unwrap_or_default()
+                } else {
+                    Vec::new()
+                };
+                let data = self.commands.get(entry.original.as_ref())?;
+                let invocation = data.invocation(ranked.invocation)?;
+                let history = invocation.to_history(&entry.original, &self.interner);
+                let context_boost =
+                    weights.map_or(0, |weights| origin.boost(&data, &weights, &self.interner));
+                drop(data);
+                Some(SearchHit {
+                    history,
+                    match_score: ranked.fuzzy_score,
+                    frecency: ranked.frecency,
+                    context_boost,
+                    ranges,
+                })
+            })
+            .collect();
+
+        // Stable, so ties keep the order `rank` gave them
+        hits.sort_by(|a, b| {
+            let weight = |hit: &SearchHit| hit.frecency.saturating_add(hit.context_boost);
+            b.match_score.cmp(&a.match_score).then_with(|| weight(b).cmp(&weight(a)))
+        });
+        hits
+    }
+
+    /// Run a non-interactive search the way `Sqlite::search` runs it, including the commands
+    /// interactive search leaves out.
+    ///
+    /// Results come newest first (oldest first with `reverse`), one per command unless duplicates
+    /// are asked for, and paged with `limit` and `offset`. Reordering fuzzy matches is left to the
+    /// caller, just as it follows the database query.
+    #[instrument(skip_all, level = tracing::Level::TRACE, name = "index_query")]
+    pub fn query(&self, query: &SearchQuery) -> Vec<History> {
+        let mut runs = Vec::new();
+        for index in std::iter::once(self).chain(self.excluded.as_deref()) {
+            index.collect_runs(query, &mut runs);
+        }
+
+        if !query.include_duplicates {
+            // A command can be in both indexes, when both a user and an agent ran it
+            runs.sort_unstable_by(|a, b| {
+                a.command.cmp(&b.command).then_with(|| b.key().cmp(&a.key()))
+            });
+            runs.dedup_by(|run, latest| run.command == latest.command);
+        }
+
+        runs.sort_unstable_by_key(|run| std::cmp::Reverse(run.key()));
+        if query.reverse {
+            runs.reverse();
+        }
+
+        runs.iter()
+            .skip(query.offset)
+            .take(query.limit.unwrap_or(usize::MAX))
+            .filter_map(Run::to_history)
+            .collect()
+    }
+
+    /// Collect the runs in this index, not counting [`Self::excluded`], that pass `query`: all of
+    /// them, or the latest of each command.
+    fn collect_runs<'a>(&'a self, query: &SearchQuery, runs: &mut Vec<Run<'a>>) {
+        let haystack = self.haystack.read();
+        for entry in haystack.iter().filter(|entry| query.matches_command(&entry.original)) {
+            let Some(data) = self.commands.get(entry.original.as_ref()) else {
+                continue;
+            };
+            let matching = data
+                .invocations
+                .iter()
+                .enumerate()
+                .filter(|(_, invocation)| {
+                    query.matches_invocation(&invocation.resolve(&self.interner))
+                })
+                .map(|(position, invocation)| Run {
+                    timestamp: invocation.timestamp,
+                    id: invocation.id,
+                    command: entry.original.clone(),
+                    invocation: position,
+                    index: self,
+                });
+            if query.include_duplicates {
+                runs.extend(matching);
+            } else {
+                runs.extend(matching.max_by_key(Run::key));
+            }
+        }
+    }
+
+    /// Find the top `limit` commands matching a query, best first.
+    fn rank(&self, query: &str, filter_mode: &IndexFilterMode, limit: u32) -> Vec<Ranked> {
         // Get precomputed frecency map (may be None if not yet computed)
         let frecency_map = self.frecency_map.read().clone();
 
-        let query = super::truncate_query(query);
+        let (query, query_filters) = split_filters(query);
+        let query = super::truncate_query(&query);
         // Match accent-insensitively: the haystack side is normalized in
         // add_history, so an accented query must be normalized too
         let query = normalize_diacritics(query);
@@ -436,36 +840,56 @@ impl SearchIndex {
         let haystack = self.haystack.read();
         let filter = filter_mode.compile(&self.interner);
 
+        let matches_filter_mode = |entry: &CommandData| match &filter {
+            CompiledFilter::All => true,
+            CompiledFilter::Nothing => false,
+            CompiledFilter::Directory(dir) => entry.has_invocation_in_dir(*dir),
+            CompiledFilter::Workspace(prefix) => {
+                entry.has_invocation_in_workspace(prefix, &self.interner)
+            }
+            CompiledFilter::Host(hostname) => entry.has_invocation_on_host(*hostname),
+            CompiledFilter::Session(session) => entry.has_invocation_in_session(session),
+        };
+
         // Filter pre-pass: collect the candidate commands for this filter mode. This is sorted
-        // vector of haystack indices.
+        // vector of haystack indices. With inline filters, this also collects the position and ID
+        // of each candidate's latest matching invocation, aligned with the candidates.
         let get_candidates = || match &filter {
-            CompiledFilter::All => haystack.iter().enumerate().map(|(i, _)| i as u32).collect(),
-            CompiledFilter::Nothing => Vec::new(),
+            CompiledFilter::Nothing => (Vec::new(), Vec::new()),
+            _ if !query_filters.is_empty() => {
+                let mut found: Vec<(u32, (usize, [u8; 16]))> = self
+                    .commands
+                    .iter()
+                    .filter(|entry| (entry.haystack_index as usize) < haystack.len())
+                    .filter(|entry| matches_filter_mode(entry))
+                    .filter_map(|entry| {
+                        let position = entry.latest_matching(&query_filters, &self.interner)?;
+                        let id = entry.invocations[position].id;
+                        Some((entry.haystack_index, (position, id)))
+                    })
+                    .collect();
+                found.sort_unstable_by_key(|(index, _)| *index);
+                found.into_iter().unzip()
+            }
+            CompiledFilter::All => {
+                (haystack.iter().enumerate().map(|(i, _)| i as u32).collect(), Vec::new())
+            }
             _ => {
                 let mut indices: Vec<u32> = self
                     .commands
                     .iter()
                     .filter(|entry| (entry.haystack_index as usize) < haystack.len())
-                    .filter(|entry| match &filter {
-                        CompiledFilter::All | CompiledFilter::Nothing => unreachable!(),
-                        CompiledFilter::Directory(dir) => entry.has_invocation_in_dir(*dir),
-                        CompiledFilter::Workspace(prefix) => {
-                            entry.has_invocation_in_workspace(prefix, &self.interner)
-                        }
-                        CompiledFilter::Host(hostname) => entry.has_invocation_on_host(*hostname),
-                        CompiledFilter::Session(session) => {
-                            entry.has_invocation_in_session(session)
-                        }
-                    })
+                    .filter(|entry| matches_filter_mode(entry))
                     .map(|entry| entry.haystack_index)
                     .collect();
                 indices.sort_unstable();
-                indices
+                (indices, Vec::new())
             }
         };
 
-        let candidates =
+        let (candidates, matching): (Vec<u32>, Vec<(usize, [u8; 16])>) =
             tracing::span!(Level::TRACE, "index_search_filter").in_scope(get_candidates);
+        let has_query_filters = !query_filters.is_empty();
 
         let candidate_frecency = |candidate_index: usize| {
             let hay_idx = candidates[candidate_index] as usize;
@@ -520,12 +944,26 @@ impl SearchIndex {
                 scored.truncate(limit);
             }
             scored.sort_unstable();
-            scored.into_iter().filter_map(move |score| {
-                let haystack_index = candidates[score.index as usize];
-                self.commands
-                    .get(haystack[haystack_index as usize].original.as_ref())
-                    .map(|data| data.most_recent_id())
-            })
+            scored
+                .into_iter()
+                .filter_map(|score| {
+                    let haystack_index = candidates[score.index as usize];
+                    let (invocation, id) = if has_query_filters {
+                        matching[score.index as usize]
+                    } else {
+                        self.commands
+                            .get(haystack[haystack_index as usize].original.as_ref())
+                            .map(|data| (data.latest, data.most_recent_id()))?
+                    };
+                    Some(Ranked {
+                        id,
+                        invocation,
+                        haystack_index,
+                        fuzzy_score: score.fuzzy_score,
+                        frecency: score.frecency,
+                    })
+                })
+                .collect()
         })
     }
 
@@ -538,8 +976,11 @@ impl SearchIndex {
     /// - `recency_score_multiplier`: Weight for recency component
     /// - `frequency_score_multiplier`: Weight for frequency component
     /// - `frecency_score_multiplier`: Overall multiplier for final score
+    ///
+    /// The context boost weights for [`Self::search_hits`] are taken from `search.ranking` at the
+    /// same time, when `smart_sort` is enabled.
     #[instrument(skip_all, level = tracing::Level::DEBUG, name = "rebuild_frecency")]
-    pub fn rebuild_frecency(&self, search_settings: &Search) {
+    pub fn rebuild_frecency(&self, search_settings: &Search, smart_sort: bool) {
         let now = OffsetDateTime::now_utc().unix_timestamp();
 
         // Clamp multipliers to non-negative values to prevent broken frecency ranking
@@ -565,12 +1006,14 @@ impl SearchIndex {
         };
 
         *self.frecency_map.write() = Some(Arc::new(frecencies));
+        *self.context_weights.write() =
+            smart_sort.then(|| ContextWeights::new(&search_settings.ranking));
     }
 }
 
 impl Default for SearchIndex {
     fn default() -> Self {
-        Self::new(OrFilter::all())
+        Self::new(OrFilter::all(), false)
     }
 }
 
@@ -735,6 +1178,39 @@ mod tests {
         assert!(!data.has_invocation_in_workspace(&check3, &interner));
     }
 
+    #[test]
+    fn inline_filters_pick_the_matching_invocation() {
+        let index = SearchIndex::default();
+        let run = |command: &str, cwd: &str, exit: i64, duration: i64, at| -> History {
+            History::import()
+                .timestamp(at)
+                .command(command)
+                .cwd(cwd)
+                .exit(exit)
+                .duration(duration)
+                .build()
+                .into()
+        };
+
+        let failed =
+            run("cargo test", "/src", 101, 40_000_000_000, datetime!(2024-01-01 10:00 UTC));
+        let passed = run("cargo test", "/src", 0, 5_000_000_000, datetime!(2024-01-01 11:00 UTC));
+        let elsewhere = run("cargo build", "/tmp", 0, 1_000_000, datetime!(2024-01-01 12:00 UTC));
+        index.add_histories(&[failed.clone(), passed.clone(), elsewhere.clone()]);
+
+        let search =
+            |query: &str| index.search(query, &IndexFilterMode::Global, 10).collect::<Vec<_>>();
+        let id = |history: &History| parse_uuid_bytes(&history.id.0).unwrap();
+
+        assert_eq!(search("test"), [id(&passed)]);
+        assert_eq!(search("test exit:!0"), [id(&failed)]);
+        assert_eq!(search("test dur:>30s"), [id(&failed)]);
+        assert_eq!(search("exit:0 test"), [id(&passed)]);
+        assert_eq!(search("cwd:/tmp"), [id(&elsewhere)]);
+        assert!(search("test cwd:/tmp").is_empty());
+        assert!(search("cargo host:nowhere").is_empty());
+    }
+
     #[test]
     fn search_index_add_and_search() {
         let index = SearchIndex::default();
@@ -789,7 +1265,7 @@ mod tests {
             index.add_history(&h);
         }
 
-        index.rebuild_frecency(&Search::default());
+        index.rebuild_frecency(&Search::default(), false);
 
         let results: Vec<_> = index.search("foo bar", &IndexFilterMode::Global, 10).collect();
         assert_eq!(results.len(), 2);
@@ -814,7 +1290,7 @@ mod tests {
             index.add_history(&h);
         }
 
-        index.rebuild_frecency(&Search::default());
+        index.rebuild_frecency(&Search::default(), false);
 
         let results: Vec<_> = index.search("echo", &IndexFilterMode::Global, 10).collect();
         assert_eq!(results.len(), 2);
@@ -845,6 +1321,74 @@ mod tests {
         assert_eq!(results, vec![expected]);
     }
 
+    /// Search hits carry the whole history entry, so clients don't have to load it, and the
+    /// matched chars of the command, counted in chars even where the command has multibyte ones.
+    #[test]
+    fn search_hits_carry_history_and_ranges() {
+        let index = SearchIndex::default();
+
+        let history: History = History::import()
+            .timestamp(datetime!(2024-01-01 10:00 UTC))
+            .command("echo déjà-vu")
+            .cwd("/tmp")
+            .exit(0)
+            .duration(1_000)
+            .author("ellie")
+            .intent("say it again")
+            .shell("zsh")
+            .author_kind(Some(AuthorKind::User))
+            .build()
+            .into();
+        index.add_history(&history);
+
+        let hits =
+            index.search_hits("deja", &IndexFilterMode::Global, &SearchOrigin::default(), 10);
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].history, history);
+        assert_eq!(hits[0].ranges, vec![5..9]);
+        assert!(hits[0].match_score > 0);
+        assert_eq!(hits[0].context_boost, 0);
+
+        let hits = index.search_hits("", &IndexFilterMode::Global, &SearchOrigin::default(), 10);
+        assert!(hits[0].ranges.is_empty());
+    }
+
+    /// With `smart_sort` enabled, commands run where the search is run from are boosted ahead of
+    /// equally good matches with more frecency.
+    #[test]
+    fn context_boost_breaks_ties() {
+        let index = SearchIndex::default();
+
+        for _ in 0..3 {
+            index.add_history(&make_history("echo alpha", "/tmp", datetime!(2024-01-01 10:00 UTC)));
+        }
+        index.add_history(&make_history("echo beta", "/src", datetime!(2024-01-01 10:00 UTC)));
+        index.rebuild_frecency(&Search::default(), true);
+
+        let commands = |origin: &SearchOrigin| {
+            index
+                .search_hits("echo", &IndexFilterMode::Global, origin, 10)
+                .into_iter()
+                .map(|hit| (hit.history.command, hit.context_boost))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(commands(&SearchOrigin::default()), [
+            ("echo alpha".to_owned(), 0),
+            ("echo beta".to_owned(), 0)
+        ]);
+
+        let origin = SearchOrigin {
+            cwd: Some("/src".display_rich().trailing_slash(true).to_string()),
+            ..SearchOrigin::default()
+        };
+        assert_eq!(commands(&origin), [("echo beta".to_owned(), 50), ("echo alpha".to_owned(), 0)]);
+
+        // Without it, frecency alone breaks the tie
+        index.rebuild_frecency(&Search::default(), false);
+        assert_eq!(commands(&origin), [("echo alpha".to_owned(), 0), ("echo beta".to_owned(), 0)]);
+    }
+
     /// A deterministic synthetic corpus large enough to cross the 10k
     /// parallel-matching threshold, shaped like real history (repeated
     /// prefixes, multi-word commands, a few accented entries).
@@ -903,7 +1447,7 @@ mod tests {
             index.add_history(&make_history(command, "/tmp", ts));
         }
         assert!(index.command_count() > 10_000, "corpus must cross threshold");
-        index.rebuild_frecency(&Search::default());
+        index.rebuild_frecency(&Search::default(), false);
 
         for query in ["git", "git p", "docker compose up", "deja", ""] {
             let first: Vec<_> = index.search(query, &IndexFilterMode::Global, 200).collect();
@@ -933,11 +1477,15 @@ mod tests {
     #[case::unknown(&[""], 4)]
     #[case::fish(&["fish"], 0)]
     #[case::fish_unknown(&["fish", ""], 4)]
-    fn search_with_shell_filter(#[case] shells: &[&str], #[case] expected_count: usize) {
+    fn search_with_shell_filter(
+        #[case] shells: &[&str],
+        #[case] expected_count: usize,
+        #[values(false, true)] queryable: bool,
+    ) {
         let filter =
             OrFilter::from_list(shells.iter().map(|s| (*s).to_owned()).collect::<Vec<_>>())
                 .unwrap_or_default();
-        let index = SearchIndex::new(filter);
+        let index = SearchIndex::new(filter, queryable);
 
         for (command, shell) in [
             ("echo unknown1", None),
@@ -955,5 +1503,8 @@ mod tests {
 
         let results: Vec<_> = index.search("echo", &IndexFilterMode::Global, 100).collect();
         assert_eq!(results.len(), expected_count, "{results:?}");
+        // Only an index answering queries keeps the rest
+        let excluded = index.excluded.as_ref().map(|excluded| excluded.command_count());
+        assert_eq!(excluded, queryable.then_some(7 - expected_count));
     }
 }
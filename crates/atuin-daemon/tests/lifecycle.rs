@@ -191,6 +191,71 @@ mod unix {
         assert_eq!(ended_history.duration, 1_000_000);
     }
 
+    #[rstest]
+    #[tokio::test]
+    async fn test_reloaded_history_filter_applies_to_running_commands(
+        #[future] daemon: (HistoryClient, DaemonHandle, TempDir),
+    ) {
+        use atuin_client::history::History;
+
+        let (mut client, handle, _tmp) = daemon.await;
+
+        let history = History::daemon()
+            .timestamp(time::OffsetDateTime::now_utc())
+            .command("deploy --token abc".to_string())
+            .cwd("/tmp".to_string())
+            .session("test-session".to_string())
+            .cmd_origin(atuin_domain::record::CmdOrigin::try_from("test-host:ellie").unwrap())
+            .build()
+            .into();
+        let start_reply = client.start_history(history).await.unwrap();
+
+        // The filter arrives while the command is still running.
+        let mut settings = handle.settings().await.clone();
+        settings.history_filter = regex::RegexSet::new(["^deploy "]).unwrap();
+        handle.apply_settings(settings).await.unwrap();
+
+        let end_reply = client.end_history(start_reply.id.clone(), 1_000_000, 0).await.unwrap();
+        assert!(end_reply.id.is_empty());
+        assert!(handle.history_db().load(&start_reply.id).await.unwrap().is_none());
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_reload_keeps_settings_the_daemon_cant_run_with_out(
+        #[future] daemon: (HistoryClient, DaemonHandle, TempDir),
+    ) {
+        let (_client, handle, _tmp) = daemon.await;
+        let frequency = handle.settings().await.daemon.sync_frequency;
+
+        let mut settings = handle.settings().await.clone();
+        settings.daemon.sync_frequency = 0;
+        assert!(handle.apply_settings(settings).await.is_err());
+
+        assert_eq!(handle.settings().await.daemon.sync_frequency, frequency);
+        let error = handle.config_error().await.unwrap();
+        assert!(error.contains("daemon.sync_frequency"), "{error}");
+    }
+
+    #[rstest]
+    #[tokio::test]
+    async fn test_reload_reports_settings_that_need_a_restart(
+        #[future] daemon: (HistoryClient, DaemonHandle, TempDir),
+    ) {
+        let (_client, handle, tmp) = daemon.await;
+        let initial = handle.settings().await.clone();
+
+        let mut settings = initial.clone();
+        settings.output.db_path = tmp.path().join("moved.db").to_string_lossy().to_string();
+        settings.daemon.sync_frequency += 1;
+        handle.apply_settings(settings).await.unwrap();
+        assert_eq!(handle.restart_required().await, vec!["output.db_path".to_string()]);
+
+        // Changing it back needs no restart after all.
+        handle.apply_settings(initial).await.unwrap();
+        assert!(handle.restart_required().await.is_empty());
+    }
+
     #[rstest]
     #[tokio::test]
     async fn test_end_unknown_history_fails(
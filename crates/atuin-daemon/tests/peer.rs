@@ -0,0 +1,193 @@
+//! Integration tests for syncing with a peer's daemon.
+//!
+//! Each test serves a daemon's peer router on a local port, and syncs a second record store with
+//! it, as `atuin sync peer` does.
+
+use atuin_client::database::Sqlite;
+use atuin_client::history::History;
+use atuin_client::history::store::HistoryStore;
+use atuin_client::record::sqlite_store::SqliteStore;
+use atuin_client::record::sync::peer::{self as record_peer, Challenge};
+use atuin_client::record::sync::{ClientSource, SyncEngine};
+use atuin_client::settings::{Settings, init_meta_config_for_testing};
+use atuin_common::encryption::paseto_v4;
+use atuin_common::utils::uuid_v7;
+use atuin_daemon::components::peer;
+use atuin_daemon::{Daemon, DaemonHandle};
+use atuin_domain::record::HostId;
+use reqwest::StatusCode;
+use reqwest::header::AUTHORIZATION;
+use rstest::*;
+use tempfile::TempDir;
+use tokio::net::TcpListener;
+use url::Url;
+
+const SECRET: &str = "correct horse battery staple";
+
+struct PeerDaemon {
+    handle: DaemonHandle,
+    settings: Settings,
+    history_db: Sqlite,
+    address: Url,
+    _tmp: TempDir,
+}
+
+/// A daemon whose peer router listens on a local port, with `peer.secret` set to [`SECRET`].
+#[fixture]
+async fn peer_daemon() -> PeerDaemon {
+    let tmp = tempfile::tempdir().unwrap();
+
+    let db_path = tmp.path().join("history.db");
+    let record_path = tmp.path().join("records.db");
+    let key_path = tmp.path().join("key");
+    let meta_path = tmp.path().join("meta.db");
+
+    // Initialize the meta store config for testing (required for Settings::host_id())
+    init_meta_config_for_testing(meta_path.to_str().unwrap(), 5.0);
+
+    let settings: Settings = Settings::builder()
+        .expect("could not build settings builder")
+        .set_override("db_path", db_path.to_str().unwrap())
+        .expect("failed to set db_path")
+        .set_override("record_store_path", record_path.to_str().unwrap())
+        .expect("failed to set record_store_path")
+        .set_override("key_path", key_path.to_str().unwrap())
+        .expect("failed to set key_path")
+        .set_override("meta.db_path", meta_path.to_str().unwrap())
+        .expect("failed to set meta.db_path")
+        .set_override("peer.secret", SECRET)
+        .expect("failed to set peer.secret")
+        .build()
+        .expect("could not build settings")
+        .try_deserialize()
+        .expect("could not deserialize settings");
+
+    let history_db = Sqlite::new(&db_path, 5.0).await.unwrap();
+    let store = SqliteStore::new(&record_path, 5.0).await.unwrap();
+
+    let daemon = Daemon::builder(settings.clone())
+        .store(store)
+        .history_db(history_db.clone())
+        .build()
+        .unwrap();
+    let handle = daemon.handle();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = Url::parse(&format!("http://{}", listener.local_addr().unwrap())).unwrap();
+    let router = peer::router(handle.clone(), SECRET).await.unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+    PeerDaemon {
+        handle,
+        settings,
+        history_db,
+        address,
+        _tmp: tmp,
+    }
+}
+
+async fn push_history(store: &SqliteStore, key: &paseto_v4::Key, command: &str) {
+    let history_store = HistoryStore::new(store.clone(), HostId(uuid_v7()), key.clone());
+
+    let history = History::import()
+        .timestamp(time::OffsetDateTime::now_utc())
+        .command(command)
+        .cwd("/tmp")
+        .build()
+        .into();
+
+    history_store.push(history).await.unwrap();
+}
+
+async fn engine(settings: &Settings, store: &SqliteStore, address: &Url) -> SyncEngine {
+    SyncEngine::builder()
+        .store(store.clone())
+        .client_source(ClientSource::FromPeer {
+            settings,
+            address: address.clone(),
+        })
+        .build()
+        .connect()
+        .await
+        .unwrap()
+}
+
+#[rstest]
+#[tokio::test]
+async fn syncs_both_ways_with_a_peer(#[future] peer_daemon: PeerDaemon) {
+    let peer = peer_daemon.await;
+    let key = peer.handle.encryption_key().clone();
+
+    let tmp = tempfile::tempdir().unwrap();
+    let store = SqliteStore::new(tmp.path().join("records.db"), 5.0).await.unwrap();
+
+    push_history(peer.handle.store(), &key, "echo from the peer").await;
+    push_history(&store, &key, "echo from here").await;
+
+    let engine = engine(&peer.settings, &store, &peer.address).await;
+    let (uploaded, downloaded) = engine.keyed(&key).sync().await.unwrap();
+
+    assert_eq!(uploaded, 1);
+    assert_eq!(downloaded.len(), 1);
+    assert_eq!(
+        store.status().await.unwrap().hosts,
+        peer.handle.store().status().await.unwrap().hosts
+    );
+
+    // the peer builds the history it was sent
+    assert_eq!(peer.history_db.history_count(true).await.unwrap(), 1);
+}
+
+#[rstest]
+#[tokio::test]
+async fn peers_need_the_secret(#[future] peer_daemon: PeerDaemon) {
+    let peer = peer_daemon.await;
+    let key = peer.handle.encryption_key().clone();
+
+    let tmp = tempfile::tempdir().unwrap();
+    let store = SqliteStore::new(tmp.path().join("records.db"), 5.0).await.unwrap();
+
+    let mut settings = peer.settings.clone();
+    settings.peer.secret = Some("not the secret".to_string());
+
+    let engine = engine(&settings, &store, &peer.address).await;
+    let err = engine.keyed(&key).sync().await.unwrap_err();
+
+    assert!(err.to_string().contains("the peer secret doesn't match"), "got: {err}");
+}
+
+#[rstest]
+#[tokio::test]
+async fn answered_challenges_cant_be_replayed(#[future] peer_daemon: PeerDaemon) {
+    let peer = peer_daemon.await;
+    let http = reqwest::Client::new();
+
+    let Challenge { challenge } = http
+        .get(peer.address.join(record_peer::CHALLENGE_PATH).unwrap())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    let url = peer.address.join("/api/v0/record").unwrap();
+    let answer = record_peer::response(
+        SECRET,
+        &challenge,
+        &reqwest::Method::GET,
+        &record_peer::path_and_query(&url),
+        b"",
+    );
+    let status = |authorization: String| {
+        let request = http.get(url.clone()).header(AUTHORIZATION, authorization);
+        async move { request.send().await.unwrap().status() }
+    };
+
+    let authorization = format!("{} {challenge} {answer}", record_peer::SCHEME);
+    assert_eq!(status(authorization.clone()).await, StatusCode::OK);
+    assert_eq!(status(authorization).await, StatusCode::UNAUTHORIZED);
+
+    // nor is the secret itself accepted
+    assert_eq!(status(format!("Token {SECRET}")).await, StatusCode::UNAUTHORIZED);
+}
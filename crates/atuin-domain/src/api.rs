@@ -4,8 +4,11 @@ use std::sync::LazyLock;
 
 use semver::Version;
 use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
 use url::Url;
 
+use crate::record::RecordId;
+
 // the usage of X- has been deprecated for quite along time, it turns out
 pub static ATUIN_HEADER_VERSION: &str = "Atuin-Version";
 pub static ATUIN_CARGO_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -23,6 +26,9 @@ pub struct RegisterRequest {
     pub email: String,
     pub username: String,
     pub password: String,
+    /// An invite code, for registering on a server that isn't open for registrations.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invite: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -75,6 +81,40 @@ pub struct IndexResponse {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MeResponse {
     pub username: String,
+    /// How much the user has stored. Older servers don't send this.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<UsageResponse>,
+}
+
+/// How much a user has stored on the server, against its quota.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UsageResponse {
+    pub records: u64,
+    /// The size of the user's encrypted records, in bytes.
+    pub bytes: u64,
+    /// The most records the user can store, if there's a limit.
+    pub max_records: Option<u64>,
+    /// The most bytes the user can store, if there's a limit.
+    pub max_bytes: Option<u64>,
+}
+
+/// A place the user is logged in.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionResponse {
+    pub id: i64,
+    /// `None` for sessions created before the server recorded it.
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub created_at: Option<OffsetDateTime>,
+    #[serde(with = "time::serde::rfc3339::option")]
+    pub last_seen_at: Option<OffsetDateTime>,
+    pub user_agent: Option<String>,
+    /// Whether this is the session the request was made with.
+    pub current: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionsResponse {
+    pub sessions: Vec<SessionResponse>,
 }
 
 /// The capabilities a server advertises, as returned from its capabilities endpoint.
@@ -87,6 +127,19 @@ pub struct CapabilitiesResponse {
     pub capabilities: HashMap<String, serde_json::Value>,
 }
 
+/// Request body for `POST /api/v0/packfiles`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackfileRequest {
+    /// The id of the packfile manifest record the packfile belongs to.
+    pub manifest_id: RecordId,
+    /// The ids of the records in the packfile.
+    pub records: Vec<RecordId>,
+    pub packfile_size_bytes: u64,
+    /// The base64 SHA-256 of the packfile. The server refuses uploads that don't match it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub packfile_sha256: Option<String>,
+}
+
 /// Response to `POST /api/v0/packfiles`.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PackfileResponse {
@@ -101,6 +154,49 @@ pub struct PackfileDownloadResponse {
     pub download_url: Url,
 }
 
+/// Body of `PUT /api/v0/me/key`, and the response to `GET /api/v0/users/{username}/key`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicKeyRequest {
+    /// The key team keys are sealed to, as a `k4.public.` PASERK.
+    pub public_key: String,
+}
+
+/// Request body for `POST /api/v0/teams`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateTeamRequest {
+    pub name: String,
+    /// The new team's key, sealed to the creator's public key.
+    pub sealed_key: String,
+}
+
+/// Request body for `POST /api/v0/teams/{team}/members`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AddTeamMemberRequest {
+    pub username: String,
+    /// The team key, sealed to the new member's public key.
+    pub sealed_key: String,
+    #[serde(default)]
+    pub admin: bool,
+}
+
+/// Someone's membership of a team.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TeamMemberResponse {
+    pub team: String,
+    pub username: String,
+    /// The team key, sealed to the member's public key. Only sent to the member themselves.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sealed_key: Option<String>,
+    pub admin: bool,
+}
+
+/// Response to `GET /api/v0/teams`, the teams the user is in, and to
+/// `GET /api/v0/teams/{team}/members`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TeamMembersResponse {
+    pub members: Vec<TeamMemberResponse>,
+}
+
 // Hub CLI authentication types
 
 /// Response from `POST /auth/cli/code` - generates a code for CLI auth
@@ -49,3 +49,28 @@ impl Capability for PackfileCap {
         serde_json::to_value(self)
     }
 }
+
+/// Shared team stores.
+///
+/// This capability communicates that the server keeps a store for each team, under
+/// `/teams/{team}`, that its members can sync. Team records are encrypted with a team key the
+/// server never sees, sealed to each member's public key.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TeamsCap {
+    /// The version of the teams protocol the server implements.
+    pub version: u32,
+}
+
+impl Capability for TeamsCap {
+    fn static_name() -> &'static str {
+        "sh.atuin.server/teams"
+    }
+
+    fn name(&self) -> &'static str {
+        Self::static_name()
+    }
+
+    fn json(&self) -> Result<serde_json::Value, serde_json::Error> {
+        serde_json::to_value(self)
+    }
+}
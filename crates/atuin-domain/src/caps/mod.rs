@@ -57,7 +57,7 @@ mod client;
 mod middleware;
 mod server;
 
-pub use all::{CapabilitiesCap, PackfileCap};
+pub use all::{CapabilitiesCap, PackfileCap, TeamsCap};
 pub use client::{CapClient, ServerSupportError};
 pub use middleware::{CapMiddleware, CapMismatch, CapabilitiesExt};
 pub use server::{CapServer, Negotiation};
@@ -26,6 +26,8 @@ pub enum RecordTag {
     ConfigShellAlias,
     #[strum(serialize = "packfile")]
     Packfile,
+    #[strum(serialize = "command-output")]
+    CommandOutput,
     /// Legacy code supported arbitrary types, so we need to support this.
     #[strum(default, transparent)]
     Other(String),
@@ -45,7 +47,8 @@ impl RecordTag {
             Self::DotfilesVar => 3,
             Self::ConfigShellAlias => 4,
             Self::Packfile => 5,
-            Self::Other(_) => 6,
+            Self::CommandOutput => 6,
+            Self::Other(_) => 7,
         }
     }
 }
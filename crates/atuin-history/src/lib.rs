@@ -1,2 +1,3 @@
+pub mod predict;
 pub mod sort;
 pub mod stats;
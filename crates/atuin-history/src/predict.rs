@@ -0,0 +1,262 @@
+//! Next-command prediction.
+//!
+//! [`Predictor`] is a small Markov model of command transitions: for every
+//! command, it counts which commands came directly after it in the same shell
+//! session, both overall and per directory. It also keeps second-order
+//! (trigram) counts, so that `git add` → `git commit` → ? can predict
+//! `git push` even if `git commit` is often followed by something else.
+//!
+//! Predictions interpolate between the models, preferring the most specific
+//! one that has seen the current context.
+
+use std::collections::HashMap;
+
+use atuin_client::history::History;
+
+/// How many recent history entries to build a model from.
+pub const MODEL_HISTORY_LIMIT: i64 = 50_000;
+
+// Interpolation weights, from most to least specific.
+const DIR_TRIGRAM_WEIGHT: f64 = 0.35;
+const DIR_BIGRAM_WEIGHT: f64 = 0.25;
+const TRIGRAM_WEIGHT: f64 = 0.2;
+const BIGRAM_WEIGHT: f64 = 0.15;
+const DIR_UNIGRAM_WEIGHT: f64 = 0.05;
+
+type Counts = HashMap<String, u32>;
+
+/// A predicted next command.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Prediction {
+    pub command: String,
+    /// How likely this command is to be next, in `0.0..=1.0`.
+    pub score: f64,
+}
+
+/// The last two commands run in a session, most recent last.
+#[derive(Debug, Clone, Default)]
+struct Tail {
+    previous: Option<String>,
+    last: Option<String>,
+}
+
+impl Tail {
+    fn push(&mut self, command: &str) {
+        self.previous = self.last.replace(command.to_string());
+    }
+}
+
+/// An n-gram model of which commands follow which.
+#[derive(Debug, Default)]
+pub struct Predictor {
+    bigrams: HashMap<String, Counts>,
+    trigrams: HashMap<(String, String), Counts>,
+    dir_bigrams: HashMap<(String, String), Counts>,
+    dir_trigrams: HashMap<(String, String, String), Counts>,
+    /// Commands run in each directory, regardless of what came before.
+    dir_unigrams: HashMap<String, Counts>,
+    sessions: HashMap<String, Tail>,
+}
+
+impl Predictor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a model from history, which must be in chronological order.
+    pub fn from_history<'a>(history: impl IntoIterator<Item = &'a History>) -> Self {
+        let mut predictor = Self::new();
+        for h in history {
+            predictor.observe(h);
+        }
+        predictor
+    }
+
+    /// Add a command to the model. Commands from a session must be observed in
+    /// the order they were run.
+    ///
+    /// Deleted commands are skipped, as are commands run by agents, as they'd predict what the
+    /// agent does next.
+    pub fn observe(&mut self, history: &History) {
+        let command = history.command.as_str();
+        if command.trim().is_empty() || history.deleted_at.is_some() || history.is_agent() {
+            return;
+        }
+
+        bump(self.dir_unigrams.entry(history.cwd.clone()).or_default(), command);
+
+        let tail = self.sessions.entry(history.session.clone()).or_default();
+
+        if let Some(last) = &tail.last {
+            // Running the same command again isn't a transition worth predicting.
+            if last == command {
+                return;
+            }
+
+            bump(self.bigrams.entry(last.clone()).or_default(), command);
+            bump(self.dir_bigrams.entry((history.cwd.clone(), last.clone())).or_default(), command);
+
+            if let Some(previous) = &tail.previous {
+                bump(self.trigrams.entry((previous.clone(), last.clone())).or_default(), command);
+                bump(
+                    self.dir_trigrams
+                        .entry((history.cwd.clone(), previous.clone(), last.clone()))
+                        .or_default(),
+                    command,
+                );
+            }
+        }
+
+        tail.push(command);
+    }
+
+    /// The commands most likely to be run next in `session`, from `cwd`.
+    pub fn predict(&self, session: &str, cwd: &str, limit: usize) -> Vec<Prediction> {
+        let tail = self.sessions.get(session).cloned().unwrap_or_default();
+        self.predict_after(cwd, tail.previous.as_deref(), tail.last.as_deref(), limit)
+    }
+
+    /// The commands most likely to follow `previous` then `last`, from `cwd`.
+    pub fn predict_after(
+        &self,
+        cwd: &str,
+        previous: Option<&str>,
+        last: Option<&str>,
+        limit: usize,
+    ) -> Vec<Prediction> {
+        let cwd = cwd.to_string();
+        let mut models: Vec<(f64, Option<&Counts>)> = Vec::with_capacity(5);
+
+        if let Some(last) = last {
+            let last = last.to_string();
+
+            if let Some(previous) = previous {
+                let previous = previous.to_string();
+                models.push((
+                    DIR_TRIGRAM_WEIGHT,
+                    self.dir_trigrams.get(&(cwd.clone(), previous.clone(), last.clone())),
+                ));
+                models.push((TRIGRAM_WEIGHT, self.trigrams.get(&(previous, last.clone()))));
+            }
+
+            models.push((DIR_BIGRAM_WEIGHT, self.dir_bigrams.get(&(cwd.clone(), last.clone()))));
+            models.push((BIGRAM_WEIGHT, self.bigrams.get(&last)));
+        }
+
+        models.push((DIR_UNIGRAM_WEIGHT, self.dir_unigrams.get(&cwd)));
+
+        let mut scores: HashMap<&str, f64> = HashMap::new();
+        for (weight, counts) in models {
+            let Some(counts) = counts else {
+                continue;
+            };
+            let total: u32 = counts.values().sum();
+            for (command, &count) in counts {
+                *scores.entry(command.as_str()).or_default() +=
+                    weight * f64::from(count) / f64::from(total);
+            }
+        }
+
+        // Suggesting the command that was just run is never useful.
+        if let Some(last) = last {
+            scores.remove(last);
+        }
+
+        let mut predictions: Vec<Prediction> = scores
+            .into_iter()
+            .map(|(command, score)| Prediction {
+                command: command.to_string(),
+                score,
+            })
+            .collect();
+
+        predictions
+            .sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.command.cmp(&b.command)));
+        predictions.truncate(limit);
+        predictions
+    }
+}
+
+fn bump(counts: &mut Counts, command: &str) {
+    *counts.entry(command.to_string()).or_default() += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use time::{Duration, OffsetDateTime};
+
+    use super::*;
+
+    fn history(session: &str, cwd: &str, commands: &[&str]) -> Vec<History> {
+        let start = OffsetDateTime::now_utc() - Duration::hours(1);
+        commands
+            .iter()
+            .enumerate()
+            .map(|(i, command)| {
+                History::import()
+                    .timestamp(start + Duration::seconds(i as i64))
+                    .command(*command)
+                    .cwd(cwd)
+                    .session(session)
+                    .build()
+                    .into()
+            })
+            .collect()
+    }
+
+    fn commands(predictions: Vec<Prediction>) -> Vec<String> {
+        predictions.into_iter().map(|p| p.command).collect()
+    }
+
+    #[test]
+    fn predicts_the_usual_next_command() {
+        let mut entries = history("a", "/src", &["cargo build", "cargo test", "git push"]);
+        entries.extend(history("b", "/src", &["cargo build", "cargo test", "cargo build"]));
+        entries.extend(history("c", "/src", &["cargo build", "cargo clippy"]));
+        entries.extend(history("now", "/src", &["cargo build"]));
+
+        let predictor = Predictor::from_history(&entries);
+        let predictions = commands(predictor.predict("now", "/src", 2));
+
+        assert_eq!(predictions, vec!["cargo test", "cargo clippy"]);
+    }
+
+    #[test]
+    fn trigrams_disambiguate() {
+        let mut entries = history("a", "/src", &["git add .", "git commit", "git push"]);
+        entries.extend(history("b", "/src", &["vim", "git commit", "git log"]));
+        entries.extend(history("c", "/src", &["vim", "git commit", "git log"]));
+
+        let predictor = Predictor::from_history(&entries);
+
+        let after_add = predictor.predict_after("/src", Some("git add ."), Some("git commit"), 1);
+        assert_eq!(commands(after_add), vec!["git push"]);
+
+        let after_vim = predictor.predict_after("/src", Some("vim"), Some("git commit"), 1);
+        assert_eq!(commands(after_vim), vec!["git log"]);
+    }
+
+    #[test]
+    fn prefers_this_directory() {
+        let mut entries = history("a", "/web", &["make", "npm test"]);
+        entries.extend(history("b", "/web", &["make", "npm test"]));
+        entries.extend(history("c", "/web", &["make", "npm test"]));
+        entries.extend(history("d", "/rust", &["make", "cargo test"]));
+
+        let predictor = Predictor::from_history(&entries);
+        let predictions = predictor.predict_after("/rust", None, Some("make"), 1);
+
+        assert_eq!(commands(predictions), vec!["cargo test"]);
+    }
+
+    #[test]
+    fn empty_session_falls_back_to_directory() {
+        let entries = history("a", "/src", &["ls", "cargo build", "cargo build"]);
+
+        let predictor = Predictor::from_history(&entries);
+        let predictions = predictor.predict("new", "/src", 5);
+
+        assert_eq!(commands(predictions), vec!["cargo build", "ls"]);
+        assert!(predictor.predict("new", "/elsewhere", 5).is_empty());
+    }
+}
@@ -1,46 +1,35 @@
-use atuin_client::history::History;
-
-type ScoredHistory = (f64, History);
+//! Result ranking ("smart sort").
+//!
+//! The ranking pipeline itself lives in [`atuin_client::ranking`], so that
+//! `Sqlite::search` can rank every search mode the same way; it's re-exported
+//! here for everything that sorts history outside of a search.
 
-// Fuzzy search already comes sorted by minspan
-// This sorting should be applicable to all search modes, and solve the more "obvious" issues
-// first.
-// Later on, we can pass in context and do some boosts there too.
+use atuin_client::database::Context;
+use atuin_client::history::History;
+pub use atuin_client::ranking::{
+    Affinity, ExitSuccess, Frecency, FrecencyData, Pipeline, QueryMatch, RankContext, Ranker,
+    RankingWeights, Recency, SameCwd, SameGitRoot, SameHost, SameSession, Scorer,
+};
+use atuin_domain::record::CmdOrigin;
+
+/// Sort history by how well it matches `query`, preferring newer history.
+///
+/// This has no context to boost with, so it only uses the [`QueryMatch`] and [`Recency`]
+/// scorers. Use [`sort_with`] to rank with a full [`Pipeline`].
 pub fn sort(query: &str, input: Vec<History>) -> Vec<History> {
-    // This can totally be extended. We need to be _careful_ that it's not slow.
-    // We also need to balance sorting db-side with sorting here. SQLite can do a lot,
-    // but some things are just much easier/more doable in Rust.
-
-    let mut scored = input
-        .into_iter()
-        .map(|h| {
-            // If history is _prefixed_ with the query, score it more highly
-            let score = if h.command.starts_with(query) {
-                2.0
-            } else if h.command.contains(query) {
-                1.75
-            } else {
-                1.0
-            };
-
-            // calculate how long ago the history was, in seconds
-            let now = time::OffsetDateTime::now_utc().unix_timestamp();
-            let time = h.timestamp.unix_timestamp();
-            let diff = std::cmp::max(1, now - time); // no /0 please
-
-            // prefer newer history, but not hugely so as to offset the other scoring
-            // the numbers will get super small over time, but I don't want time to overpower other
-            // scoring
-            #[allow(clippy::cast_precision_loss)]
-            let time_score = 1.0 + (1.0 / diff as f64);
-            let score = score * time_score;
-
-            (score, h)
-        })
-        .collect::<Vec<ScoredHistory>>();
-
-    scored.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap().reverse());
+    let context = Context {
+        session: String::new(),
+        cwd: String::new(),
+        cmd_origin: CmdOrigin::default(),
+        host_id: String::new(),
+        git_root: None,
+    };
+    let pipeline = Pipeline::new().with(1.0, QueryMatch).with(1.0, Recency);
+
+    sort_with(&pipeline, &RankContext::new(query, &context), input)
+}
 
-    // Remove the scores and return the history
-    scored.into_iter().map(|(_, h)| h).collect::<Vec<History>>()
+/// Sort history with any [`Ranker`].
+pub fn sort_with(ranker: &dyn Ranker, ctx: &RankContext<'_>, input: Vec<History>) -> Vec<History> {
+    ranker.rank(ctx, input)
 }
@@ -172,7 +172,7 @@ fn index(scale: usize) -> Arc<SearchIndex> {
     }
 
     eprintln!("building index from {scale} history lines...");
-    let index = SearchIndex::new(OrFilter::all());
+    let index = SearchIndex::new(OrFilter::all(), false);
     let now = OffsetDateTime::now_utc();
     for (i, command) in commands()[..scale].iter().enumerate() {
         let history: History = History::import()
@@ -183,7 +183,7 @@ fn index(scale: usize) -> Arc<SearchIndex> {
             .into();
         index.add_history(&history);
     }
-    index.rebuild_frecency(&SearchSettings::default());
+    index.rebuild_frecency(&SearchSettings::default(), false);
     eprintln!("index ready: {} unique commands", index.command_count());
 
     for query in QUERIES {
@@ -0,0 +1,247 @@
+//! The daemon's index must answer non-interactive searches exactly as the database does.
+//!
+//! Both engines are loaded with the same synthetic history (see `src/corpus.rs`), spread over
+//! directories, hosts, sessions, authors, shells and exit codes, and every search runs through
+//! both: `Sqlite::search` on one side, `SearchIndex::query` plus `Sqlite::arrange` (which is what
+//! the client does with the daemon's results) on the other.
+
+use atuin_client::database::search::SearchQuery;
+use atuin_client::database::{Context, DbSearchMode, OptFilters, Sqlite};
+use atuin_client::history::{AuthorKind, AuthorPattern, History};
+use atuin_client::settings::FilterMode;
+use atuin_common::filter::OrFilter;
+use atuin_common::utils::uuid_v7;
+use atuin_daemon::search::SearchIndex;
+use atuin_domain::record::CmdOrigin;
+use atuin_search_bench::corpus;
+use time::{Duration, OffsetDateTime};
+
+const LINES: usize = 5000;
+
+const DIRS: &[&str] = &[
+    "/home/user/src/atuin",
+    "/home/user/src/atuin/crates",
+    "/home/user/src/backend",
+    "/home/user",
+    "/tmp/scratch",
+];
+
+const ORIGINS: &[&str] = &["laptop:user", "laptop:root", "build-01:ci"];
+
+const SHELLS: &[Option<&str>] = &[Some("zsh"), Some("bash"), Some("fish"), None, Some("")];
+
+const EXITS: &[i64] = &[0, 0, 0, 1, 0, 127, -1, 0, 2];
+
+const MODES: &[DbSearchMode] = &[DbSearchMode::Prefix, DbSearchMode::FullText, DbSearchMode::Fuzzy];
+
+const QUERIES: &[&str] = &[
+    "",
+    "g",
+    "git",
+    "git p",
+    "Git",
+    "cargo build",
+    "git c*t",
+    "^git !push",
+    "'status log$",
+    "docker | kubectl",
+    "r/^git (push|pull)",
+    "exit:1 g",
+    "cwd:/home/*",
+    "zzznomatchzzz",
+];
+
+const FILTERS: &[FilterMode] = &[
+    FilterMode::Global,
+    FilterMode::Host,
+    FilterMode::Session,
+    FilterMode::SessionPreload,
+    FilterMode::Directory,
+    FilterMode::Workspace,
+];
+
+struct Engines {
+    db: Sqlite,
+    index: SearchIndex,
+    context: Context,
+}
+
+async fn engines() -> Engines {
+    let db = Sqlite::new("sqlite::memory:", 5.0).await.unwrap();
+    let index = SearchIndex::new(OrFilter::all(), true);
+    let sessions: Vec<String> = (0..3).map(|_| uuid_v7().as_simple().to_string()).collect();
+
+    let now = OffsetDateTime::now_utc();
+    let history: Vec<History> = corpus::generate(LINES, 42)
+        .into_iter()
+        .enumerate()
+        .map(|(i, command)| {
+            let agent = i % 11 == 0;
+            History::import()
+                .timestamp(now - Duration::seconds(37 * i as i64))
+                .command(command)
+                .cwd(DIRS[i % DIRS.len()])
+                .cmd_origin(CmdOrigin::try_from(ORIGINS[i % ORIGINS.len()]).unwrap())
+                .session(sessions[i % sessions.len()].clone())
+                .shell_opt(SHELLS[i % SHELLS.len()].map(str::to_string))
+                .exit(EXITS[i % EXITS.len()])
+                .author(if agent {
+                    "claude-code"
+                } else {
+                    ""
+                })
+                .author_kind(agent.then_some(AuthorKind::Agent))
+                .build()
+                .into()
+        })
+        .collect();
+
+    db.save_bulk(&history).await.unwrap();
+    for h in &history {
+        index.add_history(h);
+    }
+    index.mark_loaded();
+
+    let context = Context {
+        session: sessions[0].clone(),
+        cwd: DIRS[0].to_string(),
+        cmd_origin: CmdOrigin::try_from(ORIGINS[0]).unwrap(),
+        host_id: String::new(),
+        git_root: Some("/home/user/src/atuin".into()),
+    };
+
+    Engines { db, index, context }
+}
+
+impl Engines {
+    async fn assert_agree(
+        &self,
+        search_mode: DbSearchMode,
+        filter: FilterMode,
+        query: &str,
+        filter_options: OptFilters<'_>,
+    ) {
+        let expected = self
+            .db
+            .search(search_mode, filter, &self.context, query, filter_options)
+            .await
+            .unwrap();
+
+        let search =
+            SearchQuery::new(search_mode, filter, &self.context, query, &filter_options).unwrap();
+        let found = self
+            .db
+            .arrange(search_mode, query, &self.context, None, self.index.query(&search))
+            .await
+            .unwrap();
+
+        let ids = |history: &[History]| history.iter().map(|h| h.id.0.clone()).collect::<Vec<_>>();
+        assert_eq!(
+            ids(&found),
+            ids(&expected),
+            "{search_mode:?} search for {query:?} in {filter:?} returned different entries",
+        );
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn queries() {
+    let engines = engines().await;
+    for &mode in MODES {
+        for &query in QUERIES {
+            engines.assert_agree(mode, FilterMode::Global, query, OptFilters::default()).await;
+            let all_runs = OptFilters {
+                include_duplicates: true,
+                ..OptFilters::default()
+            };
+            engines.assert_agree(mode, FilterMode::Global, query, all_runs).await;
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn filter_modes() {
+    let engines = engines().await;
+    for &mode in MODES {
+        for &filter in FILTERS {
+            for query in ["", "git", "^cargo !test"] {
+                engines.assert_agree(mode, filter, query, OptFilters::default()).await;
+            }
+        }
+    }
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn options() {
+    let engines = engines().await;
+    let users = [AuthorPattern::AllUser];
+    let agents = [AuthorPattern::AllAgent];
+    let shells = [String::new(), "fish".to_string()];
+
+    let options = [
+        OptFilters {
+            limit: Some(20),
+            offset: Some(5),
+            ..OptFilters::default()
+        },
+        OptFilters {
+            limit: Some(20),
+            reverse: true,
+            ..OptFilters::default()
+        },
+        OptFilters {
+            include_duplicates: true,
+            reverse: true,
+            offset: Some(100),
+            ..OptFilters::default()
+        },
+        OptFilters {
+            exit: Some(1),
+            ..OptFilters::default()
+        },
+        OptFilters {
+            exclude_exit: Some(0),
+            ..OptFilters::default()
+        },
+        OptFilters {
+            only_failed: true,
+            include_duplicates: true,
+            ..OptFilters::default()
+        },
+        OptFilters {
+            cwd: Some("/home/user"),
+            ..OptFilters::default()
+        },
+        OptFilters {
+            exclude_cwd: Some("/tmp/scratch"),
+            ..OptFilters::default()
+        },
+        OptFilters {
+            after: Some("3 days ago"),
+            before: Some("1 day ago"),
+            ..OptFilters::default()
+        },
+        OptFilters {
+            authors: OrFilter::new_unchecked(&users),
+            ..OptFilters::default()
+        },
+        OptFilters {
+            authors: OrFilter::new_unchecked(&agents),
+            include_duplicates: true,
+            ..OptFilters::default()
+        },
+        OptFilters {
+            shells: OrFilter::new_unchecked(&shells),
+            ..OptFilters::default()
+        },
+    ];
+
+    for &mode in MODES {
+        for filter_options in options {
+            for query in ["", "git", "docker | kubectl"] {
+                engines.assert_agree(mode, FilterMode::Global, query, filter_options).await;
+            }
+            engines.assert_agree(mode, FilterMode::Directory, "g", filter_options).await;
+        }
+    }
+}
@@ -7,8 +7,13 @@ use std::fmt::Debug;
 use async_trait::async_trait;
 use atuin_domain::record::{EncryptedData, Record, RecordIdx, RecordSeriesKey, RecordStatus};
 use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use time::OffsetDateTime;
 
-use self::models::{NewSession, NewUser, Session, User};
+use self::models::{
+    Invite, NewInvite, NewPackfile, NewSession, NewTeamMember, NewUser, Packfile, Session, Team,
+    TeamMember, Usage, UsageCharge, User,
+};
 
 #[derive(Debug, derive_more::Display, derive_more::Error, derive_more::From)]
 #[display("{self:?}")]
@@ -56,6 +61,36 @@ impl DbSettings {
     }
 }
 
+/// Limits on how much each user can store. `0` means no limit.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Quota {
+    /// The most records a user can store.
+    pub records: u64,
+    /// The most bytes of (encrypted) record data a user can store.
+    pub bytes: u64,
+}
+
+impl Quota {
+    pub fn is_unlimited(&self) -> bool {
+        self.records == 0 && self.bytes == 0
+    }
+
+    /// Whether a user can store this many records, taking up this many bytes.
+    pub fn allows(&self, records: u64, bytes: u64) -> bool {
+        (self.records == 0 || records <= self.records) && (self.bytes == 0 || bytes <= self.bytes)
+    }
+
+    /// Whether the upload that brought a user to `usage` can be kept. Uploads that add nothing
+    /// are always allowed, even if the user is already over a lowered quota.
+    pub fn allows_upload(&self, usage: &Usage, added: &Usage) -> bool {
+        (added.records == 0 && added.bytes == 0)
+            || self.allows(
+                u64::try_from(usage.records).unwrap_or_default(),
+                u64::try_from(usage.bytes).unwrap_or_default(),
+            )
+    }
+}
+
 fn redact_db_uri(uri: &str) -> String {
     url::Url::parse(uri)
         .map(|mut url| {
@@ -90,18 +125,43 @@ pub trait Database: Sized + Clone + Send + Sync + 'static {
 
     async fn get_session(&self, token: &str) -> DbResult<Session>;
     async fn get_session_user(&self, token: &str) -> DbResult<User>;
+    /// The session and the user it belongs to, in one query, for authenticating requests.
+    async fn get_session_with_user(&self, token: &str) -> DbResult<(Session, User)>;
     async fn add_session(&self, session: &NewSession) -> DbResult<()>;
 
+    /// Delete every session belonging to the user, logging them out everywhere.
+    async fn delete_user_sessions(&self, u: &User) -> DbResult<()>;
+    async fn list_sessions(&self, u: &User) -> DbResult<Vec<Session>>;
+    /// Fails with [`DbError::NotFound`] if the user has no session with the id.
+    async fn delete_session(&self, u: &User, id: i64) -> DbResult<()>;
+    /// Delete every session belonging to the user, apart from `keep`.
+    async fn delete_other_sessions(&self, u: &User, keep: i64) -> DbResult<()>;
+    /// Record that a session was used at `at`.
+    async fn touch_session(&self, id: i64, at: OffsetDateTime) -> DbResult<()>;
+
     async fn get_user(&self, username: &str) -> DbResult<User>;
-    async fn get_user_session(&self, u: &User) -> DbResult<Session>;
     async fn add_user(&self, user: &NewUser) -> DbResult<i64>;
+    /// Add a user, using up one of the invite's uses. Fails with [`DbError::NotFound`] if the
+    /// invite doesn't exist, has been used up, or has expired; the user isn't added then.
+    async fn add_user_with_invite(&self, user: &NewUser, invite: &str) -> DbResult<i64>;
+    async fn list_users(&self) -> DbResult<Vec<User>>;
 
     async fn update_user_password(&self, u: &User) -> DbResult<()>;
+    async fn set_user_disabled(&self, u: &User, disabled: bool) -> DbResult<()>;
 
+    /// Deleting a user or their store also forgets their packfiles, but the blobs have to be
+    /// removed from packfile storage separately.
     async fn delete_user(&self, u: &User) -> DbResult<()>;
     async fn delete_store(&self, user: &User) -> DbResult<()>;
 
-    async fn add_records(&self, user: &User, record: &[Record<EncryptedData>]) -> DbResult<()>;
+    /// Store the records the server doesn't already have, and charge them to the user, unless
+    /// that would take the user over `quota`. Records the server already has don't count.
+    async fn add_records(
+        &self,
+        user: &User,
+        record: &[Record<EncryptedData>],
+        quota: &Quota,
+    ) -> DbResult<UsageCharge>;
     async fn next_records(
         &self,
         user: &User,
@@ -112,4 +172,55 @@ pub trait Database: Sized + Clone + Send + Sync + 'static {
 
     // Return the tail record ID for each store, so (HostID, Tag, TailRecordID)
     async fn status(&self, user: &User) -> DbResult<RecordStatus>;
+    /// What's charged to the account owning `user`'s store: their own store, and the stores of
+    /// the teams they own. For a team's store, that's its owner's.
+    async fn usage(&self, user: &User) -> DbResult<Usage>;
+
+    /// Whether registration has been opened or closed at runtime, overriding
+    /// `open_registration` in the server config. `None` if it hasn't.
+    async fn registration_override(&self) -> DbResult<Option<bool>>;
+    async fn set_registration_override(&self, open: Option<bool>) -> DbResult<()>;
+
+    async fn add_invite(&self, invite: &NewInvite) -> DbResult<()>;
+    async fn list_invites(&self) -> DbResult<Vec<Invite>>;
+    /// Fails with [`DbError::NotFound`] if there's no invite with the code.
+    async fn delete_invite(&self, code: &str) -> DbResult<()>;
+
+    /// Start a packfile upload. Uploading a manifest's packfile again replaces the previous one,
+    /// which is unconfirmed, and stops counting towards the user's usage, until the new upload is
+    /// confirmed.
+    async fn add_packfile(&self, packfile: &NewPackfile) -> DbResult<()>;
+    async fn get_packfile(&self, user: &User, manifest_id: Uuid) -> DbResult<Packfile>;
+    async fn get_packfile_by_token(&self, token: &str) -> DbResult<Packfile>;
+    /// Mark the upload finished, and charge the packfile to the user's usage, unless that would
+    /// take the user over `quota`. Confirming again does nothing. Fails with
+    /// [`DbError::NotFound`] if the user has no packfile for the manifest.
+    async fn confirm_packfile(
+        &self,
+        user: &User,
+        manifest_id: Uuid,
+        quota: &Quota,
+    ) -> DbResult<UsageCharge>;
+
+    /// The public key others seal team keys to, if the user has published one.
+    async fn get_public_key(&self, u: &User) -> DbResult<Option<String>>;
+    async fn set_public_key(&self, u: &User, public_key: &str) -> DbResult<()>;
+
+    /// Create a team with `admin` as its only member, and its owner.
+    async fn add_team(&self, name: &str, admin: &User, sealed_key: &str) -> DbResult<Team>;
+    async fn get_team(&self, name: &str) -> DbResult<Team>;
+    /// Forget the team, its members and its store. Its packfile blobs have to be removed from
+    /// packfile storage separately.
+    async fn delete_team(&self, team: &Team) -> DbResult<()>;
+
+    /// The user's memberships of every team they're in.
+    async fn list_user_teams(&self, u: &User) -> DbResult<Vec<TeamMember>>;
+    async fn list_team_members(&self, team: &Team) -> DbResult<Vec<TeamMember>>;
+    /// Fails with [`DbError::NotFound`] if the user isn't in the team.
+    async fn get_team_member(&self, team: &Team, u: &User) -> DbResult<TeamMember>;
+    /// Adding someone who's already a member replaces their sealed key and role.
+    async fn add_team_member(&self, member: &NewTeamMember) -> DbResult<()>;
+    /// Fails with [`DbError::NotFound`] if the user isn't in the team. If they owned it, it
+    /// passes to the longest-standing remaining admin.
+    async fn delete_team_member(&self, team: &Team, u: &User) -> DbResult<()>;
 }
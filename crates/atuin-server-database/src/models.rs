@@ -1,9 +1,14 @@
+use sqlx::types::Uuid;
+use time::OffsetDateTime;
+
 #[derive(sqlx::FromRow)]
 pub struct User {
     pub id: i64,
     pub username: String,
     pub email: String,
     pub password: String,
+    /// Disabled users can't log in, and their sessions are refused.
+    pub disabled: bool,
 }
 
 #[derive(sqlx::FromRow)]
@@ -11,6 +16,11 @@ pub struct Session {
     pub id: i64,
     pub user_id: i64,
     pub token: String,
+    /// `None` for sessions created before this was recorded.
+    pub created_at: Option<OffsetDateTime>,
+    pub last_seen_at: Option<OffsetDateTime>,
+    /// The user agent of the client that logged in.
+    pub user_agent: Option<String>,
 }
 
 pub struct NewUser {
@@ -22,4 +32,108 @@ pub struct NewUser {
 pub struct NewSession {
     pub user_id: i64,
     pub token: String,
+    pub user_agent: Option<String>,
+}
+
+/// How much a user has stored on the server.
+#[derive(sqlx::FromRow)]
+pub struct Usage {
+    pub records: i64,
+    /// The size of the records' encrypted data, in bytes.
+    pub bytes: i64,
+}
+
+/// What came of charging an upload to a user's usage.
+pub enum UsageCharge {
+    /// The upload was stored, bringing the user's usage to this.
+    Stored(Usage),
+    /// The upload would have brought the user's usage to this, over their quota, so it wasn't
+    /// stored.
+    OverQuota(Usage),
+}
+
+/// A code that lets someone register while registration is closed.
+#[derive(sqlx::FromRow)]
+pub struct Invite {
+    pub id: i64,
+    pub code: String,
+    /// How many accounts can be registered with the code.
+    pub max_uses: i64,
+    pub uses: i64,
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+pub struct NewInvite {
+    pub code: String,
+    pub max_uses: i64,
+    pub expires_at: Option<OffsetDateTime>,
+}
+
+/// A packfile blob a client has uploaded, or is uploading.
+#[derive(sqlx::FromRow)]
+pub struct Packfile {
+    pub id: i64,
+    pub user_id: i64,
+    /// The id of the packfile manifest record the blob belongs to.
+    pub manifest_id: Uuid,
+    /// The size of the blob in bytes, as the client declared it.
+    pub size: i64,
+    /// The base64 SHA-256 of the blob, if the client declared one.
+    pub sha256: Option<String>,
+    /// How many records the packfile holds.
+    pub records: i64,
+    /// Authorises uploading and downloading the blob without a session.
+    pub token: String,
+    /// Whether the client has confirmed it finished uploading the blob.
+    pub confirmed: bool,
+}
+
+pub struct NewPackfile {
+    pub user_id: i64,
+    pub manifest_id: Uuid,
+    pub size: i64,
+    pub sha256: Option<String>,
+    /// How many records the packfile holds.
+    pub records: i64,
+    pub token: String,
+}
+
+/// A group of users sharing a store, encrypted with a key only the members hold.
+#[derive(sqlx::FromRow)]
+pub struct Team {
+    pub id: i64,
+    pub name: String,
+}
+
+impl Team {
+    /// Who the team's store and packfiles belong to. They're kept with users' stores, under the
+    /// team's id negated so the two never collide.
+    pub fn store_owner(&self) -> User {
+        User {
+            id: -self.id,
+            username: format!("team:{}", self.name),
+            email: String::new(),
+            password: String::new(),
+            disabled: false,
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+pub struct TeamMember {
+    pub team_id: i64,
+    pub team: String,
+    pub user_id: i64,
+    pub username: String,
+    /// The team key, sealed to the member's public key.
+    pub sealed_key: String,
+    /// Admins can add and remove members, and delete the team.
+    pub admin: bool,
+}
+
+pub struct NewTeamMember {
+    pub team_id: i64,
+    pub user_id: i64,
+    pub sealed_key: String,
+    pub admin: bool,
 }
@@ -0,0 +1,9 @@
+-- codes that let people register while open_registration is off
+create table invites (
+	id bigserial primary key,
+	code text not null unique,
+	max_uses bigint not null,
+	uses bigint not null default 0,
+	expires_at timestamp with time zone default null,
+	created_at timestamp with time zone not null default current_timestamp
+);
@@ -0,0 +1,5 @@
+-- let users see where they're logged in, and expire sessions that aren't used.
+-- sessions from before this don't know when they were created
+alter table sessions add column created_at timestamp with time zone default null;
+alter table sessions add column last_seen_at timestamp with time zone default null;
+alter table sessions add column user_agent text default null;
@@ -0,0 +1,13 @@
+-- packfiles clients have uploaded. The blobs themselves live in packfile storage, not here
+create table packfiles (
+	id bigserial primary key,
+	user_id bigint not null,
+	manifest_id uuid not null,      -- the id of the packfile manifest record the blob belongs to
+	size bigint not null,
+	sha256 text,                    -- the base64 sha-256 of the blob, if the client declared one
+	token text not null unique,     -- lets the blob be uploaded and downloaded without a session
+	confirmed_at timestamp with time zone default null,
+	created_at timestamp with time zone not null default current_timestamp
+);
+
+create unique index packfiles_uniq on packfiles(user_id, manifest_id);
@@ -0,0 +1,12 @@
+-- running totals of what each user has stored, kept up to date as records are added, so checking
+-- a quota doesn't mean counting the whole store
+create table store_usage (
+	user_id bigint primary key,
+	records bigint not null default 0,
+	bytes bigint not null default 0   -- the size of the records' data and cek
+);
+
+insert into store_usage (user_id, records, bytes)
+select user_id, count(*), sum(length(data) + length(cek))
+from store
+group by user_id;
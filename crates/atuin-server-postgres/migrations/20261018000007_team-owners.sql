@@ -0,0 +1,12 @@
+-- a team's store is charged to its owner's quota: whoever created it, or the longest-standing
+-- admin once they've left
+alter table teams add column owner_id bigint default null;
+
+update teams set owner_id = (
+	select user_id from team_members
+	where team_members.team_id = teams.id and admin
+	order by created_at
+	limit 1
+);
+
+create index teams_owner on teams(owner_id);
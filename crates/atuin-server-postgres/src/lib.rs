@@ -4,10 +4,15 @@ use async_trait::async_trait;
 use atuin_domain::record::{
     EncryptedData, HostId, Record, RecordIdx, RecordSeriesKey, RecordStatus, RecordTag,
 };
-use atuin_server_database::models::{NewSession, NewUser, Session, User};
-use atuin_server_database::{Database, DbError, DbResult, DbSettings};
+use atuin_server_database::models::{
+    Invite, NewInvite, NewPackfile, NewSession, NewTeamMember, NewUser, Packfile, Session, Team,
+    TeamMember, Usage, UsageCharge, User,
+};
+use atuin_server_database::{Database, DbError, DbResult, DbSettings, Quota};
 use rand::Rng;
 use sqlx::postgres::PgPoolOptions;
+use sqlx::{FromRow, Row};
+use time::OffsetDateTime;
 use tracing::instrument;
 use uuid::Uuid;
 use wrappers::DbRecord;
@@ -87,26 +92,64 @@ impl Database for Postgres {
 
     #[instrument(skip_all)]
     async fn get_session(&self, token: &str) -> DbResult<Session> {
-        sqlx::query_as("select id, user_id, token from sessions where token = $1")
-            .bind(token)
-            .fetch_one(self.read_pool())
-            .await
-            .map_err(Into::into)
+        sqlx::query_as(
+            "select id, user_id, token, created_at, last_seen_at, user_agent from sessions
+            where token = $1",
+        )
+        .bind(token)
+        .fetch_one(self.read_pool())
+        .await
+        .map_err(Into::into)
     }
 
     #[instrument(skip_all)]
     async fn get_user(&self, username: &str) -> DbResult<User> {
-        sqlx::query_as("select id, username, email, password from users where username = $1")
-            .bind(username)
-            .fetch_one(self.read_pool())
-            .await
-            .map_err(Into::into)
+        sqlx::query_as(
+            "select id, username, email, password, (disabled_at is not null) as disabled from \
+             users
+            where username = $1",
+        )
+        .bind(username)
+        .fetch_one(self.read_pool())
+        .await
+        .map_err(Into::into)
+    }
+
+    #[instrument(skip_all)]
+    async fn get_session_with_user(&self, token: &str) -> DbResult<(Session, User)> {
+        let row = sqlx::query(
+            "select sessions.id as session_id, sessions.token, sessions.created_at,
+                sessions.last_seen_at, sessions.user_agent,
+                users.id, users.username, users.email, users.password,
+                (users.disabled_at is not null) as disabled
+            from sessions
+            inner join users
+            on users.id = sessions.user_id
+            where sessions.token = $1",
+        )
+        .bind(token)
+        .fetch_one(self.read_pool())
+        .await?;
+
+        let user = User::from_row(&row)?;
+        let session = Session {
+            id: row.try_get("session_id")?,
+            user_id: user.id,
+            token: row.try_get("token")?,
+            created_at: row.try_get("created_at")?,
+            last_seen_at: row.try_get("last_seen_at")?,
+            user_agent: row.try_get("user_agent")?,
+        };
+
+        Ok((session, user))
     }
 
     #[instrument(skip_all)]
     async fn get_session_user(&self, token: &str) -> DbResult<User> {
         sqlx::query_as(
-            "select users.id, users.username, users.email, users.password from users
+            "select users.id, users.username, users.email, users.password,
+                (users.disabled_at is not null) as disabled
+            from users
             inner join sessions
             on users.id = sessions.user_id
             and sessions.token = $1",
@@ -136,6 +179,16 @@ impl Database for Postgres {
         .execute(&mut *tx)
         .await?;
 
+        sqlx::query("delete from store_usage where user_id = $1")
+            .bind(user.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("delete from packfiles where user_id = $1")
+            .bind(user.id)
+            .execute(&mut *tx)
+            .await?;
+
         tx.commit().await?;
 
         Ok(())
@@ -155,6 +208,24 @@ impl Database for Postgres {
 
         sqlx::query("delete from store where user_id = $1").bind(u.id).execute(&self.pool).await?;
 
+        sqlx::query("delete from store_usage where user_id = $1")
+            .bind(u.id)
+            .execute(&self.pool)
+            .await?;
+
+        sqlx::query("delete from packfiles where user_id = $1")
+            .bind(u.id)
+            .execute(&self.pool)
+            .await?;
+
+        let mut tx = self.pool.begin().await?;
+        reassign_teams(&mut tx, u, None).await?;
+        sqlx::query("delete from team_members where user_id = $1")
+            .bind(u.id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
         sqlx::query("delete from total_history_count_user where user_id = $1")
             .bind(u.id)
             .execute(&self.pool)
@@ -201,17 +272,58 @@ impl Database for Postgres {
         Ok(res.0)
     }
 
+    #[instrument(skip_all)]
+    async fn add_user_with_invite(&self, user: &NewUser, invite: &str) -> DbResult<i64> {
+        let mut tx = self.pool.begin().await?;
+
+        // checking and using up the invite in one statement means two people can't both take
+        // its last use
+        let claimed = sqlx::query(
+            "update invites
+            set uses = uses + 1
+            where code = $1
+            and uses < max_uses
+            and (expires_at is null or expires_at > $2)",
+        )
+        .bind(invite)
+        .bind(OffsetDateTime::now_utc())
+        .execute(&mut *tx)
+        .await?;
+
+        if claimed.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+
+        let res: (i64,) = sqlx::query_as(
+            "insert into users
+                (username, email, password)
+            values($1, $2, $3)
+            returning id",
+        )
+        .bind(&user.username)
+        .bind(&user.email)
+        .bind(&user.password)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(res.0)
+    }
+
     #[instrument(skip_all)]
     async fn add_session(&self, session: &NewSession) -> DbResult<()> {
-        let token: &str = &session.token;
+        let now = OffsetDateTime::now_utc();
 
         sqlx::query(
             "insert into sessions
-                (user_id, token)
-            values($1, $2)",
+                (user_id, token, created_at, last_seen_at, user_agent)
+            values($1, $2, $3, $3, $4)",
         )
         .bind(session.user_id)
-        .bind(token)
+        .bind(&session.token)
+        .bind(now)
+        .bind(&session.user_agent)
         .execute(&self.pool)
         .await?;
 
@@ -219,16 +331,99 @@ impl Database for Postgres {
     }
 
     #[instrument(skip_all)]
-    async fn get_user_session(&self, u: &User) -> DbResult<Session> {
-        sqlx::query_as("select id, user_id, token from sessions where user_id = $1")
+    async fn delete_user_sessions(&self, u: &User) -> DbResult<()> {
+        sqlx::query("delete from sessions where user_id = $1")
             .bind(u.id)
-            .fetch_one(self.read_pool())
-            .await
-            .map_err(Into::into)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn list_sessions(&self, u: &User) -> DbResult<Vec<Session>> {
+        sqlx::query_as(
+            "select id, user_id, token, created_at, last_seen_at, user_agent from sessions
+            where user_id = $1
+            order by id",
+        )
+        .bind(u.id)
+        .fetch_all(self.read_pool())
+        .await
+        .map_err(Into::into)
     }
 
     #[instrument(skip_all)]
-    async fn add_records(&self, user: &User, records: &[Record<EncryptedData>]) -> DbResult<()> {
+    async fn delete_session(&self, u: &User, id: i64) -> DbResult<()> {
+        let res = sqlx::query("delete from sessions where user_id = $1 and id = $2")
+            .bind(u.id)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if res.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn delete_other_sessions(&self, u: &User, keep: i64) -> DbResult<()> {
+        sqlx::query("delete from sessions where user_id = $1 and id != $2")
+            .bind(u.id)
+            .bind(keep)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn touch_session(&self, id: i64, at: OffsetDateTime) -> DbResult<()> {
+        sqlx::query("update sessions set last_seen_at = $1 where id = $2")
+            .bind(at)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn list_users(&self) -> DbResult<Vec<User>> {
+        sqlx::query_as(
+            "select id, username, email, password, (disabled_at is not null) as disabled from \
+             users
+            order by id",
+        )
+        .fetch_all(self.read_pool())
+        .await
+        .map_err(Into::into)
+    }
+
+    #[instrument(skip_all)]
+    async fn set_user_disabled(&self, u: &User, disabled: bool) -> DbResult<()> {
+        sqlx::query(
+            "update users
+            set disabled_at = case when $1 then coalesce(disabled_at, current_timestamp) end
+            where id = $2",
+        )
+        .bind(disabled)
+        .bind(u.id)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn add_records(
+        &self,
+        user: &User,
+        records: &[Record<EncryptedData>],
+        quota: &Quota,
+    ) -> DbResult<UsageCharge> {
         let mut tx = self.pool.begin().await?;
 
         // We won't have uploaded this data if it wasn't the max. Therefore, we can deduce the max
@@ -239,6 +434,10 @@ impl Database for Postgres {
         // idx, so clients upload again. The cache logic can be verified with a sql query anyway :)
 
         let mut heads = HashMap::<(HostId, &str), u64>::new();
+        let mut added = Usage {
+            records: 0,
+            bytes: 0,
+        };
 
         for i in records {
             let id = atuin_common::utils::uuid_v7();
@@ -263,8 +462,10 @@ impl Database for Postgres {
             .execute(&mut *tx)
             .await?;
 
-            // Only update heads if we actually inserted the record
+            // Only update heads and usage if we actually inserted the record
             if result.rows_affected() > 0 {
+                added.records += 1;
+                added.bytes += (i.data.raw.len() + i.data.cek.len()) as i64;
                 heads
                     .entry((i.host.id, i.tag.as_str()))
                     .and_modify(|e| {
@@ -294,9 +495,7 @@ impl Database for Postgres {
             .await?;
         }
 
-        tx.commit().await?;
-
-        Ok(())
+        charge(tx, user.id, &added, quota).await
     }
 
     #[instrument(skip_all)]
@@ -388,4 +587,497 @@ impl Database for Postgres {
 
         Ok(status)
     }
+
+    #[instrument(skip_all)]
+    async fn usage(&self, user: &User) -> DbResult<Usage> {
+        account_usage(self.read_pool(), user.id).await
+    }
+
+    #[instrument(skip_all)]
+    async fn registration_override(&self) -> DbResult<Option<bool>> {
+        let value: Option<(String,)> =
+            sqlx::query_as("select value from server_settings where name = 'open_registration'")
+                .fetch_optional(self.read_pool())
+                .await?;
+
+        Ok(value.map(|(v,)| v == "true"))
+    }
+
+    #[instrument(skip_all)]
+    async fn set_registration_override(&self, open: Option<bool>) -> DbResult<()> {
+        match open {
+            Some(open) => {
+                sqlx::query(
+                    "insert into server_settings (name, value) values ('open_registration', $1)
+                    on conflict (name) do update set value = excluded.value",
+                )
+                .bind(open.to_string())
+                .execute(&self.pool)
+                .await?;
+            }
+            None => {
+                sqlx::query("delete from server_settings where name = 'open_registration'")
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn add_invite(&self, invite: &NewInvite) -> DbResult<()> {
+        sqlx::query(
+            "insert into invites
+                (code, max_uses, expires_at)
+            values($1, $2, $3)",
+        )
+        .bind(&invite.code)
+        .bind(invite.max_uses)
+        .bind(invite.expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn list_invites(&self) -> DbResult<Vec<Invite>> {
+        sqlx::query_as("select id, code, max_uses, uses, expires_at from invites order by id")
+            .fetch_all(self.read_pool())
+            .await
+            .map_err(Into::into)
+    }
+
+    #[instrument(skip_all)]
+    async fn delete_invite(&self, code: &str) -> DbResult<()> {
+        let res = sqlx::query("delete from invites where code = $1")
+            .bind(code)
+            .execute(&self.pool)
+            .await?;
+
+        if res.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn add_packfile(&self, packfile: &NewPackfile) -> DbResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        // an upload replaces the manifest's previous packfile, so that stops counting if it was
+        // confirmed, and the new one only counts once it is
+        sqlx::query(
+            "with old as (
+                select records, size
+                from packfiles
+                where user_id = $1 and manifest_id = $2 and confirmed_at is not null
+                for update
+            )
+            insert into store_usage (user_id, records, bytes)
+            select $1, -coalesce(sum(records), 0), -coalesce(sum(size), 0)
+            from old
+            on conflict (user_id) do update
+            set records = store_usage.records + excluded.records,
+                bytes = store_usage.bytes + excluded.bytes",
+        )
+        .bind(packfile.user_id)
+        .bind(packfile.manifest_id)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "insert into packfiles
+                (user_id, manifest_id, size, sha256, records, token, created_at)
+            values($1, $2, $3, $4, $5, $6, $7)
+            on conflict (user_id, manifest_id) do update
+            set size = excluded.size,
+                sha256 = excluded.sha256,
+                records = excluded.records,
+                token = excluded.token,
+                confirmed_at = null,
+                created_at = excluded.created_at",
+        )
+        .bind(packfile.user_id)
+        .bind(packfile.manifest_id)
+        .bind(packfile.size)
+        .bind(&packfile.sha256)
+        .bind(packfile.records)
+        .bind(&packfile.token)
+        .bind(OffsetDateTime::now_utc())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn get_packfile(&self, user: &User, manifest_id: Uuid) -> DbResult<Packfile> {
+        sqlx::query_as(
+            "select id, user_id, manifest_id, size, sha256, records, token,
+                (confirmed_at is not null) as confirmed
+            from packfiles
+            where user_id = $1 and manifest_id = $2",
+        )
+        .bind(user.id)
+        .bind(manifest_id)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    #[instrument(skip_all)]
+    async fn get_packfile_by_token(&self, token: &str) -> DbResult<Packfile> {
+        sqlx::query_as(
+            "select id, user_id, manifest_id, size, sha256, records, token,
+                (confirmed_at is not null) as confirmed
+            from packfiles
+            where token = $1",
+        )
+        .bind(token)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(Into::into)
+    }
+
+    #[instrument(skip_all)]
+    async fn confirm_packfile(
+        &self,
+        user: &User,
+        manifest_id: Uuid,
+        quota: &Quota,
+    ) -> DbResult<UsageCharge> {
+        let mut tx = self.pool.begin().await?;
+
+        // only the first confirmation charges the packfile to the user
+        let confirmed: Option<(i64, i64)> = sqlx::query_as(
+            "update packfiles set confirmed_at = $3
+            where user_id = $1 and manifest_id = $2 and confirmed_at is null
+            returning records, size",
+        )
+        .bind(user.id)
+        .bind(manifest_id)
+        .bind(OffsetDateTime::now_utc())
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        let added = match confirmed {
+            Some((records, bytes)) => Usage { records, bytes },
+            None => {
+                let exists: Option<(i64,)> = sqlx::query_as(
+                    "select id from packfiles where user_id = $1 and manifest_id = $2",
+                )
+                .bind(user.id)
+                .bind(manifest_id)
+                .fetch_optional(&mut *tx)
+                .await?;
+
+                if exists.is_none() {
+                    return Err(DbError::NotFound);
+                }
+
+                Usage {
+                    records: 0,
+                    bytes: 0,
+                }
+            }
+        };
+
+        charge(tx, user.id, &added, quota).await
+    }
+
+    #[instrument(skip_all)]
+    async fn get_public_key(&self, u: &User) -> DbResult<Option<String>> {
+        let res: (Option<String>,) = sqlx::query_as("select public_key from users where id = $1")
+            .bind(u.id)
+            .fetch_one(self.read_pool())
+            .await?;
+
+        Ok(res.0)
+    }
+
+    #[instrument(skip_all)]
+    async fn set_public_key(&self, u: &User, public_key: &str) -> DbResult<()> {
+        sqlx::query("update users set public_key = $1 where id = $2")
+            .bind(public_key)
+            .bind(u.id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn add_team(&self, name: &str, admin: &User, sealed_key: &str) -> DbResult<Team> {
+        let mut tx = self.pool.begin().await?;
+
+        let team: Team = sqlx::query_as(
+            "insert into teams (name, owner_id, created_at) values ($1, $2, $3)
+            returning id, name",
+        )
+        .bind(name)
+        .bind(admin.id)
+        .bind(OffsetDateTime::now_utc())
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query(
+            "insert into team_members
+                (team_id, user_id, sealed_key, admin, created_at)
+            values ($1, $2, $3, true, $4)",
+        )
+        .bind(team.id)
+        .bind(admin.id)
+        .bind(sealed_key)
+        .bind(OffsetDateTime::now_utc())
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(team)
+    }
+
+    #[instrument(skip_all)]
+    async fn get_team(&self, name: &str) -> DbResult<Team> {
+        sqlx::query_as("select id, name from teams where name = $1")
+            .bind(name)
+            .fetch_one(self.read_pool())
+            .await
+            .map_err(Into::into)
+    }
+
+    #[instrument(skip_all)]
+    async fn delete_team(&self, team: &Team) -> DbResult<()> {
+        let owner = team.store_owner();
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("delete from store where user_id = $1")
+            .bind(owner.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("delete from store_usage where user_id = $1")
+            .bind(owner.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("delete from packfiles where user_id = $1")
+            .bind(owner.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("delete from team_members where team_id = $1")
+            .bind(team.id)
+            .execute(&mut *tx)
+            .await?;
+
+        sqlx::query("delete from teams where id = $1").bind(team.id).execute(&mut *tx).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn list_user_teams(&self, u: &User) -> DbResult<Vec<TeamMember>> {
+        sqlx::query_as(
+            "select team_members.team_id, teams.name as team, team_members.user_id,
+                users.username, team_members.sealed_key, team_members.admin
+            from team_members
+            inner join teams on teams.id = team_members.team_id
+            inner join users on users.id = team_members.user_id
+            where team_members.user_id = $1
+            order by teams.name",
+        )
+        .bind(u.id)
+        .fetch_all(self.read_pool())
+        .await
+        .map_err(Into::into)
+    }
+
+    #[instrument(skip_all)]
+    async fn list_team_members(&self, team: &Team) -> DbResult<Vec<TeamMember>> {
+        sqlx::query_as(
+            "select team_members.team_id, teams.name as team, team_members.user_id,
+                users.username, team_members.sealed_key, team_members.admin
+            from team_members
+            inner join teams on teams.id = team_members.team_id
+            inner join users on users.id = team_members.user_id
+            where team_members.team_id = $1
+            order by users.username",
+        )
+        .bind(team.id)
+        .fetch_all(self.read_pool())
+        .await
+        .map_err(Into::into)
+    }
+
+    #[instrument(skip_all)]
+    async fn get_team_member(&self, team: &Team, u: &User) -> DbResult<TeamMember> {
+        sqlx::query_as(
+            "select team_members.team_id, teams.name as team, team_members.user_id,
+                users.username, team_members.sealed_key, team_members.admin
+            from team_members
+            inner join teams on teams.id = team_members.team_id
+            inner join users on users.id = team_members.user_id
+            where team_members.team_id = $1 and team_members.user_id = $2",
+        )
+        .bind(team.id)
+        .bind(u.id)
+        .fetch_one(self.read_pool())
+        .await
+        .map_err(Into::into)
+    }
+
+    #[instrument(skip_all)]
+    async fn add_team_member(&self, member: &NewTeamMember) -> DbResult<()> {
+        sqlx::query(
+            "insert into team_members
+                (team_id, user_id, sealed_key, admin, created_at)
+            values ($1, $2, $3, $4, $5)
+            on conflict (team_id, user_id) do update
+            set sealed_key = excluded.sealed_key,
+                admin = excluded.admin",
+        )
+        .bind(member.team_id)
+        .bind(member.user_id)
+        .bind(&member.sealed_key)
+        .bind(member.admin)
+        .bind(OffsetDateTime::now_utc())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    #[instrument(skip_all)]
+    async fn delete_team_member(&self, team: &Team, u: &User) -> DbResult<()> {
+        let mut tx = self.pool.begin().await?;
+
+        let res = sqlx::query("delete from team_members where team_id = $1 and user_id = $2")
+            .bind(team.id)
+            .bind(u.id)
+            .execute(&mut *tx)
+            .await?;
+
+        if res.rows_affected() == 0 {
+            return Err(DbError::NotFound);
+        }
+
+        reassign_teams(&mut tx, u, Some(team)).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+/// Add what was just stored (or, when negative, removed) to the user's running totals, in the same
+/// transaction.
+async fn add_usage(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    user_id: i64,
+    added: &Usage,
+) -> DbResult<()> {
+    if added.records == 0 && added.bytes == 0 {
+        return Ok(());
+    }
+
+    sqlx::query(
+        "insert into store_usage (user_id, records, bytes)
+        values ($1, $2, $3)
+        on conflict (user_id) do update
+        set records = store_usage.records + excluded.records,
+            bytes = store_usage.bytes + excluded.bytes",
+    )
+    .bind(user_id)
+    .bind(added.records)
+    .bind(added.bytes)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// The usage charged to the account `user_id`'s store belongs to. A team's store is charged to its
+/// owner, along with the rest of the owner's teams.
+async fn account_usage<'c>(executor: impl sqlx::PgExecutor<'c>, user_id: i64) -> DbResult<Usage> {
+    sqlx::query_as(
+        "with account as (
+            select coalesce((select owner_id from teams where id = -$1), $1) as id
+        )
+        select cast(coalesce(sum(store_usage.records), 0) as bigint) as records,
+            cast(coalesce(sum(store_usage.bytes), 0) as bigint) as bytes
+        from store_usage, account
+        where store_usage.user_id = account.id
+        or store_usage.user_id in (select -teams.id from teams where teams.owner_id = account.id)",
+    )
+    .bind(user_id)
+    .fetch_one(executor)
+    .await
+    .map_err(Into::into)
+}
+
+/// Charge what the transaction added to `user_id`, and commit it, unless that takes their account
+/// over `quota`.
+async fn charge(
+    mut tx: sqlx::Transaction<'_, sqlx::Postgres>,
+    user_id: i64,
+    added: &Usage,
+    quota: &Quota,
+) -> DbResult<UsageCharge> {
+    add_usage(&mut tx, user_id, added).await?;
+
+    // Uploads to the same account wait for each other here, so each is checked against the
+    // usage the others committed.
+    if !quota.is_unlimited() {
+        sqlx::query(
+            "select id from users
+            where id = coalesce((select owner_id from teams where id = -$1), $1)
+            for no key update",
+        )
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    let usage = account_usage(&mut *tx, user_id).await?;
+
+    if !quota.allows_upload(&usage, added) {
+        return Ok(UsageCharge::OverQuota(usage));
+    }
+
+    tx.commit().await?;
+
+    Ok(UsageCharge::Stored(usage))
+}
+
+/// Hand the teams `user` owns (or just `team`) to their longest-standing remaining admin, once
+/// `user` has left them.
+async fn reassign_teams(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    user: &User,
+    team: Option<&Team>,
+) -> DbResult<()> {
+    sqlx::query(
+        "update teams
+        set owner_id = (
+            select user_id from team_members
+            where team_members.team_id = teams.id and admin and user_id != $1
+            order by created_at
+            limit 1
+        )
+        where owner_id = $1 and ($2 is null or id = $2)",
+    )
+    .bind(user.id)
+    .bind(team.map(|t| t.id))
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
 }
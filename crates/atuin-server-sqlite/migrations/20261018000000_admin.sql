@@ -0,0 +1,8 @@
+-- let admins lock an account without deleting it
+alter table users add column disabled_at timestamp with time zone default null;
+
+-- settings changed at runtime with `atuin-server admin`, overriding the config file
+create table server_settings (
+	name text primary key,
+	value text not null
+);
@@ -0,0 +1,9 @@
+-- codes that let people register while open_registration is off
+create table invites (
+	id integer primary key autoincrement,
+	code text not null unique,
+	max_uses integer not null,
+	uses integer not null default 0,
+	expires_at timestamp with time zone default null,
+	created_at timestamp not null default current_timestamp
+);
@@ -0,0 +1,21 @@
+-- teams share a store, encrypted with a team key that's sealed to each member's public key.
+-- The server never sees the team key
+create table teams (
+	id integer primary key autoincrement,
+	name text not null unique,
+	created_at timestamp not null default current_timestamp
+);
+
+create table team_members (
+	team_id bigint not null,
+	user_id bigint not null,
+	sealed_key text not null,       -- the team key, sealed to the member's public key
+	admin boolean not null default false,
+	created_at timestamp not null default current_timestamp,
+	primary key (team_id, user_id)
+);
+
+create index team_members_user on team_members(user_id);
+
+-- lets others seal team keys to the user
+alter table users add column public_key text default null;
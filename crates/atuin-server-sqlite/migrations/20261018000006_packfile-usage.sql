@@ -0,0 +1,12 @@
+-- confirmed packfiles count towards their owner's usage too: the records they hold, and the
+-- blob's size
+alter table packfiles add column records bigint not null default 0;
+
+insert into store_usage (user_id, records, bytes)
+select user_id, sum(records), sum(size)
+from packfiles
+where confirmed_at is not null
+group by user_id
+on conflict (user_id) do update
+set records = store_usage.records + excluded.records,
+	bytes = store_usage.bytes + excluded.bytes;
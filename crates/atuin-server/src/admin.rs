@@ -0,0 +1,277 @@
+//! `atuin-server admin`: manage users, registration and invites without going through the HTTP
+//! API.
+//!
+//! Everything here is built on the [`Database`] trait, so it works with every backend the server
+//! does. Commands talk to the database directly, so they can be run while the server is up.
+
+use std::time::Duration;
+
+use atuin_common::utils::crypto_random_string;
+use atuin_server_database::models::{NewInvite, User};
+use atuin_server_database::{Database, DbError};
+use clap::{Subcommand, ValueEnum};
+use eyre::{Context, Result, bail, eyre};
+use rand::Rng;
+use rand::distributions::Alphanumeric;
+use time::OffsetDateTime;
+use time::format_description::well_known::Rfc3339;
+
+use crate::Settings;
+use crate::handlers::user::hash_secret;
+use crate::packfiles::PackfileStorage;
+
+#[derive(Subcommand, Debug)]
+#[command(infer_subcommands = true)]
+pub enum Cmd {
+    /// List every user on the server
+    Users,
+
+    /// Show how many records a user has stored, and how much space they take up
+    Status {
+        username: String,
+    },
+
+    /// Stop a user from logging in or syncing, without deleting anything
+    Disable {
+        username: String,
+    },
+
+    /// Let a disabled user log in and sync again
+    Enable {
+        username: String,
+    },
+
+    /// Delete a user and everything they've stored
+    Delete {
+        username: String,
+
+        /// Don't ask for confirmation
+        #[arg(long)]
+        yes: bool,
+    },
+
+    /// Log a user out of every device
+    RevokeSessions {
+        username: String,
+    },
+
+    /// Set a new password for a user, logging them out of every device
+    ResetPassword {
+        username: String,
+
+        /// The new password. A random one is generated and printed if this isn't given
+        #[arg(long)]
+        password: Option<String>,
+    },
+
+    /// Open or close registration, overriding `open_registration` in the config
+    Registration {
+        #[arg(value_enum)]
+        state: Registration,
+    },
+
+    /// Manage invite codes, which let people register while registration is closed
+    #[command(subcommand)]
+    Invite(InviteCmd),
+}
+
+#[derive(Subcommand, Debug)]
+pub enum InviteCmd {
+    /// Create an invite code
+    Create {
+        /// How many accounts can be registered with the code
+        #[arg(long, default_value_t = 1)]
+        uses: u32,
+
+        /// How long the code can be used for, e.g. `7d` or `12h`. Codes don't expire by default
+        #[arg(long, value_parser = humantime::parse_duration)]
+        expires_in: Option<Duration>,
+    },
+
+    /// List invite codes, and how many times each has been used
+    List,
+
+    /// Delete an invite code, so it can't be used any more
+    Delete {
+        code: String,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Registration {
+    Open,
+    Closed,
+    /// Go back to whatever the config says
+    Default,
+}
+
+impl Cmd {
+    pub async fn run<Db: Database>(self, settings: &Settings) -> Result<()> {
+        let db = Db::new(&settings.db_settings).await.wrap_err("could not connect to database")?;
+
+        self.run_with(&db, settings).await
+    }
+
+    pub async fn run_with(self, db: &impl Database, settings: &Settings) -> Result<()> {
+        match self {
+            Self::Users => {
+                let users = db.list_users().await?;
+                for user in users {
+                    let status = if user.disabled {
+                        "disabled"
+                    } else {
+                        "active"
+                    };
+                    println!("{}\t{}\t{}\t{status}", user.id, user.username, user.email);
+                }
+            }
+
+            Self::Status { username } => {
+                let user = get_user(db, &username).await?;
+                let usage = db.usage(&user).await?;
+                let status = db.status(&user).await?;
+
+                println!("user: {} ({})", user.username, user.email);
+                println!("disabled: {}", user.disabled);
+                println!("records: {}", usage.records);
+                println!("storage: {} bytes", usage.bytes);
+
+                let mut stores: Vec<_> = status
+                    .hosts
+                    .iter()
+                    .flat_map(|(host, tags)| tags.iter().map(move |(tag, idx)| (host, tag, idx)))
+                    .collect();
+                stores.sort();
+
+                for (host, tag, idx) in stores {
+                    // indices start at 0, so there's one more record than the tail's index
+                    println!("  {host} {tag}: {}", idx + 1);
+                }
+            }
+
+            Self::Disable { username } => {
+                let user = get_user(db, &username).await?;
+                db.set_user_disabled(&user, true).await?;
+                println!("disabled {username}");
+            }
+
+            Self::Enable { username } => {
+                let user = get_user(db, &username).await?;
+                db.set_user_disabled(&user, false).await?;
+                println!("enabled {username}");
+            }
+
+            Self::Delete { username, yes } => {
+                let user = get_user(db, &username).await?;
+
+                if !yes {
+                    bail!("this deletes {username} and all their data; pass --yes to confirm");
+                }
+
+                db.delete_user(&user).await?;
+                if let Some(packfiles) = PackfileStorage::new(&settings.packfiles)? {
+                    packfiles.delete_user(user.id).await?;
+                }
+                println!("deleted {username}");
+            }
+
+            Self::RevokeSessions { username } => {
+                let user = get_user(db, &username).await?;
+                db.delete_user_sessions(&user).await?;
+                println!("revoked every session for {username}");
+            }
+
+            Self::ResetPassword { username, password } => {
+                let mut user = get_user(db, &username).await?;
+                let generated = password.is_none();
+                let password = password.unwrap_or_else(crypto_random_string::<24>);
+
+                user.password = hash_secret(&password);
+                db.update_user_password(&user).await?;
+                db.delete_user_sessions(&user).await?;
+
+                if generated {
+                    println!("new password for {username}: {password}");
+                } else {
+                    println!("reset password for {username}");
+                }
+            }
+
+            Self::Registration { state } => {
+                let open = match state {
+                    Registration::Open => Some(true),
+                    Registration::Closed => Some(false),
+                    Registration::Default => None,
+                };
+                db.set_registration_override(open).await?;
+
+                let open = open.unwrap_or(settings.open_registration);
+                let state = if open {
+                    "open"
+                } else {
+                    "closed"
+                };
+                println!("registration is {state}");
+            }
+
+            Self::Invite(cmd) => cmd.run(db).await?,
+        }
+
+        Ok(())
+    }
+}
+
+impl InviteCmd {
+    async fn run(self, db: &impl Database) -> Result<()> {
+        match self {
+            Self::Create { uses, expires_in } => {
+                if uses == 0 {
+                    bail!("an invite needs at least one use");
+                }
+
+                let invite = NewInvite {
+                    code: invite_code(),
+                    max_uses: uses.into(),
+                    expires_at: expires_in.map(|d| OffsetDateTime::now_utc() + d),
+                };
+                db.add_invite(&invite).await?;
+
+                println!("{}", invite.code);
+            }
+
+            Self::List => {
+                for invite in db.list_invites().await? {
+                    let expires = match invite.expires_at {
+                        Some(t) => t.format(&Rfc3339)?,
+                        None => "never".to_owned(),
+                    };
+                    println!(
+                        "{}\t{}/{} used\texpires {expires}",
+                        invite.code, invite.uses, invite.max_uses
+                    );
+                }
+            }
+
+            Self::Delete { code } => match db.delete_invite(&code).await {
+                Ok(()) => println!("deleted invite {code}"),
+                Err(DbError::NotFound) => bail!("no invite with code {code}"),
+                Err(e) => return Err(e.into()),
+            },
+        }
+
+        Ok(())
+    }
+}
+
+/// Letters and digits only, so codes are easy to pass around and never look like a flag.
+fn invite_code() -> String {
+    rand::thread_rng().sample_iter(&Alphanumeric).take(16).map(char::from).collect()
+}
+
+async fn get_user(db: &impl Database, username: &str) -> Result<User> {
+    match db.get_user(username).await {
+        Ok(user) => Ok(user),
+        Err(DbError::NotFound) => Err(eyre!("no user called {username}")),
+        Err(DbError::Other(e)) => Err(e.wrap_err("could not look up user")),
+    }
+}
@@ -2,7 +2,7 @@
 
 use std::net::SocketAddr;
 
-use atuin_server::{Settings, example_config, launch, launch_metrics_server};
+use atuin_server::{Settings, admin, example_config, launch, launch_metrics_server};
 use atuin_server_database::DbType;
 use atuin_server_postgres::Postgres;
 use atuin_server_sqlite::Sqlite;
@@ -27,6 +27,12 @@ enum Cmd {
 
     /// Print server example configuration
     DefaultConfig,
+
+    /// Manage users and registration
+    Admin {
+        #[command(subcommand)]
+        cmd: admin::Cmd,
+    },
 }
 
 #[tokio::main]
@@ -57,6 +63,15 @@ async fn main() -> Result<()> {
                 DbType::Unknown => Err(eyre!("db_uri must start with postgres:// or sqlite://")),
             }
         }
+        Cmd::Admin { cmd } => {
+            let settings = Settings::new().wrap_err("could not load server settings")?;
+
+            match settings.db_settings.db_type() {
+                DbType::Postgres => cmd.run::<Postgres>(&settings).await,
+                DbType::Sqlite => cmd.run::<Sqlite>(&settings).await,
+                DbType::Unknown => Err(eyre!("db_uri must start with postgres:// or sqlite://")),
+            }
+        }
         Cmd::DefaultConfig => {
             println!("{}", example_config());
             Ok(())
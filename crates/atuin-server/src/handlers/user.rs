@@ -11,10 +11,10 @@ use atuin_server_database::models::{NewSession, NewUser};
 use atuin_server_database::{Database, DbError};
 use axum::Json;
 use axum::extract::{ConnectInfo, Path, State};
-use axum::http::StatusCode;
+use axum::http::{HeaderMap, StatusCode};
 use metrics::counter;
 use rand::rngs::OsRng;
-use reqwest::header::CONTENT_TYPE;
+use reqwest::header::{CONTENT_TYPE, USER_AGENT};
 use tracing::{debug, error, info, instrument, warn};
 
 use super::{ErrorResponse, ErrorResponseStatus, RespExt};
@@ -79,9 +79,26 @@ pub async fn get<DB: Database>(
 #[instrument(skip_all, err(level = "warn"), fields(user.username = register.username.as_str()))]
 pub async fn register<DB: Database>(
     state: State<AppState<DB>>,
+    headers: HeaderMap,
     Json(register): Json<RegisterRequest>,
 ) -> Result<Json<RegisterResponse>, ErrorResponseStatus<'static>> {
-    if !state.settings.open_registration {
+    let open_registration = match state.database.registration_override().await {
+        Ok(open) => open.unwrap_or(state.settings.open_registration),
+        Err(e) => {
+            error!("failed to check registration override: {}", e);
+            return Err(ErrorResponse::reply("database error")
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+        }
+    };
+
+    // an invite only matters when registration is closed
+    let invite = if open_registration {
+        None
+    } else {
+        register.invite.as_deref()
+    };
+
+    if !open_registration && invite.is_none() {
         return Err(ErrorResponse::reply("this server is not open for registrations")
             .with_status(StatusCode::BAD_REQUEST));
     }
@@ -107,8 +124,18 @@ pub async fn register<DB: Database>(
     };
 
     let db = &state.0.database;
-    let user_id = match db.add_user(&new_user).await {
+    let added = match invite {
+        Some(invite) => db.add_user_with_invite(&new_user, invite).await,
+        None => db.add_user(&new_user).await,
+    };
+
+    let user_id = match added {
         Ok(id) => id,
+        Err(DbError::NotFound) => {
+            debug!("registration with an invalid, used up or expired invite");
+            return Err(ErrorResponse::reply("invite code is invalid, used up or expired")
+                .with_status(StatusCode::BAD_REQUEST));
+        }
         Err(e) => {
             error!("failed to add user: {}", e);
             return Err(
@@ -117,7 +144,11 @@ pub async fn register<DB: Database>(
         }
     };
 
-    info!(user.id = user_id, "registered new user");
+    info!(user.id = user_id, invited = invite.is_some(), "registered new user");
+
+    if invite.is_some() {
+        counter!("atuin_invites_used").increment(1);
+    }
 
     // 24 bytes encoded as base64
     let token = crypto_random_string::<24>();
@@ -125,6 +156,7 @@ pub async fn register<DB: Database>(
     let new_session = NewSession {
         user_id,
         token: (&token).into(),
+        user_agent: user_agent(&headers),
     };
 
     if let Some(url) = &state.settings.register_webhook_url {
@@ -167,6 +199,13 @@ pub async fn delete<DB: Database>(
             .with_status(StatusCode::INTERNAL_SERVER_ERROR));
     };
 
+    if let Some(packfiles) = &state.0.packfiles
+        && let Err(e) = packfiles.delete_user(user.id).await
+    {
+        // the database no longer knows about them, so they can't be downloaded anyway
+        error!("failed to delete packfiles: {}", e);
+    }
+
     counter!("atuin_users_deleted").increment(1);
 
     info!(user.id = user.id, "deleted user account");
@@ -208,6 +247,7 @@ pub async fn change_password<DB: Database>(
 pub async fn login<DB: Database>(
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     state: State<AppState<DB>>,
+    headers: HeaderMap,
     login: Json<LoginRequest>,
 ) -> Result<Json<LoginResponse>, ErrorResponseStatus<'static>> {
     let db = &state.0.database;
@@ -224,19 +264,6 @@ pub async fn login<DB: Database>(
         }
     };
 
-    let session = match db.get_user_session(&user).await {
-        Ok(u) => u,
-        Err(DbError::NotFound) => {
-            debug!("user session not found for user id={}", user.id);
-            return Err(ErrorResponse::reply("user not found").with_status(StatusCode::NOT_FOUND));
-        }
-        Err(DbError::Other(err)) => {
-            error!("database error for user {}: {}", login.username, err);
-            return Err(ErrorResponse::reply("database error")
-                .with_status(StatusCode::INTERNAL_SERVER_ERROR));
-        }
-    };
-
     let verified = verify_str(user.password.as_str(), login.password.borrow());
 
     if !verified {
@@ -246,15 +273,39 @@ pub async fn login<DB: Database>(
         );
     }
 
+    if user.disabled {
+        warn!(user.id = user.id, "login failed: account is disabled");
+        return Err(ErrorResponse::reply("account is disabled").with_status(StatusCode::FORBIDDEN));
+    }
+
+    // every login gets its own session, so they can be listed and revoked separately
+    let token = crypto_random_string::<24>();
+    let new_session = NewSession {
+        user_id: user.id,
+        token: (&token).into(),
+        user_agent: user_agent(&headers),
+    };
+
+    if let Err(e) = db.add_session(&new_session).await {
+        error!("failed to add session: {}", e);
+        return Err(
+            ErrorResponse::reply("database error").with_status(StatusCode::INTERNAL_SERVER_ERROR)
+        );
+    }
+
     info!(user.id = user.id, "login succeeded");
 
     Ok(Json(LoginResponse {
-        session: session.token,
+        session: token,
         auth: Some("cli".into()),
     }))
 }
 
-fn hash_secret(password: &str) -> String {
+fn user_agent(headers: &HeaderMap) -> Option<String> {
+    headers.get(USER_AGENT)?.to_str().ok().map(ToOwned::to_owned)
+}
+
+pub fn hash_secret(password: &str) -> String {
     let arg2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, Params::default());
     let salt = SaltString::generate(&mut OsRng);
     let hash = arg2.hash_password(password.as_bytes(), &salt).unwrap();
@@ -1,15 +1,41 @@
 use atuin_domain::api::*;
+use atuin_server_database::Database;
 use axum::Json;
-use tracing::instrument;
+use axum::extract::State;
+use axum::http::StatusCode;
+use tracing::{error, instrument};
 
-use crate::handlers::ErrorResponseStatus;
-use crate::router::UserAuth;
+use crate::handlers::{ErrorResponse, ErrorResponseStatus, RespExt};
+use crate::router::{AppState, UserAuth};
 
 #[instrument(skip_all, err(level = "warn"), fields(user.id = user.id))]
-pub async fn get(
+pub async fn get<DB: Database>(
     UserAuth(user): UserAuth,
+    state: State<AppState<DB>>,
 ) -> Result<Json<MeResponse>, ErrorResponseStatus<'static>> {
+    let State(AppState {
+        database, settings, ..
+    }) = state;
+
+    let usage = match database.usage(&user).await {
+        Ok(usage) => usage,
+        Err(e) => {
+            error!("failed to get usage: {}", e);
+
+            return Err(ErrorResponse::reply("failed to get usage")
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+        }
+    };
+
+    let limit = |max| (max != 0).then_some(max);
+
     Ok(Json(MeResponse {
         username: user.username,
+        usage: Some(UsageResponse {
+            records: usage.records.try_into().unwrap_or_default(),
+            bytes: usage.bytes.try_into().unwrap_or_default(),
+            max_records: limit(settings.quota.records),
+            max_bytes: limit(settings.quota.bytes),
+        }),
     }))
 }
@@ -1,3 +1,6 @@
 pub mod me;
+pub mod packfile;
 pub mod record;
+pub mod session;
 pub mod store;
+pub mod team;
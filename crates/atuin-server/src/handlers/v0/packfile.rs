@@ -0,0 +1,326 @@
+use atuin_common::utils::crypto_random_string;
+use atuin_domain::api::{PackfileDownloadResponse, PackfileRequest, PackfileResponse};
+use atuin_domain::record::RecordId;
+use atuin_server_database::models::{NewPackfile, Packfile, UsageCharge, User};
+use atuin_server_database::{Database, DbError};
+use axum::Json;
+use axum::body::Body;
+use axum::extract::{Path, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use base64::prelude::{BASE64_STANDARD, Engine};
+use metrics::counter;
+use serde::Deserialize;
+use tracing::{error, instrument};
+use url::Url;
+
+use super::record::{charged, check_quota};
+use crate::handlers::{ErrorResponse, ErrorResponseStatus, RespExt};
+use crate::packfiles::PackfileStorage;
+use crate::router::{AppState, StoreAuth};
+use crate::settings::Settings;
+
+/// The path of a packfile, which may be under a team's store too.
+#[derive(Deserialize)]
+pub struct PackfilePath {
+    manifest_id: RecordId,
+}
+
+#[instrument(skip_all, err(level = "warn"), fields(user.id = user.id, manifest.id = ?req.manifest_id))]
+pub async fn create<DB: Database>(
+    StoreAuth(user): StoreAuth,
+    headers: HeaderMap,
+    state: State<AppState<DB>>,
+    Json(req): Json<PackfileRequest>,
+) -> Result<Json<PackfileResponse>, ErrorResponseStatus<'static>> {
+    let State(AppState {
+        database,
+        settings,
+        packfiles,
+    }) = state;
+    let storage = enabled(packfiles.as_ref())?;
+
+    if req.packfile_size_bytes == 0 || req.packfile_size_bytes > settings.packfiles.max_size {
+        counter!("atuin_packfile_too_large").increment(1);
+
+        return Err(ErrorResponse::reply("packfile is empty or too large")
+            .with_status(StatusCode::PAYLOAD_TOO_LARGE));
+    }
+
+    let valid_hash = |hash: &String| BASE64_STANDARD.decode(hash).is_ok_and(|h| h.len() == 32);
+    if !req.packfile_sha256.as_ref().is_none_or(valid_hash) {
+        return Err(ErrorResponse::reply("packfile hash is not a base64 sha-256")
+            .with_status(StatusCode::BAD_REQUEST));
+    }
+
+    // the records count as they're stored here, packed at the blob's size, once the upload is
+    // confirmed. Check now too, so the client doesn't upload something that'll be refused.
+    check_quota(&database, &settings, &user, req.records.len() as u64, req.packfile_size_bytes)
+        .await?;
+
+    let new = NewPackfile {
+        user_id: user.id,
+        manifest_id: req.manifest_id.0,
+        size: i64::try_from(req.packfile_size_bytes).unwrap_or(i64::MAX),
+        sha256: req.packfile_sha256,
+        records: i64::try_from(req.records.len()).unwrap_or(i64::MAX),
+        token: crypto_random_string::<24>(),
+    };
+
+    let packfile = match database.add_packfile(&new).await {
+        Ok(()) => database.get_packfile(&user, req.manifest_id.0).await,
+        Err(e) => Err(e),
+    };
+    let packfile = packfile.map_err(|e| {
+        error!("failed to add packfile: {}", e);
+
+        ErrorResponse::reply("failed to add packfile")
+            .with_status(StatusCode::INTERNAL_SERVER_ERROR)
+    })?;
+
+    let upload_url =
+        storage.upload_url(&base_url(&settings, &headers)?, &packfile).map_err(|e| {
+            error!("failed to make packfile upload url: {}", e);
+
+            ErrorResponse::reply("failed to add packfile")
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+
+    Ok(Json(PackfileResponse { upload_url }))
+}
+
+#[instrument(skip_all, err(level = "warn"), fields(user.id = user.id, manifest.id = ?manifest_id))]
+pub async fn confirm<DB: Database>(
+    Path(PackfilePath { manifest_id }): Path<PackfilePath>,
+    StoreAuth(user): StoreAuth,
+    state: State<AppState<DB>>,
+) -> Result<(), ErrorResponseStatus<'static>> {
+    let State(AppState {
+        database,
+        settings,
+        packfiles,
+    }) = state;
+    let storage = enabled(packfiles.as_ref())?;
+
+    let packfile = get_packfile(&database, &user, manifest_id).await?;
+
+    let failed = |e: eyre::Report| {
+        error!("failed to check packfile: {}", e);
+
+        ErrorResponse::reply("failed to confirm packfile")
+            .with_status(StatusCode::INTERNAL_SERVER_ERROR)
+    };
+
+    let Some(blob) = storage.stat(&packfile).await.map_err(failed)? else {
+        return Err(ErrorResponse::reply("packfile has not been uploaded")
+            .with_status(StatusCode::BAD_REQUEST));
+    };
+
+    // Presigned uploads can't stop every mismatched body, so don't keep one around.
+    if !blob.matches(&packfile) {
+        counter!("atuin_packfile_mismatched").increment(1);
+        storage.delete(&packfile).await.map_err(failed)?;
+
+        return Err(mismatched());
+    }
+
+    let charge = database.confirm_packfile(&user, manifest_id.0, &settings.quota).await;
+    let charge = charge.map_err(|e| {
+        error!("failed to confirm packfile: {}", e);
+
+        ErrorResponse::reply("failed to confirm packfile")
+            .with_status(StatusCode::INTERNAL_SERVER_ERROR)
+    })?;
+
+    if let UsageCharge::OverQuota(_) = charge {
+        storage.delete(&packfile).await.map_err(failed)?;
+    }
+    charged(&settings, &user, charge)?;
+
+    counter!("atuin_packfile_uploaded").increment(1);
+    counter!("atuin_packfile_bytes_uploaded").increment(packfile.size.unsigned_abs());
+
+    Ok(())
+}
+
+#[instrument(skip_all, err(level = "warn"), fields(user.id = user.id, manifest.id = ?manifest_id))]
+pub async fn download<DB: Database>(
+    Path(PackfilePath { manifest_id }): Path<PackfilePath>,
+    StoreAuth(user): StoreAuth,
+    headers: HeaderMap,
+    state: State<AppState<DB>>,
+) -> Result<Json<PackfileDownloadResponse>, ErrorResponseStatus<'static>> {
+    let State(AppState {
+        database,
+        settings,
+        packfiles,
+    }) = state;
+    let storage = enabled(packfiles.as_ref())?;
+
+    let packfile = get_packfile(&database, &user, manifest_id).await?;
+
+    if !packfile.confirmed {
+        return Err(ErrorResponse::reply("packfile not found").with_status(StatusCode::NOT_FOUND));
+    }
+
+    let download_url =
+        storage.download_url(&base_url(&settings, &headers)?, &packfile).map_err(|e| {
+            error!("failed to make packfile download url: {}", e);
+
+            ErrorResponse::reply("failed to get packfile")
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+
+    Ok(Json(PackfileDownloadResponse { download_url }))
+}
+
+/// Upload a blob to local packfile storage. Authorised by the token in the path rather than a
+/// session, like a presigned URL would be.
+#[instrument(skip_all, err(level = "warn"))]
+pub async fn put_blob<DB: Database>(
+    Path(token): Path<String>,
+    state: State<AppState<DB>>,
+    body: Body,
+) -> Result<(), ErrorResponseStatus<'static>> {
+    let State(AppState {
+        database,
+        packfiles,
+        ..
+    }) = state;
+    let Some(PackfileStorage::Filesystem(fs)) = packfiles else {
+        return Err(not_found());
+    };
+
+    let packfile = get_blob_packfile(&database, &token).await?;
+
+    // A confirmed packfile is only replaced by asking for a new upload URL.
+    if packfile.confirmed {
+        return Err(not_found());
+    }
+
+    let blob = fs.write(&packfile, body).await.map_err(|e| {
+        error!("failed to write packfile: {}", e);
+
+        ErrorResponse::reply("failed to store packfile")
+            .with_status(StatusCode::INTERNAL_SERVER_ERROR)
+    })?;
+
+    if !blob.matches(&packfile) {
+        counter!("atuin_packfile_mismatched").increment(1);
+
+        return Err(mismatched());
+    }
+
+    Ok(())
+}
+
+/// Download a blob from local packfile storage. Authorised by the token in the path.
+#[instrument(skip_all, err(level = "warn"))]
+pub async fn get_blob<DB: Database>(
+    Path(token): Path<String>,
+    state: State<AppState<DB>>,
+) -> Result<Response, ErrorResponseStatus<'static>> {
+    let State(AppState {
+        database,
+        packfiles,
+        ..
+    }) = state;
+    let Some(PackfileStorage::Filesystem(fs)) = packfiles else {
+        return Err(not_found());
+    };
+
+    let packfile = get_blob_packfile(&database, &token).await?;
+
+    if !packfile.confirmed {
+        return Err(not_found());
+    }
+
+    let body = fs.read(&packfile).await.map_err(|e| {
+        error!("failed to read packfile: {}", e);
+
+        ErrorResponse::reply("failed to read packfile")
+            .with_status(StatusCode::INTERNAL_SERVER_ERROR)
+    })?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/octet-stream".to_owned()),
+            (header::CONTENT_LENGTH, packfile.size.to_string()),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+fn enabled(
+    packfiles: Option<&PackfileStorage>,
+) -> Result<&PackfileStorage, ErrorResponseStatus<'static>> {
+    packfiles.ok_or_else(|| {
+        ErrorResponse::reply("packfiles are not enabled on this server")
+            .with_status(StatusCode::NOT_FOUND)
+    })
+}
+
+fn mismatched() -> ErrorResponseStatus<'static> {
+    ErrorResponse::reply("packfile is not the size or hash it was declared with")
+        .with_status(StatusCode::BAD_REQUEST)
+}
+
+fn not_found() -> ErrorResponseStatus<'static> {
+    ErrorResponse::reply("packfile not found").with_status(StatusCode::NOT_FOUND)
+}
+
+async fn get_packfile<DB: Database>(
+    database: &DB,
+    user: &User,
+    manifest_id: RecordId,
+) -> Result<Packfile, ErrorResponseStatus<'static>> {
+    match database.get_packfile(user, manifest_id.0).await {
+        Ok(packfile) => Ok(packfile),
+        Err(DbError::NotFound) => Err(not_found()),
+        Err(DbError::Other(e)) => {
+            error!("failed to get packfile: {}", e);
+
+            Err(ErrorResponse::reply("failed to get packfile")
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR))
+        }
+    }
+}
+
+async fn get_blob_packfile<DB: Database>(
+    database: &DB,
+    token: &str,
+) -> Result<Packfile, ErrorResponseStatus<'static>> {
+    match database.get_packfile_by_token(token).await {
+        Ok(packfile) => Ok(packfile),
+        Err(DbError::NotFound) => Err(not_found()),
+        Err(DbError::Other(e)) => {
+            error!("failed to get packfile: {}", e);
+
+            Err(ErrorResponse::reply("failed to get packfile")
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR))
+        }
+    }
+}
+
+/// The URL the client reached this server on, for building links back to it. Behind a proxy,
+/// that's only right if the proxy passes `Host` through and sets `X-Forwarded-Proto`; otherwise
+/// `packfiles.public_url` has to be set.
+fn base_url(settings: &Settings, headers: &HeaderMap) -> Result<Url, ErrorResponseStatus<'static>> {
+    if let Some(url) = &settings.packfiles.public_url {
+        return Ok(url.clone());
+    }
+
+    let host = headers.get(header::HOST).and_then(|h| h.to_str().ok());
+    let scheme = headers
+        .get("x-forwarded-proto")
+        .and_then(|h| h.to_str().ok())
+        .filter(|s| matches!(*s, "http" | "https"))
+        .unwrap_or("http");
+
+    host.and_then(|host| Url::parse(&format!("{scheme}://{host}{}", settings.path)).ok())
+        .ok_or_else(|| {
+            ErrorResponse::reply("could not work out the server's url; set packfiles.public_url")
+                .with_status(StatusCode::BAD_REQUEST)
+        })
+}
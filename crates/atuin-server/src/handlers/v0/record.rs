@@ -2,6 +2,7 @@ use atuin_domain::record::{
     EncryptedData, HostId, Record, RecordIdx, RecordSeriesKey, RecordStatus, RecordTag,
 };
 use atuin_server_database::Database;
+use atuin_server_database::models::{UsageCharge, User};
 use axum::Json;
 use axum::extract::{Query, State};
 use axum::http::StatusCode;
@@ -10,11 +11,13 @@ use serde::Deserialize;
 use tracing::{error, instrument};
 
 use crate::handlers::{ErrorResponse, ErrorResponseStatus, RespExt};
-use crate::router::{AppState, UserAuth};
+use crate::metrics::record_quota_usage;
+use crate::router::{AppState, StoreAuth};
+use crate::settings::Settings;
 
 #[instrument(skip_all, err(level = "warn"), fields(user.id = user.id, record.count = records.len()))]
 pub async fn post<DB: Database>(
-    UserAuth(user): UserAuth,
+    StoreAuth(user): StoreAuth,
     state: State<AppState<DB>>,
     Json(records): Json<Vec<Record<EncryptedData>>>,
 ) -> Result<(), ErrorResponseStatus<'static>> {
@@ -37,19 +40,85 @@ pub async fn post<DB: Database>(
             .with_status(StatusCode::BAD_REQUEST));
     }
 
-    if let Err(e) = database.add_records(&user, &records).await {
+    let charge = database.add_records(&user, &records, &settings.quota).await.map_err(|e| {
         error!("failed to add record: {}", e);
 
-        return Err(ErrorResponse::reply("failed to add record")
-            .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+        ErrorResponse::reply("failed to add record").with_status(StatusCode::INTERNAL_SERVER_ERROR)
+    })?;
+
+    charged(&settings, &user, charge)
+}
+
+/// Refuse an upload the database didn't store because it would have taken `user` over their
+/// storage quota.
+pub(super) fn charged(
+    settings: &Settings,
+    user: &User,
+    charge: UsageCharge,
+) -> Result<(), ErrorResponseStatus<'static>> {
+    match charge {
+        UsageCharge::Stored(usage) => {
+            if !settings.quota.is_unlimited() {
+                record_quota_usage(
+                    &settings.quota,
+                    user.id,
+                    u64::try_from(usage.records).unwrap_or_default(),
+                    u64::try_from(usage.bytes).unwrap_or_default(),
+                );
+            }
+
+            Ok(())
+        }
+        UsageCharge::OverQuota(_) => {
+            counter!("atuin_quota_exceeded").increment(1);
+
+            Err(ErrorResponse::reply("could not add records; storage quota exceeded")
+                .with_status(StatusCode::INSUFFICIENT_STORAGE))
+        }
+    }
+}
+
+/// Refuse an upload of `records` records and `bytes` bytes that would take `user` over their
+/// storage quota, before it's started. Uploads are checked again as they're stored.
+pub(super) async fn check_quota<DB: Database>(
+    database: &DB,
+    settings: &Settings,
+    user: &User,
+    records: u64,
+    bytes: u64,
+) -> Result<(), ErrorResponseStatus<'static>> {
+    if settings.quota.is_unlimited() {
+        return Ok(());
+    }
+
+    let usage = match database.usage(user).await {
+        Ok(usage) => usage,
+        Err(e) => {
+            error!("failed to get usage: {}", e);
+
+            return Err(ErrorResponse::reply("failed to check storage quota")
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+        }
     };
 
+    let total_records = u64::try_from(usage.records).unwrap_or_default() + records;
+    let total_bytes = u64::try_from(usage.bytes).unwrap_or_default() + bytes;
+
+    if !settings.quota.allows(total_records, total_bytes) {
+        counter!("atuin_quota_exceeded").increment(1);
+
+        return Err(ErrorResponse::reply("could not add records; storage quota exceeded")
+            .with_status(StatusCode::INSUFFICIENT_STORAGE));
+    }
+
+    record_quota_usage(&settings.quota, user.id, total_records, total_bytes);
+
     Ok(())
 }
 
 #[instrument(skip_all, err(level = "warn"), fields(user.id = user.id))]
 pub async fn index<DB: Database>(
-    UserAuth(user): UserAuth,
+    StoreAuth(user): StoreAuth,
     state: State<AppState<DB>>,
 ) -> Result<Json<RecordStatus>, ErrorResponseStatus<'static>> {
     let State(AppState { database, .. }) = state;
@@ -80,7 +149,7 @@ pub struct NextParams {
 #[instrument(skip_all, err(level = "warn"), fields(user.id = user.id, host.id = %params.host, tag = params.tag.as_str(), count = params.count))]
 pub async fn next<DB: Database>(
     params: Query<NextParams>,
-    UserAuth(user): UserAuth,
+    StoreAuth(user): StoreAuth,
     state: State<AppState<DB>>,
 ) -> Result<Json<Vec<Record<EncryptedData>>>, ErrorResponseStatus<'static>> {
     let State(AppState { database, .. }) = state;
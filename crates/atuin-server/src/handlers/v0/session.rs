@@ -0,0 +1,88 @@
+use atuin_domain::api::{SessionResponse, SessionsResponse};
+use atuin_server_database::{Database, DbError};
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use tracing::{error, info, instrument};
+
+use crate::handlers::{ErrorResponse, ErrorResponseStatus, RespExt};
+use crate::router::{AppState, SessionAuth};
+
+#[instrument(skip_all, err(level = "warn"), fields(user.id = auth.user.id))]
+pub async fn list<DB: Database>(
+    auth: SessionAuth,
+    state: State<AppState<DB>>,
+) -> Result<Json<SessionsResponse>, ErrorResponseStatus<'static>> {
+    let State(AppState { database, .. }) = state;
+
+    let sessions = match database.list_sessions(&auth.user).await {
+        Ok(sessions) => sessions,
+        Err(e) => {
+            error!("failed to list sessions: {}", e);
+
+            return Err(ErrorResponse::reply("failed to list sessions")
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+        }
+    };
+
+    let sessions = sessions
+        .into_iter()
+        .map(|s| SessionResponse {
+            id: s.id,
+            created_at: s.created_at,
+            last_seen_at: s.last_seen_at,
+            user_agent: s.user_agent,
+            current: s.id == auth.session.id,
+        })
+        .collect();
+
+    Ok(Json(SessionsResponse { sessions }))
+}
+
+#[instrument(skip_all, err(level = "warn"), fields(user.id = auth.user.id, session.id = id))]
+pub async fn delete<DB: Database>(
+    Path(id): Path<i64>,
+    auth: SessionAuth,
+    state: State<AppState<DB>>,
+) -> Result<(), ErrorResponseStatus<'static>> {
+    let State(AppState { database, .. }) = state;
+
+    match database.delete_session(&auth.user, id).await {
+        Ok(()) => {}
+        Err(DbError::NotFound) => {
+            return Err(
+                ErrorResponse::reply("session not found").with_status(StatusCode::NOT_FOUND)
+            );
+        }
+        Err(DbError::Other(e)) => {
+            error!("failed to delete session: {}", e);
+
+            return Err(ErrorResponse::reply("failed to delete session")
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+        }
+    }
+
+    info!(user.id = auth.user.id, session.id = id, "revoked session");
+
+    Ok(())
+}
+
+/// Revoke every session but the one making the request.
+#[instrument(skip_all, err(level = "warn"), fields(user.id = auth.user.id))]
+pub async fn delete_others<DB: Database>(
+    auth: SessionAuth,
+    state: State<AppState<DB>>,
+) -> Result<(), ErrorResponseStatus<'static>> {
+    let State(AppState { database, .. }) = state;
+
+    if let Err(e) = database.delete_other_sessions(&auth.user, auth.session.id).await {
+        error!("failed to delete sessions: {}", e);
+
+        return Err(ErrorResponse::reply("failed to delete sessions")
+            .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    info!(user.id = auth.user.id, "revoked other sessions");
+
+    Ok(())
+}
@@ -17,7 +17,11 @@ pub async fn delete<DB: Database>(
     UserAuth(user): UserAuth,
     state: State<AppState<DB>>,
 ) -> Result<(), ErrorResponseStatus<'static>> {
-    let State(AppState { database, .. }) = state;
+    let State(AppState {
+        database,
+        packfiles,
+        ..
+    }) = state;
 
     if let Err(e) = database.delete_store(&user).await {
         counter!("atuin_store_delete_failed").increment(1);
@@ -27,6 +31,13 @@ pub async fn delete<DB: Database>(
             .with_status(StatusCode::INTERNAL_SERVER_ERROR));
     }
 
+    if let Some(packfiles) = packfiles
+        && let Err(e) = packfiles.delete_user(user.id).await
+    {
+        // the database no longer knows about them, so they can't be downloaded anyway
+        error!("failed to delete packfiles {e:?}");
+    }
+
     counter!("atuin_store_deleted").increment(1);
 
     Ok(())
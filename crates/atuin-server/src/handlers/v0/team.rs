@@ -0,0 +1,359 @@
+use atuin_common::encryption::paseto_v4::validate_public_key;
+use atuin_domain::api::{
+    AddTeamMemberRequest, CreateTeamRequest, PublicKeyRequest, TeamMemberResponse,
+    TeamMembersResponse,
+};
+use atuin_server_database::models::{NewTeamMember, Team, TeamMember, User};
+use atuin_server_database::{Database, DbError};
+use axum::Json;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use tracing::{error, info, instrument};
+
+use crate::handlers::{ErrorResponse, ErrorResponseStatus, RespExt};
+use crate::router::{AppState, UserAuth};
+
+const MAX_TEAM_NAME_LEN: usize = 32;
+
+#[instrument(skip_all, err(level = "warn"), fields(user.id = user.id))]
+pub async fn put_key<DB: Database>(
+    UserAuth(user): UserAuth,
+    state: State<AppState<DB>>,
+    Json(req): Json<PublicKeyRequest>,
+) -> Result<(), ErrorResponseStatus<'static>> {
+    let State(AppState { database, .. }) = state;
+
+    if validate_public_key(&req.public_key).is_err() {
+        return Err(
+            ErrorResponse::reply("not a valid k4.public key").with_status(StatusCode::BAD_REQUEST)
+        );
+    }
+
+    if let Err(e) = database.set_public_key(&user, &req.public_key).await {
+        error!("failed to set public key: {}", e);
+
+        return Err(ErrorResponse::reply("failed to set public key")
+            .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    Ok(())
+}
+
+#[instrument(skip_all, err(level = "warn"), fields(user.id = user.id))]
+pub async fn get_key<DB: Database>(
+    Path(username): Path<String>,
+    UserAuth(user): UserAuth,
+    state: State<AppState<DB>>,
+) -> Result<Json<PublicKeyRequest>, ErrorResponseStatus<'static>> {
+    let State(AppState { database, .. }) = state;
+
+    let key = match database.get_user(&username).await {
+        Ok(other) => database.get_public_key(&other).await,
+        Err(e) => Err(e),
+    };
+
+    match key {
+        Ok(Some(public_key)) => Ok(Json(PublicKeyRequest { public_key })),
+        Ok(None) | Err(DbError::NotFound) => Err(ErrorResponse::reply(
+            "user not found, or they haven't synced since teams were enabled",
+        )
+        .with_status(StatusCode::NOT_FOUND)),
+        Err(DbError::Other(e)) => {
+            error!("failed to get public key: {}", e);
+
+            Err(ErrorResponse::reply("failed to get public key")
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR))
+        }
+    }
+}
+
+/// The teams the user is in, with the team keys sealed to them.
+#[instrument(skip_all, err(level = "warn"), fields(user.id = user.id))]
+pub async fn list<DB: Database>(
+    UserAuth(user): UserAuth,
+    state: State<AppState<DB>>,
+) -> Result<Json<TeamMembersResponse>, ErrorResponseStatus<'static>> {
+    let State(AppState { database, .. }) = state;
+
+    let members = database.list_user_teams(&user).await.map_err(|e| {
+        error!("failed to list teams: {}", e);
+
+        ErrorResponse::reply("failed to list teams").with_status(StatusCode::INTERNAL_SERVER_ERROR)
+    })?;
+
+    let members = members.into_iter().map(|m| member_response(m, true)).collect();
+
+    Ok(Json(TeamMembersResponse { members }))
+}
+
+#[instrument(skip_all, err(level = "warn"), fields(user.id = user.id, team = req.name))]
+pub async fn create<DB: Database>(
+    UserAuth(user): UserAuth,
+    state: State<AppState<DB>>,
+    Json(req): Json<CreateTeamRequest>,
+) -> Result<(), ErrorResponseStatus<'static>> {
+    let State(AppState { database, .. }) = state;
+
+    if !valid_team_name(&req.name) {
+        return Err(ErrorResponse::reply(
+            "team names are up to 32 lowercase letters, digits, dashes and underscores",
+        )
+        .with_status(StatusCode::BAD_REQUEST));
+    }
+
+    match database.get_team(&req.name).await {
+        Ok(_) => {
+            return Err(ErrorResponse::reply("a team with that name already exists")
+                .with_status(StatusCode::CONFLICT));
+        }
+        Err(DbError::NotFound) => {}
+        Err(DbError::Other(e)) => {
+            error!("failed to get team: {}", e);
+
+            return Err(ErrorResponse::reply("failed to create team")
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+        }
+    }
+
+    if let Err(e) = database.add_team(&req.name, &user, &req.sealed_key).await {
+        error!("failed to create team: {}", e);
+
+        return Err(ErrorResponse::reply("failed to create team")
+            .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    info!(user.id = user.id, team = req.name, "created team");
+
+    Ok(())
+}
+
+#[instrument(skip_all, err(level = "warn"), fields(user.id = user.id, team = team))]
+pub async fn members<DB: Database>(
+    Path(team): Path<String>,
+    UserAuth(user): UserAuth,
+    state: State<AppState<DB>>,
+) -> Result<Json<TeamMembersResponse>, ErrorResponseStatus<'static>> {
+    let State(AppState { database, .. }) = state;
+
+    let (team, _) = membership(&database, &team, &user).await?;
+
+    let members = database.list_team_members(&team).await.map_err(|e| {
+        error!("failed to list team members: {}", e);
+
+        ErrorResponse::reply("failed to list team members")
+            .with_status(StatusCode::INTERNAL_SERVER_ERROR)
+    })?;
+
+    let members = members.into_iter().map(|m| member_response(m, false)).collect::<Vec<_>>();
+
+    Ok(Json(TeamMembersResponse { members }))
+}
+
+/// Add someone to the team, or change the role of someone who's already in it.
+#[instrument(skip_all, err(level = "warn"), fields(user.id = user.id, team = team))]
+pub async fn add_member<DB: Database>(
+    Path(team): Path<String>,
+    UserAuth(user): UserAuth,
+    state: State<AppState<DB>>,
+    Json(req): Json<AddTeamMemberRequest>,
+) -> Result<(), ErrorResponseStatus<'static>> {
+    let State(AppState { database, .. }) = state;
+
+    let (team, _) = admin(&database, &team, &user).await?;
+    let member = get_user(&database, &req.username).await?;
+
+    if !req.admin {
+        keep_an_admin(&database, &team, &member).await?;
+    }
+
+    let new = NewTeamMember {
+        team_id: team.id,
+        user_id: member.id,
+        sealed_key: req.sealed_key,
+        admin: req.admin,
+    };
+
+    if let Err(e) = database.add_team_member(&new).await {
+        error!("failed to add team member: {}", e);
+
+        return Err(ErrorResponse::reply("failed to add team member")
+            .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    info!(user.id = user.id, team = team.name, member.id = member.id, "added team member");
+
+    Ok(())
+}
+
+/// Remove someone from the team. Admins can remove anyone, and anyone can leave.
+#[instrument(skip_all, err(level = "warn"), fields(user.id = user.id, team = team))]
+pub async fn remove_member<DB: Database>(
+    Path((team, username)): Path<(String, String)>,
+    UserAuth(user): UserAuth,
+    state: State<AppState<DB>>,
+) -> Result<(), ErrorResponseStatus<'static>> {
+    let State(AppState { database, .. }) = state;
+
+    let (team, me) = membership(&database, &team, &user).await?;
+
+    if username != user.username && !me.admin {
+        return Err(ErrorResponse::reply("only team admins can remove other members")
+            .with_status(StatusCode::FORBIDDEN));
+    }
+
+    let member = get_user(&database, &username).await?;
+    keep_an_admin(&database, &team, &member).await?;
+
+    match database.delete_team_member(&team, &member).await {
+        Ok(()) => {}
+        Err(DbError::NotFound) => {
+            return Err(
+                ErrorResponse::reply("they aren't in the team").with_status(StatusCode::NOT_FOUND)
+            );
+        }
+        Err(DbError::Other(e)) => {
+            error!("failed to remove team member: {}", e);
+
+            return Err(ErrorResponse::reply("failed to remove team member")
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+        }
+    }
+
+    info!(user.id = user.id, team = team.name, member.id = member.id, "removed team member");
+
+    Ok(())
+}
+
+/// Delete the team and everything in its store.
+#[instrument(skip_all, err(level = "warn"), fields(user.id = user.id, team = team))]
+pub async fn delete<DB: Database>(
+    Path(team): Path<String>,
+    UserAuth(user): UserAuth,
+    state: State<AppState<DB>>,
+) -> Result<(), ErrorResponseStatus<'static>> {
+    let State(AppState {
+        database,
+        packfiles,
+        ..
+    }) = state;
+
+    let (team, _) = admin(&database, &team, &user).await?;
+
+    if let Err(e) = database.delete_team(&team).await {
+        error!("failed to delete team: {}", e);
+
+        return Err(ErrorResponse::reply("failed to delete team")
+            .with_status(StatusCode::INTERNAL_SERVER_ERROR));
+    }
+
+    if let Some(packfiles) = packfiles
+        && let Err(e) = packfiles.delete_user(team.store_owner().id).await
+    {
+        // the database no longer knows about them, so they can't be downloaded anyway
+        error!("failed to delete team packfiles {e:?}");
+    }
+
+    info!(user.id = user.id, team = team.name, "deleted team");
+
+    Ok(())
+}
+
+/// The team called `name`, and the user's membership of it. Teams the user isn't in are
+/// reported as not found, so names don't leak.
+pub async fn membership<DB: Database>(
+    database: &DB,
+    name: &str,
+    user: &User,
+) -> Result<(Team, TeamMember), ErrorResponseStatus<'static>> {
+    let membership = match database.get_team(name).await {
+        Ok(team) => database.get_team_member(&team, user).await.map(|m| (team, m)),
+        Err(e) => Err(e),
+    };
+
+    membership.map_err(|e| match e {
+        DbError::NotFound => {
+            ErrorResponse::reply("team not found").with_status(StatusCode::NOT_FOUND)
+        }
+        DbError::Other(e) => {
+            error!("failed to get team membership: {}", e);
+
+            ErrorResponse::reply("failed to get team")
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    })
+}
+
+async fn admin<DB: Database>(
+    database: &DB,
+    name: &str,
+    user: &User,
+) -> Result<(Team, TeamMember), ErrorResponseStatus<'static>> {
+    let (team, member) = membership(database, name, user).await?;
+
+    if !member.admin {
+        return Err(
+            ErrorResponse::reply("only team admins can do that").with_status(StatusCode::FORBIDDEN)
+        );
+    }
+
+    Ok((team, member))
+}
+
+/// Refuse to remove or demote `member` if they're the team's only admin, which would leave no one
+/// able to manage or delete it.
+async fn keep_an_admin<DB: Database>(
+    database: &DB,
+    team: &Team,
+    member: &User,
+) -> Result<(), ErrorResponseStatus<'static>> {
+    let members = database.list_team_members(team).await.map_err(|e| {
+        error!("failed to list team members: {}", e);
+
+        ErrorResponse::reply("failed to list team members")
+            .with_status(StatusCode::INTERNAL_SERVER_ERROR)
+    })?;
+
+    let is_admin = members.iter().any(|m| m.admin && m.user_id == member.id);
+    let other_admins = members.iter().any(|m| m.admin && m.user_id != member.id);
+
+    if is_admin && !other_admins {
+        return Err(ErrorResponse::reply(
+            "a team needs an admin: make someone else an admin first, or delete the team",
+        )
+        .with_status(StatusCode::CONFLICT));
+    }
+
+    Ok(())
+}
+
+async fn get_user<DB: Database>(
+    database: &DB,
+    username: &str,
+) -> Result<User, ErrorResponseStatus<'static>> {
+    database.get_user(username).await.map_err(|e| match e {
+        DbError::NotFound => {
+            ErrorResponse::reply("user not found").with_status(StatusCode::NOT_FOUND)
+        }
+        DbError::Other(e) => {
+            error!("failed to get user: {}", e);
+
+            ErrorResponse::reply("failed to get user")
+                .with_status(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    })
+}
+
+fn member_response(member: TeamMember, with_key: bool) -> TeamMemberResponse {
+    TeamMemberResponse {
+        team: member.team,
+        username: member.username,
+        sealed_key: with_key.then_some(member.sealed_key),
+        admin: member.admin,
+    }
+}
+
+fn valid_team_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_TEAM_NAME_LEN
+        && name.bytes().all(|b| matches!(b, b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_'))
+}
@@ -6,9 +6,12 @@ use std::net::SocketAddr;
 use atuin_server_database::Database;
 use axum::{Router, serve};
 use eyre::{Context, Result};
+use packfiles::PackfileStorage;
 
+pub mod admin;
 mod handlers;
 mod metrics;
+mod packfiles;
 mod router;
 mod trace;
 
@@ -83,6 +86,7 @@ async fn make_router<Db: Database>(settings: Settings) -> Result<Router, eyre::E
     let db = Db::new(&settings.db_settings)
         .await
         .wrap_err_with(|| format!("failed to connect to db: {:?}", settings.db_settings))?;
-    let r = router::router(db, settings);
+    let packfiles = PackfileStorage::new(&settings.packfiles)?;
+    let r = router::router(db, settings, packfiles);
     Ok(r)
 }
@@ -5,6 +5,8 @@ use axum::middleware::Next;
 use axum::response::IntoResponse;
 use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
 
+use crate::settings::Quota;
+
 pub fn setup_metrics_recorder() -> PrometheusHandle {
     const EXPONENTIAL_SECONDS: &[f64] =
         &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0];
@@ -47,3 +49,17 @@ pub async fn track_metrics(req: Request, next: Next) -> impl IntoResponse {
 
     response
 }
+
+/// Record how much of their quota a user is using, after they upload.
+///
+/// The usage gauges are labelled with the user's id, so there's a series for each user who uploads
+/// while a quota is configured.
+#[allow(clippy::cast_precision_loss)]
+pub fn record_quota_usage(quota: &Quota, user_id: i64, records: u64, bytes: u64) {
+    metrics::gauge!("atuin_quota_max_records").set(quota.records as f64);
+    metrics::gauge!("atuin_quota_max_bytes").set(quota.bytes as f64);
+
+    let labels = [("user", user_id.to_string())];
+    metrics::gauge!("atuin_quota_used_records", &labels).set(records as f64);
+    metrics::gauge!("atuin_quota_used_bytes", &labels).set(bytes as f64);
+}
@@ -0,0 +1,149 @@
+use std::io;
+use std::path::{Path, PathBuf};
+
+use atuin_common::utils::uuid_v7;
+use atuin_server_database::models::Packfile;
+use axum::body::{Body, Bytes};
+use base64::prelude::{BASE64_STANDARD, Engine};
+use eyre::{Context, Result};
+use futures_util::{StreamExt, TryStreamExt, stream};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use super::Blob;
+
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Packfile blobs on local disk, one directory per user.
+#[derive(Clone, Debug)]
+pub struct Filesystem {
+    root: PathBuf,
+}
+
+impl Filesystem {
+    pub fn new(root: &Path) -> Result<Self> {
+        fs_err::create_dir_all(root).wrap_err("could not create the packfile directory")?;
+
+        Ok(Self {
+            root: root.to_owned(),
+        })
+    }
+
+    fn user_dir(&self, user_id: i64) -> PathBuf {
+        self.root.join(user_id.to_string())
+    }
+
+    fn path(&self, packfile: &Packfile) -> PathBuf {
+        self.user_dir(packfile.user_id).join(packfile.manifest_id.as_simple().to_string())
+    }
+
+    /// Store the blob, if it's the size and hash the client said it would be. Returns what was
+    /// received, stopping as soon as that's more than expected. Nothing is stored otherwise.
+    pub async fn write(&self, packfile: &Packfile, body: Body) -> Result<Blob> {
+        let path = self.path(packfile);
+        fs::create_dir_all(self.user_dir(packfile.user_id)).await?;
+
+        // Write somewhere else first, so a failed upload never replaces a good blob.
+        let tmp = path.with_extension(format!("{}.tmp", uuid_v7().as_simple()));
+        let mut file = fs::File::create(&tmp).await?;
+
+        let expected = u64::try_from(packfile.size).unwrap_or_default();
+        let mut received = 0;
+        let mut hasher = Sha256::new();
+        let mut body = body.into_data_stream();
+
+        let res = async {
+            while let Some(chunk) = body.next().await {
+                let chunk = chunk.wrap_err("could not read the packfile upload")?;
+                received += chunk.len() as u64;
+
+                if received > expected {
+                    break;
+                }
+
+                hasher.update(&chunk);
+                file.write_all(&chunk).await?;
+            }
+
+            file.sync_all().await?;
+
+            Ok::<_, eyre::Report>(())
+        }
+        .await;
+
+        let blob = Blob {
+            size: received,
+            sha256: Some(BASE64_STANDARD.encode(hasher.finalize())),
+        };
+
+        if res.is_err() || !blob.matches(packfile) {
+            let _ = fs::remove_file(&tmp).await;
+            return res.map(|()| blob);
+        }
+
+        fs::rename(&tmp, &path).await?;
+
+        Ok(blob)
+    }
+
+    /// Stream the blob back.
+    pub async fn read(&self, packfile: &Packfile) -> io::Result<Body> {
+        let file = fs::File::open(self.path(packfile)).await?;
+
+        let chunks = stream::try_unfold(file, |mut file| async move {
+            let mut buf = vec![0; READ_CHUNK_SIZE];
+            let n = file.read(&mut buf).await?;
+
+            if n == 0 {
+                return Ok(None);
+            }
+
+            buf.truncate(n);
+            Ok::<_, io::Error>(Some((Bytes::from(buf), file)))
+        });
+
+        Ok(Body::from_stream(chunks.into_stream()))
+    }
+
+    pub async fn stat(&self, packfile: &Packfile) -> io::Result<Option<Blob>> {
+        let mut file = match fs::File::open(self.path(packfile)).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+
+        let mut size = 0;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0; READ_CHUNK_SIZE];
+
+        loop {
+            let n = file.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+
+            size += n as u64;
+            hasher.update(&buf[..n]);
+        }
+
+        Ok(Some(Blob {
+            size,
+            sha256: Some(BASE64_STANDARD.encode(hasher.finalize())),
+        }))
+    }
+
+    pub async fn delete(&self, packfile: &Packfile) -> io::Result<()> {
+        match fs::remove_file(self.path(packfile)).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    pub async fn delete_user(&self, user_id: i64) -> io::Result<()> {
+        match fs::remove_dir_all(self.user_dir(user_id)).await {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,119 @@
+//! Where packfile blobs are kept.
+//!
+//! Clients upload a packfile in three steps: they ask the server where to upload it to
+//! (`POST /api/v0/packfiles`), upload it there without their session, and then confirm the upload
+//! (`POST /api/v0/packfiles/{manifest_id}/confirm`). Downloads go the same way, through
+//! `GET /api/v0/packfiles/{manifest_id}`. The server only tracks packfiles in the database; where
+//! the blobs go, and so what those URLs point at, is up to the storage backend.
+//!
+//! Clients declare each packfile's size, and optionally its SHA-256, up front. Storage refuses
+//! uploads that don't match where it can, and confirming deletes any blob that still doesn't.
+
+use atuin_common::url::UrlAppendExt;
+use atuin_server_database::models::Packfile;
+use eyre::Result;
+use url::Url;
+
+use crate::settings;
+
+mod filesystem;
+mod s3;
+
+pub use filesystem::Filesystem;
+pub use s3::S3;
+
+#[derive(Clone, Debug)]
+pub enum PackfileStorage {
+    /// Blobs are kept on local disk, and uploaded to and downloaded from this server.
+    Filesystem(Filesystem),
+
+    /// Blobs are kept in an S3-compatible bucket, and clients upload and download them there
+    /// directly.
+    S3(Box<S3>),
+}
+
+impl PackfileStorage {
+    /// The configured storage, or `None` if packfiles aren't enabled.
+    pub fn new(settings: &settings::Packfiles) -> Result<Option<Self>> {
+        if !settings.enable {
+            return Ok(None);
+        }
+
+        let storage = match settings.backend {
+            settings::PackfileBackend::Filesystem => {
+                Self::Filesystem(Filesystem::new(&settings.path)?)
+            }
+            settings::PackfileBackend::S3 => Self::S3(Box::new(S3::new(&settings.s3)?)),
+        };
+
+        Ok(Some(storage))
+    }
+
+    /// Where the client should upload the packfile to. `base` is the URL the client reaches this
+    /// server on.
+    pub fn upload_url(&self, base: &Url, packfile: &Packfile) -> Result<Url> {
+        match self {
+            Self::Filesystem(_) => blob_url(base, packfile),
+            Self::S3(s3) => s3.upload_url(packfile),
+        }
+    }
+
+    /// Where the client can download the packfile from.
+    pub fn download_url(&self, base: &Url, packfile: &Packfile) -> Result<Url> {
+        match self {
+            Self::Filesystem(_) => blob_url(base, packfile),
+            Self::S3(s3) => s3.download_url(packfile),
+        }
+    }
+
+    /// The uploaded blob, or `None` if it hasn't been uploaded.
+    pub async fn stat(&self, packfile: &Packfile) -> Result<Option<Blob>> {
+        match self {
+            Self::Filesystem(fs) => Ok(fs.stat(packfile).await?),
+            Self::S3(s3) => s3.stat(packfile).await,
+        }
+    }
+
+    /// Delete the packfile's blob, if there is one.
+    pub async fn delete(&self, packfile: &Packfile) -> Result<()> {
+        match self {
+            Self::Filesystem(fs) => Ok(fs.delete(packfile).await?),
+            Self::S3(s3) => s3.delete(packfile).await,
+        }
+    }
+
+    /// Delete every blob belonging to the user.
+    pub async fn delete_user(&self, user_id: i64) -> Result<()> {
+        match self {
+            Self::Filesystem(fs) => Ok(fs.delete_user(user_id).await?),
+            Self::S3(s3) => s3.delete_user(user_id).await,
+        }
+    }
+}
+
+/// What storage holds for a packfile.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Blob {
+    pub size: u64,
+    /// The base64 SHA-256 of the blob, if the storage knows it. S3-compatible stores without
+    /// checksum support don't report one.
+    pub sha256: Option<String>,
+}
+
+impl Blob {
+    /// Whether this is the blob the client declared: the same size, and the same hash if they
+    /// declared one and the storage knows it.
+    pub fn matches(&self, packfile: &Packfile) -> bool {
+        let hash_matches = match (&packfile.sha256, &self.sha256) {
+            (Some(declared), Some(stored)) => declared == stored,
+            _ => true,
+        };
+
+        u64::try_from(packfile.size).is_ok_and(|size| size == self.size) && hash_matches
+    }
+}
+
+/// The URL of the blob on this server. The token in it stands in for the client's session.
+fn blob_url(base: &Url, packfile: &Packfile) -> Result<Url> {
+    Ok(base.append(["api", "v0", "packfile-blobs", &packfile.token])?)
+}
@@ -2,24 +2,31 @@ use std::sync::Arc;
 
 use atuin_domain::api::{ATUIN_CARGO_VERSION, ATUIN_HEADER_VERSION, ErrorResponse};
 use atuin_domain::caps::axum::{CapabilitiesRouterExt, get as capabilities_endpoint};
-use atuin_domain::caps::{CapServer, CapabilitiesCap};
-use atuin_server_database::models::User;
+use atuin_domain::caps::{CapServer, CapabilitiesCap, PackfileCap, TeamsCap};
+use atuin_server_database::models::{Session, User};
 use atuin_server_database::{Database, DbError};
 use axum::Router;
-use axum::extract::{FromRequestParts, Request};
+use axum::extract::rejection::RawPathParamsRejection;
+use axum::extract::{FromRequestParts, RawPathParams, Request};
 use axum::http::request::Parts;
 use axum::http::{self};
 use axum::middleware::Next;
 use axum::response::{IntoResponse, Response};
-use axum::routing::{delete, get, patch, post};
+use axum::routing::{delete, get, patch, post, put};
 use eyre::Result;
+use time::{Duration, OffsetDateTime};
 use tower::ServiceBuilder;
 use tower_http::trace::TraceLayer;
 
 use super::handlers;
 use crate::handlers::{ErrorResponseStatus, RespExt};
 use crate::metrics;
-use crate::settings::Settings;
+use crate::packfiles::PackfileStorage;
+use crate::settings::{self, Settings};
+
+/// How often a session's last seen time is updated, so that not every request writes. Short idle
+/// timeouts update it more often; see [`touch_interval`].
+const TOUCH_INTERVAL: Duration = Duration::minutes(15);
 
 pub struct UserAuth(pub User);
 
@@ -29,6 +36,28 @@ where
 {
     type Rejection = ErrorResponseStatus<'static>;
 
+    async fn from_request_parts(
+        req: &mut Parts,
+        state: &AppState<DB>,
+    ) -> Result<Self, Self::Rejection> {
+        let SessionAuth { user, .. } = SessionAuth::from_request_parts(req, state).await?;
+
+        Ok(Self(user))
+    }
+}
+
+/// Like [`UserAuth`], for handlers that also need the session the request was made with.
+pub struct SessionAuth {
+    pub user: User,
+    pub session: Session,
+}
+
+impl<DB> FromRequestParts<AppState<DB>> for SessionAuth
+where
+    DB: Database + Send + Sync,
+{
+    type Rejection = ErrorResponseStatus<'static>;
+
     #[tracing::instrument(name = "auth", skip_all)]
     async fn from_request_parts(
         req: &mut Parts,
@@ -56,7 +85,7 @@ where
                 .with_status(http::StatusCode::BAD_REQUEST));
         }
 
-        let user = state.database.get_session_user(token).await.map_err(|e| match e {
+        let session_error = |e| match e {
             DbError::NotFound => {
                 tracing::warn!("presented session token was not recognised");
                 ErrorResponse::reply("session not found").with_status(http::StatusCode::FORBIDDEN)
@@ -66,14 +95,104 @@ where
                 ErrorResponse::reply("could not query user session")
                     .with_status(http::StatusCode::INTERNAL_SERVER_ERROR)
             }
-        })?;
+        };
+
+        let (session, user) =
+            state.database.get_session_with_user(token).await.map_err(session_error)?;
+        let now = OffsetDateTime::now_utc();
+
+        if is_expired(&state.settings.sessions, &session, now) {
+            tracing::info!(user.id = user.id, session.id = session.id, "session has expired");
+            if let Err(e) = state.database.delete_session(&user, session.id).await {
+                tracing::error!(error = ?e, "could not delete expired session");
+            }
+
+            return Err(ErrorResponse::reply("session expired, please log in again")
+                .with_status(http::StatusCode::FORBIDDEN));
+        }
+
+        if user.disabled {
+            tracing::warn!(user.id = user.id, "session belongs to a disabled user");
+            return Err(ErrorResponse::reply("account is disabled")
+                .with_status(http::StatusCode::FORBIDDEN));
+        }
+
+        let interval = touch_interval(&state.settings.sessions);
+        if session.last_seen_at.is_none_or(|seen| now - seen > interval)
+            && let Err(e) = state.database.touch_session(session.id, now).await
+        {
+            // not worth failing the request over
+            tracing::error!(error = ?e, "could not update session last seen time");
+        }
 
         tracing::debug!(user.id = user.id, user.username = %user.username, "request authenticated");
 
-        Ok(Self(user))
+        Ok(Self { user, session })
+    }
+}
+
+/// Who owns the store a request reads or writes. Under `/teams/{team}` that's the team, as long
+/// as the user is in it (see [`Team::store_owner`]), and otherwise it's the user.
+///
+/// [`Team::store_owner`]: atuin_server_database::models::Team::store_owner
+pub struct StoreAuth(pub User);
+
+impl<DB> FromRequestParts<AppState<DB>> for StoreAuth
+where
+    DB: Database + Send + Sync,
+{
+    type Rejection = ErrorResponseStatus<'static>;
+
+    async fn from_request_parts(
+        req: &mut Parts,
+        state: &AppState<DB>,
+    ) -> Result<Self, Self::Rejection> {
+        let UserAuth(user) = UserAuth::from_request_parts(req, state).await?;
+
+        let team = match RawPathParams::from_request_parts(req, state).await {
+            Ok(params) => params.iter().find(|(k, _)| *k == "team").map(|(_, v)| v.to_owned()),
+            Err(RawPathParamsRejection::MissingPathParams(_)) => None,
+            Err(_) => {
+                return Err(ErrorResponse::reply("invalid path parameters")
+                    .with_status(http::StatusCode::BAD_REQUEST));
+            }
+        };
+
+        let owner = match team {
+            Some(team) => {
+                let (team, _) =
+                    handlers::v0::team::membership(&state.database, &team, &user).await?;
+                team.store_owner()
+            }
+            None => user,
+        };
+
+        Ok(Self(owner))
+    }
+}
+
+/// How long a session's last seen time can go without being updated. The last seen time can lag
+/// behind by this much, so it's at most half the idle timeout, leaving sessions in use time to
+/// be seen before they expire.
+fn touch_interval(settings: &settings::Sessions) -> Duration {
+    match i64::try_from(settings.idle_timeout) {
+        Ok(0) | Err(_) => TOUCH_INTERVAL,
+        Ok(idle_timeout) => TOUCH_INTERVAL.min(Duration::seconds(idle_timeout) / 2),
     }
 }
 
+fn is_expired(settings: &settings::Sessions, session: &Session, now: OffsetDateTime) -> bool {
+    let older_than = |time: Option<OffsetDateTime>, secs: u64| {
+        secs != 0
+            && time.is_some_and(|t| {
+                u64::try_from((now - t).whole_seconds()).is_ok_and(|age| age > secs)
+            })
+    };
+
+    older_than(session.created_at, settings.max_age)
+        || older_than(session.last_seen_at.or(session.created_at), settings.idle_timeout)
+}
+
 async fn teapot() -> impl IntoResponse {
     // This used to return 418: 🫖
     // Much as it was fun, it wasn't as useful or informative as it should be
@@ -102,16 +221,42 @@ async fn semver(request: Request, next: Next) -> Response {
 pub struct AppState<DB: Database> {
     pub database: DB,
     pub settings: Settings,
+    /// `None` if packfiles aren't enabled.
+    pub packfiles: Option<PackfileStorage>,
 }
 
-pub fn router<DB: Database>(database: DB, settings: Settings) -> Router {
+pub fn router<DB: Database>(
+    database: DB,
+    settings: Settings,
+    packfiles: Option<PackfileStorage>,
+) -> Router {
     // Advertise the self-referential capabilities capability, so every server that speaks the
     // protocol carries at least one concrete capability a client can observe.
-    let caps = Arc::new(
-        CapServer::new()
-            .add(CapabilitiesCap { version: 1 })
-            .expect("the capabilities capability is the only one registered"),
-    );
+    let mut caps = CapServer::new()
+        .add(CapabilitiesCap { version: 1 })
+        .expect("the capabilities capability is the only one registered");
+
+    if packfiles.is_some() {
+        caps = caps
+            .add(PackfileCap {
+                version: 1,
+                record_count: settings.packfiles.record_count,
+            })
+            .expect("the packfile capability is only registered once");
+    }
+
+    caps = caps.add(TeamsCap { version: 1 }).expect("the teams capability is only registered once");
+
+    let caps = Arc::new(caps);
+
+    // Everything that reads or writes a store, which teams have too.
+    let store = Router::new()
+        .route("/api/v0/record", post(handlers::v0::record::post))
+        .route("/api/v0/record", get(handlers::v0::record::index))
+        .route("/api/v0/record/next", get(handlers::v0::record::next))
+        .route("/api/v0/packfiles", post(handlers::v0::packfile::create))
+        .route("/api/v0/packfiles/{manifest_id}", get(handlers::v0::packfile::download))
+        .route("/api/v0/packfiles/{manifest_id}/confirm", post(handlers::v0::packfile::confirm));
 
     let negotiated = Router::new()
         .route("/", get(handlers::index))
@@ -121,10 +266,22 @@ pub fn router<DB: Database>(database: DB, settings: Settings) -> Router {
         .route("/register", post(handlers::user::register))
         .route("/login", post(handlers::user::login))
         .route("/api/v0/me", get(handlers::v0::me::get))
-        .route("/api/v0/record", post(handlers::v0::record::post))
-        .route("/api/v0/record", get(handlers::v0::record::index))
-        .route("/api/v0/record/next", get(handlers::v0::record::next))
+        .route("/api/v0/me/key", put(handlers::v0::team::put_key))
+        .route("/api/v0/users/{username}/key", get(handlers::v0::team::get_key))
         .route("/api/v0/store", delete(handlers::v0::store::delete))
+        .route("/api/v0/sessions", get(handlers::v0::session::list))
+        .route("/api/v0/sessions", delete(handlers::v0::session::delete_others))
+        .route("/api/v0/sessions/{id}", delete(handlers::v0::session::delete))
+        .route("/api/v0/packfile-blobs/{token}", put(handlers::v0::packfile::put_blob))
+        .route("/api/v0/packfile-blobs/{token}", get(handlers::v0::packfile::get_blob))
+        .route("/api/v0/teams", get(handlers::v0::team::list))
+        .route("/api/v0/teams", post(handlers::v0::team::create))
+        .route("/api/v0/teams/{team}", delete(handlers::v0::team::delete))
+        .route("/api/v0/teams/{team}/members", get(handlers::v0::team::members))
+        .route("/api/v0/teams/{team}/members", post(handlers::v0::team::add_member))
+        .route("/api/v0/teams/{team}/members/{username}", delete(handlers::v0::team::remove_member))
+        .merge(store.clone())
+        .nest("/teams/{team}", store)
         .negotiate_capabilities(caps.clone());
 
     let unnegotiated = Router::new()
@@ -141,11 +298,18 @@ pub fn router<DB: Database>(database: DB, settings: Settings) -> Router {
         Router::new().nest(path, routes)
     };
 
-    routes.fallback(teapot).with_state(AppState { database, settings }).layer(
-        ServiceBuilder::new()
-            .layer(axum::middleware::from_fn(clacks_overhead))
-            .layer(TraceLayer::new_for_http().make_span_with(crate::trace::make_request_span))
-            .layer(axum::middleware::from_fn(metrics::track_metrics))
-            .layer(axum::middleware::from_fn(semver)),
-    )
+    routes
+        .fallback(teapot)
+        .with_state(AppState {
+            database,
+            settings,
+            packfiles,
+        })
+        .layer(
+            ServiceBuilder::new()
+                .layer(axum::middleware::from_fn(clacks_overhead))
+                .layer(TraceLayer::new_for_http().make_span_with(crate::trace::make_request_span))
+                .layer(axum::middleware::from_fn(metrics::track_metrics))
+                .layer(axum::middleware::from_fn(semver)),
+        )
 }
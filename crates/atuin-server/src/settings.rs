@@ -1,7 +1,9 @@
+use std::fmt;
 use std::io::prelude::*;
 use std::path::PathBuf;
 
 use atuin_server_database::DbSettings;
+pub use atuin_server_database::Quota;
 use config::{Config, Environment, File as ConfigFile, FileFormat};
 use eyre::{Result, eyre};
 use fs_err::{File, create_dir_all};
@@ -27,6 +29,102 @@ impl Default for Metrics {
     }
 }
 
+/// When sessions expire, in seconds. `0` means never.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Sessions {
+    /// How long after logging in a session expires. Sessions from before the server recorded
+    /// when they were created never expire this way.
+    pub max_age: u64,
+    /// How long a session can go unused before it expires. Use is only recorded every 15 minutes,
+    /// or every half of this if that's shorter, so a session can expire up to that long early.
+    pub idle_timeout: u64,
+}
+
+/// Packfiles let clients upload and download history in compressed bundles, rather than a record
+/// at a time.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Packfiles {
+    pub enable: bool,
+    /// Where packfiles are stored.
+    pub backend: PackfileBackend,
+    /// The directory packfiles are stored in, for the filesystem backend.
+    pub path: PathBuf,
+    pub s3: S3,
+    /// How many history records clients bundle into each packfile.
+    pub record_count: u64,
+    /// The largest packfile the server accepts, in bytes.
+    pub max_size: u64,
+    /// The URL clients reach the server on, which packfile upload and download links are built
+    /// from. Worked out from each request if not set.
+    pub public_url: Option<url::Url>,
+}
+
+impl Default for Packfiles {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            backend: PackfileBackend::Filesystem,
+            path: PathBuf::from("packfiles"),
+            s3: S3::default(),
+            record_count: 500,
+            max_size: 64 * 1024 * 1024,
+            public_url: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PackfileBackend {
+    #[default]
+    Filesystem,
+    S3,
+}
+
+/// An S3-compatible bucket to store packfiles in.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct S3 {
+    /// Defaults to AWS, for the region.
+    pub endpoint: Option<url::Url>,
+    pub bucket: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    /// Address the bucket as `endpoint/bucket` rather than `bucket.endpoint`. Most self-hosted
+    /// S3-compatible stores need this.
+    pub path_style: bool,
+    /// How long upload and download links stay valid for, in seconds.
+    pub url_expiry: u64,
+}
+
+impl Default for S3 {
+    fn default() -> Self {
+        Self {
+            endpoint: None,
+            bucket: String::new(),
+            region: String::from("us-east-1"),
+            access_key_id: String::new(),
+            secret_access_key: String::new(),
+            path_style: false,
+            url_expiry: 3600,
+        }
+    }
+}
+
+// Keep the secret key out of logs.
+impl fmt::Debug for S3 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("S3")
+            .field("endpoint", &self.endpoint)
+            .field("bucket", &self.bucket)
+            .field("region", &self.region)
+            .field("access_key_id", &self.access_key_id)
+            .field("path_style", &self.path_style)
+            .field("url_expiry", &self.url_expiry)
+            .finish_non_exhaustive()
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct Settings {
     pub host: String,
@@ -37,6 +135,9 @@ pub struct Settings {
     pub register_webhook_url: Option<url::Url>,
     pub register_webhook_username: String,
     pub metrics: Metrics,
+    pub quota: Quota,
+    pub sessions: Sessions,
+    pub packfiles: Packfiles,
 
     /// Advertise a version that is not what we are _actually_ running
     /// Many clients compare their version with api.atuin.sh, and if they differ, notify the user
@@ -60,6 +161,7 @@ impl Settings {
             config_file
         };
 
+        let packfiles_path = config_file.join("packfiles");
         config_file.push("server.toml");
 
         // create the config file if it does not exist
@@ -73,6 +175,21 @@ impl Settings {
             .set_default("metrics.enable", false)?
             .set_default("metrics.host", "127.0.0.1")?
             .set_default("metrics.port", 9001)?
+            .set_default("quota.records", 0)?
+            .set_default("quota.bytes", 0)?
+            .set_default("sessions.max_age", 0)?
+            .set_default("sessions.idle_timeout", 0)?
+            .set_default("packfiles.enable", false)?
+            .set_default("packfiles.backend", "filesystem")?
+            .set_default("packfiles.path", packfiles_path.to_string_lossy().into_owned())?
+            .set_default("packfiles.record_count", 500)?
+            .set_default("packfiles.max_size", 64 * 1024 * 1024)?
+            .set_default("packfiles.s3.bucket", "")?
+            .set_default("packfiles.s3.region", "us-east-1")?
+            .set_default("packfiles.s3.access_key_id", "")?
+            .set_default("packfiles.s3.secret_access_key", "")?
+            .set_default("packfiles.s3.path_style", false)?
+            .set_default("packfiles.s3.url_expiry", 3600)?
             .add_source(Environment::with_prefix("atuin").prefix_separator("_").separator("__"));
 
         config_builder = if config_file.exists() {
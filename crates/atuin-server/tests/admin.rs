@@ -0,0 +1,90 @@
+use atuin_common::utils::uuid_v7;
+use atuin_server::admin::Cmd;
+use atuin_server_database::Database;
+use atuin_server_postgres::Postgres;
+use rstest::{fixture, rstest};
+
+mod common;
+
+type TestServer = (url::Url, tokio::sync::oneshot::Sender<()>, tokio::task::JoinHandle<()>);
+
+#[fixture]
+async fn server() -> TestServer {
+    let path = format!("/{}", uuid_v7().as_simple());
+    common::start_server(&path).await
+}
+
+async fn admin(cmd: Cmd) {
+    let settings = common::settings("/");
+    let db = Postgres::new(&settings.db_settings).await.unwrap();
+    cmd.run_with(&db, &settings).await.unwrap();
+}
+
+#[rstest]
+#[tokio::test]
+async fn disable_and_enable(#[future] server: TestServer) {
+    let (address, shutdown, server_task) = server.await;
+
+    let username = uuid_v7().as_simple().to_string();
+    let password = uuid_v7().as_simple().to_string();
+    let client = common::register_inner(&address, &username, &password).await;
+    client.me().await.unwrap();
+
+    // -- DISABLE --
+
+    admin(Cmd::Disable {
+        username: username.clone(),
+    })
+    .await;
+
+    // the existing session is refused, and so is logging in again
+    assert!(client.me().await.is_err());
+    let login = atuin_client::api_client::login(
+        &address,
+        atuin_domain::api::LoginRequest {
+            username: username.clone(),
+            password: password.clone(),
+        },
+        &Default::default(),
+    )
+    .await;
+    assert!(login.is_err());
+
+    // -- ENABLE --
+
+    admin(Cmd::Enable {
+        username: username.clone(),
+    })
+    .await;
+
+    client.me().await.unwrap();
+
+    shutdown.send(()).unwrap();
+    server_task.await.unwrap();
+}
+
+#[rstest]
+#[tokio::test]
+async fn revoke_sessions(#[future] server: TestServer) {
+    let (address, shutdown, server_task) = server.await;
+
+    let username = uuid_v7().as_simple().to_string();
+    let password = uuid_v7().as_simple().to_string();
+    let client = common::register_inner(&address, &username, &password).await;
+    client.me().await.unwrap();
+
+    admin(Cmd::RevokeSessions {
+        username: username.clone(),
+    })
+    .await;
+
+    assert!(client.me().await.is_err());
+
+    // logging in again starts a new session
+    let client = common::login(&address, username.clone(), password).await;
+    let status = client.me().await.unwrap();
+    assert_eq!(status.username, username);
+
+    shutdown.send(()).unwrap();
+    server_task.await.unwrap();
+}
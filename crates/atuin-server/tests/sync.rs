@@ -1,17 +1,37 @@
+use std::collections::{BTreeMap, HashMap};
 use std::env::temp_dir;
+use std::sync::Arc;
 use std::time::Duration;
 
-use atuin_client::api_client;
+use atuin_client::packfile::try_pack;
 use atuin_client::record::sqlite_store::SqliteStore;
-use atuin_client::record::sync::{ClientSource, SyncEngine};
+use atuin_client::record::sync::{ClientSource, SyncEngine, SyncError};
+use atuin_client::settings::sync;
+use atuin_client::{api_client, team};
 use atuin_common::encryption::paseto_v4;
 use atuin_common::utils::uuid_v7;
-use atuin_domain::record::{EncryptedData, Host, HostId, Record, RecordId, RecordIdx, RecordTag};
+use atuin_domain::api::{
+    LoginRequest, PackfileDownloadResponse, PackfileRequest, PackfileResponse,
+};
+use atuin_domain::caps::PackfileCap;
+use atuin_domain::record::{
+    DecryptedData, EncryptedData, Host, HostId, Record, RecordId, RecordIdx, RecordSeriesKey,
+    RecordTag,
+};
+use atuin_server::settings::{PackfileBackend, Packfiles, Quota, S3, Sessions};
 use atuin_server::{Settings as ServerSettings, launch_with_tcp_listener};
 use atuin_server_database::DbSettings;
 use atuin_server_sqlite::Sqlite;
+use axum::body::Bytes;
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, HeaderName, Method, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{any, get};
+use base64::prelude::{BASE64_STANDARD, Engine};
 use futures_util::TryFutureExt;
+use parking_lot::Mutex;
 use rstest::{fixture, rstest};
+use sha2::{Digest, Sha256};
 use tokio::net::TcpListener;
 use tokio::sync::oneshot;
 use tokio::task::JoinHandle;
@@ -20,6 +40,7 @@ use tokio::task::JoinHandle;
 struct TestServer {
     address: url::Url,
     db: std::path::PathBuf,
+    packfiles: std::path::PathBuf,
     shutdown: Option<oneshot::Sender<()>>,
     handle: JoinHandle<()>,
 }
@@ -31,14 +52,40 @@ impl TestServer {
         let password = uuid_v7().as_simple().to_string();
         let email = format!("{}@example.com", uuid_v7().as_simple());
 
-        let resp =
-            api_client::register(&self.address, &username, &email, &password, &Default::default())
-                .await
-                .unwrap();
+        let resp = api_client::register(
+            &self.address,
+            &username,
+            &email,
+            &password,
+            None,
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+
+        self.client(resp.session)
+    }
 
+    /// Log in as an existing user, starting a new session.
+    async fn login(&self, username: &str, password: &str) -> api_client::Client {
+        let resp = api_client::login(
+            &self.address,
+            LoginRequest {
+                username: username.to_owned(),
+                password: password.to_owned(),
+            },
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+
+        self.client(resp.session)
+    }
+
+    fn client(&self, session: String) -> api_client::Client {
         api_client::Client::new(
             self.address.clone(),
-            &api_client::AuthToken::Token(resp.session),
+            &api_client::AuthToken::Token(session),
             5,
             30,
             &Default::default(),
@@ -61,14 +108,20 @@ impl Drop for TestServer {
             path.push(suffix);
             let _ = std::fs::remove_file(path);
         }
+        let _ = std::fs::remove_dir_all(&self.packfiles);
     }
 }
 
 #[fixture]
 async fn server() -> TestServer {
+    start(|_| {}).await
+}
+
+/// Start a server, with `configure` changing the settings from the defaults.
+async fn start(configure: impl FnOnce(&mut ServerSettings)) -> TestServer {
     let db = temp_dir().join(format!("atuin-record-sync-{}.db", uuid_v7().as_simple()));
 
-    let server_settings = ServerSettings {
+    let mut server_settings = ServerSettings {
         host: "127.0.0.1".to_owned(),
         port: 0,
         path: String::new(),
@@ -81,8 +134,16 @@ async fn server() -> TestServer {
             read_db_uri: None,
         },
         metrics: atuin_server::settings::Metrics::default(),
+        quota: Quota::default(),
+        sessions: Sessions::default(),
+        packfiles: Packfiles {
+            path: temp_dir().join(format!("atuin-packfiles-{}", uuid_v7().as_simple())),
+            ..Packfiles::default()
+        },
         fake_version: None,
     };
+    configure(&mut server_settings);
+    let packfiles = server_settings.packfiles.path.clone();
 
     let (shutdown_tx, shutdown_rx) = oneshot::channel();
     let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
@@ -105,6 +166,7 @@ async fn server() -> TestServer {
     TestServer {
         address: url::Url::parse(&format!("http://{addr}")).expect("valid test server url"),
         db,
+        packfiles,
         shutdown: Some(shutdown_tx),
         handle,
     }
@@ -128,6 +190,119 @@ fn record(host: HostId, tag: &RecordTag, idx: RecordIdx) -> Record<EncryptedData
         .build()
 }
 
+/// An in-memory S3 bucket, addressed path-style as `/bucket/{key}`. It checks requests are
+/// presigned and carry the headers they were signed with, but not the signatures themselves, and
+/// lists one key per page so callers have to follow continuation tokens. Like S3, it keeps the
+/// checksum objects are uploaded with, without checking it.
+#[derive(Clone, Default)]
+struct FakeS3 {
+    objects: Arc<Mutex<BTreeMap<String, FakeObject>>>,
+}
+
+/// An object's body, and the checksum it was uploaded with.
+type FakeObject = (Bytes, Option<String>);
+
+impl FakeS3 {
+    async fn start(&self) -> url::Url {
+        async fn list(
+            State(s3): State<FakeS3>,
+            Query(query): Query<HashMap<String, String>>,
+        ) -> Response {
+            if !query.contains_key("X-Amz-Signature") {
+                return StatusCode::FORBIDDEN.into_response();
+            }
+
+            let prefix = query.get("prefix").cloned().unwrap_or_default();
+            let start = query.get("continuation-token").cloned().unwrap_or_default();
+            let keys: Vec<String> = s3
+                .objects
+                .lock()
+                .keys()
+                .filter(|k| k.starts_with(&prefix) && **k >= start)
+                .take(2)
+                .cloned()
+                .collect();
+            let mut keys = keys.iter();
+
+            let mut xml = String::from("<ListBucketResult>");
+            if let Some(key) = keys.next() {
+                xml += &format!("<Contents><Key>{key}</Key></Contents>");
+            }
+            if let Some(next) = keys.next() {
+                xml += &format!("<NextContinuationToken>{next}</NextContinuationToken>");
+            }
+            xml += "</ListBucketResult>";
+
+            xml.into_response()
+        }
+
+        async fn object(
+            State(s3): State<FakeS3>,
+            method: Method,
+            Path(key): Path<String>,
+            Query(query): Query<HashMap<String, String>>,
+            headers: HeaderMap,
+            body: Bytes,
+        ) -> Response {
+            if !query.contains_key("X-Amz-Signature") {
+                return StatusCode::FORBIDDEN.into_response();
+            }
+
+            let signed = query.get("X-Amz-SignedHeaders").cloned().unwrap_or_default();
+            if signed.split(';').any(|name| !headers.contains_key(name)) {
+                return StatusCode::FORBIDDEN.into_response();
+            }
+
+            let checksum = headers
+                .get("x-amz-checksum-sha256")
+                .and_then(|h| h.to_str().ok())
+                .map(ToOwned::to_owned);
+
+            match method {
+                Method::PUT => {
+                    s3.objects.lock().insert(key, (body, checksum));
+                    StatusCode::OK.into_response()
+                }
+                Method::GET => match s3.objects.lock().get(&key).cloned() {
+                    Some((blob, _)) => blob.into_response(),
+                    None => StatusCode::NOT_FOUND.into_response(),
+                },
+                Method::HEAD => match s3.objects.lock().get(&key).cloned() {
+                    Some((blob, Some(checksum))) if headers.contains_key("x-amz-checksum-mode") => {
+                        [
+                            (header::CONTENT_LENGTH, blob.len().to_string()),
+                            (HeaderName::from_static("x-amz-checksum-sha256"), checksum),
+                        ]
+                        .into_response()
+                    }
+                    Some((blob, _)) => [(header::CONTENT_LENGTH, blob.len())].into_response(),
+                    None => StatusCode::NOT_FOUND.into_response(),
+                },
+                Method::DELETE => {
+                    s3.objects.lock().remove(&key);
+                    StatusCode::NO_CONTENT.into_response()
+                }
+                _ => StatusCode::METHOD_NOT_ALLOWED.into_response(),
+            }
+        }
+
+        let app = axum::Router::new()
+            .route("/bucket", get(list))
+            .route("/bucket/{*key}", any(object))
+            .with_state(self.clone());
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        url::Url::parse(&format!("http://{addr}")).unwrap()
+    }
+
+    fn keys(&self) -> Vec<String> {
+        self.objects.lock().keys().cloned().collect()
+    }
+}
+
 /// Populate the remote with records `0..=remote_max` and the local store with records
 /// `0..=local_max` (or nothing if `local_max` is [`None`]), then run a real sync.
 ///
@@ -266,3 +441,672 @@ async fn upload_sends_exactly_the_missing_records(
     assert_eq!(remote_idx, local_max, "remote is missing records");
     assert_eq!(uploaded, expected);
 }
+
+#[rstest]
+#[case::records(Quota { records: 3, bytes: 0 })]
+// each test record is 17 bytes
+#[case::bytes(Quota { records: 0, bytes: 3 * 17 })]
+#[tokio::test]
+async fn uploads_over_the_quota_are_refused(#[case] quota: Quota) {
+    let server = start(|settings| settings.quota = quota).await;
+    let client = server.register().await;
+
+    let host = HostId(uuid_v7());
+    let tag = RecordTag::Other(uuid_v7().as_simple().to_string());
+    let records: Vec<Record<EncryptedData>> = (0..5).map(|idx| record(host, &tag, idx)).collect();
+
+    client.post_records(&records[..3]).await.unwrap();
+
+    let usage = client.me().await.unwrap().usage.unwrap();
+    assert_eq!(usage.records, 3);
+    assert_eq!(usage.bytes, 3 * 17);
+
+    // the quota is full, so the next upload is refused with a typed error...
+    let err = client.post_records(&records[3..4]).await.unwrap_err();
+    assert!(err.downcast_ref::<api_client::QuotaExceeded>().is_some(), "got {err:?}");
+
+    // ...which sync reports as such
+    let store = SqliteStore::new(":memory:", 2.0).await.unwrap();
+    store.push_batch(records.iter()).await.unwrap();
+
+    let engine = SyncEngine::builder()
+        .store(store)
+        .client_source(ClientSource::FromClient(client))
+        .build()
+        .connect()
+        .await
+        .unwrap();
+    let (diff, _) = engine.diff().await.unwrap();
+    let operations = SyncEngine::operations(diff).unwrap();
+    let err = engine.keyed(&key()).sync_remote(operations).await.unwrap_err();
+    assert!(matches!(err, SyncError::QuotaExceeded { .. }), "got {err:?}");
+}
+
+#[rstest]
+#[tokio::test]
+async fn usage_counts_each_record_once(#[future(awt)] server: TestServer) {
+    let client = server.register().await;
+
+    let host = HostId(uuid_v7());
+    let tag = RecordTag::Other(uuid_v7().as_simple().to_string());
+    let records: Vec<Record<EncryptedData>> = (0..4).map(|idx| record(host, &tag, idx)).collect();
+
+    client.post_records(&records[..2]).await.unwrap();
+    // the server already has the first two
+    client.post_records(&records).await.unwrap();
+
+    let usage = client.me().await.unwrap().usage.unwrap();
+    assert_eq!(usage.records, 4);
+    assert_eq!(usage.bytes, 4 * 17);
+}
+
+/// Sync `store` both ways, syncing only what `filter` allows.
+async fn sync_filtered(client: &api_client::Client, store: &SqliteStore, filter: sync::Settings) {
+    let engine = SyncEngine::builder()
+        .store(store.clone())
+        .client_source(ClientSource::FromClient(client.clone()))
+        .build()
+        .connect()
+        .await
+        .unwrap()
+        .with_filter(filter);
+    let (diff, _) = engine.diff().await.unwrap();
+    let operations = SyncEngine::operations(diff).unwrap();
+    engine.keyed(&key()).sync_remote(operations).await.unwrap();
+}
+
+#[rstest]
+#[tokio::test]
+async fn filtered_series_are_not_synced(#[future(awt)] server: TestServer) {
+    let client = server.register().await;
+    let (work, personal) = (HostId(uuid_v7()), HostId(uuid_v7()));
+    let alias = RecordTag::ConfigShellAlias;
+
+    for host in [work, personal] {
+        let records = [record(host, &RecordTag::History, 0), record(host, &alias, 0)];
+        client.post_records(&records).await.unwrap();
+    }
+
+    // a work laptop that leaves personal machines alone
+    let laptop = SqliteStore::new(":memory:", 2.0).await.unwrap();
+    let filter = sync::Settings {
+        ignore_hosts: vec![personal],
+        ..sync::Settings::default()
+    };
+    sync_filtered(&client, &laptop, filter).await;
+
+    let status = laptop.status().await.unwrap();
+    assert_eq!(status.hosts.keys().collect::<Vec<_>>(), [&work]);
+    assert_eq!(status.hosts[&work].len(), 2);
+
+    // a server that shares aliases but not history, in either direction
+    let machine = HostId(uuid_v7());
+    let store = SqliteStore::new(":memory:", 2.0).await.unwrap();
+    store.push(&record(machine, &RecordTag::History, 0)).await.unwrap();
+    store.push(&record(machine, &alias, 0)).await.unwrap();
+    let filter = sync::Settings {
+        ignore_tags: vec![RecordTag::History],
+        ..sync::Settings::default()
+    };
+    sync_filtered(&client, &store, filter).await;
+
+    let remote = client.record_status().await.unwrap();
+    assert_eq!(remote.hosts[&machine].keys().collect::<Vec<_>>(), [&alias]);
+    let local = store.status().await.unwrap();
+    assert_eq!(local.hosts[&work].keys().collect::<Vec<_>>(), [&alias]);
+    assert_eq!(local.hosts[&personal].keys().collect::<Vec<_>>(), [&alias]);
+}
+
+#[rstest]
+#[tokio::test]
+async fn sessions_can_be_listed_and_revoked(#[future(awt)] server: TestServer) {
+    let username = uuid_v7().as_simple().to_string();
+    let password = uuid_v7().as_simple().to_string();
+    let email = format!("{username}@example.com");
+    let resp = api_client::register(
+        &server.address,
+        &username,
+        &email,
+        &password,
+        None,
+        &Default::default(),
+    )
+    .await
+    .unwrap();
+    let first = server.client(resp.session);
+    let second = server.login(&username, &password).await;
+    let third = server.login(&username, &password).await;
+
+    let sessions = first.sessions().await.unwrap().sessions;
+    assert_eq!(sessions.len(), 3);
+    assert!(sessions[0].current);
+    assert!(!sessions[1].current && !sessions[2].current);
+    assert!(
+        sessions.iter().all(|s| s.user_agent.as_deref().is_some_and(|ua| ua.starts_with("atuin/")))
+    );
+
+    // nobody else can revoke them
+    let other = server.register().await;
+    assert!(other.revoke_session(sessions[1].id).await.is_err());
+    second.me().await.unwrap();
+
+    first.revoke_session(sessions[1].id).await.unwrap();
+    assert!(second.me().await.is_err());
+    third.me().await.unwrap();
+
+    first.revoke_other_sessions().await.unwrap();
+    assert!(third.me().await.is_err());
+    first.me().await.unwrap();
+    assert_eq!(first.sessions().await.unwrap().sessions.len(), 1);
+}
+
+#[rstest]
+#[case::max_age(Sessions { max_age: 1, idle_timeout: 0 })]
+#[case::idle_timeout(Sessions { max_age: 0, idle_timeout: 1 })]
+#[tokio::test]
+async fn sessions_expire(#[case] sessions: Sessions) {
+    let server = start(|settings| settings.sessions = sessions).await;
+    let client = server.register().await;
+    client.me().await.unwrap();
+
+    tokio::time::sleep(Duration::from_millis(2100)).await;
+
+    assert!(client.me().await.is_err());
+}
+
+#[tokio::test]
+async fn sessions_in_use_stay_alive() {
+    let server = start(|settings| {
+        settings.sessions = Sessions {
+            max_age: 0,
+            idle_timeout: 2,
+        };
+    })
+    .await;
+    let client = server.register().await;
+
+    // longer than the idle timeout in all, but never idle for that long
+    for _ in 0..3 {
+        client.me().await.unwrap();
+        tokio::time::sleep(Duration::from_millis(1200)).await;
+    }
+
+    client.me().await.unwrap();
+}
+
+#[rstest]
+#[case::filesystem(PackfileBackend::Filesystem)]
+#[case::s3(PackfileBackend::S3)]
+#[tokio::test]
+async fn history_syncs_through_packfiles(#[case] backend: PackfileBackend) {
+    let bucket = FakeS3::default();
+    let endpoint = bucket.start().await;
+    let server = start(|settings| {
+        settings.packfiles.enable = true;
+        settings.packfiles.record_count = 3;
+        settings.packfiles.backend = backend;
+        settings.packfiles.s3 = S3 {
+            endpoint: Some(endpoint),
+            bucket: "bucket".to_owned(),
+            access_key_id: "access".to_owned(),
+            secret_access_key: "secret".to_owned(),
+            path_style: true,
+            ..S3::default()
+        };
+    })
+    .await;
+    let client = server.register().await;
+
+    let cap = client.caps().get_server::<PackfileCap>().await.unwrap();
+    let cap = cap.expect("server should advertise packfiles");
+    assert_eq!(cap.record_count, 3);
+
+    let key = key();
+    let host = HostId(uuid_v7());
+    let history = RecordSeriesKey::new(host, RecordTag::History);
+
+    let up = SqliteStore::new(":memory:", 2.0).await.unwrap();
+    for idx in 0..7 {
+        let record = Record::builder()
+            .host(Host::new(host))
+            .version("v1".into())
+            .tag(RecordTag::History)
+            .idx(idx)
+            .data(DecryptedData(format!("cmd {idx}").into_bytes()))
+            .build()
+            .encrypt(&key);
+        up.push(&record).await.unwrap();
+    }
+
+    // two packs of three, with one record left over
+    try_pack(&up, &history, Some(cap)).await.unwrap();
+    let manifests = up.next(&RecordSeriesKey::new(host, RecordTag::Packfile), 0, 10).await;
+    let manifests = manifests.unwrap();
+    assert_eq!(manifests.len(), 2);
+
+    let engine = SyncEngine::builder()
+        .store(up)
+        .client_source(ClientSource::FromClient(client.clone()))
+        .build()
+        .connect()
+        .await
+        .unwrap();
+    engine.keyed(&key).sync().await.unwrap();
+
+    for manifest in &manifests {
+        assert!(!client.download_packfile(manifest.id).await.unwrap().is_empty());
+    }
+
+    // a second machine gets all the history back
+    let down = SqliteStore::new(":memory:", 2.0).await.unwrap();
+    let engine = SyncEngine::builder()
+        .store(down.clone())
+        .client_source(ClientSource::FromClient(client.clone()))
+        .build()
+        .connect()
+        .await
+        .unwrap();
+    engine.keyed(&key).sync().await.unwrap();
+
+    let idxs: Vec<RecordIdx> =
+        down.next(&history, 0, 10).await.unwrap().iter().map(|r| r.idx).collect();
+    assert_eq!(idxs, (0..7).collect::<Vec<_>>());
+
+    // deleting the store takes the blobs with it
+    let stored = || match backend {
+        PackfileBackend::Filesystem => std::fs::read_dir(&server.packfiles).unwrap().count(),
+        PackfileBackend::S3 => bucket.keys().len(),
+    };
+    assert_ne!(stored(), 0);
+    client.delete_store().await.unwrap();
+    assert_eq!(stored(), 0);
+}
+
+/// A sync profile keeps its server's records under its own key, so someone with only that key can
+/// read them, and re-wraps them for the local store on the way back.
+#[tokio::test]
+async fn profiles_sync_with_their_own_key() {
+    let server = start(|settings| {
+        settings.packfiles.enable = true;
+        settings.packfiles.record_count = 3;
+    })
+    .await;
+    let client = server.register().await;
+    let cap = client.caps().get_server::<PackfileCap>().await.unwrap();
+
+    let (local_key, work_key) = (key(), paseto_v4::Key::from([9u8; 32]));
+    let host = HostId(uuid_v7());
+    let history = RecordSeriesKey::new(host, RecordTag::History);
+
+    let up = SqliteStore::new(":memory:", 2.0).await.unwrap();
+    for idx in 0..4 {
+        let record = Record::builder()
+            .host(Host::new(host))
+            .version("v1".into())
+            .tag(RecordTag::History)
+            .idx(idx)
+            .data(DecryptedData(format!("cmd {idx}").into_bytes()))
+            .build()
+            .encrypt(&local_key);
+        up.push(&record).await.unwrap();
+    }
+    try_pack(&up, &history, cap).await.unwrap();
+
+    let sync = |store: SqliteStore, key: paseto_v4::Key, remote_key: paseto_v4::Key| {
+        let client = client.clone();
+        async move {
+            let engine = SyncEngine::builder()
+                .store(store)
+                .client_source(ClientSource::FromClient(client))
+                .build()
+                .connect()
+                .await
+                .unwrap();
+            engine.keyed_with_remote(&key, &remote_key).sync().await.map(|_| ())
+        }
+    };
+    let commands = |store: SqliteStore, key: paseto_v4::Key| {
+        let history = history.clone();
+        async move {
+            let records = store.next(&history, 0, 10).await.unwrap();
+            records
+                .into_iter()
+                .map(|r| String::from_utf8(r.decrypt(&key).unwrap().data.0).unwrap())
+                .collect::<Vec<_>>()
+        }
+    };
+    let expected: Vec<_> = (0..4).map(|idx| format!("cmd {idx}")).collect();
+
+    sync(up.clone(), local_key.clone(), work_key.clone()).await.unwrap();
+    assert_eq!(commands(up, local_key.clone()).await, expected);
+
+    // a teammate with only the profile's key
+    let teammate = SqliteStore::new(":memory:", 2.0).await.unwrap();
+    sync(teammate.clone(), work_key.clone(), work_key.clone()).await.unwrap();
+    assert_eq!(commands(teammate, work_key.clone()).await, expected);
+
+    // another of our machines, syncing the profile into a store under our own key
+    let down = SqliteStore::new(":memory:", 2.0).await.unwrap();
+    sync(down.clone(), local_key.clone(), work_key).await.unwrap();
+    assert_eq!(commands(down.clone(), local_key.clone()).await, expected);
+
+    // while our own key can't read the profile's server
+    let err = sync(down, local_key.clone(), local_key).await.unwrap_err();
+    assert!(matches!(err, SyncError::WrongKey), "got {err:?}");
+}
+
+#[rstest]
+#[tokio::test]
+async fn teams_share_an_encrypted_store(#[future(awt)] server: TestServer) {
+    let (alice, bob, eve) =
+        (server.register().await, server.register().await, server.register().await);
+    let (alice_key, bob_key, eve_key) =
+        (key(), paseto_v4::Key::from([8u8; 32]), paseto_v4::Key::from([9u8; 32]));
+    for (client, key) in [(&alice, &alice_key), (&bob, &bob_key), (&eve, &eve_key)] {
+        team::publish_key(client, key).await.unwrap();
+    }
+    let bob_name = bob.me().await.unwrap().username;
+    let eve_name = eve.me().await.unwrap().username;
+
+    let alice_name = alice.me().await.unwrap().username;
+
+    team::create(&alice, &alice_key, "ops").await.unwrap();
+    // the key the server hands out is bob's own
+    let bob_public = team::member_key(&alice, &bob_name).await.unwrap();
+    assert_eq!(paseto_v4::fingerprint(&bob_public).unwrap(), bob_key.identity().fingerprint());
+    team::add_member(&alice, &alice_key, "ops", &bob_name, &bob_public, false).await.unwrap();
+
+    let sync = |client: api_client::Client, store: SqliteStore, key: paseto_v4::Key, team_key| async move {
+        let engine = SyncEngine::builder()
+            .store(store)
+            .client_source(ClientSource::FromClient(client.for_team("ops").unwrap()))
+            .build()
+            .connect()
+            .await
+            .unwrap();
+        engine.keyed_with_remote(&key, &team_key).sync().await.map(|_| ())
+    };
+
+    // alice shares a record with the team, from her store under her own key
+    let host = HostId(uuid_v7());
+    let shared = Record::builder()
+        .host(Host::new(host))
+        .version("v1".into())
+        .tag(RecordTag::Kv)
+        .idx(0)
+        .data(DecryptedData(b"shared".to_vec()))
+        .build();
+    let up = SqliteStore::new(":memory:", 2.0).await.unwrap();
+    up.push(&shared.clone().encrypt(&alice_key)).await.unwrap();
+
+    let alice_team = team::membership(&alice, &alice_key, "ops").await.unwrap();
+    sync(alice.clone(), up, alice_key.clone(), alice_team.key.clone()).await.unwrap();
+
+    // bob unseals the same team key, and gets the record under his own
+    let bob_team = team::membership(&bob, &bob_key, "ops").await.unwrap();
+    assert!(bob_team.key == alice_team.key);
+    assert!(!bob_team.admin);
+
+    let down = SqliteStore::new(":memory:", 2.0).await.unwrap();
+    sync(bob.clone(), down.clone(), bob_key.clone(), bob_team.key).await.unwrap();
+    let records = down.next(&RecordSeriesKey::new(host, RecordTag::Kv), 0, 10).await.unwrap();
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].clone().decrypt(&bob_key).unwrap().data.0, b"shared");
+
+    // the team's store is its own
+    assert!(alice.record_status().await.unwrap().hosts.is_empty());
+    assert_eq!(alice.team_members("ops").await.unwrap().members.len(), 2);
+
+    // and out of reach of everyone else
+    assert!(eve.for_team("ops").unwrap().record_status().await.is_err());
+    assert!(eve.team_members("ops").await.is_err());
+    assert!(team::membership(&eve, &eve_key, "ops").await.is_err());
+    assert!(bob.add_team_member("ops", &eve_name, "k4.seal.x", false).await.is_err());
+    assert!(bob.delete_team("ops").await.is_err());
+
+    // it's charged to alice's quota, as she created it
+    let records = |client: api_client::Client| async move {
+        client.me().await.unwrap().usage.unwrap().records
+    };
+    assert_eq!(records(alice.clone()).await, 1);
+    assert_eq!(records(bob.clone()).await, 0);
+
+    // she's its only admin, so she can't leave or stop being one
+    let alice_public = team::member_key(&alice, &alice_name).await.unwrap();
+    assert!(alice.remove_team_member("ops", &alice_name).await.is_err());
+    let demoted = team::add_member(&alice, &alice_key, "ops", &alice_name, &alice_public, false);
+    assert!(demoted.await.is_err());
+
+    // until they're removed
+    alice.remove_team_member("ops", &bob_name).await.unwrap();
+    assert!(bob.for_team("ops").unwrap().record_status().await.is_err());
+
+    // with another admin, she can leave, and the team is charged to them instead
+    team::add_member(&alice, &alice_key, "ops", &bob_name, &bob_public, true).await.unwrap();
+    alice.remove_team_member("ops", &alice_name).await.unwrap();
+    assert_eq!(records(alice.clone()).await, 0);
+    assert_eq!(records(bob.clone()).await, 1);
+
+    bob.delete_team("ops").await.unwrap();
+    assert!(bob.teams().await.unwrap().members.is_empty());
+}
+
+#[tokio::test]
+async fn packfile_uploads_are_checked() {
+    let server = start(|settings| {
+        settings.packfiles.enable = true;
+        settings.packfiles.max_size = 8;
+    })
+    .await;
+    let http = reqwest::Client::new();
+    let session = |name: &str| {
+        let address = server.address.clone();
+        let name = name.to_owned();
+        async move {
+            let resp = api_client::register(
+                &address,
+                &name,
+                &format!("{name}@example.com"),
+                &format!("{name}-password"),
+                None,
+                &Default::default(),
+            )
+            .await
+            .unwrap();
+            format!("Token {}", resp.session)
+        }
+    };
+    let alice = session(&uuid_v7().as_simple().to_string()).await;
+    let bob = session(&uuid_v7().as_simple().to_string()).await;
+
+    let manifest_id = RecordId(uuid_v7());
+    let api = |path: &str| server.address.join(path).unwrap();
+    let create = |size: u64| {
+        http.post(api("/api/v0/packfiles")).header("authorization", &alice).json(&PackfileRequest {
+            manifest_id,
+            records: vec![],
+            packfile_size_bytes: size,
+            packfile_sha256: Some(BASE64_STANDARD.encode(Sha256::digest(b"12345"))),
+        })
+    };
+    let download = |token: &str| {
+        http.get(api(&format!("/api/v0/packfiles/{}", manifest_id.0)))
+            .header("authorization", token.to_owned())
+    };
+    let confirm = || {
+        http.post(api(&format!("/api/v0/packfiles/{}/confirm", manifest_id.0)))
+            .header("authorization", &alice)
+    };
+
+    let resp = create(9).send().await.unwrap();
+    assert_eq!(resp.status(), 413);
+
+    let resp = create(5).send().await.unwrap();
+    let upload_url = resp.json::<PackfileResponse>().await.unwrap().upload_url;
+
+    // nothing can be downloaded or confirmed until the whole packfile is uploaded
+    assert_eq!(download(&alice).send().await.unwrap().status(), 404);
+    let resp = http.put(upload_url.clone()).body("1234").send().await.unwrap();
+    assert_eq!(resp.status(), 400);
+    assert_eq!(confirm().send().await.unwrap().status(), 400);
+
+    // the right size isn't enough, it has to be what was declared
+    let resp = http.put(upload_url.clone()).body("12346").send().await.unwrap();
+    assert_eq!(resp.status(), 400);
+    assert_eq!(confirm().send().await.unwrap().status(), 400);
+
+    let resp = http.put(upload_url.clone()).body("12345").send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(confirm().send().await.unwrap().status(), 200);
+
+    // once confirmed, the upload url can't be used to replace it
+    let resp = http.put(upload_url).body("54321").send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+
+    let resp = download(&alice).send().await.unwrap();
+    let download_url = resp.json::<PackfileDownloadResponse>().await.unwrap().download_url;
+    let blob = http.get(download_url).send().await.unwrap().bytes().await.unwrap();
+    assert_eq!(&blob[..], b"12345");
+
+    // other users can't see it
+    assert_eq!(download(&bob).send().await.unwrap().status(), 404);
+}
+
+/// S3 checks uploads against the size and hash signed into the URL, but a bucket that doesn't is
+/// still caught when the upload is confirmed.
+#[tokio::test]
+async fn mismatched_s3_packfiles_are_deleted_on_confirm() {
+    let bucket = FakeS3::default();
+    let endpoint = bucket.start().await;
+    let server = start(|settings| {
+        settings.packfiles.enable = true;
+        settings.packfiles.backend = PackfileBackend::S3;
+        settings.packfiles.s3 = S3 {
+            endpoint: Some(endpoint),
+            bucket: "bucket".to_owned(),
+            access_key_id: "access".to_owned(),
+            secret_access_key: "secret".to_owned(),
+            path_style: true,
+            ..S3::default()
+        };
+    })
+    .await;
+    let http = reqwest::Client::new();
+
+    let name = uuid_v7().as_simple().to_string();
+    let resp = api_client::register(
+        &server.address,
+        &name,
+        &format!("{name}@example.com"),
+        &format!("{name}-password"),
+        None,
+        &Default::default(),
+    )
+    .await
+    .unwrap();
+    let token = format!("Token {}", resp.session);
+
+    let manifest_id = RecordId(uuid_v7());
+    let sha256 = |body: &[u8]| BASE64_STANDARD.encode(Sha256::digest(body));
+    let resp = http
+        .post(server.address.join("/api/v0/packfiles").unwrap())
+        .header("authorization", &token)
+        .json(&PackfileRequest {
+            manifest_id,
+            records: vec![],
+            packfile_size_bytes: 5,
+            packfile_sha256: Some(sha256(b"12345")),
+        })
+        .send()
+        .await
+        .unwrap();
+    let upload_url = resp.json::<PackfileResponse>().await.unwrap().upload_url;
+
+    let signed = upload_url.query_pairs().find(|(k, _)| k == "X-Amz-SignedHeaders").unwrap().1;
+    assert_eq!(signed, "content-length;host;x-amz-checksum-sha256");
+
+    let resp = http
+        .put(upload_url)
+        .header("x-amz-checksum-sha256", sha256(b"12346"))
+        .body("12346")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(bucket.keys().len(), 1);
+
+    let confirm =
+        server.address.join(&format!("/api/v0/packfiles/{}/confirm", manifest_id.0)).unwrap();
+    let resp = http.post(confirm).header("authorization", &token).send().await.unwrap();
+    assert_eq!(resp.status(), 400);
+    assert!(bucket.keys().is_empty());
+}
+
+#[tokio::test]
+async fn packfile_uploads_count_towards_the_quota() {
+    let server = start(|settings| {
+        settings.packfiles.enable = true;
+        settings.packfiles.max_size = 8;
+        settings.quota = Quota {
+            records: 4,
+            bytes: 10,
+        };
+    })
+    .await;
+    let http = reqwest::Client::new();
+
+    let name = uuid_v7().as_simple().to_string();
+    let resp = api_client::register(
+        &server.address,
+        &name,
+        &format!("{name}@example.com"),
+        &format!("{name}-password"),
+        None,
+        &Default::default(),
+    )
+    .await
+    .unwrap();
+    let token = format!("Token {}", resp.session);
+
+    let create = |manifest_id: RecordId, records: usize, size: u64| {
+        http.post(server.address.join("/api/v0/packfiles").unwrap())
+            .header("authorization", &token)
+            .json(&PackfileRequest {
+                manifest_id,
+                records: (0..records).map(|_| RecordId(uuid_v7())).collect(),
+                packfile_size_bytes: size,
+                packfile_sha256: None,
+            })
+            .send()
+    };
+    let upload_and_confirm = async |manifest_id: RecordId, resp: reqwest::Response, size: u64| {
+        let upload_url = resp.json::<PackfileResponse>().await.unwrap().upload_url;
+        let body = vec![0; usize::try_from(size).unwrap()];
+        http.put(upload_url).body(body).send().await.unwrap().error_for_status().unwrap();
+
+        let confirm = format!("/api/v0/packfiles/{}/confirm", manifest_id.0);
+        http.post(server.address.join(&confirm).unwrap())
+            .header("authorization", &token)
+            .send()
+            .await
+            .unwrap()
+            .status()
+    };
+
+    // packfiles only count once they're uploaded, so one that never is doesn't use up the quota
+    assert_eq!(create(RecordId(uuid_v7()), 4, 8).await.unwrap().status(), 200);
+    let first = RecordId(uuid_v7());
+    let resp = create(first, 2, 6).await.unwrap();
+    assert_eq!(upload_and_confirm(first, resp, 6).await, 200);
+
+    // too many bytes, then too many records, on top of the first
+    assert_eq!(create(RecordId(uuid_v7()), 1, 5).await.unwrap().status(), 507);
+    assert_eq!(create(RecordId(uuid_v7()), 3, 1).await.unwrap().status(), 507);
+
+    // uploads are checked again when they're confirmed, as others may have been confirmed since
+    let (second, third) = (RecordId(uuid_v7()), RecordId(uuid_v7()));
+    let second_resp = create(second, 2, 4).await.unwrap();
+    let third_resp = create(third, 2, 4).await.unwrap();
+    assert_eq!(upload_and_confirm(third, third_resp, 4).await, 200);
+    assert_eq!(upload_and_confirm(second, second_resp, 4).await, 507);
+}
@@ -15,6 +15,9 @@ mod sync;
 #[cfg(feature = "sync")]
 mod account;
 
+#[cfg(feature = "sync")]
+mod team;
+
 #[cfg(feature = "daemon")]
 mod daemon;
 
@@ -71,6 +74,10 @@ pub enum Cmd {
     #[cfg(feature = "sync")]
     Account(account::Cmd),
 
+    /// Share kv entries, scripts and dotfiles with a team
+    #[cfg(feature = "sync")]
+    Team(team::Cmd),
+
     /// Get or set small key-value pairs
     #[command(subcommand)]
     Kv(kv::Cmd),
@@ -254,7 +261,7 @@ impl Cmd {
 
         match self {
             Self::Setup => setup::run(&settings).await,
-            Self::Import(import) => import.run(&db).await,
+            Self::Import(import) => import.run(&settings, &db, sqlite_store).await,
             Self::Stats(stats) => stats.run(&db, &settings, theme).await,
             Self::Search(search) => search.run(db, &mut settings, sqlite_store, theme).await,
 
@@ -264,6 +271,9 @@ impl Cmd {
             #[cfg(feature = "sync")]
             Self::Account(account) => account.run(settings, sqlite_store).await,
 
+            #[cfg(feature = "sync")]
+            Self::Team(team) => team.run(&settings).await,
+
             Self::Kv(kv) => kv.run(&settings, &sqlite_store).await,
 
             Self::Store(store) => store.run(&settings, &db, sqlite_store).await,
@@ -305,7 +315,7 @@ impl Cmd {
             Self::Ai(cli) => atuin_ai::commands::run(cli, &settings).await,
 
             #[cfg(feature = "ai")]
-            Self::Mcp => atuin_ai::mcp::run(&db).await,
+            Self::Mcp => atuin_ai::mcp::run(&db, &settings).await,
         }
     }
 
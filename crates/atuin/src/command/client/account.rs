@@ -10,6 +10,7 @@ pub mod link;
 pub mod login;
 pub mod logout;
 pub mod register;
+pub mod sessions;
 
 #[derive(Args, Debug)]
 pub struct Cmd {
@@ -26,7 +27,11 @@ pub enum Commands {
     Register(register::Cmd),
 
     /// Log out
-    Logout,
+    Logout {
+        /// The sync profile to log out of, from `[profiles.<name>]` in the config
+        #[arg(long)]
+        profile: Option<String>,
+    },
 
     /// Delete your account, and all synced data
     Delete(delete::Cmd),
@@ -36,6 +41,9 @@ pub enum Commands {
 
     /// Link your CLI sync account to your Hub account
     Link,
+
+    /// List and revoke the devices logged in to your account
+    Sessions(sessions::Cmd),
 }
 
 impl Cmd {
@@ -44,10 +52,11 @@ impl Cmd {
         match self.command {
             Commands::Login(l) => l.run(&settings, &store).await,
             Commands::Register(r) => r.run(&settings, &store).await,
-            Commands::Logout => logout::run().await,
+            Commands::Logout { profile } => logout::run(&settings, profile.as_deref()).await,
             Commands::Delete(d) => d.run(&settings).await,
             Commands::ChangePassword(c) => c.run(&settings).await,
             Commands::Link => link::run(&settings).await,
+            Commands::Sessions(s) => s.run(&settings).await,
         }
     }
 }
@@ -27,6 +27,10 @@ pub struct Cmd {
 
     #[clap(long, hide = true)]
     pub from_registration: bool,
+
+    /// The sync profile to log in to, from `[profiles.<name>]` in the config
+    #[clap(long)]
+    pub profile: Option<String>,
 }
 
 /// Read a line from stdin, returning `None` at end of input. The distinction
@@ -42,15 +46,17 @@ fn get_input() -> Result<Option<String>> {
 
 impl Cmd {
     pub async fn run(&self, settings: &Settings, store: &SqliteStore) -> Result<()> {
+        let settings = &*settings.for_profile(self.profile.as_deref())?;
+
         match settings.resolve_sync_auth().await {
             SyncAuth::Hub { .. } => {
                 println!("You are authenticated with Atuin Hub.");
-                println!("Run 'atuin logout' to log out.");
+                println!("Run '{}' to log out.", settings.profile_command("logout"));
                 return Ok(());
             }
             SyncAuth::Legacy { .. } => {
                 println!("You are logged in to your sync server.");
-                println!("Run 'atuin logout' to log out.");
+                println!("Run '{}' to log out.", settings.profile_command("logout"));
                 return Ok(());
             }
             SyncAuth::HubViaCli { .. } => {
@@ -104,7 +110,7 @@ impl Cmd {
                 }
             };
 
-            let meta = Settings::meta_store().await?;
+            let meta = settings.sessions().await?;
             let is_hub_token = auth_type.as_deref() == Some("hub") || session.starts_with("atapi_");
 
             if is_hub_token {
@@ -120,7 +126,7 @@ impl Cmd {
         } else {
             // Interactive login via browser OAuth flow.
             if self.from_registration {
-                paseto_v4::Key::try_load_from_path(&settings.key_path)?;
+                super::register::load_or_generate_key(settings)?;
             } else {
                 self.prompt_and_store_key(settings, store).await?;
             }
@@ -152,7 +158,7 @@ impl Cmd {
 
         match response {
             AuthResponse::Success { session, .. } => {
-                Settings::meta_store().await?.save_session(&session).await?;
+                settings.sessions().await?.save_session(&session).await?;
             }
             AuthResponse::TwoFactorRequired => {
                 // Legacy server doesn't support 2FA, so this shouldn't happen.
@@ -164,7 +170,7 @@ impl Cmd {
         Ok(())
     }
 
-    async fn ensure_hub_session(&self, _settings: &Settings, hub_address: &url::Url) -> Result<()> {
+    async fn ensure_hub_session(&self, settings: &Settings, hub_address: &url::Url) -> Result<()> {
         tracing::info!("Authenticating with Atuin Hub...");
 
         let session = atuin_client::hub::HubAuthSession::start(hub_address).await?;
@@ -180,13 +186,18 @@ impl Cmd {
 
         tracing::info!("Authentication complete, saving session token");
 
-        atuin_client::hub::save_session(&token).await?;
+        settings
+            .sessions()
+            .await?
+            .save_hub_session(&token)
+            .await
+            .context("Failed to save hub session")?;
 
         Ok(())
     }
 
     async fn prompt_and_store_key(&self, settings: &Settings, store: &SqliteStore) -> Result<()> {
-        let key_path = &settings.key_path;
+        let key_path = &settings.sync_key_path()?;
 
         println!("IMPORTANT");
         println!(
@@ -240,8 +251,18 @@ impl Cmd {
 }
 
 /// Write the key to the key file, re-encrypting the local store first if it was
-/// previously encrypted with a different key.
+/// previously encrypted with a different key. A sync profile's key only encrypts
+/// what's on its server, so it's written as it is.
 async fn store_key(settings: &Settings, store: &SqliteStore, key: &paseto_v4::Key) -> Result<()> {
+    if settings.profile.is_some() {
+        let key_path = settings.sync_key_path()?;
+        if let Some(dir) = key_path.parent() {
+            fs_err::create_dir_all(dir)?;
+        }
+        key.overwrite_path(&key_path)?;
+        return Ok(());
+    }
+
     let key_path = &settings.key_path;
 
     if !key_path.exists() {
@@ -268,7 +289,9 @@ async fn verify_key_against_remote(
     store: &SqliteStore,
     interactive: bool,
 ) -> Result<()> {
-    let mut key = paseto_v4::Key::try_load_from_path(&settings.key_path)
+    let local_key = paseto_v4::Key::try_load_from_path(&settings.key_path)
+        .context("could not load encryption key for verification")?;
+    let mut key = paseto_v4::Key::try_load_from_path(&settings.sync_key_path()?)
         .context("could not load encryption key for verification")?;
 
     // Build the engine once (this hits the network). The key can change between retries below, so
@@ -283,14 +306,14 @@ async fn verify_key_against_remote(
         .connect()
         .await?;
     loop {
-        let check = engine.keyed(&key).key_valid().await;
+        let check = engine.keyed_with_remote(&local_key, &key).key_valid().await;
         match check {
             // Only persist a key the server has confirmed can read the data, so
             // that cancelling out of a retry leaves the local store as it was.
             None => return store_key(settings, store, &key).await,
             Some(SyncError::WrongKey) => {
                 if !interactive {
-                    logout_wrong_key().await;
+                    logout_wrong_key(settings).await;
                 }
 
                 println!(
@@ -310,7 +333,7 @@ async fn verify_key_against_remote(
                         }
                     }
                     // A blank line or exhausted stdin both mean "give up".
-                    _ => logout_wrong_key().await,
+                    _ => logout_wrong_key(settings).await,
                 }
             }
             Some(e) => {
@@ -326,16 +349,19 @@ async fn verify_key_against_remote(
 
 /// Roll back the saved session so the user is not left in a half-authenticated
 /// state with a key that can't read the data, then exit.
-async fn logout_wrong_key() -> ! {
-    if let Ok(meta) = Settings::meta_store().await {
-        let _ = meta.delete_session().await;
-        let _ = meta.delete_hub_session().await;
+async fn logout_wrong_key(settings: &Settings) -> ! {
+    if let Ok(sessions) = settings.sessions().await {
+        let _ = sessions.delete_session().await;
+        let _ = sessions.delete_hub_session().await;
     }
     crate::print_error::print_error(
         "Wrong encryption key",
-        "The encryption key on this machine does not match the data on the server. You have been \
-         logged out.\n\nTo fix this, find your existing key by running `atuin key` on a machine \
-         that already syncs successfully, then run `atuin login` again here with that key.",
+        &format!(
+            "The encryption key on this machine does not match the data on the server. You have \
+             been logged out.\n\nTo fix this, find your existing key by running `atuin key` on a \
+             machine that already syncs successfully, then run `{}` again here with that key.",
+            settings.profile_command("login")
+        ),
     );
     std::process::exit(1);
 }
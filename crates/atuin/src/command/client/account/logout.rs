@@ -1,5 +1,6 @@
+use atuin_client::settings::Settings;
 use eyre::Result;
 
-pub async fn run() -> Result<()> {
-    atuin_client::logout::logout().await
+pub async fn run(settings: &Settings, profile: Option<&str>) -> Result<()> {
+    atuin_client::logout::logout(&*settings.for_profile(profile)?).await
 }
@@ -17,20 +17,30 @@ pub struct Cmd {
 
     #[clap(long, short)]
     pub email: Option<String>,
+
+    /// An invite code, for self-hosted servers that aren't open for registration
+    #[clap(long)]
+    pub invite: Option<String>,
+
+    /// The sync profile to register with, from `[profiles.<name>]` in the config
+    #[clap(long)]
+    pub profile: Option<String>,
 }
 
 impl Cmd {
     #[allow(clippy::too_many_lines)]
     pub async fn run(&self, settings: &Settings, store: &SqliteStore) -> Result<()> {
+        let settings = &*settings.for_profile(self.profile.as_deref())?;
+
         match settings.resolve_sync_auth().await {
             SyncAuth::Hub { .. } => {
                 println!("You are already authenticated with Atuin Hub.");
-                println!("Run 'atuin logout' to log out.");
+                println!("Run '{}' to log out.", settings.profile_command("logout"));
                 return Ok(());
             }
             SyncAuth::Legacy { .. } => {
                 println!("You are already logged in.");
-                println!("Run 'atuin logout' to log out.");
+                println!("Run '{}' to log out.", settings.profile_command("logout"));
                 return Ok(());
             }
             SyncAuth::HubViaCli { .. } => {
@@ -38,13 +48,20 @@ impl Cmd {
                     "You already have a sync session. Run 'atuin login' to upgrade to full Hub \
                      authentication."
                 );
-                println!("Run 'atuin logout' first if you want to register a new account.");
+                println!(
+                    "Run '{}' first if you want to register a new account.",
+                    settings.profile_command("logout")
+                );
                 return Ok(());
             }
             SyncAuth::NotLoggedIn { .. } => {}
         }
 
         if settings.is_hub_sync() {
+            if self.invite.is_some() {
+                bail!("invite codes are only used by self-hosted servers");
+            }
+
             let required_for_headless = 3;
             let provided = [self.username.is_some(), self.email.is_some(), self.password.is_some()]
                 .iter()
@@ -71,7 +88,7 @@ impl Cmd {
 
                 match response {
                     AuthResponse::Success { session, auth_type } => {
-                        let meta = Settings::meta_store().await?;
+                        let meta = settings.sessions().await?;
                         let is_hub_token =
                             auth_type.as_deref() == Some("hub") || session.starts_with("atapi_");
 
@@ -94,7 +111,7 @@ impl Cmd {
                     }
                 }
 
-                let _key = paseto_v4::Key::try_load_or_generate(&settings.key_path)?;
+                let _key = load_or_generate_key(settings)?;
 
                 println!(
                     "Registration successful! Please make a note of your key (run 'atuin key') \
@@ -114,6 +131,7 @@ impl Cmd {
                     key: None,
                     totp_code: None,
                     from_registration: true,
+                    profile: None,
                 }
                 .run(settings, store)
                 .await?;
@@ -135,14 +153,14 @@ impl Cmd {
                 &username,
                 &email,
                 &password,
+                self.invite.as_deref(),
                 &settings.extra_headers,
             )
             .await?;
 
-            let meta = Settings::meta_store().await?;
-            meta.save_session(&session.session).await?;
+            settings.sessions().await?.save_session(&session.session).await?;
 
-            let _key = paseto_v4::Key::try_load_or_generate(&settings.key_path)?;
+            let _key = load_or_generate_key(settings)?;
 
             println!(
                 "Registration successful! Please make a note of your key (run 'atuin key') and \
@@ -157,3 +175,13 @@ impl Cmd {
         Ok(())
     }
 }
+
+/// The account's key: the local store's for the default account, or a new one for a profile.
+pub(super) fn load_or_generate_key(settings: &Settings) -> Result<paseto_v4::Key> {
+    let key_path = settings.sync_key_path()?;
+    if let Some(dir) = key_path.parent() {
+        fs_err::create_dir_all(dir)?;
+    }
+
+    Ok(paseto_v4::Key::try_load_or_generate(&key_path)?)
+}
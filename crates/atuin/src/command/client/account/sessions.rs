@@ -0,0 +1,88 @@
+use atuin_client::api_client;
+use atuin_client::settings::Settings;
+use clap::{Parser, Subcommand};
+use eyre::{Result, bail};
+use time::OffsetDateTime;
+use time::macros::format_description;
+
+#[derive(Parser, Debug)]
+pub struct Cmd {
+    #[command(subcommand)]
+    command: Option<Commands>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// List the devices logged in to your account (the default)
+    List,
+
+    /// Log a device out, by the id shown in `atuin account sessions`
+    Revoke {
+        id: i64,
+    },
+
+    /// Log out every device except this one
+    RevokeOthers,
+}
+
+impl Cmd {
+    pub async fn run(self, settings: &Settings) -> Result<()> {
+        if !settings.logged_in().await? {
+            bail!("You are not logged in");
+        }
+
+        if settings.is_hub_sync() {
+            bail!("sessions can only be managed on self-hosted servers");
+        }
+
+        let caps = api_client::caps_client(&settings.sync_address, &settings.extra_headers)?;
+        let client = api_client::Client::new(
+            settings.sync_address.clone(),
+            &settings.sync_auth_token().await?,
+            settings.network_connect_timeout,
+            settings.network_timeout,
+            &settings.extra_headers,
+            caps,
+        )?;
+
+        match self.command.unwrap_or(Commands::List) {
+            Commands::List => {
+                let sessions = client.sessions().await?;
+
+                for session in sessions.sessions {
+                    let current = if session.current {
+                        " (this device)"
+                    } else {
+                        ""
+                    };
+                    println!(
+                        "{}\tcreated {}\tlast seen {}\t{}{current}",
+                        session.id,
+                        format_time(session.created_at, settings),
+                        format_time(session.last_seen_at, settings),
+                        session.user_agent.as_deref().unwrap_or("unknown client"),
+                    );
+                }
+            }
+
+            Commands::Revoke { id } => {
+                client.revoke_session(id).await?;
+                println!("Revoked session {id}");
+            }
+
+            Commands::RevokeOthers => {
+                client.revoke_other_sessions().await?;
+                println!("Logged out every other device");
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn format_time(time: Option<OffsetDateTime>, settings: &Settings) -> String {
+    let format = format_description!("[year]-[month]-[day] [hour]:[minute]");
+
+    time.and_then(|t| t.to_offset(settings.timezone.0).format(format).ok())
+        .unwrap_or_else(|| "unknown".to_owned())
+}
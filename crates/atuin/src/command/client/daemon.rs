@@ -589,6 +589,24 @@ async fn status_cmd(settings: &Settings) -> Result<()> {
             println!("  Socket:   {}", settings.daemon.existing_socket_path().display());
             #[cfg(not(unix))]
             println!("  Port:     {}", settings.daemon.tcp_port);
+
+            // Daemons from before config errors were reported don't answer this; say nothing.
+            if let Ok(mut control) = ControlClient::from_settings(settings).await
+                && let Ok(config) = control.config_status().await
+            {
+                match config.error {
+                    None => println!("  Config:   ok"),
+                    Some(error) => {
+                        println!("  Config:   error");
+                        for line in error.lines() {
+                            println!("    {line}");
+                        }
+                    }
+                }
+                if !config.restart_required.is_empty() {
+                    println!("  Restart to apply: {}", config.restart_required.join(", "));
+                }
+            }
         }
         Probe::NeedsRestart(reason) => {
             println!("Daemon running (needs restart)");
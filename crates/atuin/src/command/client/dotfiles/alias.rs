@@ -22,11 +22,19 @@ pub enum Cmd {
     Set {
         name: String,
         value: String,
+
+        /// Share it with this team, rather than only syncing it with your own account
+        #[arg(long)]
+        team: Option<String>,
     },
 
     /// Delete an alias
     Delete {
         name: String,
+
+        /// Share it with this team, rather than only syncing it with your own account
+        #[arg(long)]
+        team: Option<String>,
     },
 
     /// List all aliases
@@ -150,6 +158,14 @@ impl Cmd {
     }
     */
 
+    /// The team the command writes for, if any.
+    fn team(&self) -> Option<&str> {
+        match self {
+            Self::Set { team, .. } | Self::Delete { team, .. } => team.as_deref(),
+            _ => None,
+        }
+    }
+
     pub async fn run(&self, settings: &Settings, store: SqliteStore) -> Result<()> {
         if !settings.dotfiles.enabled {
             eprintln!(
@@ -162,13 +178,18 @@ impl Cmd {
 
         let encryption_key = paseto_v4::Key::try_load_from_path(&settings.key_path)
             .context("could not load encryption key")?;
-        let host_id = Settings::host_id().await?;
+        let host_id = match self.team() {
+            Some(team) => Settings::team_host_id(team).await?,
+            None => Settings::host_id().await?,
+        };
 
         let alias_store = AliasStore::new(store, host_id, encryption_key);
 
         match self {
-            Self::Set { name, value } => self.set(&alias_store, name.clone(), value.clone()).await,
-            Self::Delete { name } => self.delete(&alias_store, name.clone()).await,
+            Self::Set { name, value, .. } => {
+                self.set(&alias_store, name.clone(), value.clone()).await
+            }
+            Self::Delete { name, .. } => self.delete(&alias_store, name.clone()).await,
             Self::List {
                 sort_by,
                 reverse,
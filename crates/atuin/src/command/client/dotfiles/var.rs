@@ -25,11 +25,19 @@ pub enum Cmd {
 
         #[clap(long, short, action)]
         no_export: bool,
+
+        /// Share it with this team, rather than only syncing it with your own account
+        #[arg(long)]
+        team: Option<String>,
     },
 
     /// Delete a variable
     Delete {
         name: String,
+
+        /// Share it with this team, rather than only syncing it with your own account
+        #[arg(long)]
+        team: Option<String>,
     },
 
     /// List all variables
@@ -154,6 +162,14 @@ impl Cmd {
         Ok(())
     }
 
+    /// The team the command writes for, if any.
+    fn team(&self) -> Option<&str> {
+        match self {
+            Self::Set { team, .. } | Self::Delete { team, .. } => team.as_deref(),
+            Self::List { .. } => None,
+        }
+    }
+
     pub async fn run(&self, settings: &Settings, store: SqliteStore) -> Result<()> {
         if !settings.dotfiles.enabled {
             eprintln!(
@@ -166,7 +182,10 @@ impl Cmd {
 
         let encryption_key = paseto_v4::Key::try_load_from_path(&settings.key_path)
             .context("could not load encryption key")?;
-        let host_id = Settings::host_id().await?;
+        let host_id = match self.team() {
+            Some(team) => Settings::team_host_id(team).await?,
+            None => Settings::host_id().await?,
+        };
 
         let var_store = VarStore::new(store, host_id, encryption_key);
 
@@ -175,8 +194,9 @@ impl Cmd {
                 name,
                 value,
                 no_export,
+                ..
             } => self.set(var_store, name.clone(), value.clone(), !no_export).await,
-            Self::Delete { name } => self.delete(var_store, name.clone()).await,
+            Self::Delete { name, .. } => self.delete(var_store, name.clone()).await,
             Self::List {
                 sort_by,
                 reverse,
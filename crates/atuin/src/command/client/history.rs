@@ -1,15 +1,28 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::{self, Display};
-use std::io::{self, IsTerminal, Write};
+use std::fs::File;
+use std::io::{self, BufWriter, IsTerminal, Write};
+use std::path::{Path, PathBuf};
 use std::time::Duration;
 
-use atuin_client::database::{Sqlite, current_context};
+use atuin_client::database::{DbSearchMode, OptFilters, Sqlite, current_context};
+use atuin_client::export::Exporter;
+use atuin_client::export::bash::Bash;
+use atuin_client::export::csv::Csv;
+use atuin_client::export::fish::Fish;
+use atuin_client::export::jsonl::Jsonl;
+use atuin_client::export::zsh::Zsh;
+use atuin_client::history::amend::Redacts;
+use atuin_client::history::retention::{self, Reason};
 use atuin_client::history::store::HistoryStore;
-use atuin_client::history::{AuthorKind, History, probe_author};
+use atuin_client::history::{AuthorKind, History, HistoryId, probe_author};
+use atuin_client::output::database::Database as OutputDatabase;
 #[cfg(feature = "sync")]
 use atuin_client::record;
 use atuin_client::record::sqlite_store::SqliteStore;
+use atuin_client::secrets::{Secret, find_secrets, redact_secrets};
 use atuin_client::settings::FilterMode::{Directory, Global, Session};
-use atuin_client::settings::Settings;
+use atuin_client::settings::{Settings, secrets};
 use atuin_common::encryption::paseto_v4;
 use atuin_common::logs::LogConfig;
 use atuin_common::string::{EscapeNonPrintablePosixExt as _, NonNulStr};
@@ -19,10 +32,12 @@ use atuin_common::utils::normalize_optional_string;
 #[cfg(feature = "daemon")]
 use atuin_daemon::history::{HistoryEventKind, TailHistoryReply};
 use atuin_domain::record::CmdOrigin;
+use atuin_history::predict::{MODEL_HISTORY_LIMIT, Prediction, Predictor};
 use clap::Subcommand;
 #[cfg(feature = "daemon")]
 use colored::Colorize;
 use eyre::{Context, Result, bail};
+use itertools::Itertools;
 use runtime_format::{FormatKey, FormatKeyError, ParseSegment, ParsedFmt};
 #[cfg(feature = "daemon")]
 use serde::Serialize;
@@ -150,6 +165,28 @@ pub enum Cmd {
         format: Option<String>,
     },
 
+    /// Predict the commands most likely to be run next in this session
+    Predict {
+        /// How many predictions to show
+        #[arg(long, short, default_value_t = 5)]
+        limit: usize,
+
+        /// Show only the text of the command, without its score
+        #[arg(long)]
+        cmd_only: bool,
+    },
+
+    /// Export history, oldest first, in a format other tools or shells can read
+    Export {
+        /// The format to write
+        #[arg(long, short, value_enum, default_value_t = ExportFormat::Jsonl)]
+        format: ExportFormat,
+
+        /// Write to this file rather than stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+
     InitStore,
 
     /// Delete history entries matching the configured exclusion filters
@@ -173,6 +210,144 @@ pub enum Cmd {
         #[arg(long)]
         dupkeep: u32,
     },
+
+    /// Replace the command or intent of a history entry, on every host it syncs to
+    Edit {
+        /// The ID of the entry to edit
+        id: String,
+
+        /// The new command
+        #[arg(long)]
+        command: Option<String>,
+
+        /// The new intent. Pass an empty string to clear it.
+        #[arg(long)]
+        intent: Option<String>,
+    },
+
+    /// Blank whatever a regular expression matches in history entries, on every host they sync to
+    Redact {
+        /// The regular expression to redact
+        #[arg(long)]
+        pattern: String,
+
+        /// List matching history lines without redacting them.
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+
+    /// Find history entries holding secrets, going by the secret filters
+    ScanSecrets {
+        /// Delete or redact the entries found, as `secrets_action` says
+        #[arg(long)]
+        apply: bool,
+
+        /// Act on the entries found this way, rather than as `secrets_action` says
+        #[arg(long, value_enum, requires = "apply")]
+        action: Option<secrets::Action>,
+    },
+
+    /// Delete the history entries the retention settings no longer keep, on every host they sync to
+    Retention {
+        /// Report what would be deleted, and list it, without deleting anything.
+        #[arg(short = 'n', long)]
+        dry_run: bool,
+    },
+
+    /// Find the commands whose captured output contains a phrase, newest last
+    SearchOutput {
+        /// The phrase to find, ignoring case and punctuation, e.g. "connection refused"
+        #[arg(required = true)]
+        query: Vec<String>,
+
+        /// How many commands to show
+        #[arg(long, short, default_value_t = 20)]
+        limit: usize,
+
+        #[arg(long)]
+        human: bool,
+
+        /// Show only the text of the command, without the line of output that matched
+        #[arg(long)]
+        cmd_only: bool,
+
+        /// Display the command time in another timezone other than the configured default.
+        ///
+        /// This option takes one of the following kinds of values:
+        ///
+        /// - the special value "local" (or "l") which refers to the system time zone
+        /// - an offset from UTC (e.g. "+9", "-2:30")
+        #[arg(long, visible_alias = "tz", verbatim_doc_comment)]
+        timezone: Option<UtcOffsetSpec>,
+
+        /// Available variables: {command}, {directory}, {duration}, {user}, {host}, {author}, {intent}, {exit}, {time}, {session}, and {uuid}
+        ///
+        /// Example: --format "{time} - [{duration}] - {directory}$\t{command}"
+        #[arg(long, short)]
+        format: Option<String>,
+    },
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum ExportFormat {
+    /// One JSON object per line, with every field
+    Jsonl,
+    /// CSV with a header row, and the same fields as jsonl
+    Csv,
+    /// zsh extended history
+    Zsh,
+    /// bash history, with the timestamps bash writes when HISTTIMEFORMAT is set
+    Bash,
+    /// fish history
+    Fish,
+}
+
+/// The commands most likely to be run next in `context`.
+///
+/// Asks the daemon when it's enabled, as it keeps a model up to date as commands are run.
+/// Otherwise, or if the daemon can't be reached, a model is built from recent history.
+#[instrument(level = "trace", skip_all, fields(limit), err)]
+pub async fn predict(
+    settings: &Settings,
+    db: &Sqlite,
+    context: atuin_client::database::Context,
+    limit: usize,
+) -> Result<Vec<Prediction>> {
+    #[cfg(feature = "daemon")]
+    if settings.daemon.enabled {
+        match predict_with_daemon(settings, context.clone(), limit).await {
+            Ok(predictions) => return Ok(predictions),
+            Err(e) => debug!(?e, "daemon prediction failed, building a local model"),
+        }
+    }
+
+    #[cfg(not(feature = "daemon"))]
+    let _ = settings;
+
+    let mut history = db.before(OffsetDateTime::now_utc(), MODEL_HISTORY_LIMIT).await?;
+    history.reverse();
+
+    Ok(Predictor::from_history(&history).predict(&context.session, &context.cwd, limit))
+}
+
+#[cfg(feature = "daemon")]
+async fn predict_with_daemon(
+    settings: &Settings,
+    context: atuin_client::database::Context,
+    limit: usize,
+) -> Result<Vec<Prediction>> {
+    let mut client = atuin_daemon::client::SearchClient::from_settings(settings).await?;
+    let limit = u32::try_from(limit).unwrap_or(u32::MAX);
+
+    Ok(client
+        .predict(context, limit)
+        .await?
+        .into_iter()
+        .map(|p| Prediction {
+            command: p.command,
+            score: p.score,
+        })
+        .collect())
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -194,6 +369,16 @@ impl ListMode {
     }
 }
 
+/// Writes `history` out with `E`.
+fn export<E: Exporter>(history: &[History], writer: E::Writer) -> Result<()> {
+    let mut exporter = E::new(writer)?;
+    for h in history {
+        exporter.push(h)?;
+    }
+    exporter.finish()?;
+    Ok(())
+}
+
 #[allow(clippy::cast_sign_loss)]
 #[instrument(level = "trace", skip_all, fields(count = h.len()))]
 pub fn print_list(
@@ -422,7 +607,7 @@ fn make_starting_history(
     let author_kind =
         author_kind.or_else(|| author.is_some().then(AuthorKind::probe_current).flatten());
 
-    let h: History = History::capture()
+    let mut h: History = History::capture()
         .timestamp(OffsetDateTime::now_utc())
         .command(command)
         .cwd(cwd)
@@ -433,6 +618,7 @@ fn make_starting_history(
         .build()
         .into();
 
+    h.redact_secrets(settings);
     h.should_save(settings).then_some(h)
 }
 
@@ -455,6 +641,12 @@ async fn handle_start(
         debug!("failed to save history: {e}");
     }
 
+    if !h.should_sync(settings)
+        && let Err(e) = db.mark_local_only(&h.id).await
+    {
+        debug!("failed to keep history local: {e}");
+    }
+
     Ok(Some(h.id.0.clone()))
 }
 
@@ -530,7 +722,14 @@ async fn handle_end(
     };
 
     db.update(&h).await?;
-    history_store.push(h).await?;
+
+    if h.should_sync(settings) {
+        history_store.push(h).await?;
+    } else {
+        debug!("history holds a secret, keeping it local");
+        db.mark_local_only(&h.id).await?;
+        return Ok(());
+    }
 
     if settings.should_sync().await? {
         #[cfg(feature = "sync")]
@@ -944,6 +1143,29 @@ impl Cmd {
             (false, false) => [settings.default_filter_mode(context.git_root.is_some()), Global],
         };
 
+        #[cfg(feature = "daemon")]
+        let history = if settings.daemon.enabled && settings.daemon.search && !include_deleted {
+            // The same list, as an empty search: the directory filter is the search's `--cwd`.
+            let [filter, second] = filters;
+            let filter_options = OptFilters {
+                cwd: (second == Directory).then_some(context.cwd.as_str()),
+                include_duplicates: true,
+                ..OptFilters::default()
+            };
+            atuin_daemon::search_history(
+                settings,
+                db,
+                DbSearchMode::FullText,
+                filter,
+                &context,
+                "",
+                filter_options,
+            )
+            .await?
+        } else {
+            db.list(filters, &context, None, false, include_deleted, None).await?
+        };
+        #[cfg(not(feature = "daemon"))]
         let history = db.list(filters, &context, None, false, include_deleted, None).await?;
 
         print_list(
@@ -961,6 +1183,34 @@ impl Cmd {
         Ok(())
     }
 
+    #[instrument(level = "trace", skip_all, fields(?format), err)]
+    async fn handle_export(
+        db: &Sqlite,
+        context: atuin_client::database::Context,
+        format: ExportFormat,
+        output: Option<PathBuf>,
+    ) -> Result<()> {
+        let mut history = db.list([Global], &context, None, false, false, None).await?;
+        // shells read their history oldest first
+        history.reverse();
+
+        let writer: Box<dyn Write> = match output {
+            Some(path) => Box::new(BufWriter::new(
+                File::create(&path)
+                    .with_context(|| format!("could not create {}", path.display()))?,
+            )),
+            None => Box::new(BufWriter::new(io::stdout().lock())),
+        };
+
+        match format {
+            ExportFormat::Jsonl => export::<Jsonl<_>>(&history, writer),
+            ExportFormat::Csv => export::<Csv<_>>(&history, writer),
+            ExportFormat::Zsh => export::<Zsh<_>>(&history, writer),
+            ExportFormat::Bash => export::<Bash<_>>(&history, writer),
+            ExportFormat::Fish => export::<Fish<_>>(&history, writer),
+        }
+    }
+
     #[instrument(level = "trace", skip_all, fields(dry_run), err)]
     async fn handle_prune(
         db: &Sqlite,
@@ -1073,6 +1323,336 @@ impl Cmd {
         Ok(())
     }
 
+    #[instrument(level = "trace", skip_all, err)]
+    async fn handle_edit(
+        db: &Sqlite,
+        settings: &Settings,
+        history_store: &HistoryStore,
+        id: &str,
+        command: Option<String>,
+        intent: Option<String>,
+    ) -> Result<()> {
+        let Some(entry) = db.load(id).await?.filter(|h| h.deleted_at.is_none()) else {
+            bail!("no history entry with ID {id}");
+        };
+
+        if command.is_none() && intent.is_none() {
+            bail!("nothing to edit: pass --command, --intent or both");
+        }
+
+        let command = command.unwrap_or(entry.command);
+        if command.trim().is_empty() {
+            bail!("the command can't be empty");
+        }
+        let intent = match intent {
+            Some(intent) => normalize_optional_string(Some(intent)),
+            None => entry.intent,
+        };
+
+        if let Some(other) = db.clashing(&entry.id, &command).await? {
+            bail!(
+                "entry {other}, run in the same directory at the same time, already has that \
+                 command: delete one of them instead"
+            );
+        }
+
+        let (record_id, _) = history_store.edit(entry.id.clone(), command, intent).await?;
+        history_store.build_all(db, &[record_id]).await?;
+
+        #[cfg(feature = "daemon")]
+        daemon_cmd::emit_event(settings, atuin_daemon::DaemonEvent::HistoryAmended {
+            ids: vec![entry.id],
+        })
+        .await;
+        #[cfg(not(feature = "daemon"))]
+        let _ = settings;
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all, fields(dry_run), err)]
+    async fn handle_redact(
+        db: &Sqlite,
+        settings: &Settings,
+        history_store: &HistoryStore,
+        context: atuin_client::database::Context,
+        pattern: &str,
+        dry_run: bool,
+    ) -> Result<()> {
+        let re = regex::Regex::new(pattern)
+            .wrap_err_with(|| format!("invalid redaction pattern {pattern:?}"))?;
+
+        // As with prune, everything is checked in memory.
+        let matches: Vec<History> = db
+            .list([Global], &context, None, false, false, None)
+            .await?
+            .into_iter()
+            .filter(|h| {
+                re.is_match(&h.command) || h.intent.as_ref().is_some_and(|i| re.is_match(i))
+            })
+            .collect();
+
+        match matches.len() {
+            0 => {
+                println!("No entries to redact.");
+                return Ok(());
+            }
+            1 => println!("Found 1 entry to redact."),
+            n => println!("Found {n} entries to redact."),
+        }
+
+        if dry_run {
+            print_list(
+                &matches,
+                ListMode::Human,
+                Some(settings.history_format.as_str()),
+                false,
+                false,
+                settings.timezone,
+            );
+        } else {
+            // What the pattern matches is redacted by its hash, so neither it nor the pattern,
+            // which often spells it out, is kept.
+            let mut record_ids = Vec::new();
+            for h in &matches {
+                let texts = [Some(h.command.as_str()), h.intent.as_deref()];
+                let secrets: BTreeSet<&str> = texts
+                    .into_iter()
+                    .flatten()
+                    .flat_map(|text| re.find_iter(text).map(|m| m.as_str()))
+                    .filter(|m| !m.is_empty())
+                    .collect();
+
+                for text in secrets {
+                    let redacts = Redacts::text(text);
+                    record_ids
+                        .extend(history_store.redact_entries([h.id.clone()], &redacts).await?);
+                }
+            }
+            history_store.build_all(db, &record_ids).await?;
+
+            let ids: Vec<_> = matches.into_iter().map(|h| h.id).collect();
+
+            #[cfg(feature = "daemon")]
+            daemon_cmd::emit_event(settings, atuin_daemon::DaemonEvent::HistoryAmended { ids })
+                .await;
+        }
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all, fields(apply), err)]
+    async fn handle_scan_secrets(
+        db: &Sqlite,
+        settings: &Settings,
+        history_store: &HistoryStore,
+        context: atuin_client::database::Context,
+        apply: bool,
+        action: Option<secrets::Action>,
+    ) -> Result<()> {
+        let matches: Vec<(History, Vec<Secret>)> = db
+            .list([Global], &context, None, false, false, None)
+            .await?
+            .into_iter()
+            .filter_map(|h| {
+                let mut secrets = find_secrets(&h.command, settings);
+                if let Some(intent) = &h.intent {
+                    secrets.extend(find_secrets(intent, settings));
+                }
+                (!secrets.is_empty()).then_some((h, secrets))
+            })
+            .collect();
+
+        match matches.len() {
+            0 => {
+                println!("No secrets found.");
+                return Ok(());
+            }
+            1 => println!("Found 1 entry holding secrets."),
+            n => println!("Found {n} entries holding secrets."),
+        }
+
+        if !apply {
+            // Listed redacted, so the secrets don't end up in a terminal's scrollback too.
+            for (h, secrets) in &matches {
+                let names = secrets.iter().map(|secret| secret.name.as_str()).unique().join(", ");
+                println!("{}\t{names}\t{}", h.id, redact_secrets(&h.command, settings));
+            }
+            return Ok(());
+        }
+
+        match action.unwrap_or(settings.secrets_action) {
+            secrets::Action::Drop => {
+                let ids: Vec<_> = matches.into_iter().map(|(h, _)| h.id).collect();
+
+                let mut record_ids = Vec::with_capacity(ids.len());
+                for id in &ids {
+                    let (record_id, _) = history_store.delete(id.clone()).await?;
+                    record_ids.push(record_id);
+                }
+                history_store.build_all(db, &record_ids).await?;
+                println!("Deleted {} entries.", ids.len());
+
+                #[cfg(feature = "daemon")]
+                daemon_cmd::emit_event(settings, atuin_daemon::DaemonEvent::HistoryDeleted { ids })
+                    .await;
+            }
+            secrets::Action::Redact => {
+                // One redaction per pattern found, of every entry it was found in.
+                let mut by_pattern: BTreeMap<&Redacts, Vec<HistoryId>> = BTreeMap::new();
+                for (h, secrets) in &matches {
+                    for redacts in secrets.iter().map(|secret| &secret.redacts).unique() {
+                        by_pattern.entry(redacts).or_default().push(h.id.clone());
+                    }
+                }
+
+                let mut record_ids = Vec::new();
+                for (redacts, ids) in by_pattern {
+                    record_ids.extend(history_store.redact_entries(ids, redacts).await?);
+                }
+                history_store.build_all(db, &record_ids).await?;
+                println!("Redacted {} entries.", matches.len());
+
+                #[cfg(feature = "daemon")]
+                daemon_cmd::emit_event(settings, atuin_daemon::DaemonEvent::HistoryAmended {
+                    ids: matches.into_iter().map(|(h, _)| h.id).collect(),
+                })
+                .await;
+            }
+            secrets::Action::Local => {
+                bail!(
+                    "entries already in the record store can't be kept local: delete or redact \
+                     them with --action"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    #[instrument(level = "trace", skip_all, fields(dry_run), err)]
+    async fn handle_retention(
+        db: &Sqlite,
+        settings: &Settings,
+        history_store: &HistoryStore,
+        dry_run: bool,
+    ) -> Result<()> {
+        let retention = &settings.retention;
+        if retention.is_empty() {
+            println!("No retention rules or max_entries are configured.");
+            return Ok(());
+        }
+
+        let now = OffsetDateTime::now_utc();
+        let expired = retention::expired(db, retention, now).await?;
+
+        match expired.len() {
+            0 => {
+                println!("No entries to delete.");
+                return Ok(());
+            }
+            1 => println!("Found 1 entry to delete."),
+            n => println!("Found {n} entries to delete."),
+        }
+
+        for (reason, entries) in &expired.iter().chunk_by(|e| e.reason) {
+            let count = entries.count();
+            match reason {
+                Reason::Rule(i) => println!("  rule {} ({}): {count}", i + 1, retention.rules[i]),
+                Reason::MaxEntries => println!(
+                    "  max_entries ({}): {count}",
+                    retention.max_entries.unwrap_or_default()
+                ),
+            }
+        }
+
+        if dry_run {
+            let history: Vec<History> = expired.into_iter().map(|e| e.history).collect();
+            print_list(
+                &history,
+                ListMode::Human,
+                Some(settings.history_format.as_str()),
+                false,
+                false,
+                settings.timezone,
+            );
+        } else {
+            let ids = retention::enforce(history_store, db, retention, now).await?;
+            println!("Deleted {} entries.", ids.len());
+
+            #[cfg(feature = "daemon")]
+            daemon_cmd::emit_event(settings, atuin_daemon::DaemonEvent::HistoryDeleted { ids })
+                .await;
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    #[instrument(level = "trace", skip_all, fields(limit), err)]
+    async fn handle_search_output(
+        db: &Sqlite,
+        settings: &Settings,
+        context: atuin_client::database::Context,
+        query: &str,
+        limit: usize,
+        mode: ListMode,
+        format: Option<String>,
+        tz: UtcOffsetSpec,
+    ) -> Result<()> {
+        // Opening it would create it, empty.
+        if !Path::new(&settings.output.db_path).exists() {
+            println!(
+                "No command output has been kept. Output is captured by `atuin pty-proxy`, with \
+                 the daemon running."
+            );
+            return Ok(());
+        }
+
+        let output_db =
+            OutputDatabase::new(&settings.output.db_path, settings.local_timeout).await?;
+        // Output is searched a page at a time, as some of it may be from deleted history.
+        let mut pages = output_db.search_pages(query);
+        let mut history = Vec::new();
+        while history.len() < limit
+            && let Some(ids) = pages.next().await?
+        {
+            history.extend(
+                db.search(DbSearchMode::FullText, Global, &context, "", OptFilters {
+                    ids: Some(&ids),
+                    include_duplicates: true,
+                    ..Default::default()
+                })
+                .await?,
+            );
+        }
+        history.truncate(limit);
+
+        // Each entry found, with the line its output matched on.
+        let mut found = Vec::with_capacity(history.len());
+        for h in history {
+            let output = output_db.load(&h.id).await?;
+            let line = output.as_ref().and_then(|o| o.matching_line(query)).map(str::to_string);
+            found.push((h, line));
+        }
+
+        let format = match format {
+            None => Some(settings.history_format.as_str()),
+            _ => format.as_deref(),
+        };
+
+        // Oldest first, so the newest ends up by the prompt, like `history list`.
+        for (h, line) in found.iter().rev() {
+            print_list(std::slice::from_ref(h), mode, format, false, false, tz);
+
+            if !matches!(mode, ListMode::CmdOnly)
+                && let Some(line) = line
+            {
+                println!("    {}", line.escape_non_printable());
+            }
+        }
+
+        Ok(())
+    }
+
     #[allow(clippy::too_many_lines)]
     #[instrument(level = "trace", skip_all, err)]
     pub async fn run(self, settings: &Settings) -> Result<()> {
@@ -1176,6 +1756,22 @@ impl Cmd {
                         Ok(())
                     }
 
+                    Self::Predict { limit, cmd_only } => {
+                        for prediction in predict(settings, &db, context, limit).await? {
+                            if cmd_only {
+                                println!("{}", prediction.command);
+                            } else {
+                                println!("{:.2}\t{}", prediction.score, prediction.command);
+                            }
+                        }
+
+                        Ok(())
+                    }
+
+                    Self::Export { format, output } => {
+                        Self::handle_export(&db, context, format, output).await
+                    }
+
                     Self::InitStore => history_store.init_store(&db).await,
 
                     Self::Prune { dry_run } => {
@@ -1198,6 +1794,65 @@ impl Cmd {
                         Self::handle_dedup(&db, settings, store, before, dupkeep, dry_run).await
                     }
 
+                    Self::Edit {
+                        id,
+                        command,
+                        intent,
+                    } => {
+                        Self::handle_edit(&db, settings, &history_store, &id, command, intent).await
+                    }
+
+                    Self::Redact { pattern, dry_run } => {
+                        Self::handle_redact(
+                            &db,
+                            settings,
+                            &history_store,
+                            context,
+                            &pattern,
+                            dry_run,
+                        )
+                        .await
+                    }
+
+                    Self::ScanSecrets { apply, action } => {
+                        Self::handle_scan_secrets(
+                            &db,
+                            settings,
+                            &history_store,
+                            context,
+                            apply,
+                            action,
+                        )
+                        .await
+                    }
+
+                    Self::Retention { dry_run } => {
+                        Self::handle_retention(&db, settings, &history_store, dry_run).await
+                    }
+
+                    Self::SearchOutput {
+                        query,
+                        limit,
+                        human,
+                        cmd_only,
+                        timezone,
+                        format,
+                    } => {
+                        let mode = ListMode::from_flags(human, cmd_only);
+                        let tz = timezone.unwrap_or(settings.timezone);
+                        Self::handle_search_output(
+                            &db,
+                            settings,
+                            context,
+                            &query.join(" "),
+                            limit,
+                            mode,
+                            format,
+                            tz,
+                        )
+                        .await
+                    }
+
                     Self::Start { .. } | Self::End { .. } | Self::Tail => unreachable!(),
                 }
             }
@@ -1,10 +1,14 @@
 use std::env;
+use std::path::PathBuf;
 
 use async_trait::async_trait;
 use atuin_client::database::Sqlite;
 use atuin_client::history::History;
+use atuin_client::history::store::HistoryStore;
+use atuin_client::import::atuin_db::AtuinDb;
 use atuin_client::import::bash::Bash;
 use atuin_client::import::fish::Fish;
+use atuin_client::import::jsonl::{FieldMapping, Jsonl};
 use atuin_client::import::nu::Nu;
 use atuin_client::import::nu_histdb::NuHistDb;
 use atuin_client::import::powershell::PowerShell;
@@ -15,8 +19,11 @@ use atuin_client::import::xonsh_sqlite::XonshSqlite;
 use atuin_client::import::zsh::Zsh;
 use atuin_client::import::zsh_histdb::ZshHistDb;
 use atuin_client::import::{Importer, Loader};
+use atuin_client::record::sqlite_store::SqliteStore;
+use atuin_client::settings::Settings;
+use atuin_common::encryption::paseto_v4;
 use clap::Parser;
-use eyre::Result;
+use eyre::{Context, Result};
 use indicatif::ProgressBar;
 use tracing::instrument;
 
@@ -48,6 +55,32 @@ pub enum Cmd {
     XonshSqlite,
     /// Import history from the powershell history file
     Powershell,
+    /// Import history from a JSON Lines file, such as one written by `atuin history export`
+    ///
+    /// Entries already in the history are skipped, so importing the same file again is harmless.
+    Jsonl {
+        /// The file to import
+        path: PathBuf,
+
+        #[command(flatten)]
+        mapping: Box<FieldMapping>,
+    },
+    /// Import history from another machine's Atuin database
+    ///
+    /// Entries keep their ids, so syncing with that machine later doesn't duplicate them.
+    AtuinDb {
+        /// The other machine's history.db
+        path: PathBuf,
+
+        /// The other machine's records.db, to also import entries it hasn't added to its
+        /// history.db yet, and skip ones it has deleted
+        #[arg(long, requires = "key")]
+        records: Option<PathBuf>,
+
+        /// The other machine's key file, which its records are encrypted with
+        #[arg(long, requires = "records")]
+        key: Option<PathBuf>,
+    },
 }
 
 const BATCH_SIZE: usize = 100;
@@ -55,7 +88,7 @@ const BATCH_SIZE: usize = 100;
 impl Cmd {
     #[allow(clippy::cognitive_complexity)]
     #[instrument(level = "trace", skip_all, err)]
-    pub async fn run(&self, db: &Sqlite) -> Result<()> {
+    pub async fn run(&self, settings: &Settings, db: &Sqlite, store: SqliteStore) -> Result<()> {
         println!("        Atuin         ");
         println!("======================");
         println!("          \u{1f30d}          ");
@@ -131,6 +164,27 @@ impl Cmd {
             Self::Xonsh => import::<Xonsh>(db).await,
             Self::XonshSqlite => import::<XonshSqlite>(db).await,
             Self::Powershell => import::<PowerShell>(db).await,
+            Self::Jsonl { path, mapping } => {
+                import_from(db, Jsonl::with_mapping(path, (**mapping).clone())?).await
+            }
+            Self::AtuinDb { path, records, key } => {
+                let records = match (records, key) {
+                    (Some(records), Some(key)) => {
+                        let key = paseto_v4::Key::try_load_from_path(key)
+                            .with_context(|| format!("could not load {}", key.display()))?;
+                        Some((records.as_path(), key))
+                    }
+                    _ => None,
+                };
+                let importer = AtuinDb::open(path, records, settings.local_timeout).await?;
+                import_from(db, importer).await?;
+
+                // add them to our own record store too, so they're synced
+                let encryption_key = paseto_v4::Key::try_load_or_generate(&settings.key_path)
+                    .context("could not load encryption key")?;
+                let host_id = Settings::host_id().await?;
+                HistoryStore::new(store, host_id, encryption_key).init_store(db).await
+            }
         }
     }
 }
@@ -171,9 +225,12 @@ impl Loader for HistoryImporter<'_> {
 }
 
 async fn import<I: Importer + Send>(db: &Sqlite) -> Result<()> {
+    import_from(db, I::new().await?).await
+}
+
+async fn import_from<I: Importer + Send>(db: &Sqlite, mut importer: I) -> Result<()> {
     println!("Importing history from {}", I::NAME);
 
-    let mut importer = I::new().await?;
     let len = importer.entries().await?;
     let mut loader = HistoryImporter::new(db, len);
     importer.load(&mut loader).await?;
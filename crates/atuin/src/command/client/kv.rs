@@ -23,6 +23,10 @@ pub enum Cmd {
         /// Namespace for the key-value pair
         #[arg(long, short, default_value = "default")]
         namespace: String,
+
+        /// Share it with this team, rather than only syncing it with your own account
+        #[arg(long)]
+        team: Option<String>,
     },
 
     /// Delete one or more key-value pairs
@@ -35,6 +39,10 @@ pub enum Cmd {
         /// Namespace for the key-value pair
         #[arg(long, short, default_value = "default")]
         namespace: String,
+
+        /// Share it with this team, rather than only syncing it with your own account
+        #[arg(long)]
+        team: Option<String>,
     },
 
     /// Retrieve a saved value
@@ -64,12 +72,23 @@ pub enum Cmd {
 }
 
 impl Cmd {
+    /// The team the command writes for, if any.
+    fn team(&self) -> Option<&str> {
+        match self {
+            Self::Set { team, .. } | Self::Delete { team, .. } => team.as_deref(),
+            _ => None,
+        }
+    }
+
     #[instrument(level = "trace", skip_all, err)]
     pub async fn run(&self, settings: &Settings, store: &SqliteStore) -> Result<()> {
         let encryption_key = paseto_v4::Key::try_load_from_path(&settings.key_path)
             .context("could not load encryption key")?;
 
-        let host_id = Settings::host_id().await?;
+        let host_id = match self.team() {
+            Some(team) => Settings::team_host_id(team).await?,
+            None => Settings::host_id().await?,
+        };
 
         let kv_db = atuin_kv::database::Database::new(settings.kv.db_path.clone(), 1.0).await?;
         let kv_store = KvStore::new(store.clone(), kv_db, host_id, encryption_key);
@@ -79,6 +98,7 @@ impl Cmd {
                 key,
                 value,
                 namespace,
+                ..
             } => {
                 if namespace.is_empty() {
                     return Err(eyre!("namespace cannot be empty"));
@@ -99,7 +119,9 @@ impl Cmd {
                 kv_store.set(namespace, key, &value).await
             }
 
-            Self::Delete { keys, namespace } => kv_store.delete(namespace, keys).await,
+            Self::Delete {
+                keys, namespace, ..
+            } => kv_store.delete(namespace, keys).await,
 
             Self::Get { key, namespace } => {
                 let kv = kv_store.get(namespace, key).await?;
@@ -42,6 +42,10 @@ pub struct NewScript {
     #[arg(long)]
     /// Skip opening editor when using --last
     pub no_edit: bool,
+
+    /// Share it with this team, rather than only syncing it with your own account
+    #[arg(long)]
+    team: Option<String>,
 }
 
 #[derive(Parser, Debug)]
@@ -96,6 +100,10 @@ pub struct Edit {
     /// Skip opening editor
     #[arg(long)]
     pub no_edit: bool,
+
+    /// Share it with this team, rather than only syncing it with your own account
+    #[arg(long)]
+    team: Option<String>,
 }
 
 #[derive(Parser, Debug)]
@@ -104,6 +112,10 @@ pub struct Delete {
 
     #[arg(short, long)]
     pub force: bool,
+
+    /// Share it with this team, rather than only syncing it with your own account
+    #[arg(long)]
+    team: Option<String>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -547,6 +559,16 @@ impl Cmd {
         }
     }
 
+    /// The team the command writes for, if any.
+    fn team(&self) -> Option<&str> {
+        match self {
+            Self::New(NewScript { team, .. })
+            | Self::Edit(Edit { team, .. })
+            | Self::Delete(Delete { team, .. }) => team.as_deref(),
+            _ => None,
+        }
+    }
+
     #[instrument(level = "trace", skip_all, err)]
     pub async fn run(
         self,
@@ -554,7 +576,10 @@ impl Cmd {
         store: SqliteStore,
         history_db: &Sqlite,
     ) -> Result<()> {
-        let host_id = Settings::host_id().await?;
+        let host_id = match self.team() {
+            Some(team) => Settings::team_host_id(team).await?,
+            None => Settings::host_id().await?,
+        };
         let encryption_key = paseto_v4::Key::try_load_from_path(&settings.key_path)?;
 
         let script_store = ScriptStore::new(store, host_id, encryption_key);
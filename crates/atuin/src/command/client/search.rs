@@ -275,16 +275,22 @@ impl Cmd {
                 include_duplicates: self.include_duplicates,
                 authors: authors.as_slice_filter(),
                 shells: shells.as_slice_filter(),
+                ranker: None,
+                ids: None,
             };
 
-            let mut entries = run_non_interactive(settings, opt_filter, &query, &db).await?;
+            // What gets deleted has to come from the database: the daemon's index only catches up
+            // with each round of deletions afterwards.
+            let deleting = self.delete || self.delete_it_all;
+            let mut entries =
+                run_non_interactive(settings, opt_filter, &query, &db, !deleting).await?;
 
             if entries.is_empty() {
                 std::process::exit(1)
             }
 
             // if we aren't deleting, print it all
-            if self.delete || self.delete_it_all {
+            if deleting {
                 // delete it
                 // it only took me _years_ to add this
                 // sorry
@@ -296,7 +302,7 @@ impl Cmd {
                     let ids = history_store.delete_entries(entries).await?;
                     history_store.build_all(&db, &ids).await?;
 
-                    entries = run_non_interactive(settings, opt_filter, &query, &db).await?;
+                    entries = run_non_interactive(settings, opt_filter, &query, &db, false).await?;
                 }
             } else {
                 let format = self.format.as_deref().unwrap_or(settings.history_format.as_str());
@@ -323,6 +329,7 @@ async fn run_non_interactive(
     filter_options: OptFilters<'_>,
     query: &[String],
     db: &Sqlite,
+    from_daemon: bool,
 ) -> Result<Vec<History>> {
     let current_dir;
     let dir = if filter_options.cwd == Some(".") {
@@ -340,16 +347,26 @@ async fn run_non_interactive(
     };
 
     let filter_mode = settings.default_filter_mode(context.git_root.is_some());
+    let search_mode = settings.search_mode().closest_db_mode();
+    let query = query.join(" ");
 
-    let results = db
-        .search(
-            settings.search_mode().closest_db_mode(),
+    #[cfg(feature = "daemon")]
+    if from_daemon {
+        return atuin_daemon::search_history(
+            settings,
+            db,
+            search_mode,
             filter_mode,
             &context,
-            query.join(" ").as_str(),
+            &query,
             opt_filter,
         )
-        .await?;
+        .await;
+    }
+    #[cfg(not(feature = "daemon"))]
+    let _ = from_daemon;
+
+    let results = db.search(search_mode, filter_mode, &context, &query, opt_filter).await?;
 
     Ok(results)
 }
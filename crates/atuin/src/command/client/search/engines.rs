@@ -1,5 +1,11 @@
+use std::collections::HashSet;
+
+use atuin_client::database::predicate::Invocation;
+use atuin_client::database::search::SearchQuery;
 use atuin_client::database::{Context, DbSearchMode, OptFilters, Sqlite};
 use atuin_client::history::{History, HistoryId, all_user_author_filter};
+use atuin_client::output::database::Database as OutputDatabase;
+use atuin_client::ranking::Ranker;
 use atuin_client::settings::{FilterMode, SearchMode, Settings, Shells};
 use enum_dispatch::enum_dispatch;
 use eyre::Result;
@@ -10,7 +16,17 @@ use super::cursor::Cursor;
 pub mod daemon;
 pub mod db;
 
-#[allow(unused)] // settings is only used if daemon feature is enabled
+/// Queries starting with this search the output `atuin pty-proxy` captured, rather than commands.
+pub const OUTPUT_PREFIX: &str = "output:";
+
+/// How many commands an output search finds at most.
+const OUTPUT_LIMIT: usize = 200;
+
+/// The phrase to find in captured output, if `input` is an output search.
+pub fn output_query(input: &str) -> Option<&str> {
+    input.strip_prefix(OUTPUT_PREFIX).map(str::trim)
+}
+
 pub fn engine(search_mode: SearchMode, settings: &Settings) -> AnySearchEngine {
     match search_mode {
         #[cfg(feature = "daemon")]
@@ -18,11 +34,11 @@ pub fn engine(search_mode: SearchMode, settings: &Settings) -> AnySearchEngine {
         #[cfg(not(feature = "daemon"))]
         SearchMode::DaemonFuzzy => {
             // Fall back to fuzzy mode if daemon feature is not enabled
-            db::Search(DbSearchMode::Fuzzy).into()
+            db::Search::new(DbSearchMode::Fuzzy, settings).into()
         }
-        SearchMode::Prefix => db::Search(DbSearchMode::Prefix).into(),
-        SearchMode::FullText => db::Search(DbSearchMode::FullText).into(),
-        SearchMode::Fuzzy => db::Search(DbSearchMode::Fuzzy).into(),
+        SearchMode::Prefix => db::Search::new(DbSearchMode::Prefix, settings).into(),
+        SearchMode::FullText => db::Search::new(DbSearchMode::FullText, settings).into(),
+        SearchMode::Fuzzy => db::Search::new(DbSearchMode::Fuzzy, settings).into(),
     }
 }
 
@@ -32,6 +48,10 @@ pub struct SearchState {
     pub context: Context,
     pub custom_context: Option<HistoryId>,
     pub shells: Shells,
+    /// Predicted next commands, shown above the results while the query is empty.
+    pub predictions: Vec<History>,
+    /// Captured output, for `output:` searches and the inspector. `None` until any is kept.
+    pub output: Option<OutputDatabase>,
 }
 
 impl SearchState {
@@ -62,23 +82,53 @@ pub trait SearchEngine: Send + Sync + 'static {
     async fn full_query(&mut self, state: &SearchState, db: &mut Sqlite) -> Result<Vec<History>>;
 
     async fn query(&mut self, state: &SearchState, db: &mut Sqlite) -> Result<Vec<History>> {
+        if let Some(query) = output_query(state.input.as_str()) {
+            return search_output(state, query, db).await;
+        }
+
         if state.input.as_str().is_empty() {
             let shells = state.shells.to_filter();
-            Ok(db
-                .search(DbSearchMode::FullText, state.filter_mode, &state.context, "", OptFilters {
-                    limit: Some(200),
-                    authors: all_user_author_filter(),
-                    shells: shells.as_filter(),
-                    ..Default::default()
-                })
-                .await?
-                .into_iter()
-                .collect::<Vec<_>>())
+            let filter_options = OptFilters {
+                limit: Some(200),
+                authors: all_user_author_filter(),
+                shells: shells.as_filter(),
+                ranker: self.ranker(),
+                ..Default::default()
+            };
+            let results = db
+                .search(
+                    DbSearchMode::FullText,
+                    state.filter_mode,
+                    &state.context,
+                    "",
+                    filter_options,
+                )
+                .await?;
+
+            if state.custom_context.is_some() {
+                return Ok(results);
+            }
+            // Predictions come from every directory and session, so hold them to the same
+            // filter mode as the results they are shown above.
+            let predictions = SearchQuery::new(
+                DbSearchMode::FullText,
+                state.filter_mode,
+                &state.context,
+                "",
+                &filter_options,
+            )
+            .map_or_else(|_| Vec::new(), |search| in_scope(&state.predictions, &search));
+            Ok(with_predictions(&predictions, results))
         } else {
             self.full_query(state, db).await
         }
     }
 
+    /// The ranker results are reordered with, if smart sort is enabled.
+    fn ranker(&self) -> Option<&dyn Ranker> {
+        None
+    }
+
     fn get_highlight_indices(&self, command: &str, search_input: &str) -> Vec<usize>;
 }
 
@@ -91,11 +141,66 @@ impl<T: SearchEngine> SearchEngine for Box<T> {
         T::query(self, state, db).await
     }
 
+    fn ranker(&self) -> Option<&dyn Ranker> {
+        T::ranker(self)
+    }
+
     fn get_highlight_indices(&self, command: &str, search_input: &str) -> Vec<usize> {
         T::get_highlight_indices(self, command, search_input)
     }
 }
 
+/// The commands whose captured output contains `query`, newest first, in the current filter mode.
+async fn search_output(state: &SearchState, query: &str, db: &Sqlite) -> Result<Vec<History>> {
+    let Some(output) = &state.output else {
+        return Ok(Vec::new());
+    };
+
+    // Output is searched a page at a time, until enough of it is from history in scope.
+    let shells = state.shells.to_filter();
+    let mut pages = output.search_pages(query);
+    let mut results = Vec::new();
+    while results.len() < OUTPUT_LIMIT
+        && let Some(ids) = pages.next().await?
+    {
+        results.extend(
+            db.search(DbSearchMode::FullText, state.filter_mode, &state.context, "", OptFilters {
+                authors: all_user_author_filter(),
+                shells: shells.as_filter(),
+                // Each run printed something different.
+                include_duplicates: true,
+                ids: Some(&ids),
+                ..Default::default()
+            })
+            .await?,
+        );
+    }
+    results.truncate(OUTPUT_LIMIT);
+
+    Ok(results)
+}
+
+/// The `predictions` whose run would have been found by `search`.
+fn in_scope(predictions: &[History], search: &SearchQuery) -> Vec<History> {
+    predictions
+        .iter()
+        .filter(|h| search.matches_invocation(&Invocation::from(*h)))
+        .cloned()
+        .collect()
+}
+
+/// Put `predictions` first, dropping any other runs of the same commands from `results`.
+fn with_predictions(predictions: &[History], results: Vec<History>) -> Vec<History> {
+    if predictions.is_empty() {
+        return results;
+    }
+
+    let predicted: HashSet<&str> = predictions.iter().map(|h| h.command.as_str()).collect();
+    let rest = results.into_iter().filter(|h| !predicted.contains(h.command.as_str()));
+
+    predictions.iter().cloned().chain(rest).collect()
+}
+
 /// Static-dispatch enum over the search-engine backends.
 #[enum_dispatch(SearchEngine)]
 pub enum AnySearchEngine {
@@ -1,8 +1,12 @@
-use atuin_client::database::{DbSearchMode, OptFilters, Sqlite};
+use std::collections::HashMap;
+
+use atuin_client::database::predicate::{Predicate, QueryFilter, split_filters};
+use atuin_client::database::{DbSearchMode, OptFilters, QueryToken, QueryTokenizer, Sqlite};
 use atuin_client::history::{History, all_user_author_filter};
+use atuin_client::ranking::{Pipeline, Ranker};
 use atuin_client::settings::Settings;
 use atuin_daemon::client::{SearchClient, SearchParams};
-use atuin_daemon::search::{normalize_diacritics, truncate_query};
+use atuin_daemon::search::{MatchRange, SearchResult, normalize_diacritics, truncate_query};
 use eyre::Result;
 use tracing::{Level, debug, instrument, span};
 use uuid::Uuid;
@@ -71,10 +75,39 @@ impl LazyClient {
     }
 }
 
+/// The highlights the daemon sent with the last search's results, as byte offsets into each
+/// command.
+#[derive(Default)]
+struct Highlights {
+    query: String,
+    commands: HashMap<String, Vec<usize>>,
+}
+
+impl Highlights {
+    fn get(&self, command: &str, query: &str) -> Option<Vec<usize>> {
+        if self.query != query {
+            return None;
+        }
+        self.commands.get(command).cloned()
+    }
+}
+
+/// Turn the daemon's matched char ranges into the byte offsets of each matched char in `command`.
+fn range_byte_offsets(command: &str, ranges: &[MatchRange]) -> Vec<usize> {
+    let char_to_byte: Vec<usize> = command.char_indices().map(|(byte_idx, _)| byte_idx).collect();
+    ranges
+        .iter()
+        .flat_map(|range| range.start as usize..range.end as usize)
+        .filter_map(|char_idx| char_to_byte.get(char_idx).copied())
+        .collect()
+}
+
 pub struct Search {
     client: LazyClient,
     settings: Settings,
     query_id: u64,
+    ranker: Option<Pipeline>,
+    highlights: Highlights,
 }
 
 impl Search {
@@ -83,6 +116,8 @@ impl Search {
             client: LazyClient::default(),
             settings: settings.clone(),
             query_id: 0,
+            ranker: settings.smart_sort.then(|| Pipeline::from_settings(&settings.search)),
+            highlights: Highlights::default(),
         }
     }
 
@@ -97,6 +132,20 @@ impl Search {
         query.starts_with("r/") || query.contains(" r/")
     }
 
+    /// Check if query has an inline author filter. Agents' commands aren't in the daemon's index,
+    /// so we fall back to database search to find them
+    fn contains_author_filter(query: &str) -> bool {
+        QueryTokenizer::new(query).any(|token| {
+            matches!(
+                token,
+                QueryToken::Filter(QueryFilter {
+                    predicate: Predicate::Author(_),
+                    ..
+                })
+            )
+        })
+    }
+
     #[instrument(skip_all, level = Level::TRACE, name = "daemon_db_fallback")]
     async fn fallback_to_db_search(
         &self,
@@ -114,6 +163,7 @@ impl Search {
                     limit: Some(200),
                     authors: all_user_author_filter(),
                     shells: shells.as_filter(),
+                    ranker: self.ranker(),
                     ..Default::default()
                 },
             )
@@ -132,6 +182,22 @@ impl Search {
         Ok(db.query_history(&sql_query).await?)
     }
 
+    /// Load the histories with the given IDs from the database, in the order of `ids`.
+    async fn hydrate_ordered(&self, db: &Sqlite, ids: &[String]) -> Result<Vec<History>> {
+        let results = self.hydrate_from_db(db, ids).await?;
+
+        // Reorder results to match the order from the daemon (which is ranked by relevance)
+        Ok(span!(Level::TRACE, "reorder_results").in_scope(|| {
+            let mut ordered_results = Vec::with_capacity(results.len());
+            for id in ids {
+                if let Some(history) = results.iter().find(|h| h.id.0 == *id) {
+                    ordered_results.push(history.clone());
+                }
+            }
+            ordered_results
+        }))
+    }
+
     /// Tell the daemon to build the search index.
     pub async fn prepare_index(&mut self) -> Result<()> {
         self.client
@@ -154,6 +220,11 @@ impl SearchEngine for Search {
             return self.fallback_to_db_search(state, db).await;
         }
 
+        if Self::contains_author_filter(&query) {
+            debug!(query = %query, "[daemon-client] author filter detected, falling back to db");
+            return self.fallback_to_db_search(state, db).await;
+        }
+
         let query_id = self.next_query_id();
 
         let span =
@@ -174,6 +245,7 @@ impl SearchEngine for Search {
             .await?;
 
         let mut ids = Vec::with_capacity(200);
+        let mut results: Vec<SearchResult> = Vec::with_capacity(200);
         span!(Level::TRACE, "daemon_search.resp")
             .in_scope(async || -> Result<()> {
                 while let Some(response) = stream.message().await? {
@@ -195,6 +267,7 @@ impl SearchEngine for Search {
                             })
                             .collect::<Vec<_>>();
                         ids.extend(uuids);
+                        results.extend(response.results);
                     }
                     drop(span2_guard);
                     drop(span2);
@@ -209,27 +282,47 @@ impl SearchEngine for Search {
             return Ok(Vec::new());
         }
 
-        // // Hydrate from local database
-        let results = self.hydrate_from_db(db, &ids).await?;
-
-        // // Reorder results to match the order from the daemon (which is ranked by relevance)
-        let ordered_results = span!(Level::TRACE, "reorder_results").in_scope(|| {
-            let mut ordered_results = Vec::with_capacity(results.len());
-            for id in &ids {
-                if let Some(history) = results.iter().find(|h| h.id.0 == *id) {
-                    ordered_results.push(history.clone());
-                }
-            }
-            ordered_results
-        });
+        let ordered_results = if results.is_empty() {
+            // A daemon from before search results carried their history entries
+            self.highlights = Highlights::default();
+            self.hydrate_ordered(db, &ids).await?
+        } else {
+            let mut highlights = Highlights {
+                query: query.clone(),
+                commands: HashMap::with_capacity(results.len()),
+            };
+            let histories: Vec<History> = results
+                .into_iter()
+                .filter_map(|result| {
+                    let history: History = result.history?.into();
+                    highlights.commands.insert(
+                        history.command.clone(),
+                        range_byte_offsets(&history.command, &result.ranges),
+                    );
+                    Some(history)
+                })
+                .collect();
+            self.highlights = highlights;
+            histories
+        };
 
         debug!(
             query = %query,
-            results = results.len(),
+            results = ordered_results.len(),
             "[daemon-client]"
         );
 
-        Ok(ordered_results)
+        match self.ranker() {
+            Some(ranker) => {
+                let (text, _) = split_filters(&query);
+                Ok(db.rank(ranker, &text, &state.context, ordered_results).await?)
+            }
+            None => Ok(ordered_results),
+        }
+    }
+
+    fn ranker(&self) -> Option<&dyn Ranker> {
+        self.ranker.as_ref().map(|r| r as &dyn Ranker)
     }
 
     #[instrument(skip_all, level = Level::TRACE, name = "daemon_highlight")]
@@ -239,10 +332,17 @@ impl SearchEngine for Search {
             return super::db::get_highlight_indices_fulltext(command, search_input);
         }
 
+        // The daemon sends where each result matched; this only has to match again for commands
+        // that the list displays differently (collapsed whitespace, escaped control chars)
+        if let Some(indices) = self.highlights.get(command, search_input) {
+            return indices;
+        }
+
         // Mirror the daemon's query handling: truncate before frizbee sees
         // the query (a long enough atom panics Matcher::from_query) and
         // normalize diacritics so highlighting agrees with matching
-        let search_input = normalize_diacritics(truncate_query(search_input));
+        let (search_input, _) = split_filters(search_input);
+        let search_input = normalize_diacritics(truncate_query(&search_input));
         let matchable = normalize_diacritics(command);
 
         let config = frizbee::Config::default().casing(frizbee::CaseMatching::Smart);
@@ -317,6 +417,22 @@ mod tests {
         assert_eq!(indices, vec![7, 8, 9, 10]);
     }
 
+    /// The daemon's ranges count chars, and come out as the byte offsets the renderer tests,
+    /// the same as highlighting locally would give.
+    #[test]
+    fn daemon_ranges_become_byte_offsets() {
+        let ranges = [MatchRange { start: 5, end: 9 }];
+        assert_eq!(range_byte_offsets("echo déjà", &ranges), vec![5, 6, 8, 9]);
+
+        let mut engine = Search::new(&Settings::default());
+        engine.highlights = Highlights {
+            query: "deja".to_owned(),
+            commands: HashMap::from([("echo déjà".to_owned(), vec![5, 6, 8, 9])]),
+        };
+        assert_eq!(engine.get_highlight_indices("echo déjà", "deja"), vec![5, 6, 8, 9]);
+        assert_eq!(engine.get_highlight_indices("echo déjà", "déjà"), vec![5, 6, 8, 9]);
+    }
+
     /// Non-Latin text doesn't normalize, so matchable and command share a
     /// byte layout; offsets still land on the match ("日本 git" is 日0 本3
     /// ␣6 g7 i8 t9).
@@ -1,7 +1,10 @@
 use std::ops::Range;
 
+use atuin_client::database::predicate::split_filters;
 use atuin_client::database::{DbSearchMode, OptFilters, QueryToken, QueryTokenizer, Sqlite};
 use atuin_client::history::{History, all_user_author_filter};
+use atuin_client::ranking::{Pipeline, Ranker};
+use atuin_client::settings::Settings;
 use eyre::Result;
 use norm::Metric;
 use norm::fzf::{FzfParser, FzfV2};
@@ -9,35 +12,59 @@ use tracing::{Level, instrument};
 
 use super::{SearchEngine, SearchState};
 
-pub struct Search(pub DbSearchMode);
+pub struct Search {
+    mode: DbSearchMode,
+    ranker: Option<Pipeline>,
+}
+
+impl Search {
+    pub fn new(mode: DbSearchMode, settings: &Settings) -> Self {
+        Search {
+            mode,
+            ranker: settings.smart_sort.then(|| Pipeline::from_settings(&settings.search)),
+        }
+    }
+}
 
 impl SearchEngine for Search {
-    #[instrument(skip_all, level = Level::TRACE, name = "db_search", fields(mode = ?self.0, query = %state.input.as_str()))]
+    #[instrument(skip_all, level = Level::TRACE, name = "db_search", fields(mode = ?self.mode, query = %state.input.as_str()))]
     async fn full_query(&mut self, state: &SearchState, db: &mut Sqlite) -> Result<Vec<History>> {
         let shells = state.shells.to_filter();
         let results = db
-            .search(self.0, state.filter_mode, &state.context, state.input.as_str(), OptFilters {
-                limit: Some(200),
-                authors: all_user_author_filter(),
-                shells: shells.as_filter(),
-                ..Default::default()
-            })
+            .search(
+                self.mode,
+                state.filter_mode,
+                &state.context,
+                state.input.as_str(),
+                OptFilters {
+                    limit: Some(200),
+                    authors: all_user_author_filter(),
+                    shells: shells.as_filter(),
+                    ranker: self.ranker(),
+                    ..Default::default()
+                },
+            )
             .await
             // ignore errors as it may be caused by incomplete regex
             .map_or(Vec::new(), |r| r.into_iter().collect());
         Ok(results)
     }
 
+    fn ranker(&self) -> Option<&dyn Ranker> {
+        self.ranker.as_ref().map(|r| r as &dyn Ranker)
+    }
+
     #[instrument(skip_all, level = Level::TRACE, name = "db_highlight")]
     fn get_highlight_indices(&self, command: &str, search_input: &str) -> Vec<usize> {
-        if self.0 == DbSearchMode::Prefix {
+        if self.mode == DbSearchMode::Prefix {
             return vec![];
-        } else if self.0 == DbSearchMode::FullText {
+        } else if self.mode == DbSearchMode::FullText {
             return get_highlight_indices_fulltext(command, search_input);
         }
         let mut fzf = FzfV2::new();
         let mut parser = FzfParser::new();
-        let query = parser.parse(search_input);
+        let (search_input, _) = split_filters(search_input);
+        let query = parser.parse(&search_input);
         let mut ranges: Vec<Range<usize>> = Vec::new();
         let _ = fzf.distance_and_ranges(query, command, &mut ranges);
 
@@ -63,7 +90,7 @@ pub fn get_highlight_indices_fulltext(command: &str, search_input: &str) -> Vec<
         }
 
         match token {
-            QueryToken::Or => {}
+            QueryToken::Or | QueryToken::Filter(_) => {}
             QueryToken::Regex(r) => {
                 if let Ok(re) = regex::Regex::new(r) {
                     for m in re.find_iter(command) {
@@ -16,7 +16,7 @@ use ratatui::style::{Modifier, Style};
 use ratatui::widgets::{Block, StatefulWidget, Widget};
 use time::{OffsetDateTime, UtcOffset};
 
-use super::engines::{AnySearchEngine, SearchEngine};
+use super::engines::{AnySearchEngine, SearchEngine, output_query};
 use super::syntax;
 
 pub struct HistoryHighlighter<'a> {
@@ -26,6 +26,11 @@ pub struct HistoryHighlighter<'a> {
 
 impl HistoryHighlighter<'_> {
     pub fn get_highlight_indices(&self, command: &str) -> Vec<usize> {
+        // Output searches didn't match the command.
+        if output_query(self.search_input).is_some() {
+            return Vec::new();
+        }
+
         self.engine.get_highlight_indices(command, self.search_input)
     }
 }
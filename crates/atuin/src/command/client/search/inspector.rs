@@ -306,7 +306,39 @@ pub fn draw_full(
 
     draw_commands(f, vert_layout[0], history, stats, false, theme);
     draw_stats_table(f, stats_layout[0], history, tz, stats, theme);
-    draw_stats_charts(f, stats_layout[1], stats, theme);
+
+    if let Some(output) = &stats.output {
+        let output_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Ratio(1, 2), Constraint::Ratio(1, 2)])
+            .split(stats_layout[1]);
+
+        draw_stats_charts(f, output_layout[0], stats, theme);
+        draw_output(f, output_layout[1], output, theme);
+    } else {
+        draw_stats_charts(f, stats_layout[1], stats, theme);
+    }
+}
+
+/// The last lines of what the command printed, as many as fit.
+fn draw_output(f: &mut Frame<'_>, parent: Rect, output: &str, theme: &Theme) {
+    let height = parent.height.saturating_sub(2) as usize;
+    let lines: Vec<&str> = output.lines().collect();
+    let tail = lines[lines.len().saturating_sub(height)..]
+        .iter()
+        .map(|line| line.escape_non_printable().into_owned())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let output = Paragraph::new(Text::from(tail)).block(
+        Block::default()
+            .title("Output")
+            .style(Style::from_crossterm(theme.as_style(Meaning::Base)))
+            .borders(Borders::ALL)
+            .padding(Padding::horizontal(1)),
+    );
+
+    f.render_widget(output, parent);
 }
 
 #[cfg(test)]
@@ -379,6 +411,7 @@ mod tests {
             exits: Vec::new(),
             day_of_week: Vec::new(),
             duration_over_time: Vec::new(),
+            output: None,
         };
         (history, stats)
     }
@@ -47,6 +47,7 @@ pub enum Action {
     Copy,
     Delete,
     DeleteAll,
+    Redact,
     ReturnOriginal,
     ReturnQuery,
     Exit,
@@ -127,6 +128,7 @@ impl Action {
             "copy" => Ok(Action::Copy),
             "delete" => Ok(Action::Delete),
             "delete-all" => Ok(Action::DeleteAll),
+            "redact" => Ok(Action::Redact),
             "return-original" => Ok(Action::ReturnOriginal),
             "return-query" => Ok(Action::ReturnQuery),
             "exit" => Ok(Action::Exit),
@@ -194,6 +196,7 @@ impl Action {
             Action::Copy => "copy".to_string(),
             Action::Delete => "delete".to_string(),
             Action::DeleteAll => "delete-all".to_string(),
+            Action::Redact => "redact".to_string(),
             Action::ReturnOriginal => "return-original".to_string(),
             Action::ReturnQuery => "return-query".to_string(),
             Action::Exit => "exit".to_string(),
@@ -362,6 +362,9 @@ pub fn default_inspector_keymap(settings: &Settings) -> Keymap {
     // Inspector-specific: delete history entry
     km.bind(key("ctrl-d"), Action::Delete);
 
+    // Inspector-specific: redact secrets from history entry
+    km.bind(key("ctrl-r"), Action::Redact);
+
     // Inspector navigation
     km.bind(key("up"), Action::InspectPrevious);
     km.bind(key("down"), Action::InspectNext);
@@ -1,8 +1,10 @@
+use std::collections::BTreeMap;
+
 use atuin_client::database::Sqlite;
 use atuin_client::record::sqlite_store::SqliteStore;
 use atuin_client::settings::Settings;
 use atuin_common::time::{OffsetDateTimeExt, UtcOffsetExt};
-use atuin_domain::record::RecordSeriesKey;
+use atuin_domain::record::{HostId, RecordIdx, RecordSeriesKey, RecordStatus, RecordTag};
 use clap::Subcommand;
 use eyre::Result;
 use itertools::Itertools;
@@ -56,7 +58,7 @@ impl Cmd {
         store: SqliteStore,
     ) -> Result<()> {
         match self {
-            Self::Status => self.status(store).await,
+            Self::Status => self.status(settings, store).await,
             Self::Rebuild(rebuild) => rebuild.run(settings, store, database).await,
             Self::Rekey(rekey) => rekey.run(settings, store).await,
             Self::Verify(verify) => verify.run(settings, store).await,
@@ -70,15 +72,34 @@ impl Cmd {
         }
     }
 
-    pub async fn status(&self, store: SqliteStore) -> Result<()> {
+    pub async fn status(&self, settings: &Settings, store: SqliteStore) -> Result<()> {
         let host_id = Settings::host_id().await?;
         let offset = time::UtcOffset::local_or_utc();
 
         let status = store.status().await?;
 
+        // Series only the remote has, which sync leaves there. They're never seen here otherwise.
+        let mut skipped: BTreeMap<HostId, Vec<(RecordTag, RecordIdx, &str)>> = BTreeMap::new();
+        if let Some(remote) = remote_status(settings, &store).await {
+            for (host, tags) in remote.hosts {
+                for (tag, idx) in tags {
+                    if status.hosts.get(&host).is_some_and(|local| local.contains_key(&tag)) {
+                        continue;
+                    }
+
+                    let series = RecordSeriesKey::new(host, tag);
+                    if let Some(reason) = settings.sync.skip_reason(&series) {
+                        skipped.entry(host).or_default().push((series.tag, idx, reason));
+                    }
+                }
+            }
+        }
+
+        let hosts = status.hosts.keys().chain(skipped.keys()).copied().sorted().dedup();
+
         // TODO: should probs build some data structure and then pretty-print it or smth
-        for (host, st) in status.hosts.iter().sorted_by_key(|(h, _)| *h) {
-            let host_string = if host == &host_id {
+        for host in hosts {
+            let host_string = if host == host_id {
                 format!("host: {} <- CURRENT HOST", host.0.as_hyphenated())
             } else {
                 format!("host: {}", host.0.as_hyphenated())
@@ -86,10 +107,19 @@ impl Cmd {
 
             println!("{host_string}");
 
-            for (tag, idx) in st.iter().sorted_by_key(|(tag, _)| *tag) {
-                println!("\tstore: {tag}");
+            let local = status.hosts.get(&host).into_iter().flatten();
+            for (tag, idx) in local.sorted_by_key(|(tag, _)| *tag) {
+                let series = RecordSeriesKey::new(host, tag.clone());
+
+                match settings.sync.skip_reason(&series) {
+                    None => println!("\tstore: {tag}"),
+                    Some(reason) => {
+                        println!(
+                            "\tstore: {tag} (not synced: {reason}, see [sync] in your config)"
+                        );
+                    }
+                }
 
-                let series = RecordSeriesKey::new(*host, tag.clone());
                 let first = store.first(&series).await?;
                 let last = store.last(&series).await?;
 
@@ -112,9 +142,54 @@ impl Cmd {
                 }
             }
 
+            let remote = skipped.get(&host).into_iter().flatten();
+            for (tag, idx, reason) in remote.sorted_by_key(|(tag, _, _)| tag) {
+                println!("\tremote store: {tag} (not synced: {reason}, see [sync] in your config)");
+                println!("\t\tidx: {idx}");
+            }
+
             println!();
         }
 
         Ok(())
     }
 }
+
+/// The remote's record status, when this machine is logged in to a sync server and can reach it.
+#[cfg(feature = "sync")]
+async fn remote_status(settings: &Settings, store: &SqliteStore) -> Option<RecordStatus> {
+    use atuin_client::record::sync::{ClientSource, SyncEngine};
+
+    if !settings.logged_in().await.unwrap_or(false) {
+        return None;
+    }
+
+    let status = async {
+        SyncEngine::builder()
+            .store(store.clone())
+            .client_source(ClientSource::FromSettings {
+                settings,
+                caps: None,
+            })
+            .build()
+            .connect()
+            .await?
+            .record_status()
+            .await
+    };
+
+    match status.await {
+        Ok(status) => Some(status),
+        Err(e) => {
+            eprintln!(
+                "Couldn't fetch the sync server's status, so only local records are shown: {e}"
+            );
+            None
+        }
+    }
+}
+
+#[cfg(not(feature = "sync"))]
+async fn remote_status(_settings: &Settings, _store: &SqliteStore) -> Option<RecordStatus> {
+    None
+}
@@ -1,13 +1,17 @@
+use std::sync::Arc;
+
 use atuin_client::database::Sqlite;
 use atuin_client::history::store::HistoryStore;
 use atuin_client::record::sqlite_store::SqliteStore;
-use atuin_client::record::sync::{ClientSource, SyncEngine};
+use atuin_client::record::sync::{ClientSource, FileRemote, FileTransport, SyncEngine};
 use atuin_client::settings::Settings;
+use atuin_client::{api_client, team};
 use atuin_common::encryption::paseto_v4;
 use atuin_domain::record::RecordTag;
 use clap::Subcommand;
 use eyre::{Result, WrapErr};
 use tracing::instrument;
+use url::Url;
 
 mod status;
 
@@ -18,16 +22,28 @@ use crate::command::client::account;
 pub enum Cmd {
     /// Sync with the configured server
     Sync {
+        #[command(subcommand)]
+        with: Option<With>,
+
         /// Force re-download everything
         #[arg(long, short)]
         force: bool,
+
+        /// Sync with this profile, from `[profiles.<name>]` in the config, rather than the
+        /// default account
+        #[arg(long)]
+        profile: Option<String>,
     },
 
     /// Login to the configured server
     Login(account::login::Cmd),
 
     /// Log out
-    Logout,
+    Logout {
+        /// The sync profile to log out of, from `[profiles.<name>]` in the config
+        #[arg(long)]
+        profile: Option<String>,
+    },
 
     /// Register with the configured server
     Register(account::register::Cmd),
@@ -40,18 +56,54 @@ pub enum Cmd {
     },
 
     /// Display the sync status
-    Status,
+    Status {
+        /// Show the status of this sync profile, from `[profiles.<name>]` in the config
+        #[arg(long)]
+        profile: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum With {
+    /// Sync directly with another of your machines, rather than the server. Its daemon has to be
+    /// listening for peers, and both machines need the same key and `peer.secret`
+    Peer {
+        /// Where the peer listens, e.g. `localhost:8890` through an SSH tunnel to it
+        address: String,
+    },
+
+    /// Sync with a directory of record files, rather than the server: a local one, such as a USB
+    /// stick or network mount, or one on a host reachable over SSH. Machines syncing through it
+    /// need the same key
+    Files {
+        /// The directory, e.g. `/media/usb/atuin`, or `[user@]host:path` to reach it over SSH
+        remote: String,
+    },
 }
 
 impl Cmd {
     #[instrument(level = "trace", skip_all, err)]
     pub async fn run(self, settings: Settings, db: &Sqlite, store: SqliteStore) -> Result<()> {
         match self {
-            Self::Sync { force } => run(&settings, force, db, store).await,
+            Self::Sync {
+                with: Some(With::Peer { address }),
+                ..
+            } => peer(&settings, &address, db, store).await,
+            Self::Sync {
+                with: Some(With::Files { remote }),
+                ..
+            } => files(&settings, &remote, db, store).await,
+            Self::Sync {
+                with: None,
+                force,
+                profile,
+            } => run(&*settings.for_profile(profile.as_deref())?, force, db, store).await,
             Self::Login(l) => l.run(&settings, &store).await,
-            Self::Logout => account::logout::run().await,
+            Self::Logout { profile } => account::logout::run(&settings, profile.as_deref()).await,
             Self::Register(r) => r.run(&settings, &store).await,
-            Self::Status => status::run(&settings).await,
+            Self::Status { profile } => {
+                status::run(&*settings.for_profile(profile.as_deref())?).await
+            }
             Self::Key { base64 } => {
                 let key = paseto_v4::Key::try_load_from_path(&settings.key_path)
                     .wrap_err("could not load encryption key")?;
@@ -71,28 +123,38 @@ impl Cmd {
 async fn run(settings: &Settings, force: bool, db: &Sqlite, store: SqliteStore) -> Result<()> {
     let encryption_key = paseto_v4::Key::try_load_from_path(&settings.key_path)
         .context("could not load encryption key")?;
+    let remote_key =
+        paseto_v4::Key::try_load_from_path(&settings.sync_key_path()?).with_context(|| {
+            format!("could not load the sync key; run '{}'", settings.profile_command("login"))
+        })?;
 
     let host_id = Settings::host_id().await?;
     let history_store = HistoryStore::new(store.clone(), host_id, encryption_key.clone());
 
-    let caps =
-        atuin_client::api_client::caps_client(&settings.sync_address, &settings.extra_headers)?;
+    let caps = api_client::caps_client(&settings.sync_address, &settings.extra_headers)?;
+    let client = api_client::Client::new(
+        settings.sync_address.clone(),
+        &settings.sync_auth_token().await?,
+        settings.network_connect_timeout,
+        settings.network_timeout,
+        &settings.extra_headers,
+        caps,
+    )?;
 
     // Build the engine once and reuse it for both sync passes below. It owns a clone of the store
-    // (a shared pool), so the second pass sees whatever the store-init writes locally.
+    // (a shared pool), so the second pass sees whatever the store-init writes locally. Teams'
+    // stores are synced on their own, below, so it syncs what the `[sync]` settings allow.
     let engine = SyncEngine::builder()
         .store(store.clone())
-        .client_source(ClientSource::FromSettings {
-            settings,
-            caps: Some(caps),
-        })
+        .client_source(ClientSource::FromClient(client.clone()))
         .build()
         .connect()
         .await
-        .map_err(crate::print_error::format_sync_error)?;
+        .map_err(crate::print_error::format_sync_error)?
+        .with_filter(settings.sync_filter().await?);
 
     let (uploaded, downloaded) = engine
-        .keyed(&encryption_key)
+        .keyed_with_remote(&encryption_key, &remote_key)
         .sync()
         .await
         .map_err(crate::print_error::format_sync_error)?;
@@ -101,7 +163,22 @@ async fn run(settings: &Settings, force: bool, db: &Sqlite, store: SqliteStore)
 
     println!("{uploaded}/{} up/down to record store", downloaded.len());
 
-    let history_length = db.history_count(true).await?;
+    if team::supported(&client).await {
+        team::publish_key(&client, &remote_key).await?;
+
+        let (uploaded, downloaded) =
+            team::sync(settings, &client, &store, &encryption_key, &remote_key).await?;
+
+        if uploaded > 0 || !downloaded.is_empty() {
+            crate::sync::build(settings, &store, db, Some(&downloaded)).await?;
+
+            println!("{uploaded}/{} up/down to team stores", downloaded.len());
+        }
+    }
+
+    // Entries kept locally are never in the store.
+    let local_only = i64::try_from(db.local_only_ids().await?.len())?;
+    let history_length = db.history_count(true).await? - local_only;
     let store_history_length = store.len_tag(&RecordTag::History).await?;
 
     #[allow(clippy::cast_sign_loss)]
@@ -118,7 +195,7 @@ async fn run(settings: &Settings, force: bool, db: &Sqlite, store: SqliteStore)
         // we'll want to run sync once more, as there will now be stuff to upload -- re-key the same
         // engine rather than reconnecting.
         let (uploaded, downloaded) = engine
-            .keyed(&encryption_key)
+            .keyed_with_remote(&encryption_key, &remote_key)
             .sync()
             .await
             .map_err(crate::print_error::format_sync_error)?;
@@ -136,3 +213,68 @@ async fn run(settings: &Settings, force: bool, db: &Sqlite, store: SqliteStore)
 
     Ok(())
 }
+
+#[instrument(level = "trace", skip_all, fields(address), err)]
+async fn peer(settings: &Settings, address: &str, db: &Sqlite, store: SqliteStore) -> Result<()> {
+    let address = if address.contains("://") {
+        Url::parse(address)
+    } else {
+        Url::parse(&format!("http://{address}"))
+    }
+    .wrap_err_with(|| format!("invalid peer address {address}"))?;
+
+    let encryption_key = paseto_v4::Key::try_load_from_path(&settings.key_path)
+        .context("could not load encryption key")?;
+
+    let engine = SyncEngine::builder()
+        .store(store.clone())
+        .client_source(ClientSource::FromPeer {
+            settings,
+            address: address.clone(),
+        })
+        .build()
+        .connect()
+        .await
+        .map_err(crate::print_error::format_sync_error)?;
+
+    let (uploaded, downloaded) = engine
+        .keyed(&encryption_key)
+        .sync()
+        .await
+        .map_err(crate::print_error::format_sync_error)?;
+
+    crate::sync::build(settings, &store, db, Some(&downloaded)).await?;
+
+    println!("{uploaded}/{} up/down with {address}", downloaded.len());
+
+    Ok(())
+}
+
+#[instrument(level = "trace", skip_all, fields(remote), err)]
+async fn files(settings: &Settings, remote: &str, db: &Sqlite, store: SqliteStore) -> Result<()> {
+    let remote: FileRemote = remote.parse()?;
+
+    let encryption_key = paseto_v4::Key::try_load_from_path(&settings.key_path)
+        .context("could not load encryption key")?;
+
+    let engine = SyncEngine::builder()
+        .store(store.clone())
+        .client_source(ClientSource::FromTransport(Arc::new(FileTransport::new(remote.clone()))))
+        .build()
+        .connect()
+        .await
+        .map_err(crate::print_error::format_sync_error)?
+        .with_filter(settings.sync_filter().await?);
+
+    let (uploaded, downloaded) = engine
+        .keyed(&encryption_key)
+        .sync()
+        .await
+        .map_err(crate::print_error::format_sync_error)?;
+
+    crate::sync::build(settings, &store, db, Some(&downloaded)).await?;
+
+    println!("{uploaded}/{} up/down with {remote}", downloaded.len());
+
+    Ok(())
+}
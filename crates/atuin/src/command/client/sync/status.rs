@@ -34,9 +34,22 @@ pub async fn run(settings: &Settings) -> Result<()> {
 
     if settings.auto_sync {
         println!("{}", "[Remote]".green());
+        if let Some(profile) = &settings.profile {
+            println!("Profile: {profile}");
+        }
         println!("Address: {}", settings.sync_address);
         println!("Username: {}", me.username);
+
+        if let Some(usage) = me.usage {
+            println!("Records: {}", with_limit(usage.records, usage.max_records, ""));
+            println!("Storage: {}", with_limit(usage.bytes, usage.max_bytes, " bytes"));
+        }
     }
 
     Ok(())
 }
+
+/// `used`, and the quota it counts against if the server has one.
+fn with_limit(used: u64, max: Option<u64>, unit: &str) -> String {
+    max.map_or_else(|| format!("{used}{unit}"), |max| format!("{used}{unit} of {max}{unit}"))
+}
@@ -0,0 +1,224 @@
+use std::io::{self, IsTerminal};
+
+use atuin_client::settings::Settings;
+use atuin_client::{api_client, team};
+use atuin_common::encryption::paseto_v4;
+use clap::{Parser, Subcommand};
+use eyre::{Context, Result, bail};
+
+#[derive(Parser, Debug)]
+pub struct Cmd {
+    #[command(subcommand)]
+    command: Option<Commands>,
+
+    /// Manage the teams of this sync profile, from `[profiles.<name>]` in the config
+    #[arg(long, global = true)]
+    profile: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// List the teams you're in (the default)
+    List,
+
+    /// Create a team, with you as its admin
+    Create {
+        name: String,
+    },
+
+    /// List a team's members
+    Members {
+        team: String,
+    },
+
+    /// Add someone to a team. They have to have run `atuin sync` since the server enabled teams
+    ///
+    /// The first time, you're asked to check their key's fingerprint with them: the server hands
+    /// out keys, so only that shows the team key is sealed to them.
+    Add {
+        team: String,
+        username: String,
+
+        /// Let them add and remove members, and delete the team
+        #[arg(long)]
+        admin: bool,
+
+        /// Their key's fingerprint, from `atuin team fingerprint`, rather than being asked to check
+        /// it. Also accepts a key that's changed since they were last added
+        #[arg(long)]
+        fingerprint: Option<String>,
+    },
+
+    /// Remove someone from a team. Records already shared with them stay readable to them
+    Remove {
+        team: String,
+        username: String,
+    },
+
+    /// Leave a team
+    Leave {
+        team: String,
+    },
+
+    /// Delete a team, and everything shared with it
+    Delete {
+        team: String,
+    },
+
+    /// Show your key's fingerprint, for team admins to check when they add you
+    Fingerprint,
+}
+
+impl Cmd {
+    pub async fn run(self, settings: &Settings) -> Result<()> {
+        let settings = settings.for_profile(self.profile.as_deref())?;
+
+        if !settings.logged_in().await? {
+            bail!("You are not logged in");
+        }
+
+        if settings.is_hub_sync() {
+            bail!("teams are only available on self-hosted servers");
+        }
+
+        let caps = api_client::caps_client(&settings.sync_address, &settings.extra_headers)?;
+        let client = api_client::Client::new(
+            settings.sync_address.clone(),
+            &settings.sync_auth_token().await?,
+            settings.network_connect_timeout,
+            settings.network_timeout,
+            &settings.extra_headers,
+            caps,
+        )?;
+
+        if !team::supported(&client).await {
+            bail!("this server doesn't support teams");
+        }
+
+        let account_key = paseto_v4::Key::try_load_from_path(&settings.sync_key_path()?)
+            .with_context(|| {
+                format!("could not load the sync key; run '{}'", settings.profile_command("login"))
+            })?;
+
+        match self.command.unwrap_or(Commands::List) {
+            Commands::List => {
+                for membership in team::memberships(&client, &account_key).await? {
+                    let role = if membership.admin {
+                        "\tadmin"
+                    } else {
+                        ""
+                    };
+                    println!("{}{role}", membership.team);
+                }
+            }
+
+            Commands::Create { name } => {
+                team::create(&client, &account_key, &name).await?;
+                println!("Created team {name}");
+                println!("Share with it using --team {name}, e.g. `atuin kv set --team {name}`");
+            }
+
+            Commands::Members { team } => {
+                for member in client.team_members(&team).await?.members {
+                    let role = if member.admin {
+                        "\tadmin"
+                    } else {
+                        ""
+                    };
+                    println!("{}{role}", member.username);
+                }
+            }
+
+            Commands::Add {
+                team,
+                username,
+                admin,
+                fingerprint,
+            } => {
+                let public_key = team::member_key(&client, &username).await?;
+                check_member_key(&settings, &username, &public_key, fingerprint.as_deref()).await?;
+                team::add_member(&client, &account_key, &team, &username, &public_key, admin)
+                    .await?;
+                println!("Added {username} to {team}");
+            }
+
+            Commands::Remove { team, username } => {
+                client.remove_team_member(&team, &username).await?;
+                println!("Removed {username} from {team}");
+            }
+
+            Commands::Leave { team } => {
+                let me = client.me().await?;
+                client.remove_team_member(&team, &me.username).await?;
+                println!("Left {team}");
+            }
+
+            Commands::Delete { team } => {
+                client.delete_team(&team).await?;
+                println!("Deleted team {team}");
+            }
+
+            Commands::Fingerprint => {
+                println!("{}", account_key.identity().fingerprint());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Make sure `public_key`, which the server says is `username`'s, really is before a team key is
+/// sealed to it: it has to match the fingerprint given, the key pinned when they were last added,
+/// or be confirmed by hand. It's pinned once it's checked.
+async fn check_member_key(
+    settings: &Settings,
+    username: &str,
+    public_key: &str,
+    expected: Option<&str>,
+) -> Result<()> {
+    let meta = Settings::meta_store().await?;
+    let fingerprint = paseto_v4::fingerprint(public_key)?;
+    let ask = format!(
+        "check it with them (they can see it with `{}`)",
+        settings.profile_command("team fingerprint")
+    );
+
+    if let Some(expected) = expected {
+        let normalise = |fp: &str| fp.split_whitespace().collect::<String>().to_lowercase();
+        if normalise(expected) != normalise(&fingerprint) {
+            bail!(
+                "{username}'s key has the fingerprint {fingerprint}, not {expected}: the server \
+                 may be handing out a key that isn't theirs"
+            );
+        }
+    } else {
+        match meta.pinned_key(settings.sync_address.as_str(), username).await? {
+            Some(pinned) if pinned == public_key => return Ok(()),
+            Some(pinned) => bail!(
+                "{username}'s key has changed since you last added them to a team, from {} to \
+                 {fingerprint}. If they've changed their sync key, {ask}, and pass it with \
+                 --fingerprint",
+                paseto_v4::fingerprint(&pinned)?
+            ),
+            None if !io::stdin().is_terminal() => {
+                bail!(
+                    "{username}'s key has the fingerprint {fingerprint}: {ask}, and pass it with \
+                     --fingerprint"
+                )
+            }
+            None => {
+                println!("{username}'s key has the fingerprint {fingerprint}");
+                println!("Please {ask}. Is it the same? [y/N]");
+
+                let mut input = String::new();
+                io::stdin().read_line(&mut input)?;
+                let input = input.trim().to_lowercase();
+                if input != "y" && input != "yes" {
+                    bail!("not adding {username}: their key couldn't be checked");
+                }
+            }
+        }
+    }
+
+    meta.pin_key(settings.sync_address.as_str(), username, public_key).await
+}
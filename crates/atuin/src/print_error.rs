@@ -34,8 +34,8 @@ pub fn print_error(title: &str, description: &str) {
     eprintln!();
 }
 
-/// Convert a `SyncError` into an `eyre::Report`, exiting on `WrongKey` after
-/// painting the prominent banner.
+/// Convert a `SyncError` into an `eyre::Report`, exiting on `WrongKey` and
+/// `QuotaExceeded` after painting the prominent banner.
 pub fn format_sync_error(e: SyncError) -> eyre::Report {
     if matches!(e, SyncError::WrongKey) {
         print_error(
@@ -47,6 +47,17 @@ pub fn format_sync_error(e: SyncError) -> eyre::Report {
         );
         std::process::exit(1);
     }
+    if let SyncError::QuotaExceeded { msg } = &e {
+        print_error(
+            "Sync server storage quota exceeded",
+            &format!(
+                "The sync server refused to store more records for your account: {msg}.\n\nRun \
+                 `atuin sync status` to see how much you're using. Ask whoever runs the server to \
+                 raise your quota, or delete history you don't need."
+            ),
+        );
+        std::process::exit(1);
+    }
     e.into()
 }
 
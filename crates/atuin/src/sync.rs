@@ -1,5 +1,6 @@
 use atuin_client::database::Sqlite;
 use atuin_client::history::store::HistoryStore;
+use atuin_client::output::store::OutputStore;
 use atuin_client::record::sqlite_store::SqliteStore;
 use atuin_client::settings::Settings;
 use atuin_common::encryption::paseto_v4;
@@ -9,6 +10,7 @@ use atuin_dotfiles::store::var::VarStore;
 use atuin_kv::store::KvStore;
 use atuin_scripts::store::ScriptStore;
 use eyre::{Context, Result};
+use time::OffsetDateTime;
 
 // This is the only crate that ties together all other crates.
 // Therefore, it's the only crate where functions tying together all stores can live
@@ -35,6 +37,10 @@ pub async fn build(
     let alias_store = AliasStore::new(store.clone(), host_id, encryption_key.clone());
     let var_store = VarStore::new(store.clone(), host_id, encryption_key.clone());
     let kv_store = KvStore::new(store.clone(), kv_db, host_id, encryption_key.clone());
+
+    let output_db =
+        atuin_client::output::database::Database::new(&settings.output.db_path, 1.0).await?;
+    let output_store = OutputStore::new(store.clone(), output_db, host_id, encryption_key.clone());
     let script_store = ScriptStore::new(store.clone(), host_id, encryption_key);
 
     // A failure in one store should not stop the others from building - build as much as
@@ -55,6 +61,10 @@ pub async fn build(
         eprintln!("Warning: failed to build kv: {e}");
     }
 
+    if let Err(e) = output_store.build(db, &settings.output, OffsetDateTime::now_utc()).await {
+        eprintln!("Warning: failed to build command output: {e}");
+    }
+
     let script_db =
         atuin_scripts::database::Database::new(settings.scripts.db_path.clone(), 1.0).await?;
 
@@ -2,12 +2,15 @@ use atuin_common::utils::{crypto_random_string, uuid_v7};
 use atuin_domain::record::{
     EncryptedData, Host, HostId, Record, RecordIdx, RecordSeriesKey, RecordTag,
 };
-use atuin_server_database::models::{NewSession, NewUser, User};
-use atuin_server_database::{Database, DbSettings, DbType};
+use atuin_server_database::models::{
+    NewInvite, NewPackfile, NewSession, NewUser, Usage, UsageCharge, User,
+};
+use atuin_server_database::{Database, DbError, DbSettings, DbType, Quota};
 use atuin_server_postgres::Postgres;
 use atuin_server_sqlite::Sqlite;
 use rstest::rstest;
 use tests_database::helpers::{create_test_db, destroy_test_db};
+use time::{Duration, OffsetDateTime};
 
 struct TestDb {
     settings: DbSettings,
@@ -65,6 +68,7 @@ async fn run_the_test<DB: Database>(settings: &DbSettings) -> eyre::Result<()> {
     let new_session = NewSession {
         user_id,
         token: token.clone(),
+        user_agent: Some("atuin/18.0.0".to_owned()),
     };
     db.add_session(&new_session).await?;
 
@@ -74,6 +78,14 @@ async fn run_the_test<DB: Database>(settings: &DbSettings) -> eyre::Result<()> {
 
     let session = db.get_session(&token).await?;
     assert_eq!(session.user_id, user_id);
+    assert_eq!(session.user_agent.as_deref(), Some("atuin/18.0.0"));
+    assert!(session.created_at.is_some());
+
+    let (authenticated, owner) = db.get_session_with_user(&token).await?;
+    assert_eq!(authenticated.id, session.id);
+    assert_eq!(authenticated.user_agent, session.user_agent);
+    assert_eq!(owner.id, user_id);
+    assert!(!owner.disabled);
 
     let user = db.get_user("foo").await?;
     assert_eq!(user.password, "hunter2");
@@ -84,12 +96,111 @@ async fn run_the_test<DB: Database>(settings: &DbSettings) -> eyre::Result<()> {
         id: user_id,
         password: "hunter3".to_owned(),
         username: "foo".to_owned(),
+        disabled: false,
     };
     db.update_user_password(&user).await?;
 
     let user = db.get_user("foo").await?;
     assert_eq!(user.password, "hunter3");
 
+    // Disable the user, then enable them again
+    db.set_user_disabled(&user, true).await?;
+    assert!(db.get_session_user(&token).await?.disabled);
+    assert!(db.get_session_with_user(&token).await?.1.disabled);
+    let users = db.list_users().await?;
+    assert_eq!(users.len(), 1);
+    assert!(users[0].disabled);
+
+    db.set_user_disabled(&user, false).await?;
+    assert!(!db.get_user("foo").await?.disabled);
+
+    // Log in from two more devices, and use one of them
+    let mut tokens = vec![];
+    for _ in 0..2 {
+        let token = crypto_random_string::<24>();
+        db.add_session(&NewSession {
+            user_id,
+            token: token.clone(),
+            user_agent: None,
+        })
+        .await?;
+        tokens.push(token);
+    }
+
+    let sessions = db.list_sessions(&user).await?;
+    assert_eq!(sessions.len(), 3);
+    assert_eq!(sessions[0].id, session.id);
+
+    let seen = OffsetDateTime::now_utc() + Duration::hours(1);
+    db.touch_session(sessions[1].id, seen).await?;
+    let touched = db.get_session(&tokens[0]).await?;
+    assert_eq!(touched.last_seen_at.map(|t| t.unix_timestamp()), Some(seen.unix_timestamp()));
+
+    // Revoke one of them, then everything but the first
+    db.delete_session(&user, sessions[1].id).await?;
+    assert!(matches!(db.get_session(&tokens[0]).await, Err(DbError::NotFound)));
+    assert!(matches!(db.delete_session(&user, sessions[1].id).await, Err(DbError::NotFound)));
+
+    db.delete_other_sessions(&user, session.id).await?;
+    assert!(matches!(db.get_session(&tokens[1]).await, Err(DbError::NotFound)));
+    let sessions = db.list_sessions(&user).await?;
+    assert_eq!(sessions.len(), 1);
+    assert_eq!(sessions[0].token, token);
+
+    // Log them out everywhere
+    db.delete_user_sessions(&user).await?;
+    assert!(matches!(db.get_session(&token).await, Err(DbError::NotFound)));
+
+    // Register with an invite that can be used twice
+    let invite = NewInvite {
+        code: "let-me-in".to_owned(),
+        max_uses: 2,
+        expires_at: None,
+    };
+    db.add_invite(&invite).await?;
+
+    let invited = |name: &str| NewUser {
+        username: name.to_owned(),
+        email: format!("{name}@example.com"),
+        password: format!("{name}-hunter2"),
+    };
+    db.add_user_with_invite(&invited("bar"), "let-me-in").await?;
+    // a failed registration doesn't use the invite up
+    assert!(db.add_user_with_invite(&invited("bar"), "let-me-in").await.is_err());
+    db.add_user_with_invite(&invited("baz"), "let-me-in").await?;
+    assert!(matches!(
+        db.add_user_with_invite(&invited("qux"), "let-me-in").await,
+        Err(DbError::NotFound)
+    ));
+    assert!(matches!(db.get_user("qux").await, Err(DbError::NotFound)));
+
+    let invites = db.list_invites().await?;
+    assert_eq!(invites.len(), 1);
+    assert_eq!(invites[0].uses, 2);
+
+    // expired invites can't be used
+    db.add_invite(&NewInvite {
+        code: "too-late".to_owned(),
+        max_uses: 1,
+        expires_at: Some(OffsetDateTime::now_utc() - Duration::minutes(1)),
+    })
+    .await?;
+    assert!(matches!(
+        db.add_user_with_invite(&invited("qux"), "too-late").await,
+        Err(DbError::NotFound)
+    ));
+
+    db.delete_invite("too-late").await?;
+    assert!(matches!(db.delete_invite("too-late").await, Err(DbError::NotFound)));
+
+    // Open registration at runtime, then go back to the config
+    assert_eq!(db.registration_override().await?, None);
+    db.set_registration_override(Some(true)).await?;
+    db.set_registration_override(Some(false)).await?;
+    assert_eq!(db.registration_override().await?, Some(false));
+    db.set_registration_override(None).await?;
+    assert_eq!(db.registration_override().await?, None);
+
     // add a bunch of records
     let host_a = Host::new(HostId(uuid_v7()));
     let host_b = Host::new(HostId(uuid_v7()));
@@ -103,7 +214,7 @@ async fn run_the_test<DB: Database>(settings: &DbSettings) -> eyre::Result<()> {
         generate_record(&host_a, 5),
         generate_record(&host_a, 6),
     ];
-    db.add_records(&user, &records).await?;
+    db.add_records(&user, &records, &Quota::default()).await?;
 
     let status = db.status(&user).await?;
     assert!(status.hosts.contains_key(&host_a.id));
@@ -111,6 +222,23 @@ async fn run_the_test<DB: Database>(settings: &DbSettings) -> eyre::Result<()> {
     assert_eq!(status.hosts.get(&host_a.id).unwrap().get(&RecordTag::History).unwrap().clone(), 6);
     assert_eq!(status.hosts.get(&host_b.id).unwrap().get(&RecordTag::History).unwrap().clone(), 2);
 
+    // the second record at host b idx 2 was a duplicate, so it wasn't stored
+    let usage = db.usage(&user).await?;
+    assert_eq!(usage.records, 7);
+    assert_eq!(usage.bytes, 7 * ("some data".len() + "key".len()) as i64);
+
+    // records the server already has don't count again, so they fit right at the quota, but a new
+    // one doesn't and isn't stored
+    let quota = Quota {
+        records: 7,
+        bytes: 0,
+    };
+    let charge = db.add_records(&user, &records, &quota).await?;
+    assert!(matches!(charge, UsageCharge::Stored(Usage { records: 7, .. })));
+    let charge = db.add_records(&user, &[generate_record(&host_a, 7)], &quota).await?;
+    assert!(matches!(charge, UsageCharge::OverQuota(Usage { records: 8, .. })));
+    assert_eq!(db.usage(&user).await?.records, 7);
+
     // Get 3 records from the beginning
     let recs = db
         .next_records(&user, &RecordSeriesKey::new(host_a.id, RecordTag::History), None, 3)
@@ -127,8 +255,58 @@ async fn run_the_test<DB: Database>(settings: &DbSettings) -> eyre::Result<()> {
     assert_eq!(recs[0].idx, 4); // check the head record is idx 4
     assert_eq!(recs.last().unwrap().idx, 6);
 
+    // Upload a packfile, then upload it again before confirming it
+    let manifest_id = uuid_v7();
+    let packfile = |token: &str| NewPackfile {
+        user_id,
+        manifest_id,
+        size: 100,
+        sha256: None,
+        records: 10,
+        token: token.to_owned(),
+    };
+    let before = db.usage(&user).await?;
+    db.add_packfile(&packfile("first")).await?;
+    db.add_packfile(&packfile("second")).await?;
+    assert!(matches!(db.get_packfile_by_token("first").await, Err(DbError::NotFound)));
+
+    // nothing counts until the upload is confirmed
+    let usage = db.usage(&user).await?;
+    assert_eq!((usage.records, usage.bytes), (before.records, before.bytes));
+
+    let stored = db.get_packfile_by_token("second").await?;
+    assert_eq!(stored.manifest_id, manifest_id);
+    assert_eq!(stored.records, 10);
+    assert!(!stored.confirmed);
+
+    // and then it only counts once, however many times it's confirmed
+    db.confirm_packfile(&user, manifest_id, &Quota::default()).await?;
+    db.confirm_packfile(&user, manifest_id, &Quota::default()).await?;
+    assert!(db.get_packfile(&user, manifest_id).await?.confirmed);
+    let missing = db.confirm_packfile(&user, uuid_v7(), &Quota::default()).await;
+    assert!(matches!(missing, Err(DbError::NotFound)));
+    let usage = db.usage(&user).await?;
+    assert_eq!((usage.records, usage.bytes), (before.records + 10, before.bytes + 100));
+
+    // uploading it again stops the old packfile counting
+    db.add_packfile(&packfile("third")).await?;
+    let usage = db.usage(&user).await?;
+    assert_eq!((usage.records, usage.bytes), (before.records, before.bytes));
+
+    // it's not confirmed if it doesn't fit in the quota any more
+    let quota = Quota {
+        records: before.records.unsigned_abs() + 9,
+        bytes: 0,
+    };
+    let charge = db.confirm_packfile(&user, manifest_id, &quota).await?;
+    assert!(matches!(charge, UsageCharge::OverQuota(_)));
+    assert!(!db.get_packfile(&user, manifest_id).await?.confirmed);
+    db.confirm_packfile(&user, manifest_id, &Quota::default()).await?;
+
     // delete_store
     db.delete_store(&user).await?;
+    assert!(matches!(db.get_packfile(&user, manifest_id).await, Err(DbError::NotFound)));
+    assert_eq!(db.usage(&user).await?.records, 0);
     let recs = db
         .next_records(&user, &RecordSeriesKey::new(host_a.id, RecordTag::History), Some(4), 10)
         .await?;